@@ -0,0 +1,246 @@
+//! IOKit power-management assertions for macOS.
+//!
+//! An [`Assertion`] holds one assertion ID and releases it on drop; failures are
+//! reported as [`Error`] instead of raw `IOReturn` codes. `Error` is available on
+//! every platform so callers can share error handling across backends; the
+//! assertion API itself only exists on macOS.
+
+use std::fmt;
+
+/// The assertion types IOKit documents in `IOPMLib.h`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AssertionKind {
+    /// Keep the display on while the user is idle
+    PreventUserIdleDisplaySleep,
+    /// Keep the system from idle sleeping; the display may still sleep
+    PreventUserIdleSystemSleep,
+    /// Keep the system awake even when asked to sleep (not on battery or lid close)
+    PreventSystemSleep,
+    /// Keep disks spinning
+    PreventDiskIdle,
+    /// Keep network connections alive; the wake lasts only while networking is active
+    NetworkClientActive,
+    /// Declare the user active: the display wakes and stays on, as if the
+    /// keyboard or mouse had been used
+    UserIsActive,
+}
+
+impl AssertionKind {
+    /// The `kIOPMAssertionType…` string IOKit expects
+    pub fn type_name(self) -> &'static str {
+        match self {
+            AssertionKind::PreventUserIdleDisplaySleep => "PreventUserIdleDisplaySleep",
+            AssertionKind::PreventUserIdleSystemSleep => "PreventUserIdleSystemSleep",
+            AssertionKind::PreventSystemSleep => "PreventSystemSleep",
+            AssertionKind::PreventDiskIdle => "PreventDiskIdle",
+            AssertionKind::NetworkClientActive => "NetworkClientActive",
+            AssertionKind::UserIsActive => "UserIsActive",
+        }
+    }
+}
+
+/// A failed IOKit call, decoded from its `IOReturn`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// `kIOReturnError`
+    General,
+    /// `kIOReturnNoMemory`
+    NoMemory,
+    /// `kIOReturnNotPrivileged`
+    NotPrivileged,
+    /// `kIOReturnBadArgument`, e.g. an unknown assertion type
+    BadArgument,
+    /// `kIOReturnUnsupported`
+    Unsupported,
+    /// `kIOReturnNotFound`, e.g. releasing an ID that isn't held
+    NotFound,
+    /// Any other `IOReturn`
+    Other(i32),
+}
+
+const K_IO_RETURN_ERROR: i32 = 0xE000_02BC_u32 as i32;
+const K_IO_RETURN_NO_MEMORY: i32 = 0xE000_02BD_u32 as i32;
+const K_IO_RETURN_NOT_PRIVILEGED: i32 = 0xE000_02C1_u32 as i32;
+const K_IO_RETURN_BAD_ARGUMENT: i32 = 0xE000_02C2_u32 as i32;
+const K_IO_RETURN_UNSUPPORTED: i32 = 0xE000_02C7_u32 as i32;
+const K_IO_RETURN_NOT_FOUND: i32 = 0xE000_02F0_u32 as i32;
+
+impl Error {
+    /// Decodes a non-zero `IOReturn`
+    pub fn from_code(code: i32) -> Error {
+        match code {
+            K_IO_RETURN_ERROR => Error::General,
+            K_IO_RETURN_NO_MEMORY => Error::NoMemory,
+            K_IO_RETURN_NOT_PRIVILEGED => Error::NotPrivileged,
+            K_IO_RETURN_BAD_ARGUMENT => Error::BadArgument,
+            K_IO_RETURN_UNSUPPORTED => Error::Unsupported,
+            K_IO_RETURN_NOT_FOUND => Error::NotFound,
+            other => Error::Other(other),
+        }
+    }
+
+    /// The raw `IOReturn`
+    pub fn code(self) -> i32 {
+        match self {
+            Error::General => K_IO_RETURN_ERROR,
+            Error::NoMemory => K_IO_RETURN_NO_MEMORY,
+            Error::NotPrivileged => K_IO_RETURN_NOT_PRIVILEGED,
+            Error::BadArgument => K_IO_RETURN_BAD_ARGUMENT,
+            Error::Unsupported => K_IO_RETURN_UNSUPPORTED,
+            Error::NotFound => K_IO_RETURN_NOT_FOUND,
+            Error::Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self {
+            Error::General => "general IOKit error",
+            Error::NoMemory => "out of memory",
+            Error::NotPrivileged => "not privileged",
+            Error::BadArgument => "bad argument",
+            Error::Unsupported => "unsupported",
+            Error::NotFound => "no such assertion",
+            Error::Other(_) => "IOKit error",
+        };
+        write!(f, "{} (0x{:08x})", what, self.code() as u32)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use core_foundation::base::TCFType;
+    use core_foundation::string::{CFString, CFStringRef};
+
+    use super::{AssertionKind, Error};
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            assertion_id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(assertion_id: u32) -> i32;
+        fn IOPMAssertionDeclareUserActivity(
+            name: CFStringRef,
+            user_type: u32,
+            assertion_id: *mut u32,
+        ) -> i32;
+    }
+
+    const IOPM_ASSERTION_LEVEL_ON: u32 = 255;
+    // `kIOPMUserActiveLocal`: activity at this Mac's own keyboard and mouse
+    const IOPM_USER_ACTIVE_LOCAL: u32 = 0;
+
+    /// A held power assertion, released when dropped
+    #[derive(Debug)]
+    pub struct Assertion {
+        id: u32,
+    }
+
+    impl Assertion {
+        /// Creates an assertion of `kind`. `name` shows up in `pmset -g assertions`.
+        pub fn new(kind: AssertionKind, name: &str) -> Result<Assertion, Error> {
+            Assertion::with_type_name(kind.type_name(), name)
+        }
+
+        /// Creates an assertion from a raw `kIOPMAssertionType…` string, for types
+        /// `AssertionKind` doesn't cover
+        pub fn with_type_name(type_name: &str, name: &str) -> Result<Assertion, Error> {
+            let atype = CFString::new(type_name);
+            let aname = CFString::new(name);
+            let mut id: u32 = 0;
+
+            let result = unsafe {
+                IOPMAssertionCreateWithName(
+                    atype.as_concrete_TypeRef(),
+                    IOPM_ASSERTION_LEVEL_ON,
+                    aname.as_concrete_TypeRef(),
+                    &mut id,
+                )
+            };
+
+            match result {
+                0 => Ok(Assertion { id }),
+                code => Err(Error::from_code(code)),
+            }
+        }
+
+        /// Reports the user as active, as a key press would: the display wakes
+        /// and the idle timers restart from zero. Dropping the returned
+        /// assertion doesn't undo that; holding it keeps the user "active".
+        pub fn declare_user_activity(name: &str) -> Result<Assertion, Error> {
+            let aname = CFString::new(name);
+            let mut id: u32 = 0;
+
+            let result = unsafe {
+                IOPMAssertionDeclareUserActivity(
+                    aname.as_concrete_TypeRef(),
+                    IOPM_USER_ACTIVE_LOCAL,
+                    &mut id,
+                )
+            };
+
+            match result {
+                0 => Ok(Assertion { id }),
+                code => Err(Error::from_code(code)),
+            }
+        }
+
+        /// The IOKit assertion ID
+        pub fn id(&self) -> u32 {
+            self.id
+        }
+
+        /// Releases now, reporting failure; dropping releases silently
+        pub fn release(self) -> Result<(), Error> {
+            let id = self.id;
+            std::mem::forget(self);
+            match unsafe { IOPMAssertionRelease(id) } {
+                0 => Ok(()),
+                code => Err(Error::from_code(code)),
+            }
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe {
+                IOPMAssertionRelease(self.id);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use macos::Assertion;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes_round_trip() {
+        for error in [
+            Error::General,
+            Error::NoMemory,
+            Error::NotPrivileged,
+            Error::BadArgument,
+            Error::Unsupported,
+            Error::NotFound,
+            Error::Other(-1),
+        ] {
+            assert_eq!(Error::from_code(error.code()), error);
+        }
+    }
+
+    #[test]
+    fn display_includes_hex_code() {
+        assert_eq!(Error::BadArgument.to_string(), "bad argument (0xe00002c2)");
+    }
+}
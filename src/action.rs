@@ -0,0 +1,61 @@
+//! Actions named in text: `on`, `toggle`, `off`, or `for:60` for an hour's
+//! timer.
+//! Launcher items carry these as arguments, `awake://` links and the remote
+//! control as paths (`for/60`), so one parser serves them all.
+
+use crate::state::Command;
+
+/// The longest timer an action can ask for: a week, in minutes
+pub const MAX_MINUTES: u64 = 7 * 24 * 60;
+
+/// The command `action` names, if any
+pub fn parse(action: &str) -> Option<Command> {
+    let action = action.trim_start_matches('/').trim_end_matches('/');
+    let (verb, argument) = match action.split_once(['/', ':']) {
+        Some((verb, argument)) => (verb, Some(argument)),
+        None => (action, None),
+    };
+    match (verb, argument) {
+        ("on", None) => Some(Command::Activate),
+        ("toggle", None) => Some(Command::Toggle),
+        ("off", None) => Some(Command::Deactivate),
+        ("for", Some(minutes)) => match minutes.parse() {
+            Ok(minutes) if (1..=MAX_MINUTES).contains(&minutes) => {
+                Some(Command::ActivateFor { minutes })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_name_commands() {
+        assert!(matches!(parse("toggle"), Some(Command::Toggle)));
+        assert!(matches!(parse("/off/"), Some(Command::Deactivate)));
+        assert!(matches!(
+            parse("for/60"),
+            Some(Command::ActivateFor { minutes: 60 })
+        ));
+        assert!(matches!(
+            parse("for:15"),
+            Some(Command::ActivateFor { minutes: 15 })
+        ));
+        assert!(parse("for/0").is_none());
+        assert!(matches!(
+            parse("for/10080"),
+            Some(Command::ActivateFor {
+                minutes: MAX_MINUTES
+            })
+        ));
+        assert!(parse("for/10081").is_none());
+        assert!(parse("for/18446744073709551615").is_none());
+        assert!(parse("toggle/now").is_none());
+        assert!(matches!(parse("on"), Some(Command::Activate)));
+        assert!(parse("sleep").is_none());
+    }
+}
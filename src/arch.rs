@@ -0,0 +1,57 @@
+//! Which build is running: the architecture it was compiled for and, on
+//! macOS, whether Rosetta is translating it. An Intel build installed on an
+//! Apple silicon Mac works, but translation costs CPU time that gets reported
+//! as Awake being heavy, so the running app says so in `status.json` and
+//! `awake status`, and once in an alert.
+
+/// What the binary was compiled for, in Apple's terms on a Mac: `arm64` or
+/// `x86_64`
+pub fn compiled_for() -> &'static str {
+    match std::env::consts::ARCH {
+        "aarch64" if cfg!(target_os = "macos") => "arm64",
+        arch => arch,
+    }
+}
+
+/// Whether Rosetta is translating this process (`sysctl.proc_translated`)
+#[cfg(target_os = "macos")]
+pub fn is_translated() -> bool {
+    let mut translated: libc::c_int = 0;
+    let mut size = std::mem::size_of::<libc::c_int>();
+    // Fails with ENOENT on an Intel Mac, where nothing is translated
+    let result = unsafe {
+        libc::sysctlbyname(
+            c"sysctl.proc_translated".as_ptr(),
+            &mut translated as *mut libc::c_int as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    result == 0 && translated == 1
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn is_translated() -> bool {
+    false
+}
+
+/// "arm64", or "x86_64 under Rosetta"
+pub fn describe(arch: &str, translated: bool) -> String {
+    if translated {
+        format!("{} under Rosetta", arch)
+    } else {
+        arch.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translation_is_named() {
+        assert_eq!(describe("arm64", false), "arm64");
+        assert_eq!(describe("x86_64", true), "x86_64 under Rosetta");
+    }
+}
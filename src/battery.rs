@@ -0,0 +1,182 @@
+//! The battery's charge and whether it's on mains power, for the overnight
+//! mode's "until charged" hold. `None` where there's no battery to read.
+//! On macOS, also whether the battery is in good health, for the guard
+//! against long sessions draining a worn one.
+//!
+//! Whether it's on battery power is also kept by a background look every
+//! `POLL`, for the main thread, which shouldn't wait on `pmset` at a click.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+
+/// Charge the overnight mode waits for unless told otherwise
+pub const DEFAULT_CHARGE_PERCENT: u8 = 80;
+
+const POLL: Duration = Duration::from_secs(30);
+
+// What the last look found; false until then, and without a battery
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Battery {
+    pub percent: u8,
+    /// Plugged in; a battery draining overnight isn't worth staying awake for
+    pub on_ac: bool,
+}
+
+impl Battery {
+    pub fn is_charging_below(&self, percent: u8) -> bool {
+        self.on_ac && self.percent < percent
+    }
+}
+
+/// Starts looking at the power source every `POLL`, the first time now;
+/// once is enough, however often it's called
+pub fn watch_power_source() {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        thread::spawn(|| loop {
+            let on_battery = read().is_some_and(|battery| !battery.on_ac);
+            ON_BATTERY.store(on_battery, Ordering::Relaxed);
+            thread::sleep(POLL);
+        });
+    });
+}
+
+/// Whether the machine was drawing from its battery at the last look, from
+/// `watch_power_source`
+pub fn on_battery() -> bool {
+    ON_BATTERY.load(Ordering::Relaxed)
+}
+
+#[cfg(target_os = "macos")]
+pub fn read() -> Option<Battery> {
+    parse_pmset(&crate::process::run_capture(
+        "/usr/bin/pmset",
+        &["-g", "batt"],
+    ))
+}
+
+// "Now drawing from 'AC Power'\n -InternalBattery-0 (id=…)\t85%; charging; …"
+#[cfg(any(target_os = "macos", test))]
+fn parse_pmset(text: &str) -> Option<Battery> {
+    let on_ac = text.lines().next()?.contains("'AC Power'");
+    let (before, _) = text.split_once('%')?;
+    let digits = before.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    Some(Battery {
+        percent: digits.parse().ok()?,
+        on_ac,
+    })
+}
+
+/// Whether macOS says the battery needs service; `None` without a battery.
+/// Slow (it asks `system_profiler`), so read off the main thread.
+#[cfg(target_os = "macos")]
+pub fn needs_service() -> Option<bool> {
+    parse_condition(&crate::process::run_capture(
+        "/usr/sbin/system_profiler",
+        &["SPPowerDataType"],
+    ))
+}
+
+// "      Condition: Service Recommended", under "Health Information:"
+#[cfg(any(target_os = "macos", test))]
+fn parse_condition(text: &str) -> Option<bool> {
+    let condition = text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Condition:"))?;
+    Some(condition.trim() != "Normal")
+}
+
+#[cfg(target_os = "linux")]
+pub fn read() -> Option<Battery> {
+    use std::fs;
+
+    let supplies = fs::read_dir("/sys/class/power_supply").ok()?;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| fs::read_to_string(path.join(name)).unwrap_or_default();
+        if read("type").trim() != "Battery" {
+            continue;
+        }
+        if let Ok(percent) = read("capacity").trim().parse() {
+            return Some(Battery {
+                percent,
+                on_ac: read("status").trim() != "Discharging",
+            });
+        }
+    }
+    None
+}
+
+#[cfg(windows)]
+pub fn read() -> Option<Battery> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // `BatteryLifePercent` is 255 when unknown; flag 128 means no battery
+    const UNKNOWN: u8 = 255;
+    const NO_BATTERY: u8 = 128;
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0
+        || status.BatteryFlag & NO_BATTERY != 0
+        || status.BatteryLifePercent == UNKNOWN
+    {
+        return None;
+    }
+    Some(Battery {
+        percent: status.BatteryLifePercent,
+        on_ac: status.ACLineStatus == 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pmset_output_parses() {
+        let charging = "Now drawing from 'AC Power'\n \
+                        -InternalBattery-0 (id=4653155)\t62%; charging; 1:05 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(charging),
+            Some(Battery {
+                percent: 62,
+                on_ac: true
+            })
+        );
+        let draining = "Now drawing from 'Battery Power'\n \
+                        -InternalBattery-0 (id=4653155)\t100%; discharging; 9:12 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(draining),
+            Some(Battery {
+                percent: 100,
+                on_ac: false
+            })
+        );
+        // A desktop Mac lists no battery
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+    }
+
+    #[test]
+    fn battery_condition_parses() {
+        let worn = "Power:\n\n    Battery Information:\n\n      Health Information:\n          \
+                    Cycle Count: 1021\n          Condition: Service Recommended\n";
+        assert_eq!(parse_condition(worn), Some(true));
+        assert_eq!(parse_condition("  Condition: Normal\n"), Some(false));
+        assert_eq!(
+            parse_condition("Power:\n\n    AC Charger Information:\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn only_a_plugged_in_battery_below_the_target_is_charging() {
+        let battery = |percent, on_ac| Battery { percent, on_ac };
+        assert!(battery(50, true).is_charging_below(80));
+        assert!(!battery(80, true).is_charging_below(80));
+        assert!(!battery(50, false).is_charging_below(80));
+    }
+}
@@ -0,0 +1,255 @@
+//! Bonjour, through libSystem's DNS Service Discovery API (`dns_sd.h`): the
+//! remote control advertises itself as `_awake._tcp`, and `awake hosts`
+//! browses for every instance doing so on the local network.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::time::{Duration, Instant};
+
+const SERVICE_TYPE: &CStr = c"_awake._tcp";
+// `kDNSServiceFlagsAdd`: a service appeared, rather than went away
+const FLAG_ADD: u32 = 0x2;
+
+type DNSServiceRef = *mut c_void;
+
+type BrowseReply = extern "C" fn(
+    DNSServiceRef,
+    u32,
+    u32,
+    i32,
+    *const c_char,
+    *const c_char,
+    *const c_char,
+    *mut c_void,
+);
+
+type ResolveReply = extern "C" fn(
+    DNSServiceRef,
+    u32,
+    u32,
+    i32,
+    *const c_char,
+    *const c_char,
+    u16,
+    u16,
+    *const u8,
+    *mut c_void,
+);
+
+#[allow(non_snake_case)]
+extern "C" {
+    fn DNSServiceRegister(
+        sdRef: *mut DNSServiceRef,
+        flags: u32,
+        interfaceIndex: u32,
+        name: *const c_char,
+        regtype: *const c_char,
+        domain: *const c_char,
+        host: *const c_char,
+        port: u16,
+        txtLen: u16,
+        txtRecord: *const c_void,
+        callBack: Option<extern "C" fn()>,
+        context: *mut c_void,
+    ) -> i32;
+    fn DNSServiceBrowse(
+        sdRef: *mut DNSServiceRef,
+        flags: u32,
+        interfaceIndex: u32,
+        regtype: *const c_char,
+        domain: *const c_char,
+        callBack: BrowseReply,
+        context: *mut c_void,
+    ) -> i32;
+    fn DNSServiceResolve(
+        sdRef: *mut DNSServiceRef,
+        flags: u32,
+        interfaceIndex: u32,
+        name: *const c_char,
+        regtype: *const c_char,
+        domain: *const c_char,
+        callBack: ResolveReply,
+        context: *mut c_void,
+    ) -> i32;
+    fn DNSServiceRefSockFD(sdRef: DNSServiceRef) -> i32;
+    fn DNSServiceProcessResult(sdRef: DNSServiceRef) -> i32;
+    fn DNSServiceRefDeallocate(sdRef: DNSServiceRef);
+}
+
+/// An instance found on the network
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Host {
+    /// The computer's name, as shown in Sharing settings
+    pub name: String,
+    /// "mini.local", for `awake --host`
+    pub host: String,
+    pub port: u16,
+}
+
+/// A service found by browsing, still to be resolved to an address
+struct Found {
+    interface: u32,
+    name: String,
+    regtype: String,
+    domain: String,
+}
+
+/// Advertises the remote control on `port` for the rest of the run, named
+/// after the computer
+pub fn advertise(port: u16) {
+    let mut service: DNSServiceRef = std::ptr::null_mut();
+    // The service stays registered for as long as `service` is open
+    let error = unsafe {
+        DNSServiceRegister(
+            &mut service,
+            0,
+            0,
+            std::ptr::null(),
+            SERVICE_TYPE.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            port.to_be(),
+            0,
+            std::ptr::null(),
+            None,
+            std::ptr::null_mut(),
+        )
+    };
+    if error != 0 {
+        eprintln!(
+            "Awake: couldn't advertise the remote control: error {}",
+            error
+        );
+    }
+}
+
+unsafe fn text(text: *const c_char) -> String {
+    CStr::from_ptr(text).to_string_lossy().into_owned()
+}
+
+extern "C" fn browsed(
+    _service: DNSServiceRef,
+    flags: u32,
+    interface: u32,
+    error: i32,
+    name: *const c_char,
+    regtype: *const c_char,
+    domain: *const c_char,
+    context: *mut c_void,
+) {
+    if error != 0 || flags & FLAG_ADD == 0 {
+        return;
+    }
+    let found = unsafe { &mut *context.cast::<Vec<Found>>() };
+    unsafe {
+        found.push(Found {
+            interface,
+            name: text(name),
+            regtype: text(regtype),
+            domain: text(domain),
+        });
+    }
+}
+
+extern "C" fn resolved(
+    _service: DNSServiceRef,
+    _flags: u32,
+    _interface: u32,
+    error: i32,
+    _full_name: *const c_char,
+    host: *const c_char,
+    port: u16,
+    _txt_len: u16,
+    _txt: *const u8,
+    context: *mut c_void,
+) {
+    if error != 0 {
+        return;
+    }
+    let address = unsafe { &mut *context.cast::<Option<(String, u16)>>() };
+    let host = unsafe { text(host) };
+    *address = Some((host.trim_end_matches('.').to_string(), u16::from_be(port)));
+}
+
+// Hands `service`'s replies to their callback until `until`; stops early
+// after a batch if `done` says so
+unsafe fn process(service: DNSServiceRef, until: Instant, done: impl Fn() -> bool) {
+    let fd = DNSServiceRefSockFD(service);
+    while let Some(left) = until.checked_duration_since(Instant::now()) {
+        let mut poll = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ready = libc::poll(&mut poll, 1, left.as_millis().min(i32::MAX as u128) as i32);
+        if ready <= 0 || DNSServiceProcessResult(service) != 0 || done() {
+            return;
+        }
+    }
+}
+
+/// The instances that answer within `wait`, each resolved to a host and port
+pub fn browse(wait: Duration) -> Vec<Host> {
+    let mut found: Vec<Found> = Vec::new();
+    let mut service: DNSServiceRef = std::ptr::null_mut();
+    unsafe {
+        let error = DNSServiceBrowse(
+            &mut service,
+            0,
+            0,
+            SERVICE_TYPE.as_ptr(),
+            std::ptr::null(),
+            browsed,
+            (&mut found as *mut Vec<Found>).cast(),
+        );
+        if error != 0 {
+            return Vec::new();
+        }
+        process(service, Instant::now() + wait, || false);
+        DNSServiceRefDeallocate(service);
+    }
+
+    let mut hosts = Vec::new();
+    for found in found {
+        let (Ok(name), Ok(regtype), Ok(domain)) = (
+            std::ffi::CString::new(found.name.clone()),
+            std::ffi::CString::new(found.regtype),
+            std::ffi::CString::new(found.domain),
+        ) else {
+            continue;
+        };
+        let mut address: Option<(String, u16)> = None;
+        let context: *mut Option<(String, u16)> = &mut address;
+        unsafe {
+            let mut service: DNSServiceRef = std::ptr::null_mut();
+            let error = DNSServiceResolve(
+                &mut service,
+                0,
+                found.interface,
+                name.as_ptr(),
+                regtype.as_ptr(),
+                domain.as_ptr(),
+                resolved,
+                context.cast(),
+            );
+            if error != 0 {
+                continue;
+            }
+            process(service, Instant::now() + Duration::from_secs(1), || {
+                (*context).is_some()
+            });
+            DNSServiceRefDeallocate(service);
+        }
+        if let Some((host, port)) = address {
+            let host = Host {
+                name: found.name,
+                host,
+                port,
+            };
+            // One answer per network interface; one line each is enough
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    hosts
+}
@@ -0,0 +1,88 @@
+//! What a status change amounts to — switching on, switching off, or a timer
+//! running out — for the front-ends' sounds and announcements and for hooks.
+
+use crate::state::Status;
+
+/// A change worth telling someone about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cue {
+    Activated,
+    Deactivated,
+    Expired,
+}
+
+impl Cue {
+    /// As hooks and webhooks name it
+    pub fn name(self) -> &'static str {
+        match self {
+            Cue::Activated => "activate",
+            Cue::Deactivated => "deactivate",
+            Cue::Expired => "timer_expiry",
+        }
+    }
+}
+
+/// Whether a session is on, and when its timer runs out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Seen {
+    pub awake: bool,
+    pub expiry: Option<u64>,
+}
+
+impl Seen {
+    pub fn of(status: &Status) -> Seen {
+        Seen {
+            awake: status.is_awake(),
+            expiry: status.expiry,
+        }
+    }
+}
+
+/// What changed between two statuses. A timer that ran out counts as expiry,
+/// even when overnight mode keeps the session going.
+pub fn cue(before: Seen, after: Seen, now: u64) -> Option<Cue> {
+    let expired = before.awake
+        && before.expiry.is_some_and(|expiry| expiry <= now)
+        && after.expiry != before.expiry;
+    match (before.awake, after.awake) {
+        _ if expired => Some(Cue::Expired),
+        (false, true) => Some(Cue::Activated),
+        (true, false) => Some(Cue::Deactivated),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: Seen = Seen {
+        awake: false,
+        expiry: None,
+    };
+    const ON: Seen = Seen {
+        awake: true,
+        expiry: None,
+    };
+    const TIMED: Seen = Seen {
+        awake: true,
+        expiry: Some(100),
+    };
+
+    #[test]
+    fn switching_on_and_off() {
+        assert_eq!(cue(OFF, ON, 50), Some(Cue::Activated));
+        assert_eq!(cue(ON, OFF, 50), Some(Cue::Deactivated));
+        assert_eq!(cue(ON, ON, 50), None);
+        assert_eq!(cue(OFF, OFF, 50), None);
+    }
+
+    #[test]
+    fn a_timer_running_out_is_expiry_not_a_switch_off() {
+        assert_eq!(cue(TIMED, OFF, 100), Some(Cue::Expired));
+        // Overnight mode carries on after the timer
+        assert_eq!(cue(TIMED, ON, 100), Some(Cue::Expired));
+        // Switched off by hand before it ran out
+        assert_eq!(cue(TIMED, OFF, 60), Some(Cue::Deactivated));
+    }
+}
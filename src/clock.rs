@@ -0,0 +1,82 @@
+//! Times of day as the user reads them ("15:42", "3:42 PM", "15 h 42"):
+//! written by `NSDateFormatter` from the `jmm` template, so the locale and
+//! the 12/24-hour choice in System Settings decide, rather than a fixed
+//! "%H:%M". For the menu, notifications and `awake status`; settings and
+//! rules keep writing and reading `HH:MM`.
+
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_foundation::NSString;
+
+// Hours and minutes, with the locale's hour cycle and day-period marker
+const TEMPLATE: &str = "jmm";
+
+// A formatter for the user's locale, in `time_zone` or the local one
+unsafe fn formatter(time_zone: Option<Retained<AnyObject>>) -> Option<Retained<AnyObject>> {
+    let formatter: Option<Retained<AnyObject>> = msg_send![AnyClass::get(c"NSDateFormatter")?, new];
+    let formatter = formatter?;
+    let template = NSString::from_str(TEMPLATE);
+    let _: () = msg_send![&formatter, setLocalizedDateFormatFromTemplate: &*template];
+    if let Some(time_zone) = time_zone {
+        let _: () = msg_send![&formatter, setTimeZone: &*time_zone];
+    }
+    Some(formatter)
+}
+
+unsafe fn format(formatter: &AnyObject, secs: f64) -> Option<String> {
+    let date: Option<Retained<AnyObject>> = msg_send![
+        AnyClass::get(c"NSDate")?,
+        dateWithTimeIntervalSince1970: secs
+    ];
+    let text: Option<Retained<NSString>> = msg_send![formatter, stringFromDate: &*date?];
+    text.map(|text| text.to_string())
+}
+
+/// The local time of day at `secs` (Unix seconds)
+pub fn at(secs: u64) -> String {
+    unsafe { formatter(None).and_then(|formatter| format(&formatter, secs as f64)) }
+        .unwrap_or_else(|| fallback(local_minutes(secs)))
+}
+
+// Minutes since local midnight at `secs`
+fn local_minutes(secs: u64) -> u32 {
+    unsafe {
+        let t = secs as libc::time_t;
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            return 0;
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
+
+/// `minutes` since midnight, as a time of day
+pub fn time_of_day(minutes: u32) -> String {
+    // Read at midnight UTC on the epoch, so no time zone change can shift it
+    unsafe {
+        let utc: Option<Retained<AnyObject>> = AnyClass::get(c"NSTimeZone")
+            .and_then(|class| msg_send![class, timeZoneForSecondsFromGMT: 0isize]);
+        utc.and_then(|utc| formatter(Some(utc)))
+            .and_then(|formatter| format(&formatter, f64::from(minutes) * 60.0))
+    }
+    .unwrap_or_else(|| fallback(minutes))
+}
+
+// Without Foundation's formatter, the 24-hour clock settings are written in
+fn fallback(minutes: u32) -> String {
+    crate::quiet_hours::format_hhmm(minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_of_day_keep_their_minutes() {
+        for (minutes, digits) in [(0, "00"), (9 * 60 + 5, "05"), (15 * 60 + 42, "42")] {
+            let text = time_of_day(minutes);
+            assert!(text.contains(digits), "{}", text);
+        }
+    }
+}
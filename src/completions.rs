@@ -0,0 +1,366 @@
+//! `awake completions <shell>` — prints a completion script for bash, zsh or
+//! fish, generated from one table of the command line so the three can't drift
+//! from each other.
+
+/// What may follow a flag
+#[derive(Clone, Copy)]
+enum Takes {
+    Nothing,
+    /// A file or folder
+    Path,
+    /// One of these, or (for numbers) anything like them
+    Words(&'static [&'static str]),
+}
+
+struct Flag {
+    name: &'static str,
+    about: &'static str,
+    takes: Takes,
+}
+
+const FLAGS: &[Flag] = &[
+    Flag {
+        name: "--dry-run",
+        about: "Stub out the power backend",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--safe-mode",
+        about: "Start with default settings and no triggers",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--kiosk",
+        about: "Always on, with the controls hidden and a relaunching watchdog",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--mode",
+        about: "What the first activation keeps awake",
+        takes: Takes::Words(&["display", "system", "both", "server"]),
+    },
+    Flag {
+        name: "--watch",
+        about: "Stay awake while a path grows or a script runs",
+        takes: Takes::Path,
+    },
+    Flag {
+        name: "--while-exists",
+        about: "Stay awake while a lockfile exists",
+        takes: Takes::Path,
+    },
+    Flag {
+        name: "--trigger-policy",
+        about: "What switching off by hand means for triggers",
+        takes: Takes::Words(&["edge", "override", "override:30", "override:60"]),
+    },
+    Flag {
+        name: "--trigger-mode",
+        about: "Give a trigger its own mode, like ssh=server",
+        takes: Takes::Words(&[
+            "lockfile=display",
+            "ssh=server",
+            "port=server",
+            "containers=system",
+            "build=system",
+            "leader=display",
+            "mirroring=both",
+            "audio=both",
+            "workday=display",
+            "schedule=display",
+        ]),
+    },
+    Flag {
+        name: "--max-session",
+        about: "Cap every session, in minutes",
+        takes: Takes::Words(&["30", "60", "120", "240", "480"]),
+    },
+    Flag {
+        name: "--overnight",
+        about: "After the job, stay on until charged to this percentage",
+        takes: Takes::Words(&["80", "90", "100"]),
+    },
+    Flag {
+        name: "--display-grace",
+        about: "Restart the display's idle countdown when a session ends",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--battery-timer",
+        about: "On battery, switching on starts an hour's timer",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--timer-precision",
+        about: "Show the time left to the minute, to the second, or rounded",
+        takes: Takes::Words(&["minutes", "seconds", "rounded"]),
+    },
+    Flag {
+        name: "--while-ssh",
+        about: "Stay awake while an SSH client is connected",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--while-port",
+        about: "Stay awake while a client is connected to these ports",
+        takes: Takes::Words(&["3000", "8080", "5900"]),
+    },
+    Flag {
+        name: "--while-containers",
+        about: "Stay awake while containers or virtual machines run",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--while-building",
+        about: "Stay awake while Xcode, Cargo or a compiler is building",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--while-mirroring",
+        about: "Keep the display awake while the screen is mirrored",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--while-wireless-audio",
+        about: "Stay awake while audio plays on an AirPlay or Bluetooth speaker",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--workday",
+        about: "Switch on when the lid opens in these hours, off when they end",
+        takes: Takes::Words(&["07:00-18:00", "09:00-17:30"]),
+    },
+    Flag {
+        name: "--between",
+        about: "Stay awake between two times, which may be sunrise or sunset",
+        takes: Takes::Words(&["sunset-00:00", "sunset-30-sunrise", "09:00-17:00"]),
+    },
+    Flag {
+        name: "--location",
+        about: "Latitude and longitude for sunrise and sunset",
+        takes: Takes::Words(&["51.5,-0.12"]),
+    },
+    Flag {
+        name: "--remind-after",
+        about: "Hours before asking if an open-ended session is still needed",
+        takes: Takes::Words(&["0", "3", "6", "12"]),
+    },
+    Flag {
+        name: "--follow",
+        about: "Stay awake while a leader machine is awake",
+        takes: Takes::Words(&["mini.local"]),
+    },
+    Flag {
+        name: "--remote",
+        about: "Serve a remote control on this port to the local network",
+        takes: Takes::Words(&["8765"]),
+    },
+    Flag {
+        name: "--webhook",
+        about: "POST each change as JSON to this URL",
+        takes: Takes::Words(&["https://ntfy.sh/"]),
+    },
+    Flag {
+        name: "--crash-reports",
+        about: "Save a report with a backtrace if Awake crashes",
+        takes: Takes::Nothing,
+    },
+    Flag {
+        name: "--host",
+        about: "Control another machine's Awake: on, off, toggle or status",
+        takes: Takes::Words(&["localhost"]),
+    },
+];
+
+/// Subcommands, with what each takes after it
+const SUBCOMMANDS: &[(&str, &str, &[&str])] = &[
+    (
+        "selftest",
+        "Check the power backend and timers",
+        &["--dry-run"],
+    ),
+    (
+        "status",
+        "Print the running app's state",
+        &[
+            "--quiet",
+            "--porcelain",
+            "--prompt",
+            "--arch",
+            "--alfred",
+            "--raycast",
+        ],
+    ),
+    ("hosts", "List Awake instances on the local network", &[]),
+    (
+        "completions",
+        "Print a shell completion script",
+        &["bash", "zsh", "fish"],
+    ),
+];
+
+fn words(list: &[&str]) -> String {
+    list.join(" ")
+}
+
+fn bash() -> String {
+    let mut out = String::from(
+        "_awake() {\n    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    // Past the first word, a subcommand decides what follows it
+    out.push_str("    [ \"$COMP_CWORD\" -gt 1 ] && case \"${COMP_WORDS[1]}\" in\n");
+    for (name, _, takes) in SUBCOMMANDS {
+        out.push_str(&format!(
+            "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n",
+            name,
+            words(takes)
+        ));
+    }
+    out.push_str("    esac\n    case \"$prev\" in\n");
+    for flag in FLAGS {
+        match flag.takes {
+            Takes::Nothing => {}
+            Takes::Path => out.push_str(&format!(
+                "        {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;\n",
+                flag.name
+            )),
+            Takes::Words(list) => out.push_str(&format!(
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n",
+                flag.name,
+                words(list)
+            )),
+        }
+    }
+    let flags: Vec<&str> = FLAGS.iter().map(|flag| flag.name).collect();
+    let mut first: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _, _)| *name).collect();
+    first.extend(&flags);
+    out.push_str(&format!(
+        "    esac\n    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n    else\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n    fi\n}}\ncomplete -F _awake awake\n",
+        words(&first),
+        words(&flags)
+    ));
+    out
+}
+
+// zsh's `_arguments` spec quotes descriptions in single quotes and brackets
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+fn zsh() -> String {
+    let mut out = String::from("#compdef awake\n\n_awake() {\n    case $words[2] in\n");
+    for (name, _, takes) in SUBCOMMANDS {
+        out.push_str(&format!(
+            "        {}) _values '{}' {}; return ;;\n",
+            name,
+            name,
+            words(takes)
+        ));
+    }
+    out.push_str("    esac\n    local -a subcommands=(\n");
+    for (name, about, _) in SUBCOMMANDS {
+        out.push_str(&format!("        '{}:{}'\n", name, zsh_escape(about)));
+    }
+    out.push_str(
+        "    )\n    _arguments \\\n        '1:: :{_describe subcommand subcommands}' \\\n",
+    );
+    for flag in FLAGS {
+        let value = match flag.takes {
+            Takes::Nothing => String::new(),
+            Takes::Path => ":path:_files".into(),
+            Takes::Words(list) => format!(":value:({})", words(list)),
+        };
+        out.push_str(&format!(
+            "        '{}[{}]{}' \\\n",
+            flag.name,
+            zsh_escape(flag.about),
+            value
+        ));
+    }
+    out.truncate(out.len() - " \\\n".len());
+    out.push_str("\n}\n\n_awake \"$@\"\n");
+    out
+}
+
+// Inside fish's single quotes only a quote or a backslash needs escaping
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish() -> String {
+    let mut out = String::from("complete -c awake -f\n");
+    for (name, about, takes) in SUBCOMMANDS {
+        out.push_str(&format!(
+            "complete -c awake -n __fish_use_subcommand -a {} -d '{}'\n",
+            name,
+            fish_escape(about)
+        ));
+        out.push_str(&format!(
+            "complete -c awake -n '__fish_seen_subcommand_from {}' -a '{}'\n",
+            name,
+            words(takes)
+        ));
+    }
+    for flag in FLAGS {
+        let takes = match flag.takes {
+            Takes::Nothing => String::new(),
+            Takes::Path => " -r -F".into(),
+            Takes::Words(list) => format!(" -x -a '{}'", words(list)),
+        };
+        out.push_str(&format!(
+            "complete -c awake -n __fish_use_subcommand -l {}{} -d '{}'\n",
+            flag.name.trim_start_matches("--"),
+            takes,
+            fish_escape(flag.about)
+        ));
+    }
+    out
+}
+
+/// Prints the script for the shell `args` name; returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    let script = match args.first().map(String::as_str) {
+        Some("bash") => bash(),
+        Some("zsh") => zsh(),
+        Some("fish") => fish(),
+        _ => crate::usage(),
+    };
+    print!("{}", script);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shell_knows_every_flag_and_subcommand() {
+        for script in [bash(), zsh(), fish()] {
+            for flag in FLAGS {
+                assert!(
+                    script.contains(flag.name.trim_start_matches("--")),
+                    "{}",
+                    flag.name
+                );
+            }
+            for (name, _, takes) in SUBCOMMANDS {
+                assert!(script.contains(name));
+                assert!(script.contains(&words(takes)));
+            }
+        }
+    }
+
+    #[test]
+    fn flag_values_complete() {
+        assert!(bash().contains(
+            r#"--mode) COMPREPLY=($(compgen -W "display system both server" -- "$cur")); return ;;"#
+        ));
+        assert!(
+            zsh().contains("'--watch[Stay awake while a path grows or a script runs]:path:_files'")
+        );
+        assert!(fish()
+            .contains("complete -c awake -n __fish_use_subcommand -l overnight -x -a '80 90 100'"));
+    }
+}
@@ -0,0 +1,82 @@
+//! Opt-in crash reports: with `--crash-reports` or `CrashReports`, a panic
+//! writes what went wrong and a backtrace to `crash-report.txt` next to the
+//! event log before Awake exits, so a bug report can carry more than "it
+//! quit". On macOS the next launch offers the report for review and copying,
+//! then clears it; elsewhere it stays in the file.
+
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+
+const REPORT_FILE: &str = "crash-report.txt";
+
+fn report(message: &str, location: &str, backtrace: &str, ts: u64) -> String {
+    format!(
+        "Awake v{} ({} {}) crashed at {} (Unix time)\n\n{}\nat {}\n\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        crate::arch::describe(crate::arch::compiled_for(), crate::arch::is_translated()),
+        ts,
+        message,
+        location,
+        backtrace.trim_end()
+    )
+}
+
+fn message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "a panic without a message".into())
+}
+
+/// Writes a report for any panic from now on, then carries on as before
+pub fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = crate::app_support_dir() {
+            let location = info
+                .location()
+                .map_or_else(|| "an unknown place".into(), |l| l.to_string());
+            let text = report(
+                &message(info),
+                &location,
+                &Backtrace::force_capture().to_string(),
+                crate::now_secs(),
+            );
+            let _ = fs::create_dir_all(&dir);
+            let _ = fs::write(dir.join(REPORT_FILE), text);
+        }
+        previous(info);
+    }));
+}
+
+/// The report the last run left, if it crashed; read once, then removed
+#[cfg(target_os = "macos")]
+pub fn take_report() -> Option<String> {
+    let path = crate::app_support_dir()?.join(REPORT_FILE);
+    let text = fs::read_to_string(&path).ok()?;
+    let _ = fs::remove_file(&path);
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_report_says_what_and_where() {
+        let text = report(
+            "index out of bounds",
+            "src/state.rs:10:5",
+            "   0: awake::main\n",
+            7,
+        );
+        assert!(text.starts_with(&format!("Awake v{} (", env!("CARGO_PKG_VERSION"))));
+        assert!(text
+            .contains("crashed at 7 (Unix time)\n\nindex out of bounds\nat src/state.rs:10:5\n"));
+        assert!(text.ends_with("   0: awake::main\n"));
+    }
+}
@@ -0,0 +1,197 @@
+//! How a time left is written, for the menu bar title, the tooltip, the
+//! menu's status line and `awake status`: to the minute ("42 min", the
+//! default), to the second ("41:37"), or rounded ("~2 h").
+//!
+//! Picked with `--timer-precision` or `TimerPrecision`. A rounded figure with
+//! a fraction takes the locale's decimal separator ("~1,5 h" in German).
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How precisely a time left is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    Minutes,
+    Seconds,
+    Rounded,
+}
+
+impl Precision {
+    const ALL: [Precision; 3] = [Precision::Minutes, Precision::Seconds, Precision::Rounded];
+
+    pub fn parse(name: &str) -> Option<Precision> {
+        match name.trim() {
+            "minutes" => Some(Precision::Minutes),
+            "seconds" => Some(Precision::Seconds),
+            "rounded" => Some(Precision::Rounded),
+            _ => None,
+        }
+    }
+}
+
+// `Precision::ALL`'s index of the one in force
+static PRECISION: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_precision(precision: Precision) {
+    let index = Precision::ALL.iter().position(|&p| p == precision);
+    PRECISION.store(index.unwrap_or(0) as u8, Ordering::Relaxed);
+}
+
+pub fn precision() -> Precision {
+    Precision::ALL[PRECISION.load(Ordering::Relaxed) as usize]
+}
+
+/// The current locale's decimal separator
+#[cfg(target_os = "macos")]
+fn decimal_separator() -> char {
+    use objc2::msg_send;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::NSString;
+
+    let Some(class) = AnyClass::get(c"NSLocale") else {
+        return '.';
+    };
+    let separator: Option<Retained<NSString>> = unsafe {
+        let locale: *mut AnyObject = msg_send![class, currentLocale];
+        msg_send![locale, decimalSeparator]
+    };
+    separator
+        .and_then(|separator| separator.to_string().chars().next())
+        .unwrap_or('.')
+}
+
+/// The current locale's decimal separator, from `LC_ALL`, `LC_NUMERIC` or
+/// `LANG` (`de_DE.UTF-8`)
+#[cfg(not(target_os = "macos"))]
+fn decimal_separator() -> char {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()));
+    locale.map_or('.', |locale| separator_for(&locale))
+}
+
+// Languages that write a decimal comma; the rest are taken to use a point
+#[cfg(not(target_os = "macos"))]
+fn separator_for(locale: &str) -> char {
+    const COMMA: &[&str] = &[
+        "bg", "ca", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "is", "it",
+        "lt", "lv", "nb", "nl", "nn", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk",
+        "vi",
+    ];
+    let language = locale.split(['_', '-', '.']).next().unwrap_or("");
+    if COMMA.contains(&language) {
+        ','
+    } else {
+        '.'
+    }
+}
+
+// "41:37", or "1:05:12" from an hour
+fn clock(secs: u64) -> String {
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match hours {
+        0 => format!("{}:{:02}", minutes, secs),
+        hours => format!("{}:{:02}:{:02}", hours, minutes, secs),
+    }
+}
+
+// To the nearest 5 minutes under an hour, then half hours, then from 10 hours
+// whole ones: ("45", "min") or ("1,5", "h")
+fn rounded(secs: u64, separator: char) -> (String, &'static str) {
+    let minutes = (secs + 30) / 60;
+    let five = ((minutes + 2) / 5 * 5).max(5);
+    if five < 60 {
+        return (five.to_string(), "min");
+    }
+    if minutes >= 10 * 60 {
+        return (((minutes + 30) / 60).to_string(), "h");
+    }
+    let halves = (minutes + 15) / 30;
+    match halves % 2 {
+        0 => ((halves / 2).to_string(), "h"),
+        _ => (format!("{}{}5", halves / 2, separator), "h"),
+    }
+}
+
+/// `secs` left in a sentence's words: "42 min", "41:37" or "~2 h"
+pub fn left(secs: u64) -> String {
+    left_as(secs, precision(), decimal_separator())
+}
+
+fn left_as(secs: u64, precision: Precision, separator: char) -> String {
+    match precision {
+        Precision::Minutes => format!("{} min", secs.div_ceil(60)),
+        Precision::Seconds => clock(secs),
+        Precision::Rounded => {
+            let (figure, unit) = rounded(secs, separator);
+            format!("~{} {}", figure, unit)
+        }
+    }
+}
+
+/// `secs` left in as little room as will do: "42m", "1h05m", "41:37" or "~2h"
+pub fn compact(secs: u64) -> String {
+    compact_as(secs, precision(), decimal_separator())
+}
+
+fn compact_as(secs: u64, precision: Precision, separator: char) -> String {
+    match precision {
+        Precision::Minutes => {
+            let minutes = secs.div_ceil(60);
+            match minutes / 60 {
+                0 => format!("{}m", minutes),
+                hours => format!("{}h{:02}m", hours, minutes % 60),
+            }
+        }
+        Precision::Seconds => clock(secs),
+        Precision::Rounded => {
+            let (figure, unit) = rounded(secs, separator);
+            format!("~{}{}", figure, &unit[..1])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_precision_reads_its_own_way() {
+        let cases = [
+            (Precision::Minutes, 2497, "42 min", "42m"),
+            (Precision::Minutes, 3900, "65 min", "1h05m"),
+            (Precision::Seconds, 2497, "41:37", "41:37"),
+            (Precision::Seconds, 3912, "1:05:12", "1:05:12"),
+            (Precision::Rounded, 2497, "~40 min", "~40m"),
+            (Precision::Rounded, 7000, "~2 h", "~2h"),
+            (Precision::Rounded, 5400, "~1,5 h", "~1,5h"),
+        ];
+        for (precision, secs, words, short) in cases {
+            assert_eq!(left_as(secs, precision, ','), words);
+            assert_eq!(compact_as(secs, precision, ','), short);
+        }
+    }
+
+    #[test]
+    fn rounding_never_says_nothing_is_left() {
+        assert_eq!(rounded(20, '.'), ("5".into(), "min"));
+        assert_eq!(rounded(58 * 60, '.'), ("1".into(), "h"));
+        assert_eq!(rounded(11 * 3600 + 1200, '.'), ("11".into(), "h"));
+    }
+
+    #[test]
+    fn precisions_parse() {
+        assert_eq!(Precision::parse("seconds"), Some(Precision::Seconds));
+        assert_eq!(Precision::parse(" rounded"), Some(Precision::Rounded));
+        assert_eq!(Precision::parse("hours"), None);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn separators_follow_the_language() {
+        assert_eq!(separator_for("de_DE.UTF-8"), ',');
+        assert_eq!(separator_for("en_GB.UTF-8"), '.');
+        assert_eq!(separator_for("C"), '.');
+    }
+}
@@ -0,0 +1,66 @@
+//! The one error type the core returns. The core only records errors (in the
+//! event log); each front-end decides how to present what comes back.
+
+use std::fmt;
+#[cfg(target_os = "macos")]
+use std::io;
+
+use crate::keepawake::{self, Kind};
+
+#[derive(Debug)]
+pub enum AwakeError {
+    /// The power backend refused an assertion; `code` is the platform's own
+    /// (IOReturn, `GetLastError`, ...)
+    AssertionFailed { kind: Kind, code: i32 },
+    /// Releasing a held assertion failed
+    ReleaseFailed { code: i32 },
+    /// Writing or removing the LaunchAgent plist failed
+    #[cfg(target_os = "macos")]
+    LaunchAgentIo(io::Error),
+    #[cfg(target_os = "macos")]
+    HomeNotSet,
+    /// The path of the running executable couldn't be determined
+    #[cfg(target_os = "macos")]
+    AppPathUnknown,
+}
+
+impl fmt::Display for AwakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwakeError::AssertionFailed { kind, code } => write!(
+                f,
+                "couldn't take a {} assertion: {}",
+                kind.name(),
+                keepawake::describe_code(*code)
+            ),
+            AwakeError::ReleaseFailed { code } => write!(
+                f,
+                "couldn't release an assertion: {}",
+                keepawake::describe_code(*code)
+            ),
+            #[cfg(target_os = "macos")]
+            AwakeError::LaunchAgentIo(e) => write!(f, "LaunchAgent plist: {}", e),
+            #[cfg(target_os = "macos")]
+            AwakeError::HomeNotSet => write!(f, "HOME not set; cannot manage launch agent"),
+            #[cfg(target_os = "macos")]
+            AwakeError::AppPathUnknown => write!(f, "couldn't determine Awake's own path"),
+        }
+    }
+}
+
+impl std::error::Error for AwakeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(target_os = "macos")]
+            AwakeError::LaunchAgentIo(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<io::Error> for AwakeError {
+    fn from(e: io::Error) -> Self {
+        AwakeError::LaunchAgentIo(e)
+    }
+}
@@ -0,0 +1,182 @@
+//! Append-only JSONL log of state changes, under
+//! `~/Library/Application Support/Awake/events.jsonl`.
+//!
+//! One JSON object per line with a `ts` (Unix seconds) and `event` field. When the
+//! file passes `MAX_LOG_BYTES` it is rotated to `events.1.jsonl` (and so on, keeping
+//! `KEEP_ROTATED` old files).
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LOG_FILE: &str = "events.jsonl";
+const MAX_LOG_BYTES: u64 = 512 * 1024;
+const KEEP_ROTATED: u32 = 3;
+
+// Serializes appends and rotation across threads
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+pub enum Event<'a> {
+    Activated {
+        mode: &'a str,
+        source: &'a str,
+        reason: &'a str,
+    },
+    Deactivated,
+    ModeChanged {
+        mode: &'a str,
+    },
+    AssertionError {
+        assertion_type: &'a str,
+        code: i32,
+    },
+    /// A stored preference out of range or unreadable, brought into range or
+    /// ignored rather than stopping the app
+    BadSetting {
+        key: &'a str,
+        value: &'a str,
+    },
+}
+
+impl Event<'_> {
+    fn to_json(&self, ts: u64) -> String {
+        match self {
+            Event::Activated {
+                mode,
+                source,
+                reason,
+            } => format!(
+                r#"{{"ts":{},"event":"activated","mode":{},"source":{},"reason":{}}}"#,
+                ts,
+                json_string(mode),
+                json_string(source),
+                json_string(reason)
+            ),
+            Event::Deactivated => format!(r#"{{"ts":{},"event":"deactivated"}}"#, ts),
+            Event::ModeChanged { mode } => format!(
+                r#"{{"ts":{},"event":"mode_changed","mode":{}}}"#,
+                ts,
+                json_string(mode)
+            ),
+            Event::AssertionError {
+                assertion_type,
+                code,
+            } => format!(
+                r#"{{"ts":{},"event":"assertion_error","assertion_type":{},"code":{}}}"#,
+                ts,
+                json_string(assertion_type),
+                code
+            ),
+            Event::BadSetting { key, value } => format!(
+                r#"{{"ts":{},"event":"bad_setting","key":{},"value":{}}}"#,
+                ts,
+                json_string(key),
+                json_string(value)
+            ),
+        }
+    }
+}
+
+pub fn log(event: Event) {
+    let Some(dir) = crate::app_support_dir() else {
+        return;
+    };
+    let line = event.to_json(crate::now_secs());
+
+    let _guard = LOG_LOCK.lock().unwrap();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Failed to create {}: {}", dir.display(), e);
+        return;
+    }
+
+    let path = dir.join(LOG_FILE);
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+        rotate(&dir);
+    }
+
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| writeln!(f, "{}", line));
+    if let Err(e) = result {
+        eprintln!("Failed to write event log: {}", e);
+    }
+}
+
+// events.2.jsonl -> events.3.jsonl, events.1.jsonl -> events.2.jsonl, events.jsonl -> events.1.jsonl
+fn rotate(dir: &Path) {
+    let _ = fs::remove_file(rotated_path(dir, KEEP_ROTATED));
+    for n in (1..KEEP_ROTATED).rev() {
+        let _ = fs::rename(rotated_path(dir, n), rotated_path(dir, n + 1));
+    }
+    if let Err(e) = fs::rename(dir.join(LOG_FILE), rotated_path(dir, 1)) {
+        eprintln!("Failed to rotate event log: {}", e);
+    }
+}
+
+/// Every log file there is, oldest first
+pub fn files() -> Vec<PathBuf> {
+    let Some(dir) = crate::app_support_dir() else {
+        return Vec::new();
+    };
+    (1..=KEEP_ROTATED)
+        .rev()
+        .map(|n| rotated_path(&dir, n))
+        .chain(Some(dir.join(LOG_FILE)))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+fn rotated_path(dir: &Path, n: u32) -> PathBuf {
+    dir.join(format!("events.{}.jsonl", n))
+}
+
+/// The value after `"key":` in one flat JSON object: a string unescaped, or a
+/// bare number, `true`, `false` or `null` as written
+pub fn json_field(line: &str, key: &str) -> Option<String> {
+    let rest = &line[line.find(&json_string(key))? + key.len() + 2..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let Some(quoted) = rest.strip_prefix('"') else {
+        let end = rest.find([',', '}']).unwrap_or(rest.len());
+        return Some(rest[..end].trim().to_string());
+    };
+    let mut out = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => out.push(c),
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,149 @@
+//! Hooks: programs of the user's own, run as Awake switches on, switches off
+//! or a timer runs out, to chain automations Awake doesn't build in (pausing
+//! backups, posting to a chat).
+//!
+//! A hook is an executable in the `hooks` folder next to the event log, named
+//! for its event: `on_activate`, `on_deactivate` or `on_timer_expiry` (with
+//! `.cmd` or `.bat` on Windows). It runs in the background, not waited for,
+//! with the status after the change in its environment:
+//!
+//! - `AWAKE_EVENT`: `activate`, `deactivate` or `timer_expiry`
+//! - `AWAKE_ACTIVE`: `1` or `0`
+//! - `AWAKE_MODE`: e.g. `display+system`
+//! - `AWAKE_SOURCE` and `AWAKE_REASON`: empty once off
+//! - `AWAKE_EXPIRES`: when the timer runs out, in Unix seconds, or empty
+//!
+//! None run in safe mode.
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+use crate::change::{self, Cue, Seen};
+use crate::state::{self, Status};
+
+const DIR: &str = "hooks";
+
+thread_local! {
+    static LAST: Cell<Seen> = const { Cell::new(Seen { awake: false, expiry: None }) };
+}
+
+fn environment(cue: Cue, status: &Status) -> Vec<(&'static str, String)> {
+    let (source, reason) = match status.activation_source() {
+        Some(source) => (source.kind().to_string(), source.describe()),
+        None => Default::default(),
+    };
+    vec![
+        ("AWAKE_EVENT", cue.name().into()),
+        (
+            "AWAKE_ACTIVE",
+            if status.is_awake() { "1" } else { "0" }.into(),
+        ),
+        ("AWAKE_MODE", status.kinds.to_string()),
+        ("AWAKE_SOURCE", source),
+        ("AWAKE_REASON", reason),
+        (
+            "AWAKE_EXPIRES",
+            status.expiry.map(|e| e.to_string()).unwrap_or_default(),
+        ),
+    ]
+}
+
+#[cfg(unix)]
+fn find(dir: &Path, name: &str) -> Option<(PathBuf, Command)> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    let executable = path
+        .metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0);
+    executable.then(|| (path.clone(), Command::new(path)))
+}
+
+#[cfg(windows)]
+fn find(dir: &Path, name: &str) -> Option<(PathBuf, Command)> {
+    ["cmd", "bat"].into_iter().find_map(|extension| {
+        let path = dir.join(name).with_extension(extension);
+        path.is_file().then(|| {
+            use std::os::windows::process::CommandExt;
+            use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+
+            let mut command = Command::new("cmd");
+            // Without a console window flashing up from a tray app
+            command
+                .arg("/C")
+                .arg(&path)
+                .creation_flags(CREATE_NO_WINDOW);
+            (path, command)
+        })
+    })
+}
+
+fn run(cue: Cue, status: &Status) {
+    let Some(dir) = crate::app_support_dir().map(|dir| dir.join(DIR)) else {
+        return;
+    };
+    let name = format!("on_{}", cue.name());
+    let Some((path, mut command)) = find(&dir, &name) else {
+        return;
+    };
+    command.envs(environment(cue, status)).current_dir(&dir);
+    match command.spawn() {
+        // Waited for elsewhere, so it neither holds up the change nor lingers
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("Failed to run {}: {}", path.display(), e),
+    }
+}
+
+fn changed(status: &Status) {
+    let seen = Seen::of(status);
+    let before = LAST.with(|last| last.replace(seen));
+    if let Some(cue) = change::cue(before, seen, crate::now_secs()) {
+        run(cue, status);
+    }
+}
+
+/// Runs the hooks from now on
+pub fn install() {
+    state::on_state_change(changed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::state::Source;
+
+    #[test]
+    fn the_environment_describes_the_change() {
+        let source = Source::Manual;
+        let status = Status {
+            kinds: Mode::Both.kinds(),
+            source: Some(&source),
+            expiry: Some(3600),
+            override_until: None,
+            triggers: &[],
+            overnight: None,
+            mode_return: None,
+        };
+        let env = environment(Cue::Activated, &status);
+        assert!(env.contains(&("AWAKE_EVENT", "activate".into())));
+        assert!(env.contains(&("AWAKE_ACTIVE", "1".into())));
+        assert!(env.contains(&("AWAKE_MODE", "display+system".into())));
+        assert!(env.contains(&("AWAKE_SOURCE", "manual".into())));
+        assert!(env.contains(&("AWAKE_EXPIRES", "3600".into())));
+
+        let off = Status {
+            source: None,
+            expiry: None,
+            ..status
+        };
+        let env = environment(Cue::Expired, &off);
+        assert!(env.contains(&("AWAKE_EVENT", "timer_expiry".into())));
+        assert!(env.contains(&("AWAKE_SOURCE", String::new())));
+        assert!(env.contains(&("AWAKE_EXPIRES", String::new())));
+    }
+}
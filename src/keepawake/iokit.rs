@@ -0,0 +1,184 @@
+//! macOS backend: IOKit power assertions, via the `iopm-assertion` crate
+
+use std::sync::Mutex;
+
+use iopm_assertion::{Assertion, AssertionKind, Error};
+
+use super::{Kind, PowerManager};
+
+pub struct IoKit {
+    held: Mutex<Vec<Assertion>>,
+}
+
+pub static IOKIT: IoKit = IoKit {
+    held: Mutex::new(Vec::new()),
+};
+
+fn assertion_kind(kind: Kind) -> AssertionKind {
+    match kind {
+        Kind::Display => AssertionKind::PreventUserIdleDisplaySleep,
+        Kind::System => AssertionKind::PreventUserIdleSystemSleep,
+        Kind::Disk => AssertionKind::PreventDiskIdle,
+        Kind::NetworkClient => AssertionKind::NetworkClientActive,
+        Kind::UserActivity => AssertionKind::UserIsActive,
+    }
+}
+
+impl PowerManager for IoKit {
+    fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32> {
+        let assertion = Assertion::new(assertion_kind(kind), reason).map_err(Error::code)?;
+        let id = assertion.id();
+        self.held.lock().unwrap().push(assertion);
+        Ok(id)
+    }
+
+    fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+        let assertion = {
+            let mut held = self.held.lock().unwrap();
+            let Some(index) = held.iter().position(|a| a.id() == assertion_id) else {
+                return Err(Error::NotFound.code());
+            };
+            held.swap_remove(index)
+        };
+        assertion.release().map_err(Error::code)
+    }
+
+    fn declare_user_activity(&self, reason: &str) -> Result<(), i32> {
+        // Released at once: the declaration has already reset the idle timers
+        Assertion::declare_user_activity(reason)
+            .and_then(Assertion::release)
+            .map_err(Error::code)
+    }
+}
+
+// Run on a real Mac with `cargo test --features iokit-tests -- --ignored`
+#[cfg(all(test, feature = "iokit-tests"))]
+mod iokit_tests {
+    use super::*;
+    use crate::error::AwakeError;
+    use crate::keepawake::{KindSet, PowerSession};
+    use core_foundation::array::CFArray;
+    use core_foundation::base::CFType;
+    use core_foundation::base::TCFType;
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::number::CFNumber;
+    use core_foundation::string::CFString;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMCopyAssertionsByProcess(assertions_by_pid: *mut CFDictionaryRef) -> i32;
+    }
+
+    /// Real IOKit for displays; system assertions are requested with a bogus type
+    /// so IOKit itself rejects them, to exercise the rollback path
+    struct FailingSystem;
+
+    impl PowerManager for FailingSystem {
+        fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32> {
+            match kind {
+                Kind::System => Err(Assertion::with_type_name("NotARealAssertionType", reason)
+                    .expect_err("IOKit accepted a bogus assertion type")
+                    .code()),
+                _ => IOKIT.create_assertion(kind, reason),
+            }
+        }
+
+        fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+            IOKIT.release_assertion(assertion_id)
+        }
+
+        fn declare_user_activity(&self, reason: &str) -> Result<(), i32> {
+            IOKIT.declare_user_activity(reason)
+        }
+    }
+
+    /// Assertion names the OS currently holds on behalf of this process
+    fn our_assertion_names() -> Vec<String> {
+        let mut raw: CFDictionaryRef = std::ptr::null();
+        let result = unsafe { IOPMCopyAssertionsByProcess(&mut raw) };
+        assert_eq!(result, 0, "IOPMCopyAssertionsByProcess failed");
+        if raw.is_null() {
+            return Vec::new();
+        }
+        let by_pid: CFDictionary<CFNumber, CFArray<CFDictionary<CFString, CFType>>> =
+            unsafe { TCFType::wrap_under_create_rule(raw) };
+
+        let pid = CFNumber::from(std::process::id() as i32);
+        let Some(assertions) = by_pid.find(&pid) else {
+            return Vec::new();
+        };
+        let name_key = CFString::new("AssertName");
+        assertions
+            .iter()
+            .filter_map(|a| a.find(&name_key)?.downcast::<CFString>())
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    fn unique_name(test: &str) -> String {
+        format!("awake-iokit-test-{}-{}", test, std::process::id())
+    }
+
+    #[test]
+    #[ignore]
+    fn create_is_visible_and_release_removes_it() {
+        for kind in Kind::ALL {
+            let name = unique_name(kind.name());
+            let aid = IOKIT.create_assertion(kind, &name).unwrap();
+            assert_ne!(aid, 0);
+            assert!(our_assertion_names().contains(&name));
+
+            IOKIT.release_assertion(aid).unwrap();
+            assert!(!our_assertion_names().contains(&name));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn session_holds_every_kind() {
+        let name = unique_name("all");
+        let session =
+            PowerSession::begin_with(&IOKIT, KindSet::of(&[Kind::Display, Kind::System]), &name)
+                .unwrap();
+        let held = our_assertion_names().iter().filter(|n| **n == name).count();
+        assert_eq!(held, 2);
+
+        session.end().unwrap();
+        assert!(!our_assertion_names().contains(&name));
+    }
+
+    #[test]
+    #[ignore]
+    fn dropped_session_releases_its_assertions() {
+        let name = unique_name("drop");
+        let session =
+            PowerSession::begin_with(&IOKIT, KindSet::of(&[Kind::Display, Kind::System]), &name)
+                .unwrap();
+        drop(session);
+        assert!(!our_assertion_names().contains(&name));
+    }
+
+    #[test]
+    #[ignore]
+    fn session_rolls_back_on_partial_failure() {
+        let name = unique_name("rollback");
+        let Err(err) = PowerSession::begin_with(
+            &FailingSystem,
+            KindSet::of(&[Kind::Display, Kind::System]),
+            &name,
+        ) else {
+            panic!("session began despite a failing assertion");
+        };
+        assert!(matches!(
+            err,
+            AwakeError::AssertionFailed { kind: Kind::System, code } if code != 0
+        ));
+        assert!(!our_assertion_names().contains(&name));
+    }
+
+    #[test]
+    #[ignore]
+    fn release_of_unknown_id_fails() {
+        assert!(IOKIT.release_assertion(u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Linux backend: D-Bus inhibitors. `Kind::System` takes a logind `idle:sleep`
+//! lock, held for as long as we keep its file descriptor open; `Kind::Display`
+//! and `Kind::UserActivity` ask the desktop's `org.freedesktop.ScreenSaver`
+//! (GNOME, KDE and most Wayland compositors' session services implement it),
+//! falling back to a logind `idle` lock where there is none. Disk and network
+//! kinds map onto the nearest logind locks.
+//!
+//! The Wayland `idle-inhibit` protocol isn't used: it inhibits per surface, and
+//! a tray icon has no surface of its own.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedFd;
+
+use super::{Kind, PowerManager};
+
+const APP_NAME: &str = "Awake";
+
+// D-Bus failures have no numeric code of their own; the details are printed
+const DBUS_ERROR: i32 = -1;
+
+enum Inhibitor {
+    // Released by closing the descriptor
+    Logind(OwnedFd),
+    ScreenSaver(u32),
+}
+
+struct Held {
+    id: u32,
+    inhibitor: Inhibitor,
+}
+
+struct Buses {
+    system: Option<Connection>,
+    // Kept open for the whole run: the screensaver service drops a cookie when
+    // the connection that took it goes away
+    session: Option<Connection>,
+}
+
+pub struct Inhibitors {
+    next_id: AtomicU32,
+    buses: Mutex<Buses>,
+    held: Mutex<Vec<Held>>,
+}
+
+pub static INHIBITORS: Inhibitors = Inhibitors {
+    next_id: AtomicU32::new(1),
+    buses: Mutex::new(Buses {
+        system: None,
+        session: None,
+    }),
+    held: Mutex::new(Vec::new()),
+};
+
+pub fn describe_code(code: i32) -> String {
+    match code {
+        DBUS_ERROR => "D-Bus call failed".into(),
+        code => format!("error {}", code),
+    }
+}
+
+fn dbus_error(call: &str, e: zbus::Error) -> i32 {
+    eprintln!("{} failed: {}", call, e);
+    DBUS_ERROR
+}
+
+fn connect(
+    slot: &mut Option<Connection>,
+    open: fn() -> zbus::Result<Connection>,
+) -> zbus::Result<Connection> {
+    if let Some(conn) = slot {
+        return Ok(conn.clone());
+    }
+    let conn = open()?;
+    *slot = Some(conn.clone());
+    Ok(conn)
+}
+
+impl Inhibitors {
+    fn system_bus(&self) -> zbus::Result<Connection> {
+        connect(&mut self.buses.lock().unwrap().system, Connection::system)
+    }
+
+    fn session_bus(&self) -> zbus::Result<Connection> {
+        connect(&mut self.buses.lock().unwrap().session, Connection::session)
+    }
+
+    fn logind_inhibit(&self, what: &str, reason: &str) -> Result<Inhibitor, i32> {
+        let call = || -> zbus::Result<OwnedFd> {
+            let reply = self.system_bus()?.call_method(
+                Some("org.freedesktop.login1"),
+                "/org/freedesktop/login1",
+                Some("org.freedesktop.login1.Manager"),
+                "Inhibit",
+                &(what, APP_NAME, reason, "block"),
+            )?;
+            reply.body().deserialize()
+        };
+        call()
+            .map(Inhibitor::Logind)
+            .map_err(|e| dbus_error("login1.Manager.Inhibit", e))
+    }
+
+    fn screensaver_inhibit(&self, reason: &str) -> zbus::Result<Inhibitor> {
+        let reply = self.session_bus()?.call_method(
+            Some("org.freedesktop.ScreenSaver"),
+            "/org/freedesktop/ScreenSaver",
+            Some("org.freedesktop.ScreenSaver"),
+            "Inhibit",
+            &(APP_NAME, reason),
+        )?;
+        reply.body().deserialize().map(Inhibitor::ScreenSaver)
+    }
+
+    fn screensaver_simulate_activity(&self) -> Result<(), i32> {
+        let call = || -> zbus::Result<()> {
+            self.session_bus()?.call_method(
+                Some("org.freedesktop.ScreenSaver"),
+                "/org/freedesktop/ScreenSaver",
+                Some("org.freedesktop.ScreenSaver"),
+                "SimulateUserActivity",
+                &(),
+            )?;
+            Ok(())
+        };
+        call().map_err(|e| dbus_error("ScreenSaver.SimulateUserActivity", e))
+    }
+
+    fn screensaver_uninhibit(&self, cookie: u32) -> Result<(), i32> {
+        let call = || -> zbus::Result<()> {
+            self.session_bus()?.call_method(
+                Some("org.freedesktop.ScreenSaver"),
+                "/org/freedesktop/ScreenSaver",
+                Some("org.freedesktop.ScreenSaver"),
+                "UnInhibit",
+                &(cookie,),
+            )?;
+            Ok(())
+        };
+        call().map_err(|e| dbus_error("ScreenSaver.UnInhibit", e))
+    }
+}
+
+impl PowerManager for Inhibitors {
+    fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32> {
+        let inhibitor = match kind {
+            Kind::System => self.logind_inhibit("idle:sleep", reason)?,
+            // logind has no disk lock; an idle lock stops the idle action
+            // (usually suspend) that would spin them down
+            Kind::Disk => self.logind_inhibit("idle", reason)?,
+            Kind::NetworkClient => self.logind_inhibit("sleep", reason)?,
+            Kind::Display | Kind::UserActivity => match self.screensaver_inhibit(reason) {
+                Ok(inhibitor) => inhibitor,
+                Err(e) => {
+                    dbus_error("ScreenSaver.Inhibit", e);
+                    self.logind_inhibit("idle", reason)?
+                }
+            },
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.held.lock().unwrap().push(Held { id, inhibitor });
+        Ok(id)
+    }
+
+    fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+        let held = {
+            let mut held = self.held.lock().unwrap();
+            let Some(index) = held.iter().position(|h| h.id == assertion_id) else {
+                // EBADF
+                return Err(9);
+            };
+            held.swap_remove(index)
+        };
+
+        match held.inhibitor {
+            Inhibitor::Logind(fd) => {
+                drop(fd);
+                Ok(())
+            }
+            Inhibitor::ScreenSaver(cookie) => self.screensaver_uninhibit(cookie),
+        }
+    }
+
+    fn declare_user_activity(&self, _reason: &str) -> Result<(), i32> {
+        self.screensaver_simulate_activity()
+    }
+}
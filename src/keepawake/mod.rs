@@ -0,0 +1,431 @@
+//! Platform-neutral keep-awake core. Every assertion Awake creates or releases
+//! goes through `manager()` — IOKit on macOS, power requests on Windows, D-Bus
+//! inhibitors on Linux — so `--dry-run` can swap the real backend for a logging stub.
+
+#[cfg(target_os = "macos")]
+mod iokit;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(windows)]
+mod windows;
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+use crate::error::AwakeError;
+
+/// What a held assertion keeps awake
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Display,
+    System,
+    /// Disks don't spin down
+    Disk,
+    /// Network connections stay up, for machines serving other clients
+    NetworkClient,
+    /// Behaves as if the user were at the keyboard (display on, no screensaver)
+    UserActivity,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Kind::Display,
+        Kind::System,
+        Kind::Disk,
+        Kind::NetworkClient,
+        Kind::UserActivity,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Display => "display",
+            Kind::System => "system",
+            Kind::Disk => "disk",
+            Kind::NetworkClient => "network-client",
+            Kind::UserActivity => "user-activity",
+        }
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Any combination of kinds, held together by one session
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KindSet(u8);
+
+impl KindSet {
+    pub const fn of(kinds: &[Kind]) -> KindSet {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < kinds.len() {
+            bits |= kinds[i].bit();
+            i += 1;
+        }
+        KindSet(bits)
+    }
+
+    pub fn contains(self, kind: Kind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Kind> {
+        Kind::ALL
+            .into_iter()
+            .filter(move |&kind| self.contains(kind))
+    }
+}
+
+impl fmt::Display for KindSet {
+    /// `display+system`, or `none`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        for (i, kind) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str("+")?;
+            }
+            f.write_str(kind.name())?;
+        }
+        Ok(())
+    }
+}
+
+pub trait PowerManager: Sync {
+    /// Returns the new assertion ID, or the platform error code
+    /// (IOReturn on macOS, `GetLastError` on Windows, -1 for a D-Bus error on Linux)
+    fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32>;
+    fn release_assertion(&self, assertion_id: u32) -> Result<(), i32>;
+    /// Resets the OS's idle timers once, as a key press would, without
+    /// holding anything afterwards
+    fn declare_user_activity(&self, reason: &str) -> Result<(), i32>;
+}
+
+/// One held assertion, released when dropped — so an early return or a
+/// partially built session can't leak one.
+pub struct PowerAssertion {
+    manager: &'static dyn PowerManager,
+    kind: Kind,
+    id: u32,
+}
+
+impl PowerAssertion {
+    pub fn acquire(kind: Kind, reason: &str) -> Result<PowerAssertion, AwakeError> {
+        PowerAssertion::acquire_with(manager(), kind, reason)
+    }
+
+    fn acquire_with(
+        manager: &'static dyn PowerManager,
+        kind: Kind,
+        reason: &str,
+    ) -> Result<PowerAssertion, AwakeError> {
+        let id = manager
+            .create_assertion(kind, reason)
+            .map_err(|code| AwakeError::AssertionFailed { kind, code })?;
+        Ok(PowerAssertion { manager, kind, id })
+    }
+
+    /// Releases now, reporting failure; dropping releases without reporting
+    pub fn release(self) -> Result<(), AwakeError> {
+        let this = std::mem::ManuallyDrop::new(self);
+        this.manager
+            .release_assertion(this.id)
+            .map_err(|code| AwakeError::ReleaseFailed { code })
+    }
+}
+
+impl Drop for PowerAssertion {
+    fn drop(&mut self) {
+        let _ = self.manager.release_assertion(self.id);
+    }
+}
+
+/// The assertions held for one keep-awake session
+pub struct PowerSession {
+    manager: &'static dyn PowerManager,
+    assertions: Vec<PowerAssertion>,
+}
+
+impl PowerSession {
+    /// Holds every kind in `kinds`, or nothing: if one fails, the guards already
+    /// acquired drop and release themselves
+    pub fn begin(kinds: KindSet, reason: &str) -> Result<PowerSession, AwakeError> {
+        PowerSession::begin_with(manager(), kinds, reason)
+    }
+
+    pub fn begin_with(
+        manager: &'static dyn PowerManager,
+        kinds: KindSet,
+        reason: &str,
+    ) -> Result<PowerSession, AwakeError> {
+        let assertions = kinds
+            .iter()
+            .map(|kind| PowerAssertion::acquire_with(manager, kind, reason))
+            .collect::<Result<_, _>>()?;
+        Ok(PowerSession {
+            manager,
+            assertions,
+        })
+    }
+
+    /// Makes the session hold exactly `kinds` while it carries on: assertions
+    /// for kinds it keeps stay as they are, and new ones are taken before any
+    /// go, so nothing is left unheld in between. If one can't be taken the
+    /// session is as it was; a failed release is still reported.
+    pub fn change_kinds(&mut self, kinds: KindSet, reason: &str) -> Result<(), AwakeError> {
+        let added: Vec<PowerAssertion> = kinds
+            .iter()
+            .filter(|&kind| !self.assertions.iter().any(|a| a.kind == kind))
+            .map(|kind| PowerAssertion::acquire_with(self.manager, kind, reason))
+            .collect::<Result<_, _>>()?;
+        let (kept, dropped): (Vec<_>, Vec<_>) = std::mem::take(&mut self.assertions)
+            .into_iter()
+            .partition(|a| kinds.contains(a.kind));
+        self.assertions = kept;
+        self.assertions.extend(added);
+        PowerSession {
+            manager: self.manager,
+            assertions: dropped,
+        }
+        .end()
+    }
+
+    /// Releases every assertion, even after a failure; the first failure is returned
+    pub fn end(self) -> Result<(), AwakeError> {
+        let mut result = Ok(());
+        for assertion in self.assertions {
+            if let Err(e) = assertion.release() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Restarts the OS's idle countdown, so the display sleeps only after its
+/// usual idle time from now
+pub fn declare_user_activity(reason: &str) -> Result<(), AwakeError> {
+    manager()
+        .declare_user_activity(reason)
+        .map_err(|code| AwakeError::AssertionFailed {
+            kind: Kind::UserActivity,
+            code,
+        })
+}
+
+/// Hands out fake IDs and logs each call instead of touching the OS
+struct DryRun {
+    next_id: AtomicU32,
+}
+
+impl PowerManager for DryRun {
+    fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32> {
+        let aid = self.next_id.fetch_add(1, Ordering::Relaxed);
+        eprintln!(
+            "[dry-run] create {} assertion ({:?}) -> {}",
+            kind.name(),
+            reason,
+            aid
+        );
+        Ok(aid)
+    }
+
+    fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+        eprintln!("[dry-run] release assertion {}", assertion_id);
+        Ok(())
+    }
+
+    fn declare_user_activity(&self, reason: &str) -> Result<(), i32> {
+        eprintln!("[dry-run] declare user activity ({:?})", reason);
+        Ok(())
+    }
+}
+
+static DRY_RUN: DryRun = DryRun {
+    next_id: AtomicU32::new(1),
+};
+static MANAGER: OnceLock<&'static dyn PowerManager> = OnceLock::new();
+static DRY_RUN_ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(target_os = "macos")]
+fn platform() -> &'static dyn PowerManager {
+    &iokit::IOKIT
+}
+
+#[cfg(windows)]
+fn platform() -> &'static dyn PowerManager {
+    &windows::POWER_REQUESTS
+}
+
+#[cfg(target_os = "linux")]
+fn platform() -> &'static dyn PowerManager {
+    &linux::INHIBITORS
+}
+
+/// Human-readable form of a code returned by the platform backend
+#[cfg(target_os = "macos")]
+pub fn describe_code(code: i32) -> String {
+    iopm_assertion::Error::from_code(code).to_string()
+}
+
+#[cfg(windows)]
+pub fn describe_code(code: i32) -> String {
+    format!("Windows error {}", code)
+}
+
+#[cfg(target_os = "linux")]
+pub fn describe_code(code: i32) -> String {
+    linux::describe_code(code)
+}
+
+/// Select the backend; must be called before the first assertion is created
+pub fn init(dry_run: bool) {
+    let backend: &'static dyn PowerManager = if dry_run { &DRY_RUN } else { platform() };
+    if MANAGER.set(backend).is_err() {
+        eprintln!("power backend already initialised");
+        return;
+    }
+    DRY_RUN_ENABLED.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN_ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn manager() -> &'static dyn PowerManager {
+    *MANAGER.get_or_init(platform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Tracks live IDs; refuses `fail_kind`
+    struct Fake {
+        fail_kind: Option<Kind>,
+        next_id: AtomicU32,
+        live: Mutex<Vec<u32>>,
+    }
+
+    impl Fake {
+        fn leak(fail_kind: Option<Kind>) -> &'static Fake {
+            Box::leak(Box::new(Fake {
+                fail_kind,
+                next_id: AtomicU32::new(1),
+                live: Mutex::new(Vec::new()),
+            }))
+        }
+
+        fn live(&self) -> usize {
+            self.live.lock().unwrap().len()
+        }
+    }
+
+    impl PowerManager for Fake {
+        fn create_assertion(&self, kind: Kind, _reason: &str) -> Result<u32, i32> {
+            if self.fail_kind == Some(kind) {
+                return Err(-7);
+            }
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            self.live.lock().unwrap().push(id);
+            Ok(id)
+        }
+
+        fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+            let mut live = self.live.lock().unwrap();
+            let index = live.iter().position(|&id| id == assertion_id).ok_or(-9)?;
+            live.swap_remove(index);
+            Ok(())
+        }
+
+        fn declare_user_activity(&self, _reason: &str) -> Result<(), i32> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn partial_failure_releases_what_was_acquired() {
+        let fake = Fake::leak(Some(Kind::System));
+        let result =
+            PowerSession::begin_with(fake, KindSet::of(&[Kind::Display, Kind::System]), "test");
+        assert!(matches!(
+            result,
+            Err(AwakeError::AssertionFailed {
+                kind: Kind::System,
+                code: -7
+            })
+        ));
+        assert_eq!(fake.live(), 0);
+    }
+
+    #[test]
+    fn session_releases_on_end_and_on_drop() {
+        let fake = Fake::leak(None);
+        let session =
+            PowerSession::begin_with(fake, KindSet::of(&[Kind::Display, Kind::System]), "test");
+        assert_eq!(fake.live(), 2);
+        session.unwrap().end().unwrap();
+        assert_eq!(fake.live(), 0);
+
+        let session =
+            PowerSession::begin_with(fake, KindSet::of(&[Kind::Display, Kind::System]), "test");
+        drop(session);
+        assert_eq!(fake.live(), 0);
+    }
+
+    #[test]
+    fn changing_kinds_keeps_what_is_shared() {
+        let fake = Fake::leak(None);
+        let mut session =
+            PowerSession::begin_with(fake, KindSet::of(&[Kind::Display, Kind::System]), "test")
+                .unwrap();
+        let system_id = session.assertions[1].id;
+        session
+            .change_kinds(KindSet::of(&[Kind::System, Kind::NetworkClient]), "test")
+            .unwrap();
+        assert_eq!(fake.live(), 2);
+        // The system assertion carried on rather than being taken again
+        assert!(session.assertions.iter().any(|a| a.id == system_id));
+        assert!(!session.assertions.iter().any(|a| a.kind == Kind::Display));
+        session.end().unwrap();
+        assert_eq!(fake.live(), 0);
+    }
+
+    #[test]
+    fn a_failed_change_leaves_the_session_as_it_was() {
+        let fake = Fake::leak(Some(Kind::System));
+        let mut session =
+            PowerSession::begin_with(fake, KindSet::of(&[Kind::Display]), "test").unwrap();
+        let result = session.change_kinds(KindSet::of(&[Kind::System]), "test");
+        assert!(matches!(
+            result,
+            Err(AwakeError::AssertionFailed {
+                kind: Kind::System,
+                ..
+            })
+        ));
+        assert_eq!(fake.live(), 1);
+        assert_eq!(session.assertions[0].kind, Kind::Display);
+    }
+
+    #[test]
+    fn kind_sets_list_their_kinds_in_order() {
+        let kinds = KindSet::of(&[Kind::Disk, Kind::Display]);
+        assert_eq!(
+            kinds.iter().collect::<Vec<_>>(),
+            [Kind::Display, Kind::Disk]
+        );
+        assert_eq!(kinds.to_string(), "display+disk");
+        assert_eq!(KindSet::default().to_string(), "none");
+    }
+}
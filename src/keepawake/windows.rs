@@ -0,0 +1,115 @@
+//! Windows backend: one power request object per assertion
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::System::Power::{
+    PowerClearRequest, PowerCreateRequest, PowerRequestDisplayRequired, PowerRequestSystemRequired,
+    PowerSetRequest, SetThreadExecutionState, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+    POWER_REQUEST_TYPE,
+};
+use windows_sys::Win32::System::Threading::{
+    POWER_REQUEST_CONTEXT_SIMPLE_STRING, REASON_CONTEXT, REASON_CONTEXT_0,
+};
+
+use super::{Kind, PowerManager};
+
+// POWER_REQUEST_CONTEXT_VERSION; lives in Win32_System_SystemServices, which
+// would pull in a whole feature for one constant
+const POWER_REQUEST_CONTEXT_VERSION: u32 = 0;
+
+struct Request {
+    id: u32,
+    // HANDLE is a raw pointer, which isn't Send; kept as an address instead
+    handle: usize,
+    request_type: POWER_REQUEST_TYPE,
+}
+
+pub struct PowerRequests {
+    next_id: AtomicU32,
+    held: Mutex<Vec<Request>>,
+}
+
+pub static POWER_REQUESTS: PowerRequests = PowerRequests {
+    next_id: AtomicU32::new(1),
+    held: Mutex::new(Vec::new()),
+};
+
+fn request_type(kind: Kind) -> POWER_REQUEST_TYPE {
+    match kind {
+        Kind::Display | Kind::UserActivity => PowerRequestDisplayRequired,
+        // No request targets disks or the network on their own; keeping the
+        // system up keeps both
+        Kind::System | Kind::Disk | Kind::NetworkClient => PowerRequestSystemRequired,
+    }
+}
+
+fn last_error() -> i32 {
+    unsafe { GetLastError() as i32 }
+}
+
+impl PowerManager for PowerRequests {
+    fn create_assertion(&self, kind: Kind, reason: &str) -> Result<u32, i32> {
+        // Shown by `powercfg /requests`
+        let mut reason: Vec<u16> = reason.encode_utf16().chain(Some(0)).collect();
+        let context = REASON_CONTEXT {
+            Version: POWER_REQUEST_CONTEXT_VERSION,
+            Flags: POWER_REQUEST_CONTEXT_SIMPLE_STRING,
+            Reason: REASON_CONTEXT_0 {
+                SimpleReasonString: reason.as_mut_ptr(),
+            },
+        };
+
+        let handle: HANDLE = unsafe { PowerCreateRequest(&context) };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(last_error());
+        }
+
+        let request_type = request_type(kind);
+        if unsafe { PowerSetRequest(handle, request_type) } == 0 {
+            let code = last_error();
+            unsafe { CloseHandle(handle) };
+            return Err(code);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.held.lock().unwrap().push(Request {
+            id,
+            handle: handle as usize,
+            request_type,
+        });
+        Ok(id)
+    }
+
+    fn release_assertion(&self, assertion_id: u32) -> Result<(), i32> {
+        let request = {
+            let mut held = self.held.lock().unwrap();
+            let Some(index) = held.iter().position(|r| r.id == assertion_id) else {
+                // ERROR_INVALID_HANDLE
+                return Err(6);
+            };
+            held.swap_remove(index)
+        };
+
+        let handle = request.handle as HANDLE;
+        let cleared = unsafe { PowerClearRequest(handle, request.request_type) } != 0;
+        let code = if cleared { 0 } else { last_error() };
+        // Closing the handle drops the request even if clearing failed
+        unsafe { CloseHandle(handle) };
+
+        if cleared {
+            Ok(())
+        } else {
+            Err(code)
+        }
+    }
+
+    fn declare_user_activity(&self, _reason: &str) -> Result<(), i32> {
+        // Without ES_CONTINUOUS this only resets the idle timers, once
+        match unsafe { SetThreadExecutionState(ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED) } {
+            0 => Err(last_error()),
+            _ => Ok(()),
+        }
+    }
+}
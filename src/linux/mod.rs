@@ -0,0 +1,328 @@
+//! Linux front-end: a StatusNotifierItem tray icon (the protocol behind KDE's
+//! tray, the GNOME AppIndicator extension and most Wayland bars). Left click
+//! toggles, right click opens the menu — the same layout as the macOS status item.
+//!
+//! The tray service runs on its own thread; everything it (or a timer waiter)
+//! wants done is sent to the main thread's loop in `run`, which owns `state`.
+
+mod reminder;
+
+use std::cell::OnceCell;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{CheckmarkItem, StandardItem, SubMenu};
+use ksni::{MenuItem, ToolTip, Tray};
+
+use crate::error::AwakeError;
+use crate::keepawake::{self, KindSet};
+use crate::mode::Mode;
+use crate::{battery, state, stats};
+
+enum Message {
+    Command(state::Command),
+    Quit,
+}
+
+// Created by whichever comes first, `post` or `run`, so nothing posted before
+// the loop starts is lost; `run` takes the receiving end
+static MAIN_LOOP: OnceLock<Channel> = OnceLock::new();
+
+type Channel = (Sender<Message>, Mutex<Option<Receiver<Message>>>);
+
+fn main_loop() -> &'static Channel {
+    MAIN_LOOP.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        (tx, Mutex::new(Some(rx)))
+    })
+}
+
+thread_local! {
+    // Main thread only
+    static TRAY: OnceCell<Handle<AwakeTray>> = const { OnceCell::new() };
+}
+
+fn send(message: Message) {
+    let _ = main_loop().0.send(message);
+}
+
+// Errors surface here, at the UI boundary; for now they go to stderr
+fn present_error(error: &AwakeError) {
+    eprintln!("Awake: {}", error);
+}
+
+fn handle(cmd: state::Command) {
+    if let Err(e) = state::handle(cmd) {
+        present_error(&e);
+    }
+}
+
+/// Queue `cmd` for the main thread. Safe to call from any thread.
+pub fn post(cmd: state::Command) {
+    send(Message::Command(cmd));
+}
+
+/// Asks whether a session that has run `hours` is still needed
+pub fn remind(hours: u64) {
+    reminder::show(hours);
+}
+
+fn update_tray(f: impl FnOnce(&mut AwakeTray)) {
+    TRAY.with(|tray| {
+        if let Some(handle) = tray.get() {
+            handle.update(f);
+        }
+    });
+}
+
+pub fn show_mode(current: KindSet) {
+    update_tray(|tray| tray.mode = current);
+}
+
+fn show_status(status: &state::Status) {
+    let (awake, summary) = (status.is_awake(), status.summary());
+    let (active, triggers) = (status.active_triggers(), status.triggers.to_vec());
+    let overnight = status.overnight.is_some();
+    update_tray(|tray| {
+        tray.awake = awake;
+        tray.summary = summary;
+        tray.active = active;
+        tray.triggers = triggers;
+        tray.overnight = overnight;
+    });
+}
+
+// The tray's copy of what it displays; the session itself lives in `state`
+struct AwakeTray {
+    awake: bool,
+    mode: KindSet,
+    summary: String,
+    active: Option<String>,
+    triggers: Vec<state::Trigger>,
+    overnight: bool,
+}
+
+fn item(label: &str, cmd: fn() -> state::Command) -> MenuItem<AwakeTray> {
+    StandardItem {
+        label: label.into(),
+        activate: Box::new(move |_| post(cmd())),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn mode_item(tray: &AwakeTray, mode: Mode) -> MenuItem<AwakeTray> {
+    CheckmarkItem {
+        label: mode.title().into(),
+        checked: tray.mode == mode.kinds(),
+        activate: Box::new(move |_| post(state::Command::SetMode(mode.kinds()))),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn trigger_item(trigger: &state::Trigger) -> MenuItem<AwakeTray> {
+    let (name, enabled) = (trigger.name.clone(), trigger.enabled);
+    CheckmarkItem {
+        label: trigger.name.clone(),
+        checked: enabled,
+        activate: Box::new(move |_| {
+            post(state::Command::SetTriggerEnabled {
+                name: name.clone(),
+                enabled: !enabled,
+            })
+        }),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn overnight_item(tray: &AwakeTray) -> MenuItem<AwakeTray> {
+    let percent = (!tray.overnight).then_some(battery::DEFAULT_CHARGE_PERCENT);
+    CheckmarkItem {
+        label: "Overnight: Then Until Charged".into(),
+        checked: tray.overnight,
+        activate: Box::new(move |_| post(state::Command::SetOvernight(percent))),
+        ..Default::default()
+    }
+    .into()
+}
+
+impl Tray for AwakeTray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").into()
+    }
+
+    fn title(&self) -> String {
+        "Awake".into()
+    }
+
+    // Stock freedesktop icons stand in for the cup and the moon
+    fn icon_name(&self) -> String {
+        if self.awake {
+            "weather-clear".into()
+        } else {
+            "weather-clear-night".into()
+        }
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let mut title = format!("Awake: {}", self.summary);
+        if keepawake::is_dry_run() {
+            title.push_str(" (dry run)");
+        }
+        ToolTip {
+            title,
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        post(state::Command::Toggle);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let dry_run_suffix = if keepawake::is_dry_run() {
+            " (dry run)"
+        } else {
+            ""
+        };
+
+        let mut menu = vec![StandardItem {
+            label: self.summary.clone(),
+            enabled: false,
+            ..Default::default()
+        }
+        .into()];
+        if let Some(active) = &self.active {
+            menu.push(
+                StandardItem {
+                    label: active.clone(),
+                    enabled: false,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        if !self.triggers.is_empty() {
+            menu.push(
+                SubMenu {
+                    label: "Triggers".into(),
+                    submenu: self.triggers.iter().map(trigger_item).collect(),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        menu.extend([
+            MenuItem::Separator,
+            item("Toggle", || state::Command::Toggle),
+            MenuItem::Separator,
+            SubMenu {
+                label: "Awake For...".into(),
+                submenu: vec![
+                    item("15 minutes", || state::Command::ActivateFor { minutes: 15 }),
+                    item("30 minutes", || state::Command::ActivateFor { minutes: 30 }),
+                    item("1 hour", || state::Command::ActivateFor { minutes: 60 }),
+                    item("2 hours", || state::Command::ActivateFor { minutes: 120 }),
+                    MenuItem::Separator,
+                    item("Extend by 15 minutes", || state::Command::Extend {
+                        seconds: 15 * 60,
+                    }),
+                    MenuItem::Separator,
+                    overnight_item(self),
+                ],
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: "Mode".into(),
+                submenu: vec![
+                    mode_item(self, Mode::Display),
+                    mode_item(self, Mode::System),
+                    mode_item(self, Mode::Both),
+                    MenuItem::Separator,
+                    mode_item(self, Mode::Server),
+                ],
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            SubMenu {
+                label: "Statistics".into(),
+                submenu: stats::read(self.awake)
+                    .lines()
+                    .into_iter()
+                    .map(|line| {
+                        StandardItem {
+                            label: line,
+                            enabled: false,
+                            ..Default::default()
+                        }
+                        .into()
+                    })
+                    .collect(),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: format!("Awake v{}{}", env!("CARGO_PKG_VERSION"), dry_run_suffix),
+                enabled: false,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Quit".into(),
+                activate: Box::new(|_| send(Message::Quit)),
+                ..Default::default()
+            }
+            .into(),
+        ]);
+        menu
+    }
+}
+
+/// Registers the tray icon, then runs the main-thread loop until Quit
+pub fn run() {
+    let Some(rx) = main_loop().1.lock().unwrap().take() else {
+        return;
+    };
+
+    let tray = AwakeTray {
+        awake: false,
+        mode: state::mode(),
+        summary: String::new(),
+        active: None,
+        triggers: Vec::new(),
+        overnight: false,
+    };
+    let tray = match tray.spawn() {
+        Ok(tray) => tray,
+        Err(e) => {
+            eprintln!("Couldn't register the tray icon: {}", e);
+            std::process::exit(1);
+        }
+    };
+    TRAY.with(|cell| {
+        let _ = cell.set(tray.clone());
+    });
+    state::on_state_change(show_status);
+    // `--watch` may already have started a session
+    state::refresh();
+
+    for message in rx {
+        match message {
+            Message::Command(cmd) => handle(cmd),
+            Message::Quit => break,
+        }
+    }
+
+    // Release inhibitors and join the timer thread before exiting
+    if let Err(e) = state::shutdown() {
+        present_error(&e);
+    }
+    tray.shutdown().wait();
+}
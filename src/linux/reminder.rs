@@ -0,0 +1,75 @@
+//! The "still needed?" reminder for a long open-ended session, as a desktop
+//! notification (`org.freedesktop.Notifications`) with actions to turn Awake
+//! into a one-hour timer or switch it off. Ignoring it keeps Awake on.
+
+use std::collections::HashMap;
+use std::thread;
+
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+
+use crate::state;
+
+const ACTIONS: [&str; 4] = ["hour", "1 More Hour", "off", "Turn Off"];
+
+pub fn show(hours: u64) {
+    // Off the main thread: it waits for the notification to be answered
+    thread::spawn(move || {
+        if let Err(e) = notify(hours) {
+            eprintln!("Couldn't show the reminder: {}", e);
+        }
+    });
+}
+
+fn notify(hours: u64) -> zbus::Result<()> {
+    let conn = Connection::session()?;
+    let proxy = Proxy::new(
+        &conn,
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+    )?;
+    // Listening before it's shown, so a quick answer isn't missed
+    let signals = proxy.receive_all_signals()?;
+
+    let hints: HashMap<&str, Value> = HashMap::new();
+    let id: u32 = proxy.call(
+        "Notify",
+        &(
+            "Awake",
+            0u32,
+            "",
+            state::reminder_text(hours, "computer"),
+            "Leaving it on keeps the computer from sleeping.",
+            &ACTIONS[..],
+            hints,
+            -1i32,
+        ),
+    )?;
+
+    for signal in signals {
+        let member = signal.header().member().map(|m| m.to_string());
+        match member.as_deref() {
+            Some("ActionInvoked") => {
+                let (answered, action): (u32, String) = signal.body().deserialize()?;
+                if answered != id {
+                    continue;
+                }
+                match action.as_str() {
+                    "hour" => state::post(state::Command::ActivateFor { minutes: 60 }),
+                    "off" => state::post(state::Command::Deactivate),
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Some("NotificationClosed") => {
+                let (closed, _reason): (u32, u32) = signal.body().deserialize()?;
+                if closed == id {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! The alerts Awake asks things with: a message, the detail under it and
+//! buttons, the first being the default. Awake is an accessory app, which
+//! isn't frontmost by default, so each one is brought in front of the app
+//! being used first.
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2::{msg_send, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+use objc2_foundation::NSString;
+
+// `NSAlertFirstButtonReturn`; later buttons count up from it
+const FIRST_BUTTON: isize = 1000;
+
+/// `NSAlertStyle`
+#[derive(Clone, Copy)]
+pub enum Style {
+    Warning = 0,
+    Critical = 2,
+}
+
+/// Which of `buttons` was clicked, by index; `None` if the alert went away
+/// some other way
+pub fn ask(message: &str, info: &str, buttons: &[&str], mtm: MainThreadMarker) -> Option<usize> {
+    ask_with(message, info, buttons, Style::Warning, None, mtm)
+}
+
+/// `ask`, in `style` and with `accessory` shown under the detail
+pub fn ask_with(
+    message: &str,
+    info: &str,
+    buttons: &[&str],
+    style: Style,
+    accessory: Option<&AnyObject>,
+    mtm: MainThreadMarker,
+) -> Option<usize> {
+    let response: isize = unsafe {
+        let alert: Retained<AnyObject> = msg_send![AnyClass::get(c"NSAlert")?, new];
+        let _: () = msg_send![&alert, setMessageText: &*NSString::from_str(message)];
+        let _: () = msg_send![&alert, setInformativeText: &*NSString::from_str(info)];
+        let _: () = msg_send![&alert, setAlertStyle: style as usize];
+        for title in buttons {
+            let title = NSString::from_str(title);
+            let _: *mut AnyObject = msg_send![&alert, addButtonWithTitle: &*title];
+        }
+        if let Some(accessory) = accessory {
+            let _: () = msg_send![&alert, setAccessoryView: accessory];
+        }
+
+        let app = NSApplication::sharedApplication(mtm);
+        let _: () = msg_send![&app, activateIgnoringOtherApps: true];
+        msg_send![&alert, runModal]
+    };
+    usize::try_from(response - FIRST_BUTTON)
+        .ok()
+        .filter(|&index| index < buttons.len())
+}
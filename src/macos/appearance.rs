@@ -0,0 +1,60 @@
+//! Light and dark menu bars. `custom_image` picks the `…Dark` icon files
+//! while the status item is drawn dark; this watches `NSApp`'s
+//! `effectiveAppearance` by key-value observing and redraws the icon when it
+//! flips, as template-less coloured icons don't follow on their own.
+
+use std::ffi::c_void;
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+use objc2_foundation::NSString;
+
+const KEY_PATH: &str = "effectiveAppearance";
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    static NSAppearanceNameAqua: &'static NSString;
+    static NSAppearanceNameDarkAqua: &'static NSString;
+}
+
+pub unsafe fn observe(delegate: *mut AnyObject) {
+    let app: *mut AnyObject =
+        msg_send![AnyClass::get(c"NSApplication").unwrap(), sharedApplication];
+    let key_path = NSString::from_str(KEY_PATH);
+    let _: () = msg_send![
+        app,
+        addObserver: delegate,
+        forKeyPath: &*key_path,
+        options: 0usize,
+        context: std::ptr::null_mut::<c_void>()
+    ];
+}
+
+/// Whether `view` (the status item's button) is drawn in a dark appearance
+pub unsafe fn is_dark(view: *mut AnyObject) -> bool {
+    let appearance: *mut AnyObject = msg_send![view, effectiveAppearance];
+    if appearance.is_null() {
+        return false;
+    }
+    let array_class = AnyClass::get(c"NSArray").unwrap();
+    let names: *mut AnyObject = msg_send![
+        array_class,
+        arrayWithObjects: [NSAppearanceNameAqua, NSAppearanceNameDarkAqua].as_ptr(),
+        count: 2usize
+    ];
+    let best: *mut NSString = msg_send![appearance, bestMatchFromAppearancesWithNames: names];
+    !best.is_null() && *best == *NSAppearanceNameDarkAqua
+}
+
+pub extern "C" fn changed(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    key_path: *mut NSString,
+    _object: *mut AnyObject,
+    _change: *mut AnyObject,
+    _context: *mut c_void,
+) {
+    if !key_path.is_null() && unsafe { &*key_path }.to_string() == KEY_PATH {
+        super::update_icon();
+    }
+}
@@ -0,0 +1,71 @@
+//! "Export Settings…" and "Import Settings…": every preference in one JSON
+//! file, for backups and for setting up another Mac the same way.
+
+use std::fs;
+use std::path::PathBuf;
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2::{msg_send, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+use objc2_foundation::{NSString, NSURL};
+
+use crate::{settings, state, triggers};
+
+const DEFAULT_NAME: &str = "Awake Settings.json";
+// `NSModalResponseOK`
+const MODAL_RESPONSE_OK: isize = 1;
+
+// Runs an open or save panel; `None` if it was cancelled
+unsafe fn choose_file(panel_class: &std::ffi::CStr, mtm: MainThreadMarker) -> Option<PathBuf> {
+    let panel: Retained<AnyObject> = msg_send![AnyClass::get(panel_class)?, new];
+    if panel_class == c"NSSavePanel" {
+        let name = NSString::from_str(DEFAULT_NAME);
+        let _: () = msg_send![&panel, setNameFieldStringValue: &*name];
+    }
+    // Accessory apps aren't frontmost by default; bring the panel forward
+    let app = NSApplication::sharedApplication(mtm);
+    let _: () = msg_send![&app, activateIgnoringOtherApps: true];
+    let response: isize = msg_send![&panel, runModal];
+    if response != MODAL_RESPONSE_OK {
+        return None;
+    }
+    let url: Option<Retained<NSURL>> = msg_send![&panel, URL];
+    Some(PathBuf::from(url?.path()?.to_string()))
+}
+
+pub fn export(mtm: MainThreadMarker) {
+    let Some(path) = (unsafe { choose_file(c"NSSavePanel", mtm) }) else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, settings::export()) {
+        eprintln!(
+            "Awake: couldn't export settings to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Settings read at launch (the lockfile) take effect on the next one
+pub fn import(mtm: MainThreadMarker) {
+    let Some(path) = (unsafe { choose_file(c"NSOpenPanel", mtm) }) else {
+        return;
+    };
+    let imported = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| settings::import(&json));
+    if let Err(e) = imported {
+        eprintln!(
+            "Awake: couldn't import settings from {}: {}",
+            path.display(),
+            e
+        );
+        return;
+    }
+
+    let policy = crate::setting("TriggerPolicy")
+        .and_then(|policy| triggers::Policy::parse(&policy))
+        .unwrap_or(triggers::Policy::Edge);
+    super::handle(state::Command::SetTriggerPolicy(policy));
+}
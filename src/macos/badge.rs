@@ -0,0 +1,84 @@
+//! A compact status item: during a timed session the time left is drawn as a
+//! tiny number onto the icon itself, instead of only appearing in the menu.
+//!
+//! Toggled from the menu; stored as `IconBadge`.
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2::{msg_send, AnyThread};
+use objc2_app_kit::NSImage;
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+
+use crate::duration::{self, Precision};
+
+pub const KEY_ENABLED: &str = "IconBadge";
+const BADGE_POINTS: f64 = 8.0;
+// `NSCompositingOperationClear` and `NSCompositingOperationSourceOver`
+const OPERATION_CLEAR: usize = 0;
+const OPERATION_SOURCE_OVER: usize = 2;
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    static NSFontAttributeName: &'static NSString;
+    fn NSRectFillUsingOperation(rect: NSRect, operation: usize);
+}
+
+/// What the badge reads with `secs` left: minutes under an hour ("42"), then
+/// whole hours ("3h"); or as `duration` shows it, if set to seconds or rounded
+pub fn text(secs: u64) -> String {
+    if duration::precision() != Precision::Minutes {
+        return duration::compact(secs);
+    }
+    let minutes = secs.div_ceil(60);
+    if minutes < 60 {
+        minutes.to_string()
+    } else {
+        format!("{}h", minutes / 60)
+    }
+}
+
+/// `symbol`, widened a little, with `text` over its bottom-right corner. The
+/// result is a template image like the symbol, so the menu bar tints both.
+pub unsafe fn compose(symbol: &NSImage, text: &str) -> Retained<NSImage> {
+    let size: NSSize = msg_send![symbol, size];
+    let font_class = AnyClass::get(c"NSFont").unwrap();
+    let font: *mut AnyObject = msg_send![font_class, boldSystemFontOfSize: BADGE_POINTS];
+    let dictionary_class = AnyClass::get(c"NSDictionary").unwrap();
+    let attributes: *mut AnyObject =
+        msg_send![dictionary_class, dictionaryWithObject: font, forKey: NSFontAttributeName];
+    let label = NSString::from_str(text);
+    let label_size: NSSize = msg_send![&label, sizeWithAttributes: attributes];
+
+    let canvas_size = NSSize::new(size.width + label_size.width / 2.0, size.height);
+    let canvas: Retained<NSImage> = msg_send![NSImage::alloc(), initWithSize: canvas_size];
+    let _: () = msg_send![&canvas, lockFocus];
+    let symbol_rect = NSRect::new(NSPoint::new(0.0, 0.0), size);
+    let _: () = msg_send![
+        symbol,
+        drawInRect: symbol_rect,
+        fromRect: NSRect::ZERO,
+        operation: OPERATION_SOURCE_OVER,
+        fraction: 1.0f64
+    ];
+    // Cut the symbol away behind the number so it stays legible
+    let origin = NSPoint::new(canvas_size.width - label_size.width, 0.0);
+    NSRectFillUsingOperation(NSRect::new(origin, label_size), OPERATION_CLEAR);
+    let _: () = msg_send![&label, drawAtPoint: origin, withAttributes: attributes];
+    let _: () = msg_send![&canvas, unlockFocus];
+    let _: () = msg_send![&canvas, setTemplate: true];
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_then_hours() {
+        assert_eq!(text(30), "1");
+        assert_eq!(text(42 * 60), "42");
+        assert_eq!(text(59 * 60 + 1), "1h");
+        assert_eq!(text(3600 + 1), "1h");
+        assert_eq!(text(3 * 3600 + 1800), "3h");
+    }
+}
@@ -0,0 +1,81 @@
+//! For menu bar managers such as Bartender and Ice, which may keep the icon
+//! out of sight. Each change of state is posted as the distributed
+//! notification `<bundle id>.changed`, whose object is the `status.json`
+//! text, for a manager's trigger (or any script) to observe. With **Flash
+//! Icon on Change** ticked (`FlashIconOnChange`), the icon also blinks for a
+//! moment, so a manager that reveals items as they update brings it into
+//! view.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use dispatch2::{DispatchQueue, DispatchTime};
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_foundation::NSString;
+
+use super::bundle;
+use crate::change::Seen;
+use crate::keepawake::KindSet;
+use crate::state::Status;
+use crate::{settings, status_file};
+
+pub const KEY_FLASH: &str = "FlashIconOnChange";
+// Greyed and back this many times, each half lasting `BLINK`
+const BLINKS: u32 = 3;
+const BLINK: Duration = Duration::from_millis(250);
+
+thread_local! {
+    // What was last posted, so a status that changed nothing is skipped
+    static POSTED: Cell<Option<(Seen, KindSet)>> = const { Cell::new(None) };
+    // Halves of blinks still to go
+    static BLINKING: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Posts `status` to other processes, if it changed anything they'd see
+pub fn post(status: &Status) {
+    let seen = (Seen::of(status), status.kinds);
+    if POSTED.with(|posted| posted.replace(Some(seen))) == Some(seen) {
+        return;
+    }
+    let Some(class) = AnyClass::get(c"NSDistributedNotificationCenter") else {
+        return;
+    };
+    let name = NSString::from_str(&format!("{}.changed", bundle::identifier()));
+    let json = NSString::from_str(&status_file::to_json(status, crate::now_secs()));
+    unsafe {
+        let center: *mut AnyObject = msg_send![class, defaultCenter];
+        let _: () = msg_send![
+            center,
+            postNotificationName: &*name,
+            object: &*json,
+            userInfo: std::ptr::null::<AnyObject>(),
+            deliverImmediately: true
+        ];
+    }
+}
+
+/// Blinks the icon, if that's on and it isn't blinking already
+pub fn flash() {
+    let still = super::screen_recording::is_still();
+    if settings::get_bool(KEY_FLASH) && !still && BLINKING.with(Cell::get) == 0 {
+        blink(BLINKS * 2);
+    }
+}
+
+// Greyed on odd halves; the last, zero, leaves the icon as it was
+fn blink(halves: u32) {
+    BLINKING.with(|blinking| blinking.set(halves));
+    super::with_ui(|ui| unsafe {
+        let button: *mut AnyObject = msg_send![&ui.status_item, button];
+        if !button.is_null() {
+            let _: () = msg_send![button, setAppearsDisabled: halves % 2 == 1];
+        }
+    });
+    if halves == 0 {
+        return;
+    }
+    if let Ok(when) = DispatchTime::try_from(BLINK) {
+        let _ = DispatchQueue::main().after(when, move || blink(halves - 1));
+    }
+}
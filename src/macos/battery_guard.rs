@@ -0,0 +1,112 @@
+//! A guard for ageing batteries: switching on with a click or from the menu,
+//! for as long as it takes, while on battery power with a battery macOS says
+//! needs service, warns or asks first. Timers pass, since they end, and so
+//! does switching on with the battery timer on, which starts one.
+//!
+//! Off unless `BatteryHealthGuard` is `warn` (a notification as Awake switches
+//! on) or `confirm` (an alert offering an hour's timer instead). The battery's
+//! condition is read once, in the background, at launch.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use objc2::MainThreadMarker;
+
+use super::alert;
+use crate::{battery, settings, state};
+
+pub const KEY_GUARD: &str = "BatteryHealthGuard";
+
+const WARNING: &str = "Your battery needs service";
+const DETAIL: &str = "Staying awake on battery power wears it further. \
+                      Plug in, or use a timer.";
+
+// Set from the background once `system_profiler` has answered
+static NEEDS_SERVICE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Guard {
+    Off,
+    Warn,
+    Confirm,
+}
+
+impl Guard {
+    fn parse(text: Option<&str>) -> Guard {
+        match text.map(str::trim) {
+            Some("warn") => Guard::Warn,
+            Some("confirm") => Guard::Confirm,
+            _ => Guard::Off,
+        }
+    }
+}
+
+/// What switching on calls for: the guard only minds an open-ended session on
+/// a battery that needs service, drawing from it
+fn check(guard: Guard, awake: bool, on_battery: bool, needs_service: bool) -> Guard {
+    if awake || !on_battery || !needs_service {
+        Guard::Off
+    } else {
+        guard
+    }
+}
+
+fn guard() -> Guard {
+    Guard::parse(settings::get_string(KEY_GUARD).as_deref())
+}
+
+/// Reads the battery's condition, if the guard is on
+pub fn start() {
+    if guard() == Guard::Off {
+        return;
+    }
+    battery::watch_power_source();
+    thread::spawn(|| {
+        let needs_service = battery::needs_service().unwrap_or(false);
+        NEEDS_SERVICE.store(needs_service, Ordering::Relaxed);
+    });
+}
+
+/// Toggles, as a click does, minding the battery first
+pub fn toggle() {
+    // Switching off starts nothing, and on battery the battery timer starts a
+    // timer
+    let unguarded = state::with_status(|status| status.is_awake()) || state::battery_timer();
+    let on_battery = battery::on_battery();
+    let needs_service = NEEDS_SERVICE.load(Ordering::Relaxed);
+    match check(guard(), unguarded, on_battery, needs_service) {
+        Guard::Off => super::handle(state::Command::Toggle),
+        Guard::Warn => {
+            super::handle(state::Command::Toggle);
+            super::notification::post(WARNING, DETAIL);
+        }
+        Guard::Confirm => confirm(),
+    }
+}
+
+fn confirm() {
+    let mtm = unsafe { MainThreadMarker::new_unchecked() };
+    let buttons = ["Keep Awake", "1 Hour Instead", "Cancel"];
+    match alert::ask(WARNING, DETAIL, &buttons, mtm) {
+        Some(0) => super::handle(state::Command::Activate),
+        Some(1) => super::handle(state::Command::ActivateFor { minutes: 60 }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_an_open_ended_session_on_a_worn_battery_is_guarded() {
+        assert_eq!(Guard::parse(Some("confirm")), Guard::Confirm);
+        assert_eq!(Guard::parse(None), Guard::Off);
+        assert_eq!(check(Guard::Confirm, false, true, true), Guard::Confirm);
+        assert_eq!(check(Guard::Warn, false, true, true), Guard::Warn);
+        // Switching off, plugged in, or a healthy battery
+        assert_eq!(check(Guard::Confirm, true, true, true), Guard::Off);
+        assert_eq!(check(Guard::Confirm, false, false, true), Guard::Off);
+        assert_eq!(check(Guard::Confirm, false, true, false), Guard::Off);
+    }
+}
@@ -0,0 +1,104 @@
+//! What the app bundle's `Info.plist` says about this build: its identifier,
+//! the URL schemes it declares, and whether it's a menu bar agent
+//! (`LSUIElement`). The LaunchAgent's label, the widget's app group and the
+//! links answered follow them, so a renamed or forked build with its own
+//! identifier doesn't replace the official one's login item or take its links.
+//! Run outside a bundle (`cargo run`), the official values stand in.
+
+use std::sync::OnceLock;
+
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+pub const DEFAULT_IDENTIFIER: &str = "io.tmss.awake";
+const DEFAULT_SCHEME: &str = "awake";
+
+struct Info {
+    identifier: String,
+    schemes: Vec<String>,
+    ui_element: Option<bool>,
+}
+
+static INFO: OnceLock<Info> = OnceLock::new();
+
+// `key` from the main bundle's Info.plist
+unsafe fn info_value(key: &str) -> Option<Retained<AnyObject>> {
+    let bundle: *mut AnyObject = msg_send![AnyClass::get(c"NSBundle")?, mainBundle];
+    if bundle.is_null() {
+        return None;
+    }
+    msg_send![bundle, objectForInfoDictionaryKey: &*NSString::from_str(key)]
+}
+
+// The strings in an `NSArray`, skipping anything else
+unsafe fn strings(array: &AnyObject) -> Vec<String> {
+    let count: usize = msg_send![array, count];
+    (0..count)
+        .filter_map(|i| {
+            let item: *mut AnyObject = msg_send![array, objectAtIndex: i];
+            let is_string: Bool = msg_send![item, isKindOfClass: AnyClass::get(c"NSString")?];
+            is_string
+                .as_bool()
+                .then(|| (*(item as *const NSString)).to_string())
+        })
+        .collect()
+}
+
+// Every scheme of every `CFBundleURLTypes` entry
+unsafe fn declared_schemes() -> Vec<String> {
+    let Some(types) = info_value("CFBundleURLTypes") else {
+        return Vec::new();
+    };
+    let count: usize = msg_send![&types, count];
+    let key = NSString::from_str("CFBundleURLSchemes");
+    (0..count)
+        .flat_map(|i| {
+            let entry: *mut AnyObject = msg_send![&types, objectAtIndex: i];
+            let schemes: *mut AnyObject = msg_send![entry, objectForKey: &*key];
+            schemes.as_ref().map(|s| strings(s)).unwrap_or_default()
+        })
+        .collect()
+}
+
+fn read() -> Info {
+    unsafe {
+        let identifier = info_value("CFBundleIdentifier")
+            .map(|id| (*(Retained::as_ptr(&id) as *const NSString)).to_string())
+            .filter(|id| !id.is_empty());
+        let mut schemes = declared_schemes();
+        // Unbundled, nothing is declared but the official links still work
+        if identifier.is_none() && schemes.is_empty() {
+            schemes.push(DEFAULT_SCHEME.into());
+        }
+        let ui_element = info_value("LSUIElement").map(|value| {
+            let value: Bool = msg_send![&value, boolValue];
+            value.as_bool()
+        });
+        Info {
+            identifier: identifier.unwrap_or_else(|| DEFAULT_IDENTIFIER.into()),
+            schemes,
+            ui_element,
+        }
+    }
+}
+
+fn info() -> &'static Info {
+    INFO.get_or_init(read)
+}
+
+/// `CFBundleIdentifier`, e.g. `io.tmss.awake`
+pub fn identifier() -> &'static str {
+    &info().identifier
+}
+
+/// The schemes links may use, without the colon
+pub fn url_schemes() -> &'static [String] {
+    &info().schemes
+}
+
+/// `LSUIElement`, if the bundle gives it
+pub fn ui_element() -> Option<bool> {
+    info().ui_element
+}
@@ -0,0 +1,165 @@
+//! Decides whether a status-item click toggles or opens the menu, and confirms
+//! a toggle from the trackpad with a tap of haptic feedback
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_app_kit::{NSEvent, NSEventMask, NSEventModifierFlags, NSEventSubtype, NSEventType};
+
+/// What the status button's action should do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Click {
+    Toggle,
+    Menu,
+}
+
+/// The parts of an `NSEvent` that matter for decoding a click
+#[derive(Clone, Copy, Debug)]
+pub struct ClickEvent {
+    pub event_type: NSEventType,
+    pub button_number: isize,
+    pub modifiers: NSEventModifierFlags,
+    /// A click on a trackpad rather than a mouse
+    pub trackpad: bool,
+}
+
+/// The events the status item sends its action on
+pub const ACTION_MASK: NSEventMask = NSEventMask(
+    NSEventMask::LeftMouseUp.0 | NSEventMask::RightMouseDown.0 | NSEventMask::RightMouseUp.0,
+);
+
+// `pressedMouseButtons` bit for the secondary button
+const SECONDARY_BUTTON: usize = 1 << 1;
+// `NSHapticFeedbackPatternGeneric` and `NSHapticFeedbackPerformanceTimeNow`
+const HAPTIC_GENERIC: isize = 0;
+const HAPTIC_NOW: usize = 1;
+
+impl ClickEvent {
+    pub fn from_event(event: &NSEvent) -> ClickEvent {
+        let event_type = event.r#type();
+        let mouse = [
+            NSEventType::LeftMouseDown,
+            NSEventType::LeftMouseUp,
+            NSEventType::RightMouseDown,
+            NSEventType::RightMouseUp,
+        ]
+        .contains(&event_type);
+        ClickEvent {
+            event_type,
+            button_number: event.buttonNumber(),
+            modifiers: event.modifierFlags(),
+            // `subtype` raises for events other than mouse ones
+            trackpad: mouse && event.subtype() == NSEventSubtype::Touch,
+        }
+    }
+}
+
+/// A Force Touch trackpad's tap under the finger that clicked; nothing on
+/// other hardware, or with haptic feedback turned off in System Settings
+pub fn haptic_feedback() {
+    let Some(manager) = AnyClass::get(c"NSHapticFeedbackManager") else {
+        return;
+    };
+    unsafe {
+        let performer: *mut AnyObject = msg_send![manager, defaultPerformer];
+        let _: () = msg_send![
+            performer,
+            performFeedbackPattern: HAPTIC_GENERIC,
+            performanceTime: HAPTIC_NOW
+        ];
+    }
+}
+
+/// `event` is the app's current event, if there is one; `modifiers` and
+/// `pressed_buttons` are the live state (`NSEvent.modifierFlags`,
+/// `NSEvent.pressedMouseButtons`), so a Control key pressed just after the click
+/// still counts and a click with no current event is decoded from what's held.
+pub fn decode(
+    event: Option<ClickEvent>,
+    modifiers: NSEventModifierFlags,
+    pressed_buttons: usize,
+) -> Click {
+    if modifiers.contains(NSEventModifierFlags::Control) {
+        return Click::Menu;
+    }
+
+    let Some(event) = event else {
+        return if pressed_buttons & SECONDARY_BUTTON != 0 {
+            Click::Menu
+        } else {
+            Click::Toggle
+        };
+    };
+
+    // A two-finger trackpad click arrives as a right mouse event; some
+    // trackpad and mouse drivers report it as a left event with button 1
+    let secondary = event.event_type == NSEventType::RightMouseDown
+        || event.event_type == NSEventType::RightMouseUp
+        || event.button_number == 1;
+    if secondary || event.modifiers.contains(NSEventModifierFlags::Control) {
+        Click::Menu
+    } else {
+        Click::Toggle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: NSEventType, button_number: isize) -> Option<ClickEvent> {
+        Some(ClickEvent {
+            event_type,
+            button_number,
+            modifiers: NSEventModifierFlags::empty(),
+            trackpad: false,
+        })
+    }
+
+    #[test]
+    fn left_click_toggles_and_right_click_opens_the_menu() {
+        let none = NSEventModifierFlags::empty();
+        assert_eq!(
+            decode(event(NSEventType::LeftMouseUp, 0), none, 0),
+            Click::Toggle
+        );
+        assert_eq!(
+            decode(event(NSEventType::RightMouseDown, 1), none, 0),
+            Click::Menu
+        );
+        assert_eq!(
+            decode(event(NSEventType::RightMouseUp, 1), none, 0),
+            Click::Menu
+        );
+        assert_eq!(
+            decode(event(NSEventType::LeftMouseUp, 1), none, 0),
+            Click::Menu
+        );
+    }
+
+    #[test]
+    fn control_opens_the_menu_whether_held_at_or_after_the_click() {
+        let control = Some(ClickEvent {
+            event_type: NSEventType::LeftMouseUp,
+            button_number: 0,
+            modifiers: NSEventModifierFlags::Control,
+            trackpad: false,
+        });
+        let none = NSEventModifierFlags::empty();
+        assert_eq!(decode(control, none, 0), Click::Menu);
+        assert_eq!(
+            decode(
+                event(NSEventType::LeftMouseUp, 0),
+                NSEventModifierFlags::Control,
+                0
+            ),
+            Click::Menu
+        );
+    }
+
+    #[test]
+    fn without_an_event_the_held_buttons_decide() {
+        let none = NSEventModifierFlags::empty();
+        assert_eq!(decode(None, none, 0), Click::Toggle);
+        assert_eq!(decode(None, none, SECONDARY_BUTTON), Click::Menu);
+    }
+}
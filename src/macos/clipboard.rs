@@ -0,0 +1,15 @@
+//! Copying text to the general pasteboard, for the log window and reports.
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+pub fn copy(text: &NSString) {
+    unsafe {
+        let board: *mut AnyObject =
+            msg_send![AnyClass::get(c"NSPasteboard").unwrap(), generalPasteboard];
+        let _: isize = msg_send![board, clearContents];
+        let kind = NSString::from_str("public.utf8-plain-text");
+        let _: Bool = msg_send![board, setString: text, forType: &*kind];
+    }
+}
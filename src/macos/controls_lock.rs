@@ -0,0 +1,87 @@
+//! **Lock Controls**, for screen sharing or handing the Mac to someone: until
+//! unlocked, the menu shows only the status and **Unlock Controls…**, and
+//! clicks and drops on the status item change nothing.
+//!
+//! Unlocking asks for the login password or Touch ID (LocalAuthentication's
+//! `LAPolicyDeviceOwnerAuthentication`); on a Mac with no password to ask for,
+//! it takes ⌥ held while choosing the item instead. The lock lasts for this
+//! run only, so a relaunch always comes up unlocked.
+
+use std::cell::{Cell, RefCell};
+
+use block2::RcBlock;
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+// `LAPolicyDeviceOwnerAuthentication`: Touch ID, a watch or the password
+const POLICY: isize = 2;
+// Shown as "“Awake” is trying to unlock its controls."
+const REASON: &str = "unlock its controls";
+
+#[link(name = "LocalAuthentication", kind = "framework")]
+extern "C" {}
+
+thread_local! {
+    static LOCKED: Cell<bool> = const { Cell::new(false) };
+    // Kept until it replies, as the context must outlive its evaluation
+    static CONTEXT: RefCell<Option<Retained<AnyObject>>> = const { RefCell::new(None) };
+}
+
+pub fn is_locked() -> bool {
+    LOCKED.with(Cell::get)
+}
+
+pub fn lock() {
+    LOCKED.with(|locked| locked.set(true));
+    super::refresh_menu();
+}
+
+fn unlocked() {
+    LOCKED.with(|locked| locked.set(false));
+    CONTEXT.with(|context| context.take());
+    super::refresh_menu();
+}
+
+/// Asks for the password, or without one checks for ⌥; unlocks once satisfied
+pub fn unlock() {
+    if CONTEXT.with(|context| context.borrow().is_some()) {
+        return;
+    }
+    let context: Option<Retained<AnyObject>> =
+        unsafe { AnyClass::get(c"LAContext").and_then(|class| msg_send![class, new]) };
+    let Some(context) = context.filter(|context| unsafe {
+        let can: Bool = msg_send![
+            &**context,
+            canEvaluatePolicy: POLICY,
+            error: std::ptr::null_mut::<*mut AnyObject>()
+        ];
+        can.as_bool()
+    }) else {
+        if super::option_key_held() {
+            unlocked();
+        }
+        return;
+    };
+    // Replies on a private queue
+    let reply = RcBlock::new(|success: Bool, _error: *mut AnyObject| {
+        let success = success.as_bool();
+        super::run_on_main(move |_| {
+            if success {
+                unlocked();
+            } else {
+                CONTEXT.with(|context| context.take());
+            }
+        });
+    });
+    CONTEXT.with(|cell| cell.replace(Some(context.clone())));
+    unsafe {
+        let _: () = msg_send![
+            &*context,
+            evaluatePolicy: POLICY,
+            localizedReason: &*NSString::from_str(REASON),
+            reply: &*reply
+        ];
+    }
+}
@@ -0,0 +1,67 @@
+//! **Show Time Left Separately**: a second, text-only status item for a
+//! timer's countdown, so the icon and the time left can sit in different
+//! places in the menu bar, and a menu bar manager such as Bartender can hide
+//! or show each on its own. macOS keeps where it was dragged under its own
+//! `autosaveName`, and it's hidden while no timer runs. Clicks on it do what
+//! they do on the icon, which carries no badge while this is on.
+//!
+//! Stored as `SeparateCountdown`.
+
+use std::cell::OnceCell;
+
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2::{msg_send, sel};
+use objc2_app_kit::{NSStatusBar, NSStatusItem};
+use objc2_foundation::NSString;
+
+use super::{bundle, click, icon};
+use crate::{duration, settings};
+
+pub const KEY_ENABLED: &str = "SeparateCountdown";
+
+thread_local! {
+    // Made the first time it's wanted, then only shown and hidden, so it
+    // keeps its place
+    static ITEM: OnceCell<Retained<NSStatusItem>> = const { OnceCell::new() };
+}
+
+pub fn is_enabled() -> bool {
+    settings::get_bool(KEY_ENABLED)
+}
+
+unsafe fn create(delegate: *mut AnyObject) -> Retained<NSStatusItem> {
+    let item = NSStatusBar::systemStatusBar().statusItemWithLength(-1.0); // NSVariableStatusItemLength
+    let name = NSString::from_str(&format!("{}.countdown", bundle::identifier()));
+    let _: () = msg_send![&item, setAutosaveName: &*name];
+    let button: *mut AnyObject = msg_send![&item, button];
+    if !button.is_null() {
+        let _: () = msg_send![button, setAction: sel!(buttonClicked:)];
+        let _: () = msg_send![button, setTarget: delegate];
+    }
+    let _: () = msg_send![&item, sendActionOn: click::ACTION_MASK];
+    item
+}
+
+/// Shows the time left to `expiry` on the countdown item, or hides it when
+/// there's no timer or the item is off; the item's actions go to `delegate`
+pub unsafe fn update(delegate: *mut AnyObject, awake: bool, expiry: Option<u64>) {
+    let left = expiry
+        .filter(|_| awake && is_enabled())
+        .map(|expiry| expiry.saturating_sub(crate::now_secs()))
+        .filter(|&left| left > 0);
+    ITEM.with(|cell| {
+        let item = match (cell.get(), left) {
+            (Some(item), _) => item,
+            // Nothing to hide
+            (None, None) => return,
+            (None, Some(_)) => cell.get_or_init(|| create(delegate)),
+        };
+        let _: () = msg_send![item, setVisible: left.is_some()];
+        let button: *mut AnyObject = msg_send![item, button];
+        if let (Some(left), false) = (left, button.is_null()) {
+            let title = icon::attributed(&duration::compact(left), awake);
+            let _: () = msg_send![button, setAttributedTitle: &*title];
+        }
+    });
+}
@@ -0,0 +1,21 @@
+//! At launch after a crash: an alert offering the report `crash.rs` saved, to
+//! read in a window or copy for a bug report.
+
+use objc2::MainThreadMarker;
+use objc2_foundation::NSString;
+
+use super::alert;
+
+pub fn offer(report: &str, mtm: MainThreadMarker) {
+    let response = alert::ask(
+        "Awake quit unexpectedly last time",
+        "A crash report was saved. Review it, or copy it to attach to a bug report.",
+        &["Review…", "Copy Report", "Dismiss"],
+        mtm,
+    );
+    match response {
+        Some(0) => super::text_window::show("Crash Report", report, mtm),
+        Some(1) => super::clipboard::copy(&NSString::from_str(report)),
+        _ => {}
+    }
+}
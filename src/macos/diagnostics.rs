@@ -0,0 +1,104 @@
+//! A thermal readout for the Statistics submenu while a session is on: the CPU
+//! temperature, the fans' speed and the system's thermal pressure, for anyone
+//! keeping the Mac awake through a long, heavy job.
+//!
+//! Off unless `ShowThermalDiagnostics` is set. Read from the SMC and
+//! `NSProcessInfo` each time the menu opens.
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject};
+
+use super::smc::Smc;
+use super::temperature::CPU_KEYS;
+use crate::settings;
+
+pub const KEY_ENABLED: &str = "ShowThermalDiagnostics";
+
+// Enough for any Mac; most have one or two, or none
+const MAX_FANS: u8 = 4;
+
+fn fan_speeds(smc: &Smc) -> Vec<f64> {
+    let count = smc.read(b"FNum").unwrap_or(0.0).min(f64::from(MAX_FANS)) as u8;
+    (0..count)
+        .filter_map(|fan| smc.read(&[b'F', b'0' + fan, b'A', b'c']))
+        .collect()
+}
+
+// `NSProcessInfoThermalState`
+fn thermal_state() -> isize {
+    unsafe {
+        let info: *mut AnyObject = msg_send![AnyClass::get(c"NSProcessInfo").unwrap(), processInfo];
+        msg_send![info, thermalState]
+    }
+}
+
+/// "CPU: 62°C", "Fans: 1,850 rpm" (or "Fans: off"), "Thermal pressure:
+/// nominal"; nothing for what this Mac can't report
+fn lines(cpu: Option<f64>, fans: Option<&[f64]>, thermal: isize) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(celsius) = cpu {
+        lines.push(format!("CPU: {:.0}\u{b0}C", celsius));
+    }
+    match fans {
+        Some([]) | None => {}
+        Some(fans) if fans.iter().all(|&rpm| rpm < 1.0) => lines.push("Fans: off".into()),
+        Some(fans) => {
+            let speeds: Vec<String> = fans.iter().map(|&rpm| thousands(rpm as u64)).collect();
+            lines.push(format!("Fans: {} rpm", speeds.join(", ")));
+        }
+    }
+    let pressure = match thermal {
+        0 => "nominal",
+        1 => "fair",
+        2 => "serious",
+        _ => "critical",
+    };
+    lines.push(format!("Thermal pressure: {}", pressure));
+    lines
+}
+
+// "1,850"
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// The readout's lines, or none when it's off or Awake is
+pub fn read(awake: bool) -> Vec<String> {
+    if !awake || !settings::get_bool(KEY_ENABLED) {
+        return Vec::new();
+    }
+    let smc = Smc::open();
+    let cpu = smc.as_ref().and_then(|smc| smc.temperature(&CPU_KEYS));
+    let fans = smc.as_ref().map(fan_speeds);
+    lines(cpu, fans.as_deref(), thermal_state())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_readout_skips_what_is_missing() {
+        assert_eq!(
+            lines(Some(61.6), Some(&[1850.0, 1790.4]), 0),
+            [
+                "CPU: 62\u{b0}C",
+                "Fans: 1,850, 1,790 rpm",
+                "Thermal pressure: nominal"
+            ]
+        );
+        // A MacBook Air has no fans; one at rest reads zero
+        assert_eq!(lines(None, Some(&[]), 2), ["Thermal pressure: serious"]);
+        assert_eq!(lines(None, Some(&[0.0]), 1)[0], "Fans: off");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(12345), "12,345");
+    }
+}
@@ -0,0 +1,51 @@
+//! Pauses a timed session's countdown while the displays sleep — put to
+//! sleep by hand with ⌃⇧⏏ or a hot corner, as a session holding the display
+//! keeps them from idling off — so a timer isn't spent while the screen is
+//! already dark. It carries on from where it was when they wake.
+//!
+//! Off unless `PauseTimerWhileDisplaySleeps` is set; read at launch.
+
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+use objc2::{msg_send, sel};
+use objc2_foundation::NSString;
+
+use crate::settings;
+use crate::state::Command;
+
+pub const KEY_ENABLED: &str = "PauseTimerWhileDisplaySleeps";
+
+pub unsafe fn observe(delegate: *mut AnyObject) {
+    if !settings::get_bool(KEY_ENABLED) {
+        return;
+    }
+    let Some(workspace) = AnyClass::get(c"NSWorkspace") else {
+        return;
+    };
+    let workspace: *mut AnyObject = msg_send![workspace, sharedWorkspace];
+    let center: *mut AnyObject = msg_send![workspace, notificationCenter];
+    let observed = [
+        (
+            "NSWorkspaceScreensDidSleepNotification",
+            sel!(displaysSlept:),
+        ),
+        ("NSWorkspaceScreensDidWakeNotification", sel!(displaysWoke:)),
+    ];
+    for (name, selector) in observed {
+        let name = NSString::from_str(name);
+        let _: () = msg_send![
+            center,
+            addObserver: delegate,
+            selector: selector,
+            name: &*name,
+            object: std::ptr::null::<AnyObject>()
+        ];
+    }
+}
+
+pub extern "C" fn slept(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    super::handle(Command::DisplayAsleep(true));
+}
+
+pub extern "C" fn woke(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    super::handle(Command::DisplayAsleep(false));
+}
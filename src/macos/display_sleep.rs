@@ -0,0 +1,97 @@
+//! **Sleep Display Now**: the screen goes dark straight away while the Mac
+//! itself stays awake, for an overnight job that doesn't need watching. Like
+//! `pmset displaysleepnow`, which asks the display wrangler to idle.
+//!
+//! The session is switched to hold the system rather than the display first,
+//! starting one if Awake was off, so the display can't be held on again and
+//! the job carries on once it's dark.
+
+use std::ffi::{c_char, c_void};
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_foundation::NSString;
+
+use crate::keepawake::{Kind, KindSet};
+use crate::state;
+
+// `kIOMainPortDefault`
+const MAIN_PORT_DEFAULT: u32 = 0;
+const WRANGLER: &std::ffi::CStr = c"IOService:/IOResources/IODisplayWrangler";
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IORegistryEntryFromPath(main_port: u32, path: *const c_char) -> u32;
+    fn IORegistryEntrySetCFProperty(entry: u32, name: *const c_void, value: *const c_void) -> i32;
+    fn IOObjectRelease(object: u32) -> i32;
+}
+
+/// `kinds` without what holds the display on, and with the system held
+fn without_display(kinds: KindSet) -> KindSet {
+    let mut kept: Vec<Kind> = kinds
+        .iter()
+        .filter(|&kind| kind != Kind::Display && kind != Kind::UserActivity)
+        .collect();
+    if !kept.contains(&Kind::System) {
+        kept.push(Kind::System);
+    }
+    KindSet::of(&kept)
+}
+
+// IOKit takes Foundation objects for CoreFoundation ones: they're
+// toll-free bridged
+pub fn request_idle() -> bool {
+    unsafe {
+        let wrangler = IORegistryEntryFromPath(MAIN_PORT_DEFAULT, WRANGLER.as_ptr());
+        if wrangler == 0 {
+            return false;
+        }
+        let name = NSString::from_str("IORequestIdle");
+        let yes: *mut AnyObject =
+            msg_send![AnyClass::get(c"NSNumber").unwrap(), numberWithBool: true];
+        let status = IORegistryEntrySetCFProperty(
+            wrangler,
+            (&*name as *const NSString).cast(),
+            yes.cast_const().cast(),
+        );
+        IOObjectRelease(wrangler);
+        status == 0
+    }
+}
+
+/// Switches the session to hold the system rather than the display, starting
+/// one if Awake is off
+pub fn hold_system() {
+    let kinds = state::with_status(|status| status.kinds);
+    let wanted = without_display(kinds);
+    if wanted != kinds {
+        super::handle(state::Command::SetMode(wanted));
+    }
+    super::handle(state::Command::Activate);
+}
+
+/// Holds the system, not the display, and puts the display to sleep
+pub fn sleep_display() {
+    hold_system();
+    if !request_idle() {
+        eprintln!("awake: couldn't put the display to sleep");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+
+    #[test]
+    fn the_system_is_held_instead_of_the_display() {
+        assert_eq!(without_display(Mode::Display.kinds()), Mode::System.kinds());
+        assert_eq!(without_display(Mode::Both.kinds()), Mode::System.kinds());
+        assert_eq!(without_display(Mode::Server.kinds()), Mode::Server.kinds());
+        let busy = KindSet::of(&[Kind::UserActivity, Kind::Disk]);
+        assert_eq!(
+            without_display(busy),
+            KindSet::of(&[Kind::System, Kind::Disk])
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Dropping a file, folder or script onto the status item watches it.
+//!
+//! The status button's window is the drag destination; AppKit forwards the
+//! `NSDraggingDestination` calls to the window's delegate, `AwakeDelegate`.
+
+use std::path::PathBuf;
+
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, Bool, Sel};
+use objc2_app_kit::NSStatusItem;
+use objc2_foundation::{NSArray, NSString, NSURL};
+
+use crate::state;
+
+// `NSPasteboardTypeFileURL`
+const FILE_URL_TYPE: &str = "public.file-url";
+// `NSDragOperationCopy`: the cursor shows a plus
+const DRAG_OPERATION_COPY: usize = 1;
+const DRAG_OPERATION_NONE: usize = 0;
+
+/// Makes the status button accept dropped files, reported to `delegate`
+pub unsafe fn register(status_item: &NSStatusItem, delegate: *mut AnyObject) {
+    let button: *mut AnyObject = msg_send![status_item, button];
+    if button.is_null() {
+        return;
+    }
+    let window: *mut AnyObject = msg_send![button, window];
+    if window.is_null() {
+        return;
+    }
+    let types = NSArray::from_retained_slice(&[NSString::from_str(FILE_URL_TYPE)]);
+    let _: () = msg_send![window, registerForDraggedTypes: &*types];
+    let _: () = msg_send![window, setDelegate: delegate];
+}
+
+// The first file the drag carries
+unsafe fn dropped_path(sender: *mut AnyObject) -> Option<PathBuf> {
+    let pasteboard: *mut AnyObject = msg_send![sender, draggingPasteboard];
+    let items: *mut AnyObject = msg_send![pasteboard, pasteboardItems];
+    if items.is_null() {
+        return None;
+    }
+    let count: usize = msg_send![items, count];
+    if count == 0 {
+        return None;
+    }
+    let item: *mut AnyObject = msg_send![items, objectAtIndex: 0usize];
+    let file_type = NSString::from_str(FILE_URL_TYPE);
+    let url: Option<Retained<NSString>> = msg_send![item, stringForType: &*file_type];
+    let path = NSURL::URLWithString(&*url?)?.path()?;
+    Some(PathBuf::from(path.to_string()))
+}
+
+pub extern "C" fn dragging_entered(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    sender: *mut AnyObject,
+) -> usize {
+    // A locked status item takes nothing
+    if !super::controls_lock::is_locked() && unsafe { dropped_path(sender) }.is_some() {
+        DRAG_OPERATION_COPY
+    } else {
+        DRAG_OPERATION_NONE
+    }
+}
+
+pub extern "C" fn perform_drag_operation(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    sender: *mut AnyObject,
+) -> Bool {
+    match unsafe { dropped_path(sender) } {
+        Some(path) if !super::controls_lock::is_locked() => {
+            super::handle(state::Command::WatchPath(path));
+            Bool::YES
+        }
+        _ => Bool::NO,
+    }
+}
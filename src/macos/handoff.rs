@@ -0,0 +1,110 @@
+//! Handing a timed session to `caffeinate` when Awake quits (say, to install an
+//! update), so the time the user asked for is still honoured. The next launch
+//! stops that `caffeinate` and takes the rest of the session back.
+//!
+//! Off unless `defaults write io.tmss.awake HandOffToCaffeinate -bool true`.
+
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::keepawake::{Kind, KindSet};
+use crate::{process, settings, state};
+
+const KEY_ENABLED: &str = "HandOffToCaffeinate";
+const HANDOFF_FILE: &str = "handoff";
+const CAFFEINATE: &str = "/usr/bin/caffeinate";
+
+fn handoff_path() -> Option<PathBuf> {
+    Some(crate::app_support_dir()?.join(HANDOFF_FILE))
+}
+
+// `caffeinate`'s flags for the same assertions
+fn caffeinate_flags(kinds: KindSet) -> String {
+    let flags: String = kinds
+        .iter()
+        .map(|kind| match kind {
+            Kind::Display => 'd',
+            Kind::System => 'i',
+            Kind::Disk => 'm',
+            Kind::NetworkClient => 's',
+            Kind::UserActivity => 'u',
+        })
+        .collect();
+    format!("-{}", flags)
+}
+
+/// Called on quit, before the session is released. Only timed sessions are
+/// handed off: an indefinite `caffeinate` would outlive anyone's intent.
+pub fn hand_off() {
+    if !settings::get_bool(KEY_ENABLED) || crate::keepawake::is_dry_run() {
+        return;
+    }
+    let Some(remaining) = state::remaining_seconds().filter(|&left| left > 0) else {
+        return;
+    };
+    let kinds = state::active_assertion_kinds();
+    let expiry = crate::now_secs() + remaining;
+    let Some(path) = handoff_path() else {
+        return;
+    };
+
+    let child = Command::new(CAFFEINATE)
+        .arg(caffeinate_flags(kinds))
+        .arg("-t")
+        .arg(remaining.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Its own process group, so it isn't taken down with us
+        .process_group(0)
+        .spawn();
+    match child {
+        Ok(child) => {
+            let record = format!("{} {}\n", child.id(), expiry);
+            if let Err(e) = fs::create_dir_all(path.parent().unwrap_or(&path))
+                .and_then(|()| fs::write(&path, record))
+            {
+                eprintln!("Failed to record caffeinate hand-off: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to hand off to caffeinate: {}", e),
+    }
+}
+
+/// Called at launch: stops a `caffeinate` left by the last quit and resumes
+/// whatever time it had left
+pub fn reclaim() {
+    let Some(path) = handoff_path() else {
+        return;
+    };
+    let Ok(record) = fs::read_to_string(&path) else {
+        return;
+    };
+    let _ = fs::remove_file(&path);
+
+    let mut fields = record.split_whitespace();
+    let (Some(Ok(pid)), Some(Ok(expiry))) = (
+        fields.next().map(str::parse::<i32>),
+        fields.next().map(str::parse::<u64>),
+    ) else {
+        return;
+    };
+
+    // The pid may have been reused since; only stop it if it's still caffeinate
+    let pid_text = pid.to_string();
+    let command = process::run_capture("/bin/ps", &["-p", &pid_text, "-o", "comm="]);
+    if command.trim().ends_with("caffeinate") {
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+    }
+
+    let remaining = expiry.saturating_sub(crate::now_secs());
+    if remaining > 0 {
+        super::handle(state::Command::ActivateFor {
+            minutes: remaining.div_ceil(60),
+        });
+    }
+}
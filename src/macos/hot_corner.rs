@@ -0,0 +1,251 @@
+//! A hot corner for Awake: resting the pointer in a chosen corner of a screen
+//! for a second switches Awake on, or off again if it already is, like the
+//! system's own "disable screen saver" corner but with Awake's modes and
+//! timers.
+//!
+//! Off unless `HotCorner` names a corner (`top-left`, `top-right`,
+//! `bottom-left` or `bottom-right`); the monitor is installed at launch.
+//! `HotCornerAction` says what switching on means, as an action (`on`, the
+//! default, or `for:60` for an hour's timer).
+//!
+//! A global monitor sees pointer moves over other apps — no permission is
+//! needed for mouse events — and a pointer resting still sends none, so
+//! entering a corner schedules a look a second later.
+
+use std::cell::{Cell, OnceCell};
+use std::time::{Duration, Instant};
+
+use block2::RcBlock;
+use dispatch2::{DispatchQueue, DispatchTime};
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_app_kit::NSEvent;
+use objc2_foundation::{NSPoint, NSRect};
+
+use crate::{action, settings, state};
+
+pub const KEY_CORNER: &str = "HotCorner";
+const KEY_ACTION: &str = "HotCornerAction";
+
+const DWELL: Duration = Duration::from_secs(1);
+// How close to the corner the pointer must be, in points. The pointer stops
+// a point short of a screen's top and right edges.
+const REACH: f64 = 3.0;
+// `NSEventMaskMouseMoved`, and drags, which move the pointer too
+const MOUSE_MOVES: u64 = 1 << 5 | 1 << 6 | 1 << 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    fn parse(text: &str) -> Option<Corner> {
+        match text.trim() {
+            "top-left" => Some(Corner::TopLeft),
+            "top-right" => Some(Corner::TopRight),
+            "bottom-left" => Some(Corner::BottomLeft),
+            "bottom-right" => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
+
+    /// Which corner of `frame` `point` is in, if any. AppKit's screen
+    /// coordinates grow upwards.
+    fn at(point: NSPoint, frame: NSRect) -> Option<Corner> {
+        let (left, bottom) = (frame.origin.x, frame.origin.y);
+        let (right, top) = (left + frame.size.width, bottom + frame.size.height);
+        let near = |value: f64, edge: f64| (value - edge).abs() <= REACH;
+        match (near(point.x, left), near(point.x, right)) {
+            (true, _) if near(point.y, top) => Some(Corner::TopLeft),
+            (true, _) if near(point.y, bottom) => Some(Corner::BottomLeft),
+            (_, true) if near(point.y, top) => Some(Corner::TopRight),
+            (_, true) if near(point.y, bottom) => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+/// Where the pointer has been, so a corner fires once per visit
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Dwell {
+    #[default]
+    Away,
+    Resting(Instant),
+    Fired,
+}
+
+impl Dwell {
+    /// Notes whether the pointer is `in_corner`; true when it has just
+    /// arrived, so a look is due once it could have rested long enough
+    fn moved(&mut self, in_corner: bool, now: Instant) -> bool {
+        match (*self, in_corner) {
+            (_, false) => {
+                *self = Dwell::Away;
+                false
+            }
+            (Dwell::Away, true) => {
+                *self = Dwell::Resting(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the pointer, still `in_corner`, has now rested there long
+    /// enough; true once per visit
+    fn is_due(&mut self, in_corner: bool, now: Instant) -> bool {
+        match *self {
+            Dwell::Resting(since) if in_corner && now.duration_since(since) >= DWELL => {
+                *self = Dwell::Fired;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+thread_local! {
+    // Kept for the whole run: the monitor stops when released
+    static MONITOR: OnceCell<Option<Retained<AnyObject>>> = const { OnceCell::new() };
+    static DWELL_STATE: Cell<Dwell> = const { Cell::new(Dwell::Away) };
+}
+
+fn corner() -> Option<Corner> {
+    settings::get_string(KEY_CORNER).and_then(|text| Corner::parse(&text))
+}
+
+fn screen_frames() -> Vec<NSRect> {
+    unsafe {
+        let screens: *mut AnyObject = msg_send![AnyClass::get(c"NSScreen").unwrap(), screens];
+        let count: usize = msg_send![screens, count];
+        (0..count)
+            .map(|index| {
+                let screen: *mut AnyObject = msg_send![screens, objectAtIndex: index];
+                msg_send![screen, frame]
+            })
+            .collect()
+    }
+}
+
+fn in_corner() -> bool {
+    let Some(wanted) = corner() else {
+        return false;
+    };
+    let pointer = NSEvent::mouseLocation();
+    screen_frames()
+        .into_iter()
+        .any(|frame| Corner::at(pointer, frame) == Some(wanted))
+}
+
+fn pointer_moved() {
+    let arrived = DWELL_STATE.with(|dwell| {
+        let mut state = dwell.get();
+        let arrived = state.moved(in_corner(), Instant::now());
+        dwell.set(state);
+        arrived
+    });
+    if !arrived {
+        return;
+    }
+    if let Ok(when) = DispatchTime::try_from(DWELL) {
+        let _ = DispatchQueue::main().after(when, look);
+    }
+}
+
+// Runs on the main queue a second after the pointer arrived
+fn look() {
+    let due = DWELL_STATE.with(|dwell| {
+        let mut state = dwell.get();
+        let due = state.is_due(in_corner(), Instant::now());
+        dwell.set(state);
+        due
+    });
+    if due && !super::kiosk::is_enabled() && !super::controls_lock::is_locked() {
+        let awake = state::with_status(|status| status.is_awake());
+        super::handle(command(awake, settings::get_string(KEY_ACTION).as_deref()));
+    }
+}
+
+/// Off when awake; otherwise what `action` names, or an open-ended session
+fn command(awake: bool, action: Option<&str>) -> state::Command {
+    if awake {
+        return state::Command::Deactivate;
+    }
+    action
+        .and_then(action::parse)
+        .unwrap_or(state::Command::Activate)
+}
+
+/// Installs the monitor, if `HotCorner` names a corner
+pub fn start() {
+    if corner().is_none() {
+        return;
+    }
+    MONITOR.with(|monitor| {
+        monitor.get_or_init(|| unsafe {
+            let handler = RcBlock::new(|_event: *mut AnyObject| pointer_moved());
+            msg_send![
+                AnyClass::get(c"NSEvent").unwrap(),
+                addGlobalMonitorForEventsMatchingMask: MOUSE_MOVES,
+                handler: &*handler
+            ]
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use objc2_foundation::NSSize;
+
+    #[test]
+    fn corners_are_within_reach_of_a_screen() {
+        let screen = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(1440.0, 900.0));
+        let at = |x, y| Corner::at(NSPoint::new(x, y), screen);
+        assert_eq!(at(0.0, 899.0), Some(Corner::TopLeft));
+        assert_eq!(at(1439.0, 899.0), Some(Corner::TopRight));
+        assert_eq!(at(2.0, 1.0), Some(Corner::BottomLeft));
+        assert_eq!(at(1440.0, 0.0), Some(Corner::BottomRight));
+        assert_eq!(at(0.0, 450.0), None);
+        assert_eq!(at(720.0, 899.0), None);
+        assert_eq!(Corner::parse("top-right"), Some(Corner::TopRight));
+        assert_eq!(Corner::parse("middle"), None);
+    }
+
+    #[test]
+    fn a_corner_fires_once_per_visit() {
+        let start = Instant::now();
+        let mut dwell = Dwell::default();
+        assert!(dwell.moved(true, start));
+        assert!(!dwell.moved(true, start + Duration::from_millis(200)));
+        assert!(!dwell.is_due(true, start + Duration::from_millis(500)));
+        assert!(dwell.is_due(true, start + DWELL));
+        assert!(!dwell.is_due(true, start + DWELL * 2));
+        // Leaving before the second is up doesn't fire
+        assert!(!dwell.moved(false, start + DWELL * 2));
+        assert!(dwell.moved(true, start + DWELL * 3));
+        assert!(!dwell.is_due(false, start + DWELL * 4));
+    }
+
+    #[test]
+    fn the_corner_switches_off_or_on() {
+        assert!(matches!(
+            command(true, Some("for:60")),
+            state::Command::Deactivate
+        ));
+        assert!(matches!(
+            command(false, Some("for:60")),
+            state::Command::ActivateFor { minutes: 60 }
+        ));
+        assert!(matches!(command(false, None), state::Command::Activate));
+        assert!(matches!(
+            command(false, Some("nonsense")),
+            state::Command::Activate
+        ));
+    }
+}
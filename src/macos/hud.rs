@@ -0,0 +1,320 @@
+//! A floating indicator: a small translucent panel above every window, full
+//! screen ones included, showing the icon and the time left. For anyone who
+//! hides the menu bar or presents full screen.
+//!
+//! Shown while **Show Floating Indicator** is ticked (`FloatingIndicator`), or
+//! while the menu bar is hidden with `HiddenMenuBarFeedback` set to `hud`.
+//! Clicking it toggles and right-clicking opens the menu, as on the status
+//! item; dragging moves it, and where it was left is kept in
+//! `FloatingIndicatorPosition` ("x,y", the bottom-left corner in screen points).
+
+use std::cell::{Cell, OnceCell};
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, Bool, ClassBuilder, Sel};
+use objc2::{msg_send, sel};
+use objc2_app_kit::NSEvent;
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+
+use super::{click, menu_bar};
+use crate::{duration, settings};
+
+pub const KEY_ENABLED: &str = "FloatingIndicator";
+const KEY_POSITION: &str = "FloatingIndicatorPosition";
+
+const SIZE: NSSize = NSSize::new(88.0, 30.0);
+// From the screen's top-right corner, when no position has been kept
+const MARGIN: f64 = 20.0;
+// How far the pointer moves before a click becomes a drag
+const DRAG_POINTS: f64 = 3.0;
+const OPACITY: f64 = 0.9;
+const CORNER_RADIUS: f64 = 8.0;
+
+// `NSWindowStyleMaskBorderless | NSWindowStyleMaskNonactivatingPanel`, so
+// clicking it doesn't take focus from the app being presented
+const STYLE_MASK: usize = 1 << 7;
+const BACKING_BUFFERED: usize = 2;
+// `NSStatusWindowLevel`
+const LEVEL: isize = 25;
+// `NSWindowCollectionBehavior`: canJoinAllSpaces | stationary | ignoresCycle
+// | fullScreenAuxiliary
+const COLLECTION_BEHAVIOR: usize = 1 | 1 << 4 | 1 << 6 | 1 << 8;
+// `NSVisualEffectMaterialHUDWindow`, `…BlendingModeBehindWindow`, `…StateActive`
+const MATERIAL_HUD: isize = 13;
+const BLENDING_BEHIND_WINDOW: isize = 0;
+const STATE_ACTIVE: isize = 1;
+
+struct Hud {
+    window: Retained<AnyObject>,
+    image_view: Retained<AnyObject>,
+    label: Retained<AnyObject>,
+}
+
+/// Where a drag started: the pointer and the panel's origin
+#[derive(Clone, Copy)]
+struct Drag {
+    pointer: NSPoint,
+    origin: NSPoint,
+    moved: bool,
+}
+
+thread_local! {
+    static HUD: OnceCell<Hud> = const { OnceCell::new() };
+    static DRAG: Cell<Option<Drag>> = const { Cell::new(None) };
+}
+
+/// "Off", "On" for an open-ended session, or the time left: "42m", "1h05m"
+fn label(awake: bool, expiry: Option<u64>, now: u64) -> String {
+    match (awake, expiry) {
+        (false, _) => "Off".into(),
+        (true, None) => "On".into(),
+        (true, Some(expiry)) => duration::compact(expiry.saturating_sub(now)),
+    }
+}
+
+fn parse_position(text: &str) -> Option<(f64, f64)> {
+    let (x, y) = text.split_once(',')?;
+    let x: f64 = x.trim().parse().ok()?;
+    let y: f64 = y.trim().parse().ok()?;
+    (x.is_finite() && y.is_finite()).then_some((x, y))
+}
+
+fn is_wanted() -> bool {
+    settings::get_bool(KEY_ENABLED) || menu_bar::wants_hud()
+}
+
+/// Shows or hides the panel as the settings say, then draws the session on
+/// it. Called whenever the status item is redrawn.
+pub fn update(awake: bool, expiry: Option<u64>) {
+    if !is_wanted() {
+        HUD.with(|hud| {
+            if let Some(hud) = hud.get() {
+                unsafe {
+                    let _: () = msg_send![&hud.window, orderOut: std::ptr::null::<AnyObject>()];
+                }
+            }
+        });
+        return;
+    }
+    HUD.with(|hud| {
+        let hud = hud.get_or_init(create);
+        unsafe {
+            let view: *mut AnyObject = msg_send![&hud.window, contentView];
+            let image = super::icon::state_image(awake, super::appearance::is_dark(view));
+            let _: () = msg_send![&hud.image_view, setImage: image.as_deref()];
+            let text = NSString::from_str(&label(awake, expiry, crate::now_secs()));
+            let _: () = msg_send![&hud.label, setStringValue: &*text];
+            let visible: Bool = msg_send![&hud.window, isVisible];
+            if !visible.as_bool() {
+                let _: () = msg_send![&hud.window, orderFrontRegardless];
+            }
+        }
+    });
+}
+
+unsafe fn default_origin() -> NSPoint {
+    let screen: *mut AnyObject = msg_send![AnyClass::get(c"NSScreen").unwrap(), mainScreen];
+    if screen.is_null() {
+        return NSPoint::new(0.0, 0.0);
+    }
+    let frame: NSRect = msg_send![screen, visibleFrame];
+    NSPoint::new(
+        frame.origin.x + frame.size.width - SIZE.width - MARGIN,
+        frame.origin.y + frame.size.height - SIZE.height - MARGIN,
+    )
+}
+
+fn create() -> Hud {
+    unsafe {
+        let origin = settings::get_string(KEY_POSITION)
+            .and_then(|text| parse_position(&text))
+            .map(|(x, y)| NSPoint::new(x, y))
+            .unwrap_or_else(|| default_origin());
+        let window: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSPanel").unwrap(), alloc];
+        let window: Retained<AnyObject> = msg_send![
+            window,
+            initWithContentRect: NSRect::new(origin, SIZE),
+            styleMask: STYLE_MASK,
+            backing: BACKING_BUFFERED,
+            defer: false
+        ];
+        let _: () = msg_send![&window, setReleasedWhenClosed: false];
+        let _: () = msg_send![&window, setHidesOnDeactivate: false];
+        let _: () = msg_send![&window, setLevel: LEVEL];
+        let _: () = msg_send![&window, setCollectionBehavior: COLLECTION_BEHAVIOR];
+        let _: () = msg_send![&window, setOpaque: false];
+        let clear: *mut AnyObject = msg_send![AnyClass::get(c"NSColor").unwrap(), clearColor];
+        let _: () = msg_send![&window, setBackgroundColor: clear];
+        let _: () = msg_send![&window, setHasShadow: true];
+        let _: () = msg_send![&window, setAlphaValue: OPACITY];
+
+        let view: Allocated<AnyObject> = msg_send![register_view_class(), alloc];
+        let view: Retained<AnyObject> =
+            msg_send![view, initWithFrame: NSRect::new(NSPoint::new(0.0, 0.0), SIZE)];
+        let _: () = msg_send![&view, setMaterial: MATERIAL_HUD];
+        let _: () = msg_send![&view, setBlendingMode: BLENDING_BEHIND_WINDOW];
+        let _: () = msg_send![&view, setState: STATE_ACTIVE];
+        let _: () = msg_send![&view, setWantsLayer: true];
+        let layer: *mut AnyObject = msg_send![&view, layer];
+        let _: () = msg_send![layer, setCornerRadius: CORNER_RADIUS];
+        let _: () = msg_send![layer, setMasksToBounds: true];
+
+        let image_view: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSImageView").unwrap(), alloc];
+        let image_view: Retained<AnyObject> = msg_send![
+            image_view,
+            initWithFrame: NSRect::new(NSPoint::new(8.0, 6.0), NSSize::new(18.0, 18.0))
+        ];
+        let label: Retained<AnyObject> = msg_send![
+            AnyClass::get(c"NSTextField").unwrap(),
+            labelWithString: &*NSString::from_str("")
+        ];
+        let _: () = msg_send![
+            &label,
+            setFrame: NSRect::new(NSPoint::new(30.0, 7.0), NSSize::new(52.0, 16.0))
+        ];
+        let font: *mut AnyObject = msg_send![
+            AnyClass::get(c"NSFont").unwrap(),
+            monospacedDigitSystemFontOfSize: 12.0f64,
+            weight: 0.0f64
+        ];
+        if !font.is_null() {
+            let _: () = msg_send![&label, setFont: font];
+        }
+
+        let _: () = msg_send![&view, addSubview: &*image_view];
+        let _: () = msg_send![&view, addSubview: &*label];
+        let _: () = msg_send![&window, setContentView: &*view];
+
+        Hud {
+            window,
+            image_view,
+            label,
+        }
+    }
+}
+
+fn pointer() -> NSPoint {
+    NSEvent::mouseLocation()
+}
+
+// The panel takes every click itself, rather than its image and label
+extern "C" fn hit_test(this: *mut AnyObject, _cmd: Sel, _point: NSPoint) -> *mut AnyObject {
+    this
+}
+
+// A click goes through even though the panel never becomes key
+extern "C" fn accepts_first_mouse(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    _event: *mut AnyObject,
+) -> Bool {
+    Bool::YES
+}
+
+extern "C" fn mouse_down(this: *mut AnyObject, _cmd: Sel, _event: *mut AnyObject) {
+    unsafe {
+        let window: *mut AnyObject = msg_send![this, window];
+        let frame: NSRect = msg_send![window, frame];
+        DRAG.with(|drag| {
+            drag.set(Some(Drag {
+                pointer: pointer(),
+                origin: frame.origin,
+                moved: false,
+            }))
+        });
+    }
+}
+
+extern "C" fn mouse_dragged(this: *mut AnyObject, _cmd: Sel, _event: *mut AnyObject) {
+    let Some(mut drag) = DRAG.with(Cell::get) else {
+        return;
+    };
+    let now = pointer();
+    let (dx, dy) = (now.x - drag.pointer.x, now.y - drag.pointer.y);
+    drag.moved |= dx.hypot(dy) >= DRAG_POINTS;
+    if drag.moved {
+        unsafe {
+            let window: *mut AnyObject = msg_send![this, window];
+            let origin = NSPoint::new(drag.origin.x + dx, drag.origin.y + dy);
+            let _: () = msg_send![window, setFrameOrigin: origin];
+        }
+    }
+    DRAG.with(|cell| cell.set(Some(drag)));
+}
+
+extern "C" fn mouse_up(this: *mut AnyObject, _cmd: Sel, event: *mut AnyObject) {
+    let Some(drag) = DRAG.with(Cell::take) else {
+        return;
+    };
+    if drag.moved {
+        let frame: NSRect = unsafe {
+            let window: *mut AnyObject = msg_send![this, window];
+            msg_send![window, frame]
+        };
+        let position = format!("{},{}", frame.origin.x, frame.origin.y);
+        settings::set_string(KEY_POSITION, &position);
+        return;
+    }
+    let decoded = unsafe { event.cast::<NSEvent>().as_ref() }.map(click::ClickEvent::from_event);
+    let click = click::decode(
+        decoded,
+        NSEvent::modifierFlags_class(),
+        NSEvent::pressedMouseButtons(),
+    );
+    let ignored = super::kiosk::is_enabled() || super::controls_lock::is_locked();
+    match click {
+        click::Click::Toggle if ignored => {}
+        click::Click::Toggle => super::battery_guard::toggle(),
+        click::Click::Menu => super::pop_up_menu(this, event),
+    }
+}
+
+extern "C" fn right_mouse_down(this: *mut AnyObject, _cmd: Sel, event: *mut AnyObject) {
+    super::pop_up_menu(this, event);
+}
+
+fn register_view_class() -> &'static AnyClass {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        let mut builder = ClassBuilder::new(c"AwakeHUDView", objc2::class!(NSVisualEffectView))
+            .expect("AwakeHUDView class already registered");
+
+        type Fn3 = extern "C" fn(*mut AnyObject, Sel, *mut AnyObject);
+
+        unsafe {
+            builder.add_method(sel!(hitTest:), hit_test as extern "C" fn(_, _, _) -> _);
+            builder.add_method(
+                sel!(acceptsFirstMouse:),
+                accepts_first_mouse as extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(sel!(mouseDown:), mouse_down as Fn3);
+            builder.add_method(sel!(mouseDragged:), mouse_dragged as Fn3);
+            builder.add_method(sel!(mouseUp:), mouse_up as Fn3);
+            builder.add_method(sel!(rightMouseDown:), right_mouse_down as Fn3);
+        }
+        builder.register();
+    });
+    AnyClass::get(c"AwakeHUDView").unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_shows_the_time_left() {
+        assert_eq!(label(false, Some(100), 0), "Off");
+        assert_eq!(label(true, None, 0), "On");
+        assert_eq!(label(true, Some(42 * 60), 0), "42m");
+        assert_eq!(label(true, Some(3600 + 5 * 60), 0), "1h05m");
+    }
+
+    #[test]
+    fn positions_read_back() {
+        assert_eq!(parse_position("120,-40.5"), Some((120.0, -40.5)));
+        assert_eq!(parse_position("12"), None);
+        assert_eq!(parse_position("x,1"), None);
+        assert_eq!(parse_position("NaN,1"), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! Keeping the preferences in step across Macs through a settings file in
+//! iCloud Drive. Each sync is a three-way merge against the copy both sides
+//! last agreed on, so a change made on either Mac survives; when both changed
+//! the same setting, this Mac's value wins and the clash is reported.
+//!
+//! Off unless turned on from the menu (`SyncWithICloud`, which itself isn't
+//! synced). A Mac joining with no synced copy yet adopts the shared file as is.
+//! Settings a configuration profile manages stay out of it.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::settings;
+
+pub const KEY_ENABLED: &str = "SyncWithICloud";
+const CLOUD_DOCS: &str = "Library/Mobile Documents/com~apple~CloudDocs";
+const CLOUD_FILE: &str = "Awake/settings.json";
+// The last merged copy, on this Mac only
+const BASE_FILE: &str = "synced-settings.json";
+
+static SYNCING: Mutex<()> = Mutex::new(());
+
+fn cloud_docs() -> Option<PathBuf> {
+    Some(PathBuf::from(env::var("HOME").ok()?).join(CLOUD_DOCS))
+}
+
+// The settings this version knows, then any it doesn't, from a newer Mac
+fn read(path: &Path) -> Result<Option<(settings::Values, settings::Values)>, String> {
+    match fs::read_to_string(path) {
+        Ok(json) => settings::from_synced_json(&json)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+// Through a temporary file, so iCloud never uploads half of one
+fn write(path: &Path, values: &settings::Values) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or(path))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, settings::to_json(values))?;
+    fs::rename(&tmp, path)
+}
+
+/// Merges this Mac's preferences with the shared file and writes the result
+/// to both. Called at launch and when the menu opens. iCloud Drive can block
+/// a read or write while it downloads or coordinates the file, so that's done
+/// on a background thread and the merge applied back on the main one.
+pub fn sync() {
+    let Some(local) = local() else {
+        return;
+    };
+    thread::spawn(move || {
+        let Some(merged) = merge(&local) else {
+            return;
+        };
+        super::run_on_main(move |_| {
+            apply(&local, &merged);
+            // The shared file may have brought other quiet hours
+            super::refresh_menu();
+        });
+    });
+}
+
+/// `sync`, finished before it returns; for quitting, when nothing would be
+/// left to apply the merge on the main thread
+pub fn sync_now() {
+    let Some(local) = local() else {
+        return;
+    };
+    if let Some(merged) = merge(&local) {
+        apply(&local, &merged);
+    }
+}
+
+// This Mac's preferences as synced, or `None` if syncing is off
+fn local() -> Option<settings::Values> {
+    if !settings::get_bool(KEY_ENABLED) {
+        return None;
+    }
+    let mut local = settings::current();
+    local.retain(|(key, _)| !settings::is_managed(key));
+    Some(local)
+}
+
+fn merge(local: &settings::Values) -> Option<settings::Values> {
+    // One sync at a time, so no two write the files at once
+    let _syncing = SYNCING.lock().unwrap_or_else(PoisonError::into_inner);
+    try_merge(local)
+        .map_err(|e| eprintln!("Awake: iCloud settings sync failed: {}", e))
+        .ok()
+}
+
+// Unless the preferences changed while the files were being read; the next
+// sync merges those
+fn apply(local: &settings::Values, merged: &settings::Values) {
+    if merged != local && self::local().as_ref() == Some(local) {
+        settings::replace(merged);
+    }
+}
+
+fn try_merge(local: &settings::Values) -> Result<settings::Values, String> {
+    let docs = cloud_docs().ok_or("HOME not set")?;
+    if !docs.is_dir() {
+        return Err("iCloud Drive isn't turned on".into());
+    }
+    let cloud_path = docs.join(CLOUD_FILE);
+    let base_path = crate::app_support_dir()
+        .ok_or("HOME not set")?
+        .join(BASE_FILE);
+
+    let (remote, unknown) = match read(&cloud_path)? {
+        Some((remote, unknown)) => (Some(remote), unknown),
+        None => (None, Vec::new()),
+    };
+    let base = read(&base_path)?.map(|(base, _)| base);
+    let merged = match (&base, &remote) {
+        (Some(base), Some(remote)) => settings::merge(base, local, remote),
+        (None, Some(remote)) => settings::merge(local, local, remote),
+        (_, None) => settings::merge(local, local, local),
+    };
+    for key in &merged.conflicts {
+        eprintln!(
+            "Awake: {} was changed on this Mac and in iCloud; keeping this Mac's",
+            key
+        );
+    }
+
+    // What a newer Mac added goes back as it came
+    if remote.as_ref() != Some(&merged.values) {
+        let shared: settings::Values = merged.values.iter().cloned().chain(unknown).collect();
+        write(&cloud_path, &shared).map_err(|e| format!("{}: {}", cloud_path.display(), e))?;
+    }
+    // Most syncs, on opening the menu, find nothing new
+    if base.as_ref() != Some(&merged.values) {
+        write(&base_path, &merged.values).map_err(|e| format!("{}: {}", base_path.display(), e))?;
+    }
+    Ok(merged.values)
+}
@@ -0,0 +1,277 @@
+//! What the status item shows, drawn by `draw` at launch and after every
+//! change. Normally an SF Symbol (see `badge` for the time left drawn on it);
+//! in text-only mode (`TextOnlyIcon`) a short title instead, for older macOS
+//! where the symbols render poorly or for anyone who would rather read the
+//! state than recognise it. The titles are "AWAKE" and "zzz"
+//! unless `ActiveTitle` and `IdleTitle` say otherwise, e.g.
+//! `defaults write io.tmss.awake ActiveTitle "☕ ON"`.
+//!
+//! The symbols themselves can be replaced by PNG or PDF files named by
+//! `ActiveIconPath` and `IdleIconPath`, drawn as templates so the menu bar
+//! tints them like its own. Each file is loaded once and kept until it changes
+//! on disk; one that is missing, of another type, or doesn't load leaves the
+//! built-in symbol in place. `ActiveIconPathDark` and `IdleIconPathDark` are
+//! used instead while the menu bar is dark (see `appearance`), and with
+//! `ColoredIcons` the files keep their own colours rather than being tinted.
+//! The floating indicator shows the same image, from `state_image`.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2::{msg_send, AnyThread, ClassType};
+use objc2_app_kit::NSImage;
+
+use super::{appearance, badge, countdown_item};
+use objc2_foundation::{NSSize, NSString};
+
+use crate::settings;
+
+pub const KEY_TEXT_ONLY: &str = "TextOnlyIcon";
+const SYMBOLS: [&str; 2] = ["cup.and.saucer.fill", "moon.zzz.fill"];
+const TITLES: [(&str, &str); 2] = [("ActiveTitle", "AWAKE"), ("IdleTitle", "zzz")];
+// Active and idle, each light then dark
+const IMAGE_KEYS: [[&str; 2]; 2] = [
+    ["ActiveIconPath", "ActiveIconPathDark"],
+    ["IdleIconPath", "IdleIconPathDark"],
+];
+const KEY_COLORED: &str = "ColoredIcons";
+const IMAGE_TYPES: [&str; 2] = ["png", "pdf"];
+// The menu bar's icons are 18 points tall; a wide logo may take two squares
+const IMAGE_HEIGHT: f64 = 18.0;
+const IMAGE_MAX_WIDTH: f64 = 36.0;
+// NSFontWeightSemibold and NSFontWeightRegular
+const WEIGHT_ACTIVE: f64 = 0.3;
+const WEIGHT_IDLE: f64 = 0.0;
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    static NSFontAttributeName: &'static NSString;
+}
+
+/// A file loaded for one state: its path, when it was modified, and the
+/// image, or `None` if it didn't load
+type Loaded = (PathBuf, SystemTime, Option<Retained<NSImage>>);
+
+thread_local! {
+    // Laid out like `IMAGE_KEYS`
+    static IMAGES: RefCell<[[Option<Loaded>; 2]; 2]> =
+        const { RefCell::new([[None, None], [None, None]]) };
+}
+
+pub fn is_text_only() -> bool {
+    settings::get_bool(KEY_TEXT_ONLY)
+}
+
+/// The title for the state: the custom one if set, then the time left if the
+/// badge is on
+pub fn title(awake: bool, custom: Option<String>, time_left: Option<String>) -> String {
+    let (_, default) = TITLES[if awake { 0 } else { 1 }];
+    let title = custom
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    match time_left {
+        Some(left) => format!("{} {}", title, left),
+        None => title,
+    }
+}
+
+/// The custom title for the state, if one is set
+pub fn custom_title(awake: bool) -> Option<String> {
+    let (key, _) = TITLES[if awake { 0 } else { 1 }];
+    settings::get_string(key)
+}
+
+/// Whether `path` names a kind of file that can stand in for the symbol
+fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_TYPES.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+/// `size` scaled to the menu bar's height, narrowed if it would be too wide
+fn fit(size: NSSize) -> Option<NSSize> {
+    if size.width <= 0.0 || size.height <= 0.0 {
+        return None;
+    }
+    let scale = (IMAGE_HEIGHT / size.height).min(IMAGE_MAX_WIDTH / size.width);
+    Some(NSSize::new(size.width * scale, size.height * scale))
+}
+
+unsafe fn load(path: &Path) -> Option<Retained<NSImage>> {
+    let file = NSString::from_str(path.to_str()?);
+    let image: Option<Retained<NSImage>> =
+        msg_send![NSImage::alloc(), initWithContentsOfFile: &*file];
+    let image = image?;
+    let valid: Bool = msg_send![&image, isValid];
+    let size = fit(msg_send![&image, size]).filter(|_| valid.as_bool())?;
+    let _: () = msg_send![&image, setSize: size];
+    Some(image)
+}
+
+/// Which of the state's files to use: the dark one while dark, if set,
+/// otherwise the light one
+fn pick(
+    dark: bool,
+    light_path: Option<String>,
+    dark_path: Option<String>,
+) -> Option<(usize, String)> {
+    let set = |path: Option<String>| path.filter(|path| !path.trim().is_empty());
+    match set(dark_path) {
+        Some(path) if dark => Some((1, path)),
+        _ => set(light_path).map(|path| (0, path)),
+    }
+}
+
+/// The image file set for the state and appearance, if there is one and it
+/// loads; loaded files are reused until modified
+pub fn custom_image(awake: bool, dark: bool) -> Option<Retained<NSImage>> {
+    let state = if awake { 0 } else { 1 };
+    let [light_key, dark_key] = IMAGE_KEYS[state];
+    let (slot, path) = pick(
+        dark,
+        settings::get_string(light_key),
+        settings::get_string(dark_key),
+    )?;
+    let path = PathBuf::from(path);
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+    let image = IMAGES.with(|cell| {
+        let mut images = cell.borrow_mut();
+        if let Some((cached, when, image)) = &images[state][slot] {
+            if *cached == path && *when == modified {
+                return image.clone();
+            }
+        }
+        let image = if is_supported(&path) {
+            unsafe { load(&path) }
+        } else {
+            None
+        };
+        if image.is_none() {
+            eprintln!("Awake: can't use {} as the icon", path.display());
+        }
+        images[state][slot] = Some((path, modified, image.clone()));
+        image
+    })?;
+    unsafe {
+        let _: () = msg_send![&image, setTemplate: !settings::get_bool(KEY_COLORED)];
+    }
+    Some(image)
+}
+
+/// The image for the session: the custom file for the appearance, else the
+/// symbol. `None` if neither can be loaded.
+pub fn state_image(awake: bool, dark: bool) -> Option<Retained<NSImage>> {
+    if let Some(image) = custom_image(awake, dark) {
+        return Some(image);
+    }
+    unsafe {
+        let name = NSString::from_str(SYMBOLS[if awake { 0 } else { 1 }]);
+        let desc: Option<&NSString> = None;
+        let symbol: Option<Retained<NSImage>> = msg_send![NSImage::class(), imageWithSystemSymbolName: &*name, accessibilityDescription: desc];
+        if let Some(symbol) = &symbol {
+            let _: () = msg_send![&**symbol, setTemplate: true];
+        }
+        symbol
+    }
+}
+
+/// Shows the session on the status item's `button`: the image with the time
+/// left as a badge if that's on (and the time left has no item of its own),
+/// or a title in text-only mode and whenever no image loads. A null button is
+/// left alone.
+pub unsafe fn draw(button: *mut AnyObject, awake: bool, expiry: Option<u64>) {
+    if button.is_null() {
+        return;
+    }
+    let badge = expiry
+        .map(|expiry| expiry.saturating_sub(crate::now_secs()))
+        .filter(|&left| {
+            left > 0 && settings::get_bool(badge::KEY_ENABLED) && !countdown_item::is_enabled()
+        })
+        .map(badge::text);
+    let image = if is_text_only() {
+        None
+    } else {
+        state_image(awake, appearance::is_dark(button))
+    };
+    match image {
+        Some(img) => {
+            let _: () = msg_send![button, setTitle: &*NSString::from_str("")];
+            let img = match &badge {
+                Some(text) => badge::compose(&img, text),
+                None => img,
+            };
+            let _: () = msg_send![button, setImage: &*img];
+        }
+        // Text-only, or a macOS without the symbols
+        None => {
+            let title = title(awake, custom_title(awake), badge);
+            let _: () = msg_send![button, setImage: std::ptr::null::<NSImage>()];
+            let _: () = msg_send![button, setAttributedTitle: &*attributed(&title, awake)];
+        }
+    }
+}
+
+/// `title` in the menu bar's font, semibold while awake. Digits are
+/// fixed-width so a countdown doesn't make the item jitter.
+pub unsafe fn attributed(title: &str, awake: bool) -> Retained<AnyObject> {
+    let font_class = AnyClass::get(c"NSFont").unwrap();
+    let size: f64 = msg_send![font_class, systemFontSize];
+    let weight = if awake { WEIGHT_ACTIVE } else { WEIGHT_IDLE };
+    let font: *mut AnyObject =
+        msg_send![font_class, monospacedDigitSystemFontOfSize: size, weight: weight];
+    let dictionary_class = AnyClass::get(c"NSDictionary").unwrap();
+    let attributes: *mut AnyObject =
+        msg_send![dictionary_class, dictionaryWithObject: font, forKey: NSFontAttributeName];
+    let string: Allocated<AnyObject> =
+        msg_send![AnyClass::get(c"NSAttributedString").unwrap(), alloc];
+    let title = NSString::from_str(title);
+    msg_send![string, initWithString: &*title, attributes: attributes]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_default_then_custom() {
+        assert_eq!(title(true, None, None), "AWAKE");
+        assert_eq!(title(false, None, None), "zzz");
+        assert_eq!(title(true, Some("ON".into()), Some("42".into())), "ON 42");
+        // A blank custom title would leave nothing to click
+        assert_eq!(title(false, Some(" ".into()), None), "zzz");
+    }
+
+    #[test]
+    fn image_files_are_checked_and_fitted() {
+        assert!(is_supported(Path::new("/Users/me/awake.PNG")));
+        assert!(is_supported(Path::new("logo.pdf")));
+        assert!(!is_supported(Path::new("logo.svg")));
+        assert!(!is_supported(Path::new("logo")));
+
+        assert_eq!(fit(NSSize::new(64.0, 64.0)), Some(NSSize::new(18.0, 18.0)));
+        // A wide logo is limited by its width
+        assert_eq!(fit(NSSize::new(400.0, 100.0)), Some(NSSize::new(36.0, 9.0)));
+        assert_eq!(fit(NSSize::new(0.0, 18.0)), None);
+    }
+
+    #[test]
+    fn dark_files_apply_only_while_dark() {
+        let light = Some("on.png".to_string());
+        let dark = Some("on-dark.png".to_string());
+        assert_eq!(
+            pick(true, light.clone(), dark.clone()),
+            Some((1, "on-dark.png".into()))
+        );
+        assert_eq!(pick(false, light.clone(), dark), Some((0, "on.png".into())));
+        // Without a dark file the light one serves both
+        assert_eq!(
+            pick(true, light, Some(" ".into())),
+            Some((0, "on.png".into()))
+        );
+        assert_eq!(pick(true, None, None), None);
+    }
+}
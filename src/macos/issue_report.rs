@@ -0,0 +1,170 @@
+//! **Report an Issue…**: a GitHub issue filled in with what a bug report
+//! needs — Awake's version, the macOS version, the current mode and state, and
+//! the last 50 events. The text is shown for review first, editable, so
+//! nothing leaves the Mac that the user hasn't seen; then the browser opens
+//! the new-issue page with it filled in.
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2::{msg_send, MainThreadMarker};
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString, NSURL};
+
+use super::{alert, log_window};
+use crate::state;
+
+const NEW_ISSUE: &str = "https://github.com/anatomic/awake/issues/new";
+const LOG_LINES: usize = 50;
+// Longer links are refused by GitHub, so the body is cut to fit
+const MAX_URL: usize = 8000;
+
+const REVIEW_SIZE: NSSize = NSSize::new(520.0, 280.0);
+// NSViewWidthSizable | NSViewHeightSizable
+const AUTORESIZE_FILL: usize = 2 | 16;
+
+/// What's known about this Mac when the report is made
+struct Facts<'a> {
+    version: &'a str,
+    build: &'a str,
+    macos: &'a str,
+    mode: &'a str,
+    state: &'a str,
+    log: &'a str,
+}
+
+fn body(facts: &Facts) -> String {
+    format!(
+        "**What happened?**\n\n\n\n**What did you expect?**\n\n\n\n\
+         - Awake: {} ({})\n- macOS: {}\n- Mode: {}\n- State: {}\n\n\
+         <details><summary>Last {} events</summary>\n\n```\n{}```\n\n</details>\n",
+        facts.version, facts.build, facts.macos, facts.mode, facts.state, LOG_LINES, facts.log
+    )
+}
+
+// Everything but RFC 3986's unreserved characters, percent-encoded as UTF-8
+fn encode(text: &str, out: &mut String, max: usize) {
+    for c in text.chars() {
+        let mut piece = String::new();
+        if c.is_ascii_alphanumeric() || "-._~".contains(c) {
+            piece.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                piece.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        if out.len() + piece.len() > max {
+            return;
+        }
+        out.push_str(&piece);
+    }
+}
+
+fn issue_url(body: &str) -> String {
+    let mut url = format!("{}?body=", NEW_ISSUE);
+    encode(body, &mut url, MAX_URL);
+    url
+}
+
+fn gather() -> String {
+    let macos: Retained<NSString> = unsafe {
+        let info: *mut AnyObject = msg_send![AnyClass::get(c"NSProcessInfo").unwrap(), processInfo];
+        msg_send![info, operatingSystemVersionString]
+    };
+    let (mode, summary) = state::with_status(|s| (s.kinds.to_string(), s.summary()));
+    body(&Facts {
+        version: env!("CARGO_PKG_VERSION"),
+        build: &crate::arch::describe(crate::arch::compiled_for(), crate::arch::is_translated()),
+        macos: &macos.to_string(),
+        mode: &mode,
+        state: &summary,
+        log: &log_window::recent(LOG_LINES),
+    })
+}
+
+// The report in an editable text view, under an alert; `None` if cancelled
+fn review(text: &str, mtm: MainThreadMarker) -> Option<String> {
+    unsafe {
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), REVIEW_SIZE);
+        let scroll: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSScrollView").unwrap(), alloc];
+        let scroll: Retained<AnyObject> = msg_send![scroll, initWithFrame: frame];
+        let _: () = msg_send![&scroll, setHasVerticalScroller: true];
+        let text_view: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSTextView").unwrap(), alloc];
+        let text_view: Retained<AnyObject> = msg_send![text_view, initWithFrame: frame];
+        let _: () = msg_send![&text_view, setAutoresizingMask: AUTORESIZE_FILL];
+        let font: *mut AnyObject = msg_send![
+            AnyClass::get(c"NSFont").unwrap(),
+            monospacedSystemFontOfSize: 11.0f64,
+            weight: 0.0f64
+        ];
+        if !font.is_null() {
+            let _: () = msg_send![&text_view, setFont: font];
+        }
+        let _: () = msg_send![&text_view, setString: &*NSString::from_str(text)];
+        let _: () = msg_send![&scroll, setDocumentView: &*text_view];
+
+        let response = alert::ask_with(
+            "Report an issue on GitHub",
+            "This is what will be filled in. Edit or remove anything you'd rather not share; \
+             nothing is sent until you submit the issue in your browser.",
+            &["Open in Browser", "Cancel"],
+            alert::Style::Warning,
+            Some(&*scroll),
+            mtm,
+        );
+        if response != Some(0) {
+            return None;
+        }
+        let edited: Retained<NSString> = msg_send![&text_view, string];
+        Some(edited.to_string())
+    }
+}
+
+pub fn report_issue(mtm: MainThreadMarker) {
+    let Some(text) = review(&gather(), mtm) else {
+        return;
+    };
+    let Some(url) = NSURL::URLWithString(&NSString::from_str(&issue_url(&text))) else {
+        return;
+    };
+    unsafe {
+        let workspace: *mut AnyObject =
+            msg_send![AnyClass::get(c"NSWorkspace").unwrap(), sharedWorkspace];
+        let _: Bool = msg_send![workspace, openURL: &*url];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_body_carries_the_facts() {
+        let text = body(&Facts {
+            version: "1.2.3",
+            build: "x86_64 under Rosetta",
+            macos: "Version 15.1 (Build 24B83)",
+            mode: "display+system",
+            state: "On \u{2014} turned on manually",
+            log: "t1    Off\n",
+        });
+        assert!(text.contains(
+            "- Awake: 1.2.3 (x86_64 under Rosetta)\n- macOS: Version 15.1 (Build 24B83)\n"
+        ));
+        assert!(text.contains("- State: On \u{2014} turned on manually\n"));
+        assert!(text.contains("```\nt1    Off\n```"));
+    }
+
+    #[test]
+    fn the_url_is_encoded_and_fits() {
+        assert_eq!(
+            issue_url("a b&c\u{2014}~"),
+            format!("{}?body=a%20b%26c%E2%80%94~", NEW_ISSUE)
+        );
+        let long = issue_url(&"\u{2014}".repeat(MAX_URL));
+        assert!(long.len() <= MAX_URL);
+        // Cut between characters, never inside one's escape
+        assert!(long.ends_with("%E2%80%94"));
+    }
+}
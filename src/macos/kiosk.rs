@@ -0,0 +1,60 @@
+//! Kiosk mode, for digital signage and other machines nobody should have to
+//! tend: Awake switches on at launch and back on whenever anything switches
+//! it off, the menu shows only the status, clicks don't toggle, and launchd
+//! relaunches Awake if it ever quits (a LaunchAgent with `KeepAlive`, in
+//! effect from the next login).
+//!
+//! On with `--kiosk` or `KioskMode`, usually set by a configuration profile;
+//! holding ⌥ at launch doesn't bring up safe mode in it.
+
+use std::cell::Cell;
+
+use super::launch_agent;
+use crate::settings;
+use crate::state::{self, Command, Status};
+
+pub const KEY_ENABLED: &str = "KioskMode";
+
+thread_local! {
+    // Set by `--kiosk`
+    static FLAG: Cell<bool> = const { Cell::new(false) };
+    // Whether the last status seen was on, so only a switch off is undone
+    static WAS_AWAKE: Cell<bool> = const { Cell::new(true) };
+}
+
+pub fn enable() {
+    FLAG.with(|flag| flag.set(true));
+}
+
+pub fn is_enabled() -> bool {
+    FLAG.with(Cell::get) || settings::get_bool(KEY_ENABLED)
+}
+
+// Posted rather than handled, as observers run inside a command. Only on the
+// edge, so an activation that fails isn't retried in a loop.
+fn hold(status: &Status) {
+    let awake = status.is_awake();
+    if !WAS_AWAKE.with(|was| was.replace(awake)) || awake || super::is_quitting() {
+        return;
+    }
+    super::post(Command::Activate);
+}
+
+/// Puts kiosk mode in force, if it's on: called once the front-end is up
+pub fn start() {
+    if !is_enabled() {
+        return;
+    }
+    state::on_state_change(hold);
+    if let Err(e) = launch_agent::install_watchdog() {
+        super::present_error(&e);
+    }
+    super::handle(Command::Activate);
+}
+
+/// After a wake, switches back on if the session didn't survive it
+pub fn woke() {
+    if is_enabled() && !state::with_status(|status| status.is_awake()) {
+        super::handle(Command::Activate);
+    }
+}
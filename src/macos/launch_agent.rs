@@ -0,0 +1,119 @@
+//! Launch at login via a per-user LaunchAgent plist, labelled with the
+//! bundle's identifier. In kiosk mode the agent also keeps Awake running,
+//! passing `--kiosk` on.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::error::AwakeError;
+
+/// The agent's label and file name: the bundle identifier
+pub fn label() -> &'static str {
+    super::bundle::identifier()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn launch_agent_path() -> Option<PathBuf> {
+    let home = env::var("HOME").ok()?;
+    Some(
+        PathBuf::from(home)
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", label())),
+    )
+}
+
+pub fn is_launch_at_login() -> bool {
+    launch_agent_path().is_some_and(|p| p.exists())
+}
+
+fn get_app_path() -> String {
+    env::current_exe()
+        .ok()
+        .and_then(|p| p.to_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn launch_agent_plist(app_path: &str, kiosk: bool) -> String {
+    let (argument, keep_alive) = if kiosk {
+        (
+            "\n        <string>--kiosk</string>",
+            "\n    <key>KeepAlive</key>\n    <true/>",
+        )
+    } else {
+        ("", "")
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>{}
+    </array>
+    <key>RunAtLoad</key>
+    <true/>{}
+</dict>
+</plist>
+"#,
+        xml_escape(label()),
+        xml_escape(app_path),
+        argument,
+        keep_alive
+    )
+}
+
+fn write_plist(path: &Path, plist: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, plist)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o644))
+}
+
+pub fn write_launch_agent(path: &Path, app_path: &str) -> std::io::Result<()> {
+    write_plist(path, &launch_agent_plist(app_path, false))
+}
+
+fn write_current(path: &Path, kiosk: bool) -> Result<(), AwakeError> {
+    let app_path = get_app_path();
+    if app_path.is_empty() {
+        return Err(AwakeError::AppPathUnknown);
+    }
+    let plist = launch_agent_plist(&app_path, kiosk);
+    // Rewritten only when it changes, as launchd notices the file
+    if fs::read_to_string(path).ok().as_deref() != Some(plist.as_str()) {
+        write_plist(path, &plist)?;
+    }
+    Ok(())
+}
+
+/// Launch at login, and relaunch after any quit, with `--kiosk`
+pub fn install_watchdog() -> Result<(), AwakeError> {
+    let path = launch_agent_path().ok_or(AwakeError::HomeNotSet)?;
+    write_current(&path, true)
+}
+
+pub fn set_launch_at_login(enable: bool) -> Result<(), AwakeError> {
+    let path = launch_agent_path().ok_or(AwakeError::HomeNotSet)?;
+
+    if enable {
+        write_current(&path, super::kiosk::is_enabled())?;
+    } else if let Err(e) = fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Where the Mac is, for the sun's times in `solar`: asked of CoreLocation
+//! once at launch, and only when a schedule follows the sun and no
+//! `--location` was given. The first fix is enough, as sunrise moves by
+//! minutes only over many kilometres; a refusal leaves solar times
+//! unresolved.
+//!
+//! The bundle's `Info.plist` says why it's asked for
+//! (`NSLocationUsageDescription`).
+
+use std::cell::OnceCell;
+
+use objc2::encode::{Encode, Encoding};
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+
+use crate::{quiet_hours, solar};
+
+// `kCLLocationAccuracyThreeKilometers`: plenty for the sun, and spares the GPS
+const ACCURACY_METRES: f64 = 3000.0;
+
+#[link(name = "CoreLocation", kind = "framework")]
+extern "C" {}
+
+/// `CLLocationCoordinate2D`
+#[repr(C)]
+struct Coordinate {
+    latitude: f64,
+    longitude: f64,
+}
+
+unsafe impl Encode for Coordinate {
+    const ENCODING: Encoding =
+        Encoding::Struct("CLLocationCoordinate2D", &[f64::ENCODING, f64::ENCODING]);
+}
+
+thread_local! {
+    // Kept until it answers: a released manager never does
+    static MANAGER: OnceCell<Retained<AnyObject>> = const { OnceCell::new() };
+}
+
+/// Asks for a fix, if a schedule needs one; `delegate` gets the answer
+pub unsafe fn start(delegate: *mut AnyObject) {
+    if quiet_hours::follows_sun() {
+        solar::want_location();
+    }
+    if !solar::needs_location() {
+        return;
+    }
+    let Some(class) = AnyClass::get(c"CLLocationManager") else {
+        return;
+    };
+    let manager: Retained<AnyObject> = msg_send![class, new];
+    let _: () = msg_send![&manager, setDelegate: delegate];
+    let _: () = msg_send![&manager, setDesiredAccuracy: ACCURACY_METRES];
+    let _: () = msg_send![&manager, requestLocation];
+    MANAGER.with(|cell| cell.set(manager).ok());
+}
+
+/// `locationManager:didUpdateLocations:`
+pub extern "C" fn updated(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    _manager: *mut AnyObject,
+    locations: *mut AnyObject,
+) {
+    let coordinate: Option<Coordinate> = unsafe {
+        let location: *mut AnyObject = msg_send![locations, lastObject];
+        (!location.is_null()).then(|| msg_send![location, coordinate])
+    };
+    if let Some(Coordinate {
+        latitude,
+        longitude,
+    }) = coordinate
+    {
+        solar::set_location(latitude, longitude);
+    }
+}
+
+/// `locationManager:didFailWithError:`
+pub extern "C" fn failed(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    _manager: *mut AnyObject,
+    error: *mut AnyObject,
+) {
+    let description: Option<Retained<objc2_foundation::NSString>> =
+        unsafe { msg_send![error, localizedDescription] };
+    eprintln!(
+        "Awake: no location for sunrise and sunset: {}",
+        description.map(|d| d.to_string()).unwrap_or_default()
+    );
+}
@@ -0,0 +1,37 @@
+//! **Lock Screen & Keep Awake**: locks the session, as the menu bar's lock
+//! item or ⌃⌘Q does, after switching the session to hold only the system — for
+//! leaving the desk while a job runs on.
+//!
+//! Locking goes through `SACLockScreenImmediate` in the private `login`
+//! framework, looked up when needed so a macOS without it still launches.
+//! Failing that, the display is put to sleep, which locks the screen when a
+//! password is required straight after sleep.
+
+use std::ffi::CStr;
+
+const LOGIN_FRAMEWORK: &CStr =
+    c"/System/Library/PrivateFrameworks/login.framework/Versions/Current/login";
+const LOCK_SYMBOL: &CStr = c"SACLockScreenImmediate";
+
+fn lock() -> bool {
+    unsafe {
+        let framework = libc::dlopen(LOGIN_FRAMEWORK.as_ptr(), libc::RTLD_LAZY);
+        if framework.is_null() {
+            return false;
+        }
+        let symbol = libc::dlsym(framework, LOCK_SYMBOL.as_ptr());
+        if symbol.is_null() {
+            return false;
+        }
+        let lock: extern "C" fn() -> i32 = std::mem::transmute(symbol);
+        lock() == 0
+    }
+}
+
+/// Holds the system, not the display, and locks the screen
+pub fn lock_screen() {
+    super::display_sleep::hold_system();
+    if !lock() && !super::display_sleep::request_idle() {
+        eprintln!("awake: couldn't lock the screen");
+    }
+}
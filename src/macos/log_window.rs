@@ -0,0 +1,356 @@
+//! **Show Event Log…**: the event log in a window, newest at the bottom, for
+//! seeing what switched Awake on and why without Console.app or a terminal.
+//! A pop-up filters by level and **Copy** puts what's shown on the clipboard.
+//!
+//! Levels come from each event: switching on and off and mode changes are
+//! information, an assertion that failed to release is a warning, and one the
+//! OS refused is an error. The window is made once and refilled each time
+//! it's shown.
+
+use std::cell::{OnceCell, RefCell};
+use std::fs;
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, ClassBuilder, Sel};
+use objc2::{msg_send, sel, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+
+use crate::event_log::{self, json_field};
+
+// The newest events shown; older ones are still in the files
+const MAX_LINES: usize = 2000;
+
+const SIZE: NSSize = NSSize::new(680.0, 440.0);
+const BAR_HEIGHT: f64 = 40.0;
+// NSWindowStyleMask: titled | closable | miniaturizable | resizable
+const STYLE_MASK: usize = 1 | 2 | 4 | 8;
+const BACKING_BUFFERED: usize = 2;
+// NSViewMinXMargin, NSViewWidthSizable, NSViewMinYMargin, NSViewHeightSizable
+const MIN_X_MARGIN: usize = 1;
+const WIDTH_SIZABLE: usize = 2;
+const MIN_Y_MARGIN: usize = 8;
+const HEIGHT_SIZABLE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+/// The pop-up's choices, in order: the least level each shows
+const FILTERS: [(&str, Level); 3] = [
+    ("All Events", Level::Info),
+    ("Warnings and Errors", Level::Warning),
+    ("Errors Only", Level::Error),
+];
+
+/// One event as the window lists it
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    level: Level,
+    ts: u64,
+    text: String,
+}
+
+/// `line` from the log, if it's an event this version knows
+fn entry(line: &str) -> Option<Entry> {
+    let ts = json_field(line, "ts")?.parse().ok()?;
+    let field = |key| json_field(line, key).unwrap_or_default();
+    let (level, text) = match json_field(line, "event")?.as_str() {
+        "activated" => (
+            Level::Info,
+            format!("On ({}) \u{2014} {}", field("mode"), field("reason")),
+        ),
+        "deactivated" => (Level::Info, "Off".to_string()),
+        "mode_changed" => (Level::Info, format!("Mode: {}", field("mode"))),
+        "assertion_error" => {
+            let kind = field("assertion_type");
+            let level = if kind == "release" {
+                Level::Warning
+            } else {
+                Level::Error
+            };
+            let code: i32 = field("code").parse().ok()?;
+            (
+                level,
+                format!(
+                    "Assertion failed: {} ({})",
+                    kind,
+                    crate::keepawake::describe_code(code)
+                ),
+            )
+        }
+        _ => return None,
+    };
+    Some(Entry { level, ts, text })
+}
+
+// "2026-10-14 09:12:03", in local time
+fn local_time(ts: u64) -> String {
+    unsafe {
+        let t = ts as libc::time_t;
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            return ts.to_string();
+        }
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+}
+
+fn render(entries: &[Entry], least: Level, time: impl Fn(u64) -> String) -> String {
+    let mut text = String::new();
+    for entry in entries.iter().filter(|entry| entry.level >= least) {
+        let marker = match entry.level {
+            Level::Info => " ",
+            Level::Warning => "!",
+            Level::Error => "\u{2716}",
+        };
+        text.push_str(&format!("{} {}  {}\n", time(entry.ts), marker, entry.text));
+    }
+    if text.is_empty() {
+        text.push_str("Nothing logged yet.\n");
+    }
+    text
+}
+
+fn read_entries() -> Vec<Entry> {
+    let mut entries: Vec<Entry> = event_log::files()
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|text| text.lines().filter_map(entry).collect::<Vec<_>>())
+        .collect();
+    let excess = entries.len().saturating_sub(MAX_LINES);
+    entries.drain(..excess);
+    entries
+}
+
+/// The newest `count` events as the window shows them, for a bug report
+pub fn recent(count: usize) -> String {
+    let mut entries = read_entries();
+    entries.drain(..entries.len().saturating_sub(count));
+    render(&entries, Level::Info, local_time)
+}
+
+struct LogWindow {
+    window: Retained<AnyObject>,
+    text_view: Retained<AnyObject>,
+    popup: Retained<AnyObject>,
+    // The buttons' target; the buttons don't keep it
+    _controller: Retained<AnyObject>,
+}
+
+thread_local! {
+    static WINDOW: OnceCell<LogWindow> = const { OnceCell::new() };
+    static ENTRIES: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+fn selected_level(window: &LogWindow) -> Level {
+    let index: isize = unsafe { msg_send![&window.popup, indexOfSelectedItem] };
+    FILTERS
+        .get(index.max(0) as usize)
+        .map_or(Level::Info, |(_, level)| *level)
+}
+
+fn fill(window: &LogWindow) {
+    let text =
+        ENTRIES.with(|entries| render(&entries.borrow(), selected_level(window), local_time));
+    unsafe {
+        let _: () = msg_send![&window.text_view, setString: &*NSString::from_str(&text)];
+        let _: () =
+            msg_send![&window.text_view, scrollToEndOfDocument: std::ptr::null::<AnyObject>()];
+    }
+}
+
+pub fn show(mtm: MainThreadMarker) {
+    ENTRIES.with(|entries| *entries.borrow_mut() = read_entries());
+    WINDOW.with(|cell| {
+        let window = cell.get_or_init(create);
+        fill(window);
+        unsafe {
+            // Accessory apps aren't frontmost by default; bring the window forward
+            let app = NSApplication::sharedApplication(mtm);
+            let _: () = msg_send![&app, activateIgnoringOtherApps: true];
+            let _: () =
+                msg_send![&window.window, makeKeyAndOrderFront: std::ptr::null::<AnyObject>()];
+        }
+    });
+}
+
+extern "C" fn level_changed(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    WINDOW.with(|cell| {
+        if let Some(window) = cell.get() {
+            fill(window);
+        }
+    });
+}
+
+extern "C" fn copy_log(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    WINDOW.with(|cell| {
+        let Some(window) = cell.get() else {
+            return;
+        };
+        let text: Retained<NSString> = unsafe { msg_send![&window.text_view, string] };
+        super::clipboard::copy(&text);
+    });
+}
+
+fn register_controller_class() -> &'static AnyClass {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        let mut builder = ClassBuilder::new(c"AwakeLogWindowController", objc2::class!(NSObject))
+            .expect("AwakeLogWindowController class already registered");
+
+        type Fn3 = extern "C" fn(*mut AnyObject, Sel, *mut AnyObject);
+
+        unsafe {
+            builder.add_method(sel!(levelChanged:), level_changed as Fn3);
+            builder.add_method(sel!(copyLog:), copy_log as Fn3);
+        }
+        builder.register();
+    });
+    AnyClass::get(c"AwakeLogWindowController").unwrap()
+}
+
+fn create() -> LogWindow {
+    unsafe {
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), SIZE);
+        let controller: Retained<AnyObject> = msg_send![register_controller_class(), new];
+
+        let window: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSWindow").unwrap(), alloc];
+        let window: Retained<AnyObject> = msg_send![
+            window,
+            initWithContentRect: frame,
+            styleMask: STYLE_MASK,
+            backing: BACKING_BUFFERED,
+            defer: false
+        ];
+        // Keep the window alive across close/reopen; we hold the only reference
+        let _: () = msg_send![&window, setReleasedWhenClosed: false];
+        let _: () = msg_send![&window, setTitle: &*NSString::from_str("Awake Event Log")];
+        let content: *mut AnyObject = msg_send![&window, contentView];
+
+        let popup: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSPopUpButton").unwrap(), alloc];
+        let popup: Retained<AnyObject> = msg_send![
+            popup,
+            initWithFrame: NSRect::new(
+                NSPoint::new(12.0, SIZE.height - BAR_HEIGHT + 8.0),
+                NSSize::new(200.0, 26.0)
+            ),
+            pullsDown: false
+        ];
+        for (title, _) in FILTERS {
+            let _: () = msg_send![&popup, addItemWithTitle: &*NSString::from_str(title)];
+        }
+        let _: () = msg_send![&popup, setTarget: &*controller];
+        let _: () = msg_send![&popup, setAction: sel!(levelChanged:)];
+        let _: () = msg_send![&popup, setAutoresizingMask: MIN_Y_MARGIN];
+
+        let copy: Retained<AnyObject> = msg_send![
+            AnyClass::get(c"NSButton").unwrap(),
+            buttonWithTitle: &*NSString::from_str("Copy"),
+            target: &*controller,
+            action: sel!(copyLog:)
+        ];
+        let _: () = msg_send![
+            &copy,
+            setFrame: NSRect::new(
+                NSPoint::new(SIZE.width - 92.0, SIZE.height - BAR_HEIGHT + 6.0),
+                NSSize::new(80.0, 28.0)
+            )
+        ];
+        let _: () = msg_send![&copy, setAutoresizingMask: MIN_X_MARGIN | MIN_Y_MARGIN];
+
+        let log_frame = NSRect::new(
+            NSPoint::new(0.0, 0.0),
+            NSSize::new(SIZE.width, SIZE.height - BAR_HEIGHT),
+        );
+        let scroll: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSScrollView").unwrap(), alloc];
+        let scroll: Retained<AnyObject> = msg_send![scroll, initWithFrame: log_frame];
+        let _: () = msg_send![&scroll, setHasVerticalScroller: true];
+        let _: () = msg_send![&scroll, setAutoresizingMask: WIDTH_SIZABLE | HEIGHT_SIZABLE];
+
+        let text_view: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSTextView").unwrap(), alloc];
+        let text_view: Retained<AnyObject> = msg_send![text_view, initWithFrame: log_frame];
+        let _: () = msg_send![&text_view, setEditable: false];
+        let _: () = msg_send![&text_view, setSelectable: true];
+        let _: () = msg_send![&text_view, setAutoresizingMask: WIDTH_SIZABLE | HEIGHT_SIZABLE];
+        let font: *mut AnyObject = msg_send![
+            AnyClass::get(c"NSFont").unwrap(),
+            monospacedSystemFontOfSize: 11.0f64,
+            weight: 0.0f64
+        ];
+        if !font.is_null() {
+            let _: () = msg_send![&text_view, setFont: font];
+        }
+
+        let _: () = msg_send![&scroll, setDocumentView: &*text_view];
+        let _: () = msg_send![content, addSubview: &*popup];
+        let _: () = msg_send![content, addSubview: &*copy];
+        let _: () = msg_send![content, addSubview: &*scroll];
+        let _: () = msg_send![&window, center];
+
+        LogWindow {
+            window,
+            text_view,
+            popup,
+            _controller: controller,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_read_as_entries() {
+        assert_eq!(
+            entry(
+                r#"{"ts":7,"event":"activated","mode":"display","source":"trigger","reason":"while ssh"}"#
+            ),
+            Some(Entry {
+                level: Level::Info,
+                ts: 7,
+                text: "On (display) \u{2014} while ssh".into(),
+            })
+        );
+        let refused = entry(
+            r#"{"ts":8,"event":"assertion_error","assertion_type":"system","code":-536870212}"#,
+        )
+        .unwrap();
+        assert_eq!(refused.level, Level::Error);
+        assert!(refused.text.starts_with("Assertion failed: system ("));
+        let release =
+            entry(r#"{"ts":9,"event":"assertion_error","assertion_type":"release","code":1}"#);
+        assert_eq!(release.map(|entry| entry.level), Some(Level::Warning));
+        assert_eq!(entry(r#"{"ts":9,"event":"from_the_future"}"#), None);
+        assert_eq!(entry("not json"), None);
+    }
+
+    #[test]
+    fn the_filter_hides_lesser_levels() {
+        let entries = [
+            entry(r#"{"ts":1,"event":"deactivated"}"#).unwrap(),
+            entry(r#"{"ts":2,"event":"assertion_error","assertion_type":"display","code":1}"#)
+                .unwrap(),
+        ];
+        let time = |ts: u64| format!("t{}", ts);
+        let all = render(&entries, Level::Info, time);
+        assert!(all.starts_with("t1    Off\nt2 \u{2716}  Assertion failed: display"));
+        assert!(!render(&entries, Level::Error, time).contains("Off"));
+        assert_eq!(render(&[], Level::Info, time), "Nothing logged yet.\n");
+    }
+}
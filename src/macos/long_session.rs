@@ -0,0 +1,68 @@
+//! A check before a long timer from a link: `awake://for/720` from a launcher
+//! or a Shortcut, where one slipped digit asks for twelve hours, shows an
+//! alert first when it runs past `ConfirmLongerThanHours`. The menu's timers
+//! are short enough to pass; the remote control, with nobody at the Mac to
+//! answer, isn't asked.
+
+use objc2::MainThreadMarker;
+
+use super::alert;
+use crate::settings;
+use crate::state::Command;
+
+pub const KEY_HOURS: &str = "ConfirmLongerThanHours";
+
+/// Whether a timer of `minutes` runs past a limit of `hours`; none or `0`
+/// confirms nothing
+fn needs_confirming(minutes: u64, hours: Option<u64>) -> bool {
+    hours.is_some_and(|hours| hours > 0 && minutes > hours.saturating_mul(60))
+}
+
+// "12 hours", "90 minutes"
+fn length(minutes: u64) -> String {
+    match minutes {
+        60 => "1 hour".into(),
+        m if m % 60 == 0 => format!("{} hours", m / 60),
+        m => format!("{} minutes", m),
+    }
+}
+
+/// Handles `command`, asking first if it's a timer longer than allowed
+pub fn handle(command: Command) {
+    let hours = settings::get_string(KEY_HOURS).and_then(|h| h.trim().parse().ok());
+    match command {
+        Command::ActivateFor { minutes } if needs_confirming(minutes, hours) => {
+            if confirm(minutes) {
+                super::handle(command);
+            }
+        }
+        command => super::handle(command),
+    }
+}
+
+fn confirm(minutes: u64) -> bool {
+    let mtm = unsafe { MainThreadMarker::new_unchecked() };
+    let response = alert::ask(
+        &format!("Keep awake for {}?", length(minutes)),
+        "A link asked for a longer timer than usual.",
+        &["Keep Awake", "Cancel"],
+        mtm,
+    );
+    response == Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_timers_past_the_limit_are_confirmed() {
+        assert!(needs_confirming(12 * 60, Some(8)));
+        assert!(!needs_confirming(8 * 60, Some(8)));
+        assert!(!needs_confirming(12 * 60, Some(0)));
+        assert!(!needs_confirming(12 * 60, None));
+        assert!(!needs_confirming(12 * 60, Some(u64::MAX)));
+        assert_eq!(length(720), "12 hours");
+        assert_eq!(length(90), "90 minutes");
+    }
+}
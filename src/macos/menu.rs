@@ -0,0 +1,701 @@
+//! The status item's menu, described as data and synced onto the `NSMenu`.
+//!
+//! `describe` turns a `View` (what the menu shows: the status, the triggers,
+//! the settings ticked) into `Entry`s; `sync` brings an `NSMenu` in line with
+//! them, reusing each item that is still the same kind (a separator, a
+//! submenu or a plain item) and touching only what changed, so the menu can be
+//! refreshed while open without flicker. Item actions are the delegate's
+//! selectors, and `validateMenuItem:` still decides which are enabled.
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, Bool, Sel};
+use objc2::{msg_send, sel, MainThreadMarker, MainThreadOnly};
+use objc2_app_kit::{NSMenu, NSMenuItem};
+use objc2_foundation::NSString;
+
+use crate::keepawake::KindSet;
+use crate::mode::Mode;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub title: String,
+    /// `None` for a line of text, which AppKit greys out
+    pub action: Option<Sel>,
+    pub tag: isize,
+    pub checked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Item(Item),
+    Separator,
+    Submenu(String, Vec<Entry>),
+}
+
+impl Entry {
+    pub fn label(title: impl Into<String>) -> Entry {
+        Entry::Item(Item {
+            title: title.into(),
+            action: None,
+            tag: 0,
+            checked: false,
+        })
+    }
+
+    pub fn action(title: impl Into<String>, action: Sel) -> Entry {
+        Entry::check(title, action, false)
+    }
+
+    pub fn check(title: impl Into<String>, action: Sel, checked: bool) -> Entry {
+        Entry::Item(Item {
+            title: title.into(),
+            action: Some(action),
+            tag: 0,
+            checked,
+        })
+    }
+}
+
+pub const KEY_LAYOUT: &str = "MenuLayout";
+
+/// A part of the menu that `MenuLayout` can move or leave out. The status
+/// always comes first and Quit last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// The Triggers submenu and Edit Triggers…
+    Triggers,
+    /// Toggle, a suggested timer, and sleeping the display or locking
+    Toggle,
+    /// The Awake For... submenu
+    Timers,
+    /// The Mode submenu
+    Mode,
+    /// The settings' checkmarks, export and import
+    Settings,
+    /// Statistics, diagnostics, the about line and Uninstall Awake…
+    Stats,
+}
+
+impl Section {
+    /// The default layout
+    pub const ALL: [Section; 6] = [
+        Section::Triggers,
+        Section::Toggle,
+        Section::Timers,
+        Section::Mode,
+        Section::Settings,
+        Section::Stats,
+    ];
+
+    fn parse(name: &str) -> Option<Section> {
+        match name.trim() {
+            "triggers" => Some(Section::Triggers),
+            "toggle" => Some(Section::Toggle),
+            "timers" => Some(Section::Timers),
+            "mode" => Some(Section::Mode),
+            "settings" => Some(Section::Settings),
+            "stats" => Some(Section::Stats),
+            _ => None,
+        }
+    }
+
+    fn is_submenu(self) -> bool {
+        matches!(self, Section::Timers | Section::Mode)
+    }
+}
+
+/// The sections `text` names, in its order ("toggle,timers"); unknown names
+/// and repeats are skipped, and nothing recognised means the default layout
+pub fn parse_layout(text: &str) -> Vec<Section> {
+    let mut layout = Vec::new();
+    for section in text.split(',').filter_map(Section::parse) {
+        if !layout.contains(&section) {
+            layout.push(section);
+        }
+    }
+    layout
+}
+
+/// What the menu shows
+#[derive(Clone, Debug, Default)]
+pub struct View {
+    pub summary: String,
+    /// "Awake until 15:42", while a timer runs
+    pub until: Option<String>,
+    /// Ticks Toggle
+    pub awake: bool,
+    /// A timer is running, so it can be extended
+    pub timed: bool,
+    /// Minutes of the timer the log suggests, while asleep
+    pub suggestion: Option<u64>,
+    /// Which triggers are keeping the Mac awake
+    pub active: Option<String>,
+    /// Each trigger's name, and whether it's enabled
+    pub triggers: Vec<(String, bool)>,
+    pub mode: KindSet,
+    /// "Back to Display + System in 42 min", while a time-boxed mode lasts
+    pub mode_return: Option<String>,
+    pub overnight: bool,
+    pub launch_at_login: bool,
+    /// Titled with its window, e.g. "Quiet Hours (22:00–07:00)"
+    pub quiet_hours: (String, bool),
+    pub badge: bool,
+    /// The time left has a status item of its own
+    pub countdown_item: bool,
+    pub text_icon: bool,
+    pub sounds: bool,
+    pub speech: bool,
+    pub hud: bool,
+    pub flash: bool,
+    pub sync: bool,
+    /// Lines from the event log, read when the menu opens
+    pub stats: Vec<String>,
+    /// The thermal readout, under the statistics when it's on
+    pub diagnostics: Vec<String>,
+    pub about: String,
+    /// Only the status and the about line, for kiosk mode
+    pub kiosk: bool,
+    /// Only the status and a way to unlock, while the controls are locked
+    pub locked: bool,
+    /// The sections in the order shown; empty for `Section::ALL`
+    pub layout: Vec<Section>,
+}
+
+pub fn describe(view: &View) -> Vec<Entry> {
+    let mut entries = vec![Entry::label(&view.summary)];
+    if let Some(until) = &view.until {
+        entries.push(Entry::label(until));
+    }
+    if let Some(active) = &view.active {
+        entries.push(Entry::label(active));
+    }
+    if view.kiosk {
+        entries.extend([Entry::Separator, Entry::label(&view.about)]);
+        return entries;
+    }
+    if view.locked {
+        entries.extend([
+            Entry::Separator,
+            Entry::action("Unlock Controls…", sel!(unlockControls:)),
+            Entry::label(&view.about),
+        ]);
+        return entries;
+    }
+    let layout = if view.layout.is_empty() {
+        &Section::ALL[..]
+    } else {
+        &view.layout[..]
+    };
+    let mut previous: Option<Section> = None;
+    for &section in layout {
+        let part = match section {
+            Section::Triggers => triggers(view),
+            Section::Toggle => toggle(view),
+            Section::Timers => vec![Entry::Submenu("Awake For...".into(), timers(view))],
+            Section::Mode => vec![mode(view)],
+            Section::Settings => settings(view),
+            Section::Stats => stats(view),
+        };
+        // Awake For... and Mode share a group when they're next to each other
+        let together = previous.is_some_and(Section::is_submenu) && section.is_submenu();
+        if previous.is_some() && !together {
+            entries.push(Entry::Separator);
+        }
+        entries.extend(part);
+        previous = Some(section);
+    }
+    entries.extend([Entry::Separator, Entry::action("Quit", sel!(quit:))]);
+    entries
+}
+
+fn triggers(view: &View) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    if !view.triggers.is_empty() {
+        let triggers = view
+            .triggers
+            .iter()
+            .enumerate()
+            .map(|(tag, (name, enabled))| {
+                Entry::Item(Item {
+                    title: name.clone(),
+                    action: Some(sel!(toggleTrigger:)),
+                    tag: tag as isize,
+                    checked: *enabled,
+                })
+            });
+        entries.push(Entry::Submenu("Triggers".into(), triggers.collect()));
+    }
+    entries.push(Entry::action("Edit Triggers…", sel!(editTriggers:)));
+    entries
+}
+
+fn toggle(view: &View) -> Vec<Entry> {
+    let mut entries = vec![Entry::check("Toggle", sel!(toggle:), view.awake)];
+    // Tagged with its length in minutes
+    if let Some(minutes) = view.suggestion {
+        entries.push(Entry::Item(Item {
+            title: crate::stats::suggestion_title(minutes),
+            action: Some(sel!(startSuggested:)),
+            tag: minutes as isize,
+            checked: false,
+        }));
+    }
+    entries.extend([
+        Entry::action("Sleep Display Now", sel!(sleepDisplay:)),
+        Entry::action("Lock Screen & Keep Awake", sel!(lockScreen:)),
+        Entry::action("Lock Controls", sel!(lockControls:)),
+    ]);
+    entries
+}
+
+fn timers(view: &View) -> Vec<Entry> {
+    let mut timers = vec![
+        Entry::action("15 minutes", sel!(timer15:)),
+        Entry::action("30 minutes", sel!(timer30:)),
+        Entry::action("1 hour", sel!(timer60:)),
+        Entry::action("2 hours", sel!(timer120:)),
+    ];
+    if view.timed {
+        timers.extend([
+            Entry::Separator,
+            Entry::action("Extend by 15 minutes", sel!(extend15:)),
+        ]);
+    }
+    timers.extend([
+        Entry::Separator,
+        Entry::check(
+            "Overnight: Then Until Charged",
+            sel!(overnight:),
+            view.overnight,
+        ),
+    ]);
+    timers
+}
+
+fn mode(view: &View) -> Entry {
+    let mode = |mode: Mode, action| Entry::check(mode.title(), action, view.mode == mode.kinds());
+    // Tagged with the mode's place in `Mode::ALL`
+    let mut for_an_hour: Vec<Entry> = Mode::ALL
+        .iter()
+        .enumerate()
+        .map(|(tag, mode)| {
+            Entry::Item(Item {
+                title: format!("Use {}", mode.title()),
+                action: Some(sel!(modeForHour:)),
+                tag: tag as isize,
+                checked: false,
+            })
+        })
+        .collect();
+    if let Some(back) = &view.mode_return {
+        for_an_hour.extend([Entry::Separator, Entry::label(back)]);
+    }
+    Entry::Submenu(
+        "Mode".into(),
+        vec![
+            mode(Mode::Display, sel!(modeDisplay:)),
+            mode(Mode::System, sel!(modeSystem:)),
+            mode(Mode::Both, sel!(modeBoth:)),
+            Entry::Separator,
+            mode(Mode::Server, sel!(modeServer:)),
+            Entry::Separator,
+            Entry::Submenu("For the Next Hour".into(), for_an_hour),
+        ],
+    )
+}
+
+fn settings(view: &View) -> Vec<Entry> {
+    let (quiet_title, quiet) = &view.quiet_hours;
+    vec![
+        Entry::check("Launch at Login", sel!(toggleLogin:), view.launch_at_login),
+        Entry::check(quiet_title, sel!(toggleQuietHours:), *quiet),
+        Entry::check("Show Time Left on Icon", sel!(toggleBadge:), view.badge),
+        Entry::check(
+            "Show Time Left Separately",
+            sel!(toggleCountdownItem:),
+            view.countdown_item,
+        ),
+        Entry::check(
+            "Show Text Instead of Icon",
+            sel!(toggleTextIcon:),
+            view.text_icon,
+        ),
+        Entry::check("Play Sounds", sel!(toggleSounds:), view.sounds),
+        Entry::check("Speak Changes", sel!(toggleSpeech:), view.speech),
+        Entry::check("Show Floating Indicator", sel!(toggleHud:), view.hud),
+        Entry::check("Flash Icon on Change", sel!(toggleFlash:), view.flash),
+        Entry::action("Export Settings…", sel!(exportSettings:)),
+        Entry::action("Import Settings…", sel!(importSettings:)),
+        Entry::check("Sync Settings with iCloud", sel!(toggleSync:), view.sync),
+    ]
+}
+
+fn stats(view: &View) -> Vec<Entry> {
+    let mut stats: Vec<Entry> = view.stats.iter().map(Entry::label).collect();
+    if !view.diagnostics.is_empty() {
+        stats.push(Entry::Separator);
+        stats.extend(view.diagnostics.iter().map(Entry::label));
+    }
+    vec![
+        Entry::Submenu("Statistics".into(), stats),
+        Entry::action("Show System Power Info…", sel!(showPowerInfo:)),
+        Entry::action("Show Event Log…", sel!(showEventLog:)),
+        Entry::action("Report an Issue…", sel!(reportIssue:)),
+        Entry::label(&view.about),
+        Entry::Separator,
+        Entry::action("Uninstall Awake…", sel!(uninstall:)),
+    ]
+}
+
+#[derive(PartialEq, Eq)]
+enum Shape {
+    Item,
+    Separator,
+    Submenu,
+}
+
+impl Entry {
+    fn shape(&self) -> Shape {
+        match self {
+            Entry::Item(_) => Shape::Item,
+            Entry::Separator => Shape::Separator,
+            Entry::Submenu(..) => Shape::Submenu,
+        }
+    }
+}
+
+unsafe fn shape_of(item: &NSMenuItem) -> Shape {
+    let separator: Bool = msg_send![item, isSeparatorItem];
+    let submenu: Bool = msg_send![item, hasSubmenu];
+    if separator.as_bool() {
+        Shape::Separator
+    } else if submenu.as_bool() {
+        Shape::Submenu
+    } else {
+        Shape::Item
+    }
+}
+
+unsafe fn create(entry: &Entry, mtm: MainThreadMarker) -> Retained<NSMenuItem> {
+    if *entry == Entry::Separator {
+        return NSMenuItem::separatorItem(mtm);
+    }
+    let empty = NSString::from_str("");
+    let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+        NSMenuItem::alloc(mtm),
+        &empty,
+        None,
+        &empty,
+    );
+    if let Entry::Submenu(..) = entry {
+        item.setSubmenu(Some(&NSMenu::new(mtm)));
+    }
+    item
+}
+
+unsafe fn set_title(item: &NSMenuItem, title: &str) {
+    let current: Retained<NSString> = msg_send![item, title];
+    if current.to_string() != title {
+        let _: () = msg_send![item, setTitle: &*NSString::from_str(title)];
+    }
+}
+
+unsafe fn update(item: &NSMenuItem, entry: &Entry, target: *mut AnyObject, mtm: MainThreadMarker) {
+    match entry {
+        Entry::Separator => {}
+        Entry::Submenu(title, entries) => {
+            set_title(item, title);
+            let submenu: Option<Retained<NSMenu>> = msg_send![item, submenu];
+            if let Some(submenu) = submenu {
+                sync(&submenu, entries, target, mtm);
+            }
+        }
+        Entry::Item(entry) => {
+            set_title(item, &entry.title);
+            let action: Option<Sel> = msg_send![item, action];
+            if action != entry.action {
+                let _: () = msg_send![item, setAction: entry.action];
+                let _: () = msg_send![item, setTarget: target];
+            }
+            let tag: isize = msg_send![item, tag];
+            if tag != entry.tag {
+                let _: () = msg_send![item, setTag: entry.tag];
+            }
+            let checked: isize = if entry.checked { 1 } else { 0 };
+            let state: isize = msg_send![item, state];
+            if state != checked {
+                let _: () = msg_send![item, setState: checked];
+            }
+        }
+    }
+}
+
+/// Makes `menu` show `entries`, as described above
+pub unsafe fn sync(
+    menu: &NSMenu,
+    entries: &[Entry],
+    target: *mut AnyObject,
+    mtm: MainThreadMarker,
+) {
+    for (index, entry) in entries.iter().enumerate() {
+        let index = index as isize;
+        let count: isize = msg_send![menu, numberOfItems];
+        let existing: Option<Retained<NSMenuItem>> = if index < count {
+            msg_send![menu, itemAtIndex: index]
+        } else {
+            None
+        };
+        let item = match existing.filter(|item| shape_of(item) == entry.shape()) {
+            Some(item) => item,
+            None => {
+                let item = create(entry, mtm);
+                let _: () = msg_send![menu, insertItem: &*item, atIndex: index];
+                item
+            }
+        };
+        update(&item, entry, target, mtm);
+    }
+    loop {
+        let count: isize = msg_send![menu, numberOfItems];
+        if count <= entries.len() as isize {
+            break;
+        }
+        let _: () = msg_send![menu, removeItemAtIndex: count - 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(entries: &[Entry]) -> Vec<&str> {
+        entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Item(item) => Some(item.title.as_str()),
+                Entry::Submenu(title, _) => Some(title.as_str()),
+                Entry::Separator => None,
+            })
+            .collect()
+    }
+
+    fn submenu<'a>(entries: &'a [Entry], title: &str) -> &'a [Entry] {
+        entries
+            .iter()
+            .find_map(|entry| match entry {
+                Entry::Submenu(t, entries) if t == title => Some(entries.as_slice()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn triggers_appear_only_when_there_are_some() {
+        let view = View {
+            summary: "Off".into(),
+            ..View::default()
+        };
+        let entries = describe(&view);
+        assert_eq!(titles(&entries)[..2], ["Off", "Edit Triggers…"]);
+
+        let view = View {
+            active: Some("Kept on by SSH".into()),
+            triggers: vec![("an SSH client is connected".into(), false)],
+            ..view
+        };
+        let entries = describe(&view);
+        assert_eq!(titles(&entries)[..3], ["Off", "Kept on by SSH", "Triggers"]);
+        assert_eq!(
+            submenu(&entries, "Triggers"),
+            [Entry::Item(Item {
+                title: "an SSH client is connected".into(),
+                action: Some(sel!(toggleTrigger:)),
+                tag: 0,
+                checked: false,
+            })]
+        );
+    }
+
+    #[test]
+    fn a_timer_says_when_it_ends() {
+        let view = View {
+            summary: "On \u{2014} turned on manually, 42 min left".into(),
+            until: Some("Awake until 3:42\u{202f}PM".into()),
+            ..View::default()
+        };
+        assert_eq!(
+            titles(&describe(&view))[..3],
+            [
+                "On \u{2014} turned on manually, 42 min left",
+                "Awake until 3:42\u{202f}PM",
+                "Edit Triggers…"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_layout_moves_and_hides_sections() {
+        let view = View {
+            summary: "Off".into(),
+            layout: parse_layout("toggle, mode,nonsense,toggle"),
+            ..View::default()
+        };
+        let entries = describe(&view);
+        assert_eq!(
+            titles(&entries),
+            [
+                "Off",
+                "Toggle",
+                "Sleep Display Now",
+                "Lock Screen & Keep Awake",
+                "Lock Controls",
+                "Mode",
+                "Quit"
+            ]
+        );
+        assert_eq!(parse_layout("nonsense"), []);
+        let minimal = View {
+            layout: parse_layout("timers,mode"),
+            ..view
+        };
+        let entries = describe(&minimal);
+        assert_eq!(titles(&entries), ["Off", "Awake For...", "Mode", "Quit"]);
+        // The submenus share a group
+        assert_eq!(
+            entries.iter().filter(|e| **e == Entry::Separator).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_kiosk_shows_only_the_status() {
+        let view = View {
+            summary: "On".into(),
+            about: "Awake v1 (kiosk)".into(),
+            kiosk: true,
+            ..View::default()
+        };
+        let entries = describe(&view);
+        assert_eq!(titles(&entries), ["On", "Awake v1 (kiosk)"]);
+        assert!(!entries
+            .iter()
+            .any(|entry| matches!(entry, Entry::Item(item) if item.action.is_some())));
+    }
+
+    #[test]
+    fn locked_controls_offer_only_unlocking() {
+        let view = View {
+            summary: "On".into(),
+            about: "Awake v1".into(),
+            locked: true,
+            ..View::default()
+        };
+        let entries = describe(&view);
+        assert_eq!(titles(&entries), ["On", "Unlock Controls…", "Awake v1"]);
+        let actions: Vec<Sel> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Item(item) => item.action,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(actions, [sel!(unlockControls:)]);
+    }
+
+    #[test]
+    fn diagnostics_follow_the_statistics() {
+        let view = View {
+            stats: vec!["Last 24 hours: 2h".into()],
+            ..View::default()
+        };
+        assert_eq!(
+            titles(submenu(&describe(&view), "Statistics")),
+            ["Last 24 hours: 2h"]
+        );
+        let view = View {
+            diagnostics: vec!["Thermal pressure: fair".into()],
+            ..view
+        };
+        let entries = describe(&view);
+        let stats = submenu(&entries, "Statistics");
+        assert_eq!(stats[1], Entry::Separator);
+        assert_eq!(
+            titles(stats),
+            ["Last 24 hours: 2h", "Thermal pressure: fair"]
+        );
+    }
+
+    #[test]
+    fn the_mode_in_force_is_ticked() {
+        let view = View {
+            mode: Mode::Server.kinds(),
+            ..View::default()
+        };
+        let entries = describe(&view);
+        let ticked: Vec<&str> = submenu(&entries, "Mode")
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Item(item) if item.checked => Some(item.title.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ticked, ["Serving Network Clients"]);
+    }
+
+    #[test]
+    fn a_time_boxed_mode_says_when_it_ends() {
+        let entries = describe(&View::default());
+        let box_menu = submenu(submenu(&entries, "Mode"), "For the Next Hour");
+        assert_eq!(titles(box_menu)[0], "Use Display Only");
+        assert_eq!(box_menu.len(), Mode::ALL.len());
+
+        let view = View {
+            mode_return: Some("Back to Display + System in 42 min".into()),
+            ..View::default()
+        };
+        let entries = describe(&view);
+        let box_menu = submenu(submenu(&entries, "Mode"), "For the Next Hour");
+        assert_eq!(
+            titles(box_menu).last(),
+            Some(&"Back to Display + System in 42 min")
+        );
+    }
+
+    #[test]
+    fn a_suggestion_follows_toggle() {
+        let view = View {
+            suggestion: Some(120),
+            ..View::default()
+        };
+        let entries = describe(&view);
+        let toggle = titles(&entries)
+            .iter()
+            .position(|t| *t == "Toggle")
+            .unwrap();
+        assert_eq!(
+            entries.get(toggle + 2),
+            Some(&Entry::Item(Item {
+                title: "You usually stay awake 2 h at this time \u{2014} Start Now".into(),
+                action: Some(sel!(startSuggested:)),
+                tag: 120,
+                checked: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn extending_is_offered_only_with_a_timer() {
+        let entries = describe(&View::default());
+        assert!(!titles(submenu(&entries, "Awake For...")).contains(&"Extend by 15 minutes"));
+        let view = View {
+            awake: true,
+            timed: true,
+            ..View::default()
+        };
+        let entries = describe(&view);
+        assert!(titles(submenu(&entries, "Awake For...")).contains(&"Extend by 15 minutes"));
+        assert!(entries.iter().any(
+            |entry| matches!(entry, Entry::Item(item) if item.title == "Toggle" && item.checked)
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+//! Feedback for when the menu bar hides itself ("Automatically hide and show
+//! the menu bar" in Control Center settings), leaving the status item out of
+//! sight most of the time. While it's hidden, each change is also posted as a
+//! notification, worded as `speech` would say it, or the floating indicator
+//! (`hud`) is shown instead.
+//!
+//! `HiddenMenuBarFeedback` chooses: `notify` (the default), `hud` or `off`.
+
+use objc2_foundation::{NSString, NSUserDefaults};
+
+use super::sound::{Cue, Seen};
+use super::{notification, speech};
+use crate::{clock, settings};
+
+pub const KEY_FEEDBACK: &str = "HiddenMenuBarFeedback";
+// The system's own setting, in the global domain
+const KEY_AUTO_HIDE: &str = "_HIHideMenuBar";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Feedback {
+    Nothing,
+    Notify,
+    Hud,
+}
+
+fn feedback(setting: Option<&str>, hidden: bool) -> Feedback {
+    match setting {
+        _ if !hidden => Feedback::Nothing,
+        Some("off") => Feedback::Nothing,
+        Some("hud") => Feedback::Hud,
+        _ => Feedback::Notify,
+    }
+}
+
+/// Whether the menu bar only shows when the pointer goes to the top
+pub fn is_auto_hidden() -> bool {
+    NSUserDefaults::standardUserDefaults().boolForKey(&NSString::from_str(KEY_AUTO_HIDE))
+}
+
+fn current() -> Feedback {
+    feedback(
+        settings::get_string(KEY_FEEDBACK).as_deref(),
+        is_auto_hidden(),
+    )
+}
+
+/// Whether the floating indicator stands in for the hidden menu bar
+pub fn wants_hud() -> bool {
+    current() == Feedback::Hud
+}
+
+/// Gives `cue` another way to be noticed, if the menu bar is hidden
+pub fn changed(cue: Cue, after: Seen) {
+    if current() == Feedback::Notify {
+        let mut body = speech::announcement(cue, after, crate::now_secs());
+        // The clock time ends a timer's banner, which is read, not heard
+        if let (Cue::Activated, Some(expiry)) = (cue, after.expiry) {
+            body.push_str(&format!(", until {}", clock::at(expiry)));
+        }
+        notification::post("Awake", &body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_hidden_menu_bar_gets_feedback() {
+        assert_eq!(feedback(None, true), Feedback::Notify);
+        assert_eq!(feedback(Some("notify"), true), Feedback::Notify);
+        assert_eq!(feedback(Some("off"), true), Feedback::Nothing);
+        assert_eq!(feedback(Some("hud"), true), Feedback::Hud);
+        assert_eq!(feedback(Some("hud"), false), Feedback::Nothing);
+        assert_eq!(feedback(None, false), Feedback::Nothing);
+    }
+}
@@ -0,0 +1,885 @@
+//! macOS front-end: an NSStatusItem with a left-click toggle and right-click menu
+
+mod alert;
+mod appearance;
+mod backup;
+mod badge;
+mod bar_managers;
+mod battery_guard;
+mod bundle;
+mod click;
+mod clipboard;
+mod controls_lock;
+mod countdown_item;
+mod crash_prompt;
+mod diagnostics;
+mod display_pause;
+mod display_sleep;
+mod drop;
+mod handoff;
+mod hot_corner;
+mod hud;
+mod icloud;
+mod icon;
+mod issue_report;
+mod kiosk;
+mod launch_agent;
+mod location;
+mod lock_screen;
+mod log_window;
+mod long_session;
+mod menu;
+mod menu_bar;
+mod notification;
+mod reminder;
+mod resume;
+mod rosetta;
+mod screen_recording;
+mod smc;
+mod sound;
+mod speech;
+mod temperature;
+mod text_window;
+mod trigger_editor;
+mod trigger_events;
+mod uninstall;
+mod url_scheme;
+
+pub use bundle::identifier as bundle_identifier;
+pub use launch_agent::{label as launch_agent_label, write_launch_agent};
+
+use dispatch2::DispatchQueue;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject, Bool, ClassBuilder, Sel};
+use objc2::{msg_send, sel, ClassType, MainThreadMarker};
+use objc2_app_kit::{
+    NSApplication, NSApplicationActivationPolicy, NSEvent, NSEventModifierFlags, NSMenu,
+    NSStatusBar, NSStatusItem,
+};
+use objc2_foundation::NSString;
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::thread;
+
+use crate::error::AwakeError;
+use crate::keepawake::{self, KindSet};
+use crate::{battery, clock, duration, mode::Mode, process, quiet_hours, settings, state, stats};
+
+// How often the icon is redrawn, so a badge stays current; every second when
+// the time left is shown to the second
+const BADGE_TICK_SECS: f64 = 30.0;
+
+fn tick_secs() -> f64 {
+    match duration::precision() {
+        duration::Precision::Seconds => 1.0,
+        _ => BADGE_TICK_SECS,
+    }
+}
+
+// `applicationShouldTerminate:`'s replies: go ahead, or wait for
+// `replyToApplicationShouldTerminate:`
+const TERMINATE_NOW: usize = 1;
+const TERMINATE_LATER: usize = 2;
+
+// AppKit objects updated after launch. Session state lives in `state.rs`;
+// the menu's items are synced from `menu::describe`.
+struct Ui {
+    status_item: Retained<NSStatusItem>,
+    menu: Retained<NSMenu>,
+    delegate: *mut AnyObject,
+}
+
+thread_local! {
+    // Main thread only, like everything in AppKit
+    static UI: OnceCell<Ui> = const { OnceCell::new() };
+    // Whether a session is on and when it expires, for redrawing the icon
+    static ICON: Cell<(bool, Option<u64>)> = const { Cell::new((false, None)) };
+    // Set once the session has been released for termination
+    static CLEANED_UP: Cell<bool> = const { Cell::new(false) };
+    // The menu's view of the session, as last shown; the settings are read
+    // whenever the menu is refreshed. The triggers are in tag order.
+    static SHOWN: RefCell<menu::View> = RefCell::new(menu::View::default());
+}
+
+fn with_ui(f: impl FnOnce(&Ui)) {
+    UI.with(|ui| {
+        if let Some(ui) = ui.get() {
+            f(ui);
+        }
+    });
+}
+
+// Errors surface here, at the UI boundary; for now they go to stderr
+fn present_error(error: &AwakeError) {
+    eprintln!("Awake: {}", error);
+}
+
+fn handle(cmd: state::Command) {
+    if let Err(e) = state::handle(cmd) {
+        present_error(&e);
+    }
+}
+
+/// Runs `work` on the main thread, for AppKit, from a later turn of its run
+/// loop. Safe to call from any thread, including the main one.
+fn run_on_main(work: impl FnOnce(MainThreadMarker) + Send + 'static) {
+    DispatchQueue::main().exec_async(move || {
+        // Blocks on the main queue only ever run on the main thread
+        work(unsafe { MainThreadMarker::new_unchecked() })
+    });
+}
+
+/// Queue `cmd` for the main thread. Safe to call from any thread.
+pub fn post(cmd: state::Command) {
+    run_on_main(|_| handle(cmd));
+}
+
+/// Asks whether a session that has run `hours` is still needed. Shown once the
+/// current command is done, since the alert runs its own event loop.
+pub fn remind(hours: u64) {
+    run_on_main(move |mtm| reminder::show(hours, mtm));
+}
+
+pub fn show_mode(current: KindSet) {
+    SHOWN.with(|shown| shown.borrow_mut().mode = current);
+    refresh_menu();
+}
+
+fn show_icon(status: &state::Status) {
+    ICON.with(|cell| cell.set((status.is_awake(), status.expiry)));
+    update_icon();
+}
+
+fn announce(status: &state::Status) {
+    let seen = sound::Seen::of(status);
+    if let Some(cue) = sound::changed(seen) {
+        sound::play_for(cue);
+        speech::announce(cue, seen);
+        menu_bar::changed(cue, seen);
+        bar_managers::flash();
+    }
+}
+
+fn show_tooltip(status: &state::Status) {
+    let tooltip = NSString::from_str(&format!("Awake: {}", status.summary()));
+    with_ui(|ui| unsafe {
+        let button: *mut AnyObject = msg_send![&ui.status_item, button];
+        if !button.is_null() {
+            let _: () = msg_send![button, setToolTip: &*tooltip];
+        }
+    });
+}
+
+fn show_in_menu(status: &state::Status) {
+    SHOWN.with(|shown| {
+        let mut shown = shown.borrow_mut();
+        shown.summary = status.summary();
+        shown.until = status
+            .expiry
+            .filter(|_| status.is_awake())
+            .map(|expiry| format!("Awake until {}", clock::at(expiry)));
+        shown.awake = status.is_awake();
+        shown.timed = status.remaining_seconds(crate::now_secs()).is_some();
+        shown.active = status.active_triggers();
+        shown.triggers = status
+            .triggers
+            .iter()
+            .map(|trigger| (trigger.name.clone(), trigger.enabled))
+            .collect();
+        shown.overnight = status.overnight.is_some();
+        shown.mode_return = status.mode_return_text();
+    });
+    refresh_menu();
+}
+
+/// Kiosk mode for this run, as `--kiosk` asks
+pub fn enter_kiosk_mode() {
+    kiosk::enable();
+}
+
+/// Whether kiosk mode is on, from the flag or a setting
+pub fn is_kiosk() -> bool {
+    kiosk::is_enabled()
+}
+
+fn is_quitting() -> bool {
+    CLEANED_UP.with(Cell::get)
+}
+
+/// Whether ⌥ is down right now
+pub fn option_key_held() -> bool {
+    NSEvent::modifierFlags_class().contains(NSEventModifierFlags::Option)
+}
+
+/// Brings the menu in line with the session and the settings
+fn refresh_menu() {
+    let (start, end) = quiet_hours::window();
+    let mut quiet_title = format!(
+        "Quiet Hours ({}–{})",
+        clock::time_of_day(start),
+        clock::time_of_day(end)
+    );
+    if quiet_hours::is_quiet_now() {
+        quiet_title.push_str(" — Now");
+    }
+    let mut suffix = String::new();
+    if keepawake::is_dry_run() {
+        suffix.push_str(" (dry run)");
+    }
+    if settings::is_safe_mode() {
+        suffix.push_str(" (safe mode)");
+    }
+    if kiosk::is_enabled() {
+        suffix.push_str(" (kiosk)");
+    }
+    let view = menu::View {
+        launch_at_login: launch_agent::is_launch_at_login(),
+        quiet_hours: (quiet_title, quiet_hours::is_enabled()),
+        badge: settings::get_bool(badge::KEY_ENABLED),
+        countdown_item: countdown_item::is_enabled(),
+        text_icon: icon::is_text_only(),
+        sounds: settings::get_bool(sound::KEY_ENABLED),
+        speech: settings::get_bool(speech::KEY_ENABLED),
+        hud: settings::get_bool(hud::KEY_ENABLED),
+        flash: settings::get_bool(bar_managers::KEY_FLASH),
+        kiosk: kiosk::is_enabled(),
+        locked: controls_lock::is_locked(),
+        layout: settings::get_string(menu::KEY_LAYOUT)
+            .map(|layout| menu::parse_layout(&layout))
+            .unwrap_or_default(),
+        sync: settings::get_bool(icloud::KEY_ENABLED),
+        about: format!("Awake v{}{}", env!("CARGO_PKG_VERSION"), suffix),
+        ..SHOWN.with(|shown| shown.borrow().clone())
+    };
+    let entries = menu::describe(&view);
+    with_ui(|ui| unsafe {
+        menu::sync(
+            &ui.menu,
+            &entries,
+            ui.delegate,
+            MainThreadMarker::new_unchecked(),
+        );
+    });
+}
+
+// Also called every `BADGE_TICK_SECS`, so the badge counts down. While the
+// screen is recorded, nothing counts down.
+fn update_icon() {
+    let (awake, expiry) = ICON.with(Cell::get);
+    let expiry = expiry.filter(|_| !screen_recording::is_still());
+    hud::update(awake, expiry);
+    with_ui(|ui| unsafe {
+        let button: *mut AnyObject = msg_send![&ui.status_item, button];
+        icon::draw(button, awake, expiry);
+        countdown_item::update(ui.delegate, awake, expiry);
+    });
+}
+
+fn toggle_text_icon() {
+    settings::set_bool(icon::KEY_TEXT_ONLY, !icon::is_text_only());
+    refresh_menu();
+    update_icon();
+}
+
+fn toggle_badge() {
+    settings::set_bool(badge::KEY_ENABLED, !settings::get_bool(badge::KEY_ENABLED));
+    refresh_menu();
+    update_icon();
+}
+
+fn toggle_countdown_item() {
+    let enabled = !countdown_item::is_enabled();
+    settings::set_bool(countdown_item::KEY_ENABLED, enabled);
+    refresh_menu();
+    update_icon();
+}
+
+fn toggle_flash() {
+    let key = bar_managers::KEY_FLASH;
+    settings::set_bool(key, !settings::get_bool(key));
+    refresh_menu();
+}
+
+fn toggle_hud() {
+    settings::set_bool(hud::KEY_ENABLED, !settings::get_bool(hud::KEY_ENABLED));
+    refresh_menu();
+    update_icon();
+}
+
+fn toggle_sounds() {
+    settings::set_bool(sound::KEY_ENABLED, !settings::get_bool(sound::KEY_ENABLED));
+    refresh_menu();
+}
+
+fn toggle_speech() {
+    settings::set_bool(
+        speech::KEY_ENABLED,
+        !settings::get_bool(speech::KEY_ENABLED),
+    );
+    refresh_menu();
+}
+
+fn toggle_launch_at_login() {
+    if let Err(e) = launch_agent::set_launch_at_login(!launch_agent::is_launch_at_login()) {
+        present_error(&e);
+    }
+    refresh_menu();
+}
+
+fn toggle_quiet_hours() {
+    quiet_hours::set_enabled(!quiet_hours::is_enabled());
+    icloud::sync();
+    refresh_menu();
+}
+
+fn toggle_sync() {
+    settings::set_bool(
+        icloud::KEY_ENABLED,
+        !settings::get_bool(icloud::KEY_ENABLED),
+    );
+    icloud::sync();
+    refresh_menu();
+}
+
+// Runs `pmset -g assertions` off the main thread so users can cross-check Awake
+// against what the OS reports, then shows the output in a text window.
+fn show_power_info() {
+    thread::spawn(|| {
+        let text = process::run_capture("/usr/bin/pmset", &["-g", "assertions"]);
+        run_on_main(move |mtm| text_window::show("System Power Info", &text, mtm));
+    });
+}
+
+// Action handlers
+extern "C" fn toggle_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    battery_guard::toggle();
+}
+
+extern "C" fn sleep_display_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    display_sleep::sleep_display();
+}
+
+extern "C" fn lock_screen_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    lock_screen::lock_screen();
+}
+
+extern "C" fn lock_controls_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    controls_lock::lock();
+}
+
+extern "C" fn unlock_controls_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    controls_lock::unlock();
+}
+
+extern "C" fn login_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_launch_at_login();
+}
+
+extern "C" fn quiet_hours_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_quiet_hours();
+}
+
+extern "C" fn text_icon_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_text_icon();
+}
+
+extern "C" fn badge_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_badge();
+}
+
+extern "C" fn countdown_item_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_countdown_item();
+}
+
+extern "C" fn flash_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_flash();
+}
+
+extern "C" fn sounds_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_sounds();
+}
+
+extern "C" fn speech_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_speech();
+}
+
+extern "C" fn hud_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_hud();
+}
+
+// The tooltip and the menu's status line count down too, while they show a
+// time left
+extern "C" fn badge_tick(_this: *mut AnyObject, _cmd: Sel, _timer: *mut AnyObject) {
+    update_icon();
+    state::with_status(|status| {
+        let now = crate::now_secs();
+        let paused = status.override_until.is_some_and(|until| until > now);
+        if status.remaining_seconds(now).is_some() || (paused && !status.is_awake()) {
+            show_tooltip(status);
+            show_in_menu(status);
+        }
+    });
+}
+
+extern "C" fn sync_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    toggle_sync();
+}
+
+extern "C" fn edit_triggers_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    trigger_editor::show(unsafe { MainThreadMarker::new_unchecked() });
+}
+
+extern "C" fn power_info_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    show_power_info();
+}
+
+extern "C" fn event_log_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    log_window::show(unsafe { MainThreadMarker::new_unchecked() });
+}
+
+extern "C" fn report_issue_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    issue_report::report_issue(unsafe { MainThreadMarker::new_unchecked() });
+}
+
+extern "C" fn uninstall_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    uninstall::uninstall(unsafe { MainThreadMarker::new_unchecked() });
+}
+
+extern "C" fn timer_15_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::ActivateFor { minutes: 15 });
+}
+
+extern "C" fn timer_30_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::ActivateFor { minutes: 30 });
+}
+
+extern "C" fn timer_60_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::ActivateFor { minutes: 60 });
+}
+
+extern "C" fn timer_120_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::ActivateFor { minutes: 120 });
+}
+
+extern "C" fn extend_15_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::Extend { seconds: 15 * 60 });
+}
+
+extern "C" fn overnight_action(_this: *mut AnyObject, _cmd: Sel, sender: *mut AnyObject) {
+    let on: isize = unsafe { msg_send![sender, state] };
+    let percent = (on == 0).then_some(battery::DEFAULT_CHARGE_PERCENT);
+    handle(state::Command::SetOvernight(percent));
+}
+
+extern "C" fn mode_display_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::SetMode(Mode::Display.kinds()));
+}
+
+extern "C" fn mode_system_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::SetMode(Mode::System.kinds()));
+}
+
+extern "C" fn mode_both_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::SetMode(Mode::Both.kinds()));
+}
+
+extern "C" fn mode_server_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    handle(state::Command::SetMode(Mode::Server.kinds()));
+}
+
+extern "C" fn start_suggested_action(_this: *mut AnyObject, _cmd: Sel, sender: *mut AnyObject) {
+    let tag: isize = unsafe { msg_send![sender, tag] };
+    handle(state::Command::ActivateFor {
+        minutes: tag as u64,
+    });
+}
+
+extern "C" fn mode_for_hour_action(_this: *mut AnyObject, _cmd: Sel, sender: *mut AnyObject) {
+    let tag: isize = unsafe { msg_send![sender, tag] };
+    if let Some(mode) = Mode::ALL.get(tag as usize) {
+        handle(state::Command::SetModeFor {
+            kinds: mode.kinds(),
+            minutes: 60,
+        });
+    }
+}
+
+extern "C" fn trigger_action(_this: *mut AnyObject, _cmd: Sel, sender: *mut AnyObject) {
+    let tag: isize = unsafe { msg_send![sender, tag] };
+    let trigger = SHOWN.with(|shown| shown.borrow().triggers.get(tag as usize).cloned());
+    if let Some((name, enabled)) = trigger {
+        handle(state::Command::SetTriggerEnabled {
+            name,
+            enabled: !enabled,
+        });
+    }
+}
+
+extern "C" fn button_clicked(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    let mtm = unsafe { MainThreadMarker::new_unchecked() };
+    let event = NSApplication::sharedApplication(mtm).currentEvent();
+    let event = event.as_deref().map(click::ClickEvent::from_event);
+    let click = click::decode(
+        event,
+        NSEvent::modifierFlags_class(),
+        NSEvent::pressedMouseButtons(),
+    );
+
+    match click {
+        // Only the status shows in kiosk mode, and clicks change nothing
+        // Nor while the controls are locked
+        click::Click::Toggle if kiosk::is_enabled() || controls_lock::is_locked() => {}
+        click::Click::Toggle => {
+            battery_guard::toggle();
+            if event.is_some_and(|event| event.trackpad) {
+                click::haptic_feedback();
+            }
+        }
+        click::Click::Menu => {
+            prepare_menu();
+            with_ui(|ui| unsafe {
+                let _: () = msg_send![&ui.status_item, setMenu: &*ui.menu];
+                let button: *mut AnyObject = msg_send![&ui.status_item, button];
+                let _: () = msg_send![button, performClick: std::ptr::null::<AnyObject>()];
+                let _: () = msg_send![&ui.status_item, setMenu: std::ptr::null::<AnyObject>()];
+            });
+        }
+    }
+}
+
+// Another Mac may have changed the settings, and the quiet hours window may
+// have opened or closed, since the menu was last shown
+fn prepare_menu() {
+    icloud::sync();
+    let awake = state::with_status(|status| status.is_awake());
+    let stats = stats::read(awake);
+    let diagnostics = diagnostics::read(awake);
+    // No longer than a session may last
+    let suggestion = (!awake)
+        .then(|| stats::suggestion(quiet_hours::local_time().map_or(0, |tm| tm.tm_gmtoff)))
+        .flatten()
+        .map(|minutes| minutes.min(state::max_session_minutes().unwrap_or(u64::MAX)));
+    SHOWN.with(|shown| {
+        let mut shown = shown.borrow_mut();
+        shown.stats = stats.lines().to_vec();
+        shown.diagnostics = diagnostics;
+        shown.suggestion = suggestion;
+    });
+    refresh_menu();
+}
+
+/// Opens the status item's menu as a context menu for `event` in `view`
+fn pop_up_menu(view: *mut AnyObject, event: *mut AnyObject) {
+    prepare_menu();
+    with_ui(|ui| unsafe {
+        let _: () = msg_send![
+            NSMenu::class(),
+            popUpContextMenu: &*ui.menu,
+            withEvent: event,
+            forView: view
+        ];
+    });
+}
+
+extern "C" fn export_settings_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    backup::export(unsafe { MainThreadMarker::new_unchecked() });
+}
+
+extern "C" fn import_settings_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    backup::import(unsafe { MainThreadMarker::new_unchecked() });
+    icloud::sync();
+    refresh_menu();
+}
+
+// AppKit asks before showing each item; those for managed settings are locked,
+// as are timers longer than a maximum session length allows and extending one
+// already at it. What's shown and ticked comes from the `menu::View`.
+extern "C" fn validate_menu_item(_this: *mut AnyObject, _cmd: Sel, item: *mut AnyObject) -> Bool {
+    let action: Option<Sel> = unsafe { msg_send![item, action] };
+    let timer_minutes = match action {
+        Some(action) if action == sel!(timer15:) => Some(15),
+        Some(action) if action == sel!(timer30:) => Some(30),
+        Some(action) if action == sel!(timer60:) => Some(60),
+        Some(action) if action == sel!(timer120:) => Some(120),
+        _ => None,
+    };
+    if let (Some(minutes), Some(max)) = (timer_minutes, state::max_session_minutes()) {
+        return Bool::new(minutes <= max);
+    }
+    if action == Some(sel!(extend15:)) {
+        return Bool::new(state::can_extend());
+    }
+    let key = match action {
+        Some(action) if action == sel!(toggleQuietHours:) => quiet_hours::KEY_ENABLED,
+        Some(action) if action == sel!(toggleSync:) => icloud::KEY_ENABLED,
+        Some(action) if action == sel!(toggleBadge:) => badge::KEY_ENABLED,
+        Some(action) if action == sel!(toggleCountdownItem:) => countdown_item::KEY_ENABLED,
+        Some(action) if action == sel!(toggleTextIcon:) => icon::KEY_TEXT_ONLY,
+        Some(action) if action == sel!(toggleSounds:) => sound::KEY_ENABLED,
+        Some(action) if action == sel!(toggleSpeech:) => speech::KEY_ENABLED,
+        Some(action) if action == sel!(toggleHud:) => hud::KEY_ENABLED,
+        Some(action) if action == sel!(toggleFlash:) => bar_managers::KEY_FLASH,
+        _ => return Bool::YES,
+    };
+    Bool::new(!settings::is_managed(key))
+}
+
+// The clean-up happens in `applicationWillTerminate:`, which logout, restart
+// and shutdown go through as well
+extern "C" fn quit_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    unsafe {
+        let mtm = MainThreadMarker::new_unchecked();
+        let app = NSApplication::sharedApplication(mtm);
+        let _: () = msg_send![&app, terminate: std::ptr::null::<AnyObject>()];
+    }
+}
+
+// Releases assertions and joins the timer threads; the deactivation is the
+// event log's last line. A `caffeinate` hand-off wouldn't outlive a power-off,
+// so then the session is saved to resume instead.
+fn clean_up() {
+    if CLEANED_UP.with(|cell| cell.replace(true)) {
+        return;
+    }
+    if resume::is_powering_off() {
+        resume::save();
+    } else {
+        resume::save_open_ended();
+        handoff::hand_off();
+    }
+    icloud::sync_now();
+    if let Err(e) = state::shutdown() {
+        present_error(&e);
+    }
+}
+
+// For uninstalling: released and logged, with no hand-off, resume or sync
+// left to recreate anything once the files are gone
+fn clean_up_for_good() {
+    if CLEANED_UP.with(|cell| cell.replace(true)) {
+        return;
+    }
+    if let Err(e) = state::shutdown() {
+        present_error(&e);
+    }
+}
+
+// When the system powers off with resuming on, termination waits until the
+// session has been saved and released, done from the next turn of the run loop
+extern "C" fn should_terminate(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) -> usize {
+    if !resume::is_powering_off() {
+        return TERMINATE_NOW;
+    }
+    run_on_main(|mtm| {
+        clean_up();
+        let app = NSApplication::sharedApplication(mtm);
+        unsafe {
+            let _: () = msg_send![&app, replyToApplicationShouldTerminate: true];
+        }
+    });
+    TERMINATE_LATER
+}
+
+extern "C" fn will_terminate(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    clean_up();
+}
+
+fn register_delegate_class() -> &'static AnyClass {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    let mut cls_ptr: Option<&'static AnyClass> = None;
+
+    REGISTER.call_once(|| {
+        let superclass = objc2::class!(NSObject);
+        let mut builder = ClassBuilder::new(c"AwakeDelegate", superclass)
+            .expect("AwakeDelegate class already registered");
+
+        type Fn3 = extern "C" fn(*mut AnyObject, Sel, *mut AnyObject);
+
+        unsafe {
+            builder.add_method(sel!(toggle:), toggle_action as Fn3);
+            builder.add_method(sel!(sleepDisplay:), sleep_display_action as Fn3);
+            builder.add_method(sel!(lockScreen:), lock_screen_action as Fn3);
+            builder.add_method(sel!(lockControls:), lock_controls_action as Fn3);
+            builder.add_method(sel!(unlockControls:), unlock_controls_action as Fn3);
+            builder.add_method(sel!(toggleLogin:), login_action as Fn3);
+            builder.add_method(sel!(toggleQuietHours:), quiet_hours_action as Fn3);
+            builder.add_method(sel!(toggleSync:), sync_action as Fn3);
+            builder.add_method(sel!(toggleBadge:), badge_action as Fn3);
+            builder.add_method(sel!(toggleCountdownItem:), countdown_item_action as Fn3);
+            builder.add_method(sel!(toggleTextIcon:), text_icon_action as Fn3);
+            builder.add_method(sel!(tickBadge:), badge_tick as Fn3);
+            builder.add_method(sel!(toggleSounds:), sounds_action as Fn3);
+            builder.add_method(sel!(toggleSpeech:), speech_action as Fn3);
+            builder.add_method(sel!(toggleHud:), hud_action as Fn3);
+            builder.add_method(sel!(toggleFlash:), flash_action as Fn3);
+            builder.add_method(sel!(showPowerInfo:), power_info_action as Fn3);
+            builder.add_method(sel!(showEventLog:), event_log_action as Fn3);
+            builder.add_method(sel!(reportIssue:), report_issue_action as Fn3);
+            builder.add_method(sel!(uninstall:), uninstall_action as Fn3);
+            builder.add_method(sel!(timer15:), timer_15_action as Fn3);
+            builder.add_method(sel!(timer30:), timer_30_action as Fn3);
+            builder.add_method(sel!(timer60:), timer_60_action as Fn3);
+            builder.add_method(sel!(startSuggested:), start_suggested_action as Fn3);
+            builder.add_method(sel!(timer120:), timer_120_action as Fn3);
+            builder.add_method(sel!(extend15:), extend_15_action as Fn3);
+            builder.add_method(sel!(overnight:), overnight_action as Fn3);
+            builder.add_method(sel!(modeDisplay:), mode_display_action as Fn3);
+            builder.add_method(sel!(modeSystem:), mode_system_action as Fn3);
+            builder.add_method(sel!(modeBoth:), mode_both_action as Fn3);
+            builder.add_method(sel!(modeServer:), mode_server_action as Fn3);
+            builder.add_method(sel!(modeForHour:), mode_for_hour_action as Fn3);
+            builder.add_method(sel!(toggleTrigger:), trigger_action as Fn3);
+            builder.add_method(sel!(editTriggers:), edit_triggers_action as Fn3);
+            builder.add_method(sel!(exportSettings:), export_settings_action as Fn3);
+            builder.add_method(sel!(importSettings:), import_settings_action as Fn3);
+            builder.add_method(sel!(quit:), quit_action as Fn3);
+            builder.add_method(
+                sel!(applicationShouldTerminate:),
+                should_terminate as extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(sel!(applicationWillTerminate:), will_terminate as Fn3);
+            builder.add_method(sel!(willPowerOff:), resume::will_power_off as Fn3);
+            builder.add_method(sel!(triggersWoke:), trigger_events::woke as Fn3);
+            builder.add_method(sel!(displaysSlept:), display_pause::slept as Fn3);
+            builder.add_method(sel!(displaysWoke:), display_pause::woke as Fn3);
+            builder.add_method(
+                sel!(triggersDisplaysWoke:),
+                trigger_events::displays_woke as Fn3,
+            );
+            builder.add_method(
+                sel!(triggersAppsChanged:),
+                trigger_events::apps_changed as Fn3,
+            );
+            builder.add_method(
+                sel!(locationManager:didUpdateLocations:),
+                location::updated as extern "C" fn(_, _, _, _),
+            );
+            builder.add_method(
+                sel!(locationManager:didFailWithError:),
+                location::failed as extern "C" fn(_, _, _, _),
+            );
+            builder.add_method(
+                sel!(handleURL:withReplyEvent:),
+                url_scheme::handle_url as extern "C" fn(_, _, _, _),
+            );
+            builder.add_method(
+                sel!(observeValueForKeyPath:ofObject:change:context:),
+                appearance::changed as extern "C" fn(_, _, _, _, _, _),
+            );
+            builder.add_method(
+                sel!(validateMenuItem:),
+                validate_menu_item as extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(sel!(buttonClicked:), button_clicked as Fn3);
+            builder.add_method(
+                sel!(draggingEntered:),
+                drop::dragging_entered as extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(performDragOperation:),
+                drop::perform_drag_operation as extern "C" fn(_, _, _) -> _,
+            );
+        }
+
+        cls_ptr = Some(builder.register());
+    });
+
+    if let Some(c) = cls_ptr {
+        c
+    } else {
+        AnyClass::get(c"AwakeDelegate").unwrap()
+    }
+}
+
+/// Builds the status item and menu, then runs the AppKit event loop
+pub fn run() {
+    let mtm = MainThreadMarker::new().expect("must run on main thread");
+
+    unsafe {
+        let app = NSApplication::sharedApplication(mtm);
+        // A bundle saying `LSUIElement` false wants a Dock icon
+        if bundle::ui_element() != Some(false) {
+            app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
+        }
+
+        let delegate_class = register_delegate_class();
+        let delegate: *mut AnyObject = msg_send![delegate_class, new];
+        let _: () = msg_send![&app, setDelegate: delegate];
+        resume::observe_power_off(delegate);
+        trigger_events::observe(delegate);
+        display_pause::observe(delegate);
+        location::start(delegate);
+        appearance::observe(delegate);
+        url_scheme::observe(delegate);
+
+        let status_bar = NSStatusBar::systemStatusBar();
+        let status_item = status_bar.statusItemWithLength(-1.0); // NSVariableStatusItemLength
+
+        // Asleep until the first status arrives
+        icon::draw(msg_send![&status_item, button], false, None);
+
+        // Filled in by `refresh_menu`
+        let menu = NSMenu::new(mtm);
+
+        // Set button action for left-click toggle
+        {
+            let button: *mut AnyObject = msg_send![&status_item, button];
+            if !button.is_null() {
+                let _: () = msg_send![button, setAction: sel!(buttonClicked:)];
+                let _: () = msg_send![button, setTarget: delegate];
+            }
+        }
+
+        // Send right-click events to our button handler too:
+        // left mouse up, right mouse down/up
+        let _: () = msg_send![&status_item, sendActionOn: click::ACTION_MASK];
+
+        // Dropping a file on the icon keeps the Mac awake while it's busy
+        drop::register(&status_item, delegate);
+
+        // Keep the menu for right-click access (don't set it on status item —
+        // left click toggles, right click shows menu)
+        UI.with(|ui| {
+            let _ = ui.set(Ui {
+                status_item,
+                menu,
+                delegate,
+            });
+        });
+        show_mode(state::mode());
+        state::on_state_change(show_icon);
+        state::on_state_change(show_tooltip);
+        state::on_state_change(show_in_menu);
+        state::on_state_change(announce);
+        state::on_state_change(bar_managers::post);
+        // `--watch` may already have started a session
+        state::refresh();
+        // Left for the next normal launch, as either could switch Awake on
+        if !settings::is_safe_mode() {
+            resume::restore();
+            handoff::reclaim();
+        }
+        icloud::sync();
+        kiosk::start();
+        hot_corner::start();
+        battery_guard::start();
+        temperature::start();
+        screen_recording::start();
+        refresh_menu();
+        if let Some(report) = crate::crash::take_report() {
+            crash_prompt::offer(&report, mtm);
+        }
+        rosetta::offer_hint(mtm);
+
+        let _: *mut AnyObject = msg_send![
+            AnyClass::get(c"NSTimer").unwrap(),
+            scheduledTimerWithTimeInterval: tick_secs(),
+            target: delegate,
+            selector: sel!(tickBadge:),
+            userInfo: std::ptr::null::<AnyObject>(),
+            repeats: true
+        ];
+
+        app.run();
+    }
+}
@@ -0,0 +1,76 @@
+//! Banners from Notification Center, for feedback when the status item can't
+//! be seen. Permission is asked for the first time one is posted.
+//!
+//! Only an app bundle has a notification centre; run as a bare binary, as in
+//! development, nothing is shown. Nor is anything during quiet hours.
+
+use std::cell::Cell;
+
+use block2::RcBlock;
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+use crate::quiet_hours;
+
+// `UNAuthorizationOptionAlert`
+const ALERTS: usize = 1 << 2;
+// Each banner replaces the last, rather than piling up in Notification Center
+const IDENTIFIER: &str = "io.tmss.awake.state";
+
+#[link(name = "UserNotifications", kind = "framework")]
+extern "C" {}
+
+thread_local! {
+    static AUTHORIZATION_REQUESTED: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe fn center() -> Option<*mut AnyObject> {
+    let bundle: *mut AnyObject = msg_send![AnyClass::get(c"NSBundle")?, mainBundle];
+    let identifier: *mut AnyObject = msg_send![bundle, bundleIdentifier];
+    if identifier.is_null() {
+        return None;
+    }
+    let center: *mut AnyObject = msg_send![
+        AnyClass::get(c"UNUserNotificationCenter")?,
+        currentNotificationCenter
+    ];
+    (!center.is_null()).then_some(center)
+}
+
+pub fn post(title: &str, body: &str) {
+    if quiet_hours::is_quiet_now() {
+        return;
+    }
+    unsafe {
+        let Some(center) = center() else {
+            return;
+        };
+        if !AUTHORIZATION_REQUESTED.with(|requested| requested.replace(true)) {
+            let done = RcBlock::new(|_granted: Bool, _error: *mut AnyObject| {});
+            let _: () = msg_send![
+                center,
+                requestAuthorizationWithOptions: ALERTS,
+                completionHandler: &*done
+            ];
+        }
+        let Some(content_class) = AnyClass::get(c"UNMutableNotificationContent") else {
+            return;
+        };
+        let content: *mut AnyObject = msg_send![content_class, new];
+        let _: () = msg_send![content, setTitle: &*NSString::from_str(title)];
+        let _: () = msg_send![content, setBody: &*NSString::from_str(body)];
+        let request: *mut AnyObject = msg_send![
+            AnyClass::get(c"UNNotificationRequest").unwrap(),
+            requestWithIdentifier: &*NSString::from_str(IDENTIFIER),
+            content: content,
+            trigger: std::ptr::null::<AnyObject>()
+        ];
+        let _: () = msg_send![content, release];
+        let _: () = msg_send![
+            center,
+            addNotificationRequest: request,
+            withCompletionHandler: std::ptr::null::<AnyObject>()
+        ];
+    }
+}
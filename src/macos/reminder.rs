@@ -0,0 +1,34 @@
+//! The "still needed?" reminder for a long open-ended session: an alert with
+//! a button to keep going, one to turn Awake into a one-hour timer, and one to
+//! switch it off.
+
+use std::cell::Cell;
+
+use objc2::MainThreadMarker;
+
+use super::alert;
+use crate::state;
+
+thread_local! {
+    // One alert at a time, should the next reminder come round while it's up
+    static SHOWING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn show(hours: u64, mtm: MainThreadMarker) {
+    if SHOWING.with(|showing| showing.replace(true)) {
+        return;
+    }
+    let response = alert::ask(
+        &state::reminder_text(hours, "Mac"),
+        "Leaving it on keeps the Mac from sleeping.",
+        &["Keep On", "1 More Hour", "Turn Off"],
+        mtm,
+    );
+    SHOWING.with(|showing| showing.set(false));
+
+    match response {
+        Some(1) => super::handle(state::Command::ActivateFor { minutes: 60 }),
+        Some(2) => super::handle(state::Command::Deactivate),
+        _ => {}
+    }
+}
@@ -0,0 +1,153 @@
+//! Picking a session back up after a restart. On logout, restart or shutdown
+//! Awake holds off terminating until it has written down the session it was
+//! keeping and released it; the next launch resumes whatever is left.
+//!
+//! Off unless `defaults write io.tmss.awake ResumeAfterRestart -bool true`.
+//!
+//! `ActivateOnLaunch` covers the open-ended session on its own, for those who
+//! leave Awake always on: one kept from the menu when Awake quits for any
+//! reason is switched back on at the next launch, however long that is.
+
+use std::cell::Cell;
+use std::fs;
+use std::path::PathBuf;
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+use objc2::sel;
+use objc2_foundation::NSString;
+
+use crate::keepawake::{Kind, KindSet};
+use crate::{settings, state};
+
+pub const KEY_ENABLED: &str = "ResumeAfterRestart";
+pub const KEY_ACTIVATE_ON_LAUNCH: &str = "ActivateOnLaunch";
+const RESUME_FILE: &str = "resume";
+// An open-ended session isn't picked up after the machine has been off longer
+const MAX_OPEN_ENDED_AGE_SECS: u64 = 12 * 3600;
+
+thread_local! {
+    // Set by `NSWorkspaceWillPowerOffNotification`, which comes before the
+    // system asks apps to terminate
+    static POWERING_OFF: Cell<bool> = const { Cell::new(false) };
+}
+
+fn resume_path() -> Option<PathBuf> {
+    Some(crate::app_support_dir()?.join(RESUME_FILE))
+}
+
+/// Has `delegate`'s `willPowerOff:` called when the system is about to log
+/// out, restart or shut down
+pub unsafe fn observe_power_off(delegate: *mut AnyObject) {
+    let Some(workspace) = AnyClass::get(c"NSWorkspace") else {
+        return;
+    };
+    let workspace: *mut AnyObject = msg_send![workspace, sharedWorkspace];
+    let center: *mut AnyObject = msg_send![workspace, notificationCenter];
+    let name = NSString::from_str("NSWorkspaceWillPowerOffNotification");
+    let _: () = msg_send![
+        center,
+        addObserver: delegate,
+        selector: sel!(willPowerOff:),
+        name: &*name,
+        object: std::ptr::null::<AnyObject>()
+    ];
+}
+
+pub extern "C" fn will_power_off(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    POWERING_OFF.with(|cell| cell.set(true));
+}
+
+/// Whether this termination is the system powering off, with resuming on
+pub fn is_powering_off() -> bool {
+    POWERING_OFF.with(Cell::get) && settings::get_bool(KEY_ENABLED)
+}
+
+/// Called before the session is released: records a session started from the
+/// menu, with its expiry if it's timed. Watches and triggers set themselves up
+/// again at launch.
+pub fn save() {
+    write(false);
+}
+
+/// Called before the session is released on any other quit: records an
+/// open-ended session started from the menu, if `ActivateOnLaunch` is on
+pub fn save_open_ended() {
+    if settings::get_bool(KEY_ACTIVATE_ON_LAUNCH) {
+        write(true);
+    }
+}
+
+fn write(open_ended_only: bool) {
+    let Some(record) = state::with_status(|status| {
+        if status.activation_source() != Some(&state::Source::Manual)
+            || (open_ended_only && status.expiry.is_some())
+        {
+            return None;
+        }
+        let expiry = status
+            .expiry
+            .map_or("-".into(), |expiry| expiry.to_string());
+        Some(format!(
+            "{} {} {}\n",
+            status.kinds,
+            crate::now_secs(),
+            expiry
+        ))
+    }) else {
+        return;
+    };
+    let Some(path) = resume_path() else {
+        return;
+    };
+    if let Err(e) =
+        fs::create_dir_all(path.parent().unwrap_or(&path)).and_then(|()| fs::write(&path, record))
+    {
+        eprintln!("Failed to record the session to resume: {}", e);
+    }
+}
+
+/// Called at launch: resumes the session saved when the system last powered off
+pub fn restore() {
+    let Some(path) = resume_path() else {
+        return;
+    };
+    let Ok(record) = fs::read_to_string(&path) else {
+        return;
+    };
+    let _ = fs::remove_file(&path);
+
+    let mut fields = record.split_whitespace();
+    let (Some(names), Some(Ok(saved_at)), Some(expiry)) = (
+        fields.next(),
+        fields.next().map(str::parse::<u64>),
+        fields.next(),
+    ) else {
+        return;
+    };
+    let kinds: Vec<Kind> = Kind::ALL
+        .into_iter()
+        .filter(|kind| names.split('+').any(|name| name == kind.name()))
+        .collect();
+    let now = crate::now_secs();
+
+    let minutes = match expiry.parse::<u64>() {
+        Ok(expiry) if expiry > now => Some((expiry - now).div_ceil(60)),
+        Err(_)
+            if settings::get_bool(KEY_ACTIVATE_ON_LAUNCH)
+                || now.saturating_sub(saved_at) < MAX_OPEN_ENDED_AGE_SECS =>
+        {
+            None
+        }
+        _ => return,
+    };
+
+    super::handle(state::Command::SetMode(KindSet::of(&kinds)));
+    match minutes {
+        Some(minutes) => super::handle(state::Command::ActivateFor { minutes }),
+        None if !state::with_status(|status| status.is_awake()) => {
+            super::handle(state::Command::Toggle)
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,39 @@
+//! At launch under Rosetta: an alert, shown once (`RosettaHintShown`), saying
+//! the Intel build is installed on an Apple silicon Mac, with a link to the
+//! releases. Kiosk mode, with nobody to read it, goes without; `awake status`
+//! keeps saying so either way.
+
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2::{msg_send, MainThreadMarker};
+use objc2_foundation::{NSString, NSURL};
+
+use super::alert;
+use crate::settings;
+
+const KEY_SHOWN: &str = "RosettaHintShown";
+const RELEASES: &str = "https://github.com/anatomic/awake/releases/latest";
+
+pub fn offer_hint(mtm: MainThreadMarker) {
+    if !crate::arch::is_translated() || settings::get_bool(KEY_SHOWN) || super::kiosk::is_enabled()
+    {
+        return;
+    }
+    settings::set_bool(KEY_SHOWN, true);
+    let response = alert::ask(
+        "Awake is running under Rosetta",
+        "This is the Intel build, translated on your Apple silicon Mac, which costs \
+         CPU time. The Apple silicon or universal build runs natively.",
+        &["Download…", "Not Now"],
+        mtm,
+    );
+    if response != Some(0) {
+        return;
+    }
+    if let Some(url) = NSURL::URLWithString(&NSString::from_str(RELEASES)) {
+        unsafe {
+            let workspace: *mut AnyObject =
+                msg_send![AnyClass::get(c"NSWorkspace").unwrap(), sharedWorkspace];
+            let _: Bool = msg_send![workspace, openURL: &*url];
+        }
+    }
+}
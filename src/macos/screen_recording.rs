@@ -0,0 +1,84 @@
+//! A still icon while the screen is being recorded, so a screencast isn't
+//! full of a ticking countdown: the badge, the separate countdown item, the
+//! floating indicator's time and the flash on a change are all left out, and
+//! come back when the recording stops. Sessions and assertions carry on as
+//! they were.
+//!
+//! Off unless `StillWhileRecording` is set at launch. A recording is counted
+//! from the processes that make one, checked every `POLL_SECS` on a
+//! background thread: the system's own recorder (`screencaptureui`, behind
+//! ⇧⌘5 and QuickTime Player) and OBS, plus any names in `ScreenRecorders`
+//! ("zoom.us,Loom").
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::settings;
+use crate::triggers::processes::{self, Process};
+
+pub const KEY_ENABLED: &str = "StillWhileRecording";
+const KEY_RECORDERS: &str = "ScreenRecorders";
+
+const POLL_SECS: u64 = 5;
+const RECORDERS: [&str; 2] = ["screencaptureui", "obs"];
+
+// Whether a recording was seen at the last check
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Whether any of `processes` records the screen: one of `RECORDERS` or of
+/// `extra`, ignoring case
+fn is_recording(processes: &[Process], extra: &[String]) -> bool {
+    processes.iter().any(|process| {
+        let name = process.name.to_lowercase();
+        RECORDERS.contains(&name.as_str()) || extra.contains(&name)
+    })
+}
+
+/// Whether the countdown and the flash are held back for a recording
+pub fn is_still() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Starts looking for recordings, if `StillWhileRecording` is set
+pub fn start() {
+    if !settings::get_bool(KEY_ENABLED) {
+        return;
+    }
+    let extra: Vec<String> = settings::get_string(KEY_RECORDERS)
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    thread::spawn(move || loop {
+        let recording = is_recording(&processes::all(), &extra);
+        if RECORDING.swap(recording, Ordering::Relaxed) != recording {
+            super::run_on_main(|_| super::update_icon());
+        }
+        thread::sleep(Duration::from_secs(POLL_SECS));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(name: &str) -> Process {
+        Process {
+            pid: 1,
+            name: name.into(),
+            ppid: 0,
+            cpu: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn recorders_are_known_by_name() {
+        let extra = vec!["zoom.us".to_string()];
+        assert!(is_recording(&[process("Finder"), process("OBS")], &extra));
+        assert!(is_recording(&[process("zoom.us")], &extra));
+        assert!(!is_recording(&[process("Finder")], &extra));
+        assert!(!is_recording(&[process("zoom.us")], &[]));
+    }
+}
@@ -0,0 +1,202 @@
+//! Reads the System Management Controller: the sensors and fans behind the
+//! temperature limit and the diagnostics readout.
+//!
+//! Each value is a four-character key, asked for through the `AppleSMC` user
+//! client in two calls: one for the key's type and size, one for its bytes.
+//! Intel Macs and Apple silicon name their sensors differently, so callers
+//! give a few keys and take whichever the Mac has. Safe to use from any
+//! thread.
+
+use std::ffi::{c_char, c_void};
+
+// `kIOMainPortDefault`
+const MAIN_PORT_DEFAULT: u32 = 0;
+// The user client's one method, and its commands
+const KERNEL_INDEX: u32 = 2;
+const READ_BYTES: u8 = 5;
+const READ_KEY_INFO: u8 = 9;
+
+const fn code(name: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*name)
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Version {
+    major: u8,
+    minor: u8,
+    build: u8,
+    reserved: u8,
+    release: u16,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct PowerLimits {
+    version: u16,
+    length: u16,
+    cpu: u32,
+    gpu: u32,
+    memory: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct KeyInfo {
+    size: u32,
+    data_type: u32,
+    attributes: u8,
+}
+
+/// `SMCKeyData_t`, 80 bytes, in and out of every call
+#[repr(C)]
+#[derive(Default)]
+struct KeyData {
+    key: u32,
+    version: Version,
+    limits: PowerLimits,
+    info: KeyInfo,
+    result: u8,
+    status: u8,
+    command: u8,
+    data32: u32,
+    bytes: [u8; 32],
+}
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOServiceMatching(name: *const c_char) -> *mut c_void;
+    fn IOServiceGetMatchingService(main_port: u32, matching: *mut c_void) -> u32;
+    fn IOServiceOpen(service: u32, task: u32, kind: u32, connection: *mut u32) -> i32;
+    fn IOServiceClose(connection: u32) -> i32;
+    fn IOObjectRelease(object: u32) -> i32;
+    fn IOConnectCallStructMethod(
+        connection: u32,
+        selector: u32,
+        input: *const c_void,
+        input_size: usize,
+        output: *mut c_void,
+        output_size: *mut usize,
+    ) -> i32;
+}
+
+extern "C" {
+    // What `mach_task_self()` reads
+    static mach_task_self_: u32;
+}
+
+/// An open connection to the SMC, closed when dropped
+pub struct Smc {
+    connection: u32,
+}
+
+impl Smc {
+    pub fn open() -> Option<Smc> {
+        unsafe {
+            let service = IOServiceGetMatchingService(
+                MAIN_PORT_DEFAULT,
+                IOServiceMatching(c"AppleSMC".as_ptr()),
+            );
+            if service == 0 {
+                return None;
+            }
+            let mut connection = 0;
+            let status = IOServiceOpen(service, mach_task_self_, 0, &mut connection);
+            IOObjectRelease(service);
+            (status == 0).then_some(Smc { connection })
+        }
+    }
+
+    fn call(&self, input: &KeyData) -> Option<KeyData> {
+        let mut output = KeyData::default();
+        let mut size = std::mem::size_of::<KeyData>();
+        let status = unsafe {
+            IOConnectCallStructMethod(
+                self.connection,
+                KERNEL_INDEX,
+                (input as *const KeyData).cast(),
+                std::mem::size_of::<KeyData>(),
+                (&mut output as *mut KeyData).cast(),
+                &mut size,
+            )
+        };
+        (status == 0 && output.result == 0).then_some(output)
+    }
+
+    /// The value of `key`, as a number; `None` if this Mac hasn't got it
+    pub fn read(&self, key: &[u8; 4]) -> Option<f64> {
+        let info = self
+            .call(&KeyData {
+                key: code(key),
+                command: READ_KEY_INFO,
+                ..KeyData::default()
+            })?
+            .info;
+        let data = self.call(&KeyData {
+            key: code(key),
+            info,
+            command: READ_BYTES,
+            ..KeyData::default()
+        })?;
+        let size = (info.size as usize).min(data.bytes.len());
+        decode(info.data_type, &data.bytes[..size])
+    }
+
+    /// The hottest of `keys` this Mac has, in degrees Celsius
+    pub fn temperature(&self, keys: &[&[u8; 4]]) -> Option<f64> {
+        keys.iter()
+            .filter_map(|key| self.read(key))
+            // Unused sensors read zero, or nonsense
+            .filter(|celsius| (1.0..150.0).contains(celsius))
+            .reduce(f64::max)
+    }
+}
+
+impl Drop for Smc {
+    fn drop(&mut self) {
+        unsafe {
+            IOServiceClose(self.connection);
+        }
+    }
+}
+
+/// A value of the SMC's `data_type`. Its own types are big-endian, bar the
+/// floats Apple silicon reports in its native order.
+fn decode(data_type: u32, bytes: &[u8]) -> Option<f64> {
+    let two = || Some([*bytes.first()?, *bytes.get(1)?]);
+    match &data_type.to_be_bytes() {
+        // Signed, 7 integer and 8 fraction bits
+        b"sp78" => Some(f64::from(i16::from_be_bytes(two()?)) / 256.0),
+        // Unsigned, 14 integer and 2 fraction bits
+        b"fpe2" => Some(f64::from(u16::from_be_bytes(two()?)) / 4.0),
+        b"flt " => Some(f64::from(f32::from_le_bytes(
+            bytes.get(..4)?.try_into().ok()?,
+        ))),
+        b"ui8 " => bytes.first().map(|&byte| f64::from(byte)),
+        b"ui16" => Some(f64::from(u16::from_be_bytes(two()?))),
+        b"ui32" => Some(f64::from(u32::from_be_bytes(
+            bytes.get(..4)?.try_into().ok()?,
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_data_matches_the_kernel() {
+        assert_eq!(std::mem::size_of::<KeyData>(), 80);
+    }
+
+    #[test]
+    fn values_decode_by_type() {
+        assert_eq!(decode(code(b"sp78"), &[0x3c, 0x80]), Some(60.5));
+        assert_eq!(decode(code(b"fpe2"), &[0x1f, 0x40]), Some(2000.0));
+        assert_eq!(decode(code(b"flt "), &47.25f32.to_le_bytes()), Some(47.25));
+        assert_eq!(decode(code(b"ui8 "), &[2]), Some(2.0));
+        assert_eq!(decode(code(b"sp78"), &[0x3c]), None);
+        assert_eq!(decode(code(b"ch8*"), b"abc"), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! A short system sound when Awake switches on, switches off or a timer runs
+//! out, for when the menu bar is hidden or nobody is looking at it.
+//!
+//! Off unless **Play Sounds** is ticked (`PlaySounds`). Each sound is a name
+//! from `/System/Library/Sounds`, changed with e.g.
+//! `defaults write io.tmss.awake ExpirySound Glass`.
+
+use std::cell::Cell;
+
+use objc2::msg_send;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+pub use crate::change::{Cue, Seen};
+use crate::{change, quiet_hours, settings};
+
+pub const KEY_ENABLED: &str = "PlaySounds";
+
+thread_local! {
+    static LAST: Cell<Seen> = const { Cell::new(Seen { awake: false, expiry: None }) };
+}
+
+/// Called with every status: what changed since the last one
+pub fn changed(seen: Seen) -> Option<Cue> {
+    let before = LAST.with(|last| last.replace(seen));
+    change::cue(before, seen, crate::now_secs())
+}
+
+// The preference naming `cue`'s sound, and the sound without one
+fn sound(cue: Cue) -> (&'static str, &'static str) {
+    match cue {
+        Cue::Activated => ("ActivateSound", "Tink"),
+        Cue::Deactivated => ("DeactivateSound", "Pop"),
+        Cue::Expired => ("ExpirySound", "Glass"),
+    }
+}
+
+/// Plays `cue`'s sound, if sounds are on and it isn't quiet hours
+pub fn play_for(cue: Cue) {
+    if settings::get_bool(KEY_ENABLED) && !quiet_hours::is_quiet_now() {
+        let (key, default) = sound(cue);
+        play(&settings::get_string(key).unwrap_or_else(|| default.into()));
+    }
+}
+
+fn play(name: &str) {
+    let name = NSString::from_str(name);
+    unsafe {
+        let sound: *mut AnyObject =
+            msg_send![AnyClass::get(c"NSSound").unwrap(), soundNamed: &*name];
+        if !sound.is_null() {
+            let _: Bool = msg_send![sound, play];
+        }
+    }
+}
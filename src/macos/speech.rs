@@ -0,0 +1,110 @@
+//! Spoken announcements of state changes ("Keeping your Mac awake for one
+//! hour"), for anyone who relies on audio rather than the menu bar icon.
+//!
+//! Off unless **Speak Changes** is ticked (`SpeakChanges`). Uses the system
+//! voice, as set in Accessibility > Spoken Content.
+
+use std::cell::OnceCell;
+
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject, Bool};
+use objc2_foundation::NSString;
+
+use super::sound::{Cue, Seen};
+use crate::{quiet_hours, settings};
+
+pub const KEY_ENABLED: &str = "SpeakChanges";
+
+thread_local! {
+    // Kept for the whole run: a synthesizer stops speaking when released
+    static SYNTHESIZER: OnceCell<Option<Retained<AnyObject>>> = const { OnceCell::new() };
+}
+
+// "one hour", "2 hours and 30 minutes"
+fn spoken_duration(minutes: u64) -> String {
+    let unit = |n: u64, name: &str| match n {
+        1 => format!("one {}", name),
+        n => format!("{} {}s", n, name),
+    };
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    match (hours, minutes) {
+        (0, minutes) => unit(minutes, "minute"),
+        (hours, 0) => unit(hours, "hour"),
+        (hours, minutes) => format!("{} and {}", unit(hours, "hour"), unit(minutes, "minute")),
+    }
+}
+
+/// What's said for `cue`; `after` is the status it led to
+pub fn announcement(cue: Cue, after: Seen, now: u64) -> String {
+    match cue {
+        Cue::Activated => match after.expiry {
+            Some(expiry) => format!(
+                "Keeping your Mac awake for {}",
+                spoken_duration(expiry.saturating_sub(now).div_ceil(60))
+            ),
+            None => "Keeping your Mac awake".into(),
+        },
+        Cue::Deactivated => "Your Mac can sleep again".into(),
+        // Overnight mode carries on until the battery has charged
+        Cue::Expired if after.awake => {
+            "Timer finished. Staying awake while the battery charges".into()
+        }
+        Cue::Expired => "Timer finished. Your Mac can sleep again".into(),
+    }
+}
+
+/// Speaks `cue`, if announcements are on and it isn't quiet hours, cutting
+/// off one still being spoken
+pub fn announce(cue: Cue, after: Seen) {
+    if !settings::get_bool(KEY_ENABLED) || quiet_hours::is_quiet_now() {
+        return;
+    }
+    let text = NSString::from_str(&announcement(cue, after, crate::now_secs()));
+    SYNTHESIZER.with(|cell| {
+        let synthesizer = cell.get_or_init(|| unsafe {
+            let class = AnyClass::get(c"NSSpeechSynthesizer")?;
+            msg_send![class, new]
+        });
+        if let Some(synthesizer) = synthesizer {
+            unsafe {
+                let _: () = msg_send![synthesizer, stopSpeaking];
+                let _: Bool = msg_send![synthesizer, startSpeakingString: &*text];
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_read_aloud() {
+        assert_eq!(spoken_duration(1), "one minute");
+        assert_eq!(spoken_duration(30), "30 minutes");
+        assert_eq!(spoken_duration(60), "one hour");
+        assert_eq!(spoken_duration(150), "2 hours and 30 minutes");
+    }
+
+    #[test]
+    fn announcements_follow_the_change() {
+        let timed = Seen {
+            awake: true,
+            expiry: Some(1000 + 3600),
+        };
+        assert_eq!(
+            announcement(Cue::Activated, timed, 1000),
+            "Keeping your Mac awake for one hour"
+        );
+        let off = Seen::default();
+        assert_eq!(
+            announcement(Cue::Expired, off, 1000),
+            "Timer finished. Your Mac can sleep again"
+        );
+        assert_eq!(
+            announcement(Cue::Deactivated, off, 1000),
+            "Your Mac can sleep again"
+        );
+    }
+}
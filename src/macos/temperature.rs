@@ -0,0 +1,125 @@
+//! A temperature limit for laptops left closed in a stand: while Awake is on,
+//! a sensor that passes `MaxTemperature` (degrees Celsius) switches it off,
+//! with a notification, so the Mac can sleep and cool down.
+//!
+//! `MaxTemperatureSensor` says which: `cpu` (the default) or `skin`, the case
+//! and palm rest. Off unless `MaxTemperature` is set. Sensors are read from
+//! the SMC on a background thread every `POLL_SECS`; once over the limit, the
+//! Mac counts as hot until it has cooled `HYSTERESIS` degrees below it, and
+//! Awake is switched off again if it comes back on meanwhile.
+
+use std::thread;
+use std::time::Duration;
+
+use super::smc::Smc;
+use crate::{settings, state};
+
+pub const KEY_LIMIT: &str = "MaxTemperature";
+const KEY_SENSOR: &str = "MaxTemperatureSensor";
+
+const POLL_SECS: u64 = 30;
+const HYSTERESIS: f64 = 5.0;
+
+/// CPU die and proximity sensors: Intel's, then Apple silicon's cores
+pub const CPU_KEYS: [&[u8; 4]; 10] = [
+    b"TC0P", b"TC0D", b"TC0E", b"Tp09", b"Tp0T", b"Tp01", b"Tp05", b"Tp0D", b"Tp0H", b"Tp0L",
+];
+/// Palm rest, bottom case and battery
+pub const SKIN_KEYS: [&[u8; 4]; 4] = [b"Ts0P", b"Ts0S", b"Ts1P", b"TB0T"];
+
+/// Whether the Mac is over its limit, with some slack on the way down so a
+/// reading hovering around it doesn't flap
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Limit {
+    max: f64,
+    hot: bool,
+}
+
+impl Limit {
+    fn new(max: f64) -> Limit {
+        Limit { max, hot: false }
+    }
+
+    /// Notes `celsius`; true while the Mac is hot
+    fn update(&mut self, celsius: f64) -> bool {
+        if celsius >= self.max {
+            self.hot = true;
+        } else if celsius < self.max - HYSTERESIS {
+            self.hot = false;
+        }
+        self.hot
+    }
+}
+
+fn parse_limit(text: &str) -> Option<f64> {
+    let max: f64 = text
+        .trim()
+        .trim_end_matches('C')
+        .trim_end_matches('°')
+        .parse()
+        .ok()?;
+    (max.is_finite() && max > 0.0).then_some(max)
+}
+
+/// Starts watching the sensor, if a limit is set
+pub fn start() {
+    let Some(max) = settings::get_string(KEY_LIMIT).and_then(|text| parse_limit(&text)) else {
+        return;
+    };
+    let keys: &'static [&[u8; 4]] = match settings::get_string(KEY_SENSOR).as_deref() {
+        Some("skin") => &SKIN_KEYS,
+        _ => &CPU_KEYS,
+    };
+    thread::spawn(move || {
+        let Some(smc) = Smc::open() else {
+            eprintln!("awake: can't read the temperature sensors");
+            return;
+        };
+        let mut limit = Limit::new(max);
+        loop {
+            if let Some(celsius) = smc.temperature(keys) {
+                if limit.update(celsius) {
+                    super::run_on_main(move |_| too_hot(celsius, max));
+                }
+            }
+            thread::sleep(Duration::from_secs(POLL_SECS));
+        }
+    });
+}
+
+fn too_hot(celsius: f64, max: f64) {
+    if !state::with_status(|status| status.is_awake()) {
+        return;
+    }
+    super::handle(state::Command::Deactivate);
+    super::notification::post(
+        "Awake switched off",
+        &format!(
+            "The Mac reached {:.0}\u{b0}C, over its {:.0}\u{b0}C limit, so it can sleep and cool down.",
+            celsius, max
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_until_well_below_the_limit() {
+        let mut limit = Limit::new(90.0);
+        assert!(!limit.update(89.0));
+        assert!(limit.update(90.5));
+        assert!(limit.update(87.0));
+        assert!(!limit.update(84.0));
+        assert!(!limit.update(88.0));
+    }
+
+    #[test]
+    fn limits_read_back() {
+        assert_eq!(parse_limit("85"), Some(85.0));
+        assert_eq!(parse_limit(" 92.5°C"), Some(92.5));
+        assert_eq!(parse_limit("hot"), None);
+        assert_eq!(parse_limit("-4"), None);
+    }
+}
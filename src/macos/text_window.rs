@@ -0,0 +1,85 @@
+//! Read-only, scrollable text window for diagnostic output.
+//! A single window is created lazily and reused for every call to `show`.
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2::{msg_send, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+use std::cell::OnceCell;
+
+// NSWindowStyleMask: titled | closable | miniaturizable | resizable
+const STYLE_MASK: usize = 1 | 2 | 4 | 8;
+const BACKING_BUFFERED: usize = 2;
+// NSViewWidthSizable | NSViewHeightSizable
+const AUTORESIZE_FILL: usize = 2 | 16;
+
+struct TextWindow {
+    window: Retained<AnyObject>,
+    text_view: Retained<AnyObject>,
+}
+
+thread_local! {
+    static WINDOW: OnceCell<TextWindow> = const { OnceCell::new() };
+}
+
+pub fn show(title: &str, text: &str, mtm: MainThreadMarker) {
+    WINDOW.with(|cell| {
+        let w = cell.get_or_init(create);
+        unsafe {
+            let title = NSString::from_str(title);
+            let text = NSString::from_str(text);
+            let _: () = msg_send![&w.window, setTitle: &*title];
+            let _: () = msg_send![&w.text_view, setString: &*text];
+
+            // Accessory apps aren't frontmost by default; bring the window forward
+            let app = NSApplication::sharedApplication(mtm);
+            let _: () = msg_send![&app, activateIgnoringOtherApps: true];
+            let _: () = msg_send![&w.window, makeKeyAndOrderFront: std::ptr::null::<AnyObject>()];
+        }
+    });
+}
+
+fn create() -> TextWindow {
+    unsafe {
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(640.0, 420.0));
+
+        let window: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSWindow").unwrap(), alloc];
+        let window: Retained<AnyObject> = msg_send![
+            window,
+            initWithContentRect: frame,
+            styleMask: STYLE_MASK,
+            backing: BACKING_BUFFERED,
+            defer: false
+        ];
+        // Keep the window alive across close/reopen; we hold the only reference
+        let _: () = msg_send![&window, setReleasedWhenClosed: false];
+
+        let scroll: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSScrollView").unwrap(), alloc];
+        let scroll: Retained<AnyObject> = msg_send![scroll, initWithFrame: frame];
+        let _: () = msg_send![&scroll, setHasVerticalScroller: true];
+        let _: () = msg_send![&scroll, setAutoresizingMask: AUTORESIZE_FILL];
+
+        let text_view: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSTextView").unwrap(), alloc];
+        let text_view: Retained<AnyObject> = msg_send![text_view, initWithFrame: frame];
+        let _: () = msg_send![&text_view, setEditable: false];
+        let _: () = msg_send![&text_view, setSelectable: true];
+        let _: () = msg_send![&text_view, setAutoresizingMask: AUTORESIZE_FILL];
+        let font: *mut AnyObject = msg_send![
+            AnyClass::get(c"NSFont").unwrap(),
+            monospacedSystemFontOfSize: 11.0f64,
+            weight: 0.0f64
+        ];
+        if !font.is_null() {
+            let _: () = msg_send![&text_view, setFont: font];
+        }
+
+        let _: () = msg_send![&scroll, setDocumentView: &*text_view];
+        let _: () = msg_send![&window, setContentView: &*scroll];
+        let _: () = msg_send![&window, center];
+
+        TextWindow { window, text_view }
+    }
+}
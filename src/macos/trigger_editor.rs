@@ -0,0 +1,496 @@
+//! The **Edit Triggers…** window: the trigger rules (`triggers::rules`) as a
+//! table, edited in place — a switch to turn each on or off, pop-ups for its
+//! condition and mode, and text for its path or ports and a time limit.
+//! Buttons below add, remove and reorder rules. Every edit is saved to
+//! `TriggerRules` and applied at once; an edit that doesn't make a valid rule
+//! is refused with a beep.
+//!
+//! A single window is created lazily and reused, like `text_window`'s.
+
+use std::cell::{OnceCell, RefCell};
+
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyClass, AnyObject, ClassBuilder, Sel};
+use objc2::{msg_send, sel, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
+
+use crate::mode::Mode;
+use crate::settings;
+use crate::triggers::rules::{self, Condition, Rule};
+
+// NSWindowStyleMask: titled | closable | miniaturizable | resizable
+const STYLE_MASK: usize = 1 | 2 | 4 | 8;
+const BACKING_BUFFERED: usize = 2;
+// NSViewWidthSizable | NSViewHeightSizable
+const AUTORESIZE_FILL: usize = 2 | 16;
+// NSViewMaxYMargin: stays at the bottom as the window grows
+const AUTORESIZE_BOTTOM: usize = 32;
+const BUTTON_TYPE_SWITCH: usize = 3;
+const BUTTON_BAR_HEIGHT: f64 = 44.0;
+
+// Identifier, title and width of each column
+const COLUMNS: [(&str, &str, f64); 5] = [
+    ("enabled", "On", 32.0),
+    ("condition", "Condition", 200.0),
+    ("argument", "Path, Ports or Host", 190.0),
+    ("mode", "Mode", 170.0),
+    ("minutes", "Limit (min)", 80.0),
+];
+
+// In `Condition::ALL` order
+const CONDITION_TITLES: [&str; 10] = [
+    "Lockfile Exists",
+    "SSH Client Connected",
+    "Client Connected to Port",
+    "Containers or VMs Running",
+    "Build Running",
+    "Leader Machine Awake",
+    "Screen Mirrored",
+    "Audio on AirPlay or Bluetooth",
+    "Workday Hours",
+    "Time of Day",
+];
+
+// The menu's mode, then each of `Mode::ALL`
+const MODE_TITLES: [&str; 5] = [
+    "Menu's Mode",
+    Mode::Display.title(),
+    Mode::System.title(),
+    Mode::Both.title(),
+    Mode::Server.title(),
+];
+
+struct Editor {
+    window: Retained<AnyObject>,
+    table: Retained<AnyObject>,
+}
+
+thread_local! {
+    static EDITOR: OnceCell<Editor> = const { OnceCell::new() };
+    // The rules as shown; saved after every edit
+    static RULES: RefCell<Vec<Rule>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn show(mtm: MainThreadMarker) {
+    let saved = settings::get_string(rules::KEY).unwrap_or_default();
+    RULES.with(|cell| *cell.borrow_mut() = rules::parse(&saved));
+    EDITOR.with(|cell| {
+        let editor = cell.get_or_init(create);
+        unsafe {
+            // A configuration profile's rules can be looked at, not changed
+            let _: () = msg_send![&editor.table, setEnabled: !settings::is_managed(rules::KEY)];
+            let _: () = msg_send![&editor.table, reloadData];
+
+            let app = NSApplication::sharedApplication(mtm);
+            let _: () = msg_send![&app, activateIgnoringOtherApps: true];
+            let _: () =
+                msg_send![&editor.window, makeKeyAndOrderFront: std::ptr::null::<AnyObject>()];
+        }
+    });
+}
+
+/// Applies an edit of `column` to `value` (a cell's value, as text), or
+/// refuses it
+fn edit(rule: &mut Rule, column: &str, value: &str) -> Option<()> {
+    let index = || value.parse::<usize>().ok();
+    match column {
+        "enabled" => rule.enabled = value != "0",
+        "condition" => {
+            let (key, argument) = *Condition::ALL.get(index()?)?;
+            if key != rule.condition.key() {
+                rule.condition = Condition::parse(key, argument)?;
+            }
+        }
+        "argument" => rule.condition = Condition::parse(rule.condition.key(), value)?,
+        "mode" => {
+            rule.mode = match index()? {
+                0 => None,
+                i => Some(*Mode::ALL.get(i - 1)?),
+            }
+        }
+        "minutes" => {
+            rule.minutes = match value.trim() {
+                "" => None,
+                minutes => Some(
+                    minutes
+                        .parse()
+                        .ok()
+                        .filter(|m| (1..=rules::MAX_MINUTES).contains(m))?,
+                ),
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+// Saves and applies the rules, then shows them again
+fn commit(rules: &[Rule]) {
+    settings::set_string(rules::KEY, &rules::format(rules));
+    rules::apply(rules);
+    EDITOR.with(|cell| {
+        if let Some(editor) = cell.get() {
+            unsafe {
+                let _: () = msg_send![&editor.table, reloadData];
+            }
+        }
+    });
+}
+
+fn beep() {
+    extern "C" {
+        fn NSBeep();
+    }
+    unsafe { NSBeep() }
+}
+
+unsafe fn number(value: isize) -> *mut AnyObject {
+    msg_send![AnyClass::get(c"NSNumber").unwrap(), numberWithInteger: value]
+}
+
+unsafe fn string(text: &str) -> *mut AnyObject {
+    Retained::autorelease_ptr(NSString::from_str(text)).cast()
+}
+
+unsafe fn column_id(column: *mut AnyObject) -> String {
+    let identifier: *mut NSString = msg_send![column, identifier];
+    identifier
+        .as_ref()
+        .map(NSString::to_string)
+        .unwrap_or_default()
+}
+
+extern "C" fn number_of_rows(_this: *mut AnyObject, _cmd: Sel, _table: *mut AnyObject) -> isize {
+    RULES.with(|cell| cell.borrow().len() as isize)
+}
+
+extern "C" fn object_value(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    _table: *mut AnyObject,
+    column: *mut AnyObject,
+    row: isize,
+) -> *mut AnyObject {
+    RULES.with(|cell| {
+        let rules = cell.borrow();
+        let Some(rule) = rules.get(row as usize) else {
+            return std::ptr::null_mut();
+        };
+        unsafe {
+            match column_id(column).as_str() {
+                "enabled" => number(rule.enabled as isize),
+                "condition" => number(
+                    Condition::ALL
+                        .iter()
+                        .position(|(key, _)| *key == rule.condition.key())
+                        .unwrap_or(0) as isize,
+                ),
+                "argument" => string(&rule.condition.argument()),
+                "mode" => number(
+                    rule.mode
+                        .and_then(|mode| Mode::ALL.iter().position(|m| *m == mode))
+                        .map_or(0, |i| i + 1) as isize,
+                ),
+                "minutes" => string(&rule.minutes.map(|m| m.to_string()).unwrap_or_default()),
+                _ => std::ptr::null_mut(),
+            }
+        }
+    })
+}
+
+extern "C" fn set_object_value(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    _table: *mut AnyObject,
+    value: *mut AnyObject,
+    column: *mut AnyObject,
+    row: isize,
+) {
+    // Numbers and strings alike describe themselves as their text
+    let value = unsafe {
+        let description: *mut NSString = msg_send![value, description];
+        description
+            .as_ref()
+            .map(NSString::to_string)
+            .unwrap_or_default()
+    };
+    let column = unsafe { column_id(column) };
+    let edited = RULES.with(|cell| {
+        let mut rules = cell.borrow_mut();
+        let mut rule = rules.get(row as usize)?.clone();
+        edit(&mut rule, &column, &value)?;
+        // Two rules for the same trigger would fight over it
+        let name = rule.condition.name();
+        let taken = rules
+            .iter()
+            .enumerate()
+            .any(|(i, other)| i != row as usize && other.condition.name() == name);
+        if taken {
+            return None;
+        }
+        rules[row as usize] = rule;
+        Some(rules.clone())
+    });
+    match edited {
+        Some(rules) => commit(&rules),
+        None => beep(),
+    }
+}
+
+fn selected_row() -> Option<usize> {
+    EDITOR.with(|cell| {
+        let editor = cell.get()?;
+        let row: isize = unsafe { msg_send![&editor.table, selectedRow] };
+        usize::try_from(row).ok()
+    })
+}
+
+fn select_row(row: usize) {
+    EDITOR.with(|cell| {
+        if let Some(editor) = cell.get() {
+            unsafe {
+                let rows: *mut AnyObject = msg_send![
+                    AnyClass::get(c"NSIndexSet").unwrap(),
+                    indexSetWithIndex: row
+                ];
+                let _: () =
+                    msg_send![&editor.table, selectRowIndexes: rows, byExtendingSelection: false];
+            }
+        }
+    });
+}
+
+// Changes the rules by `change`, which says which row to select after, if any
+fn change_rules(change: impl FnOnce(&mut Vec<Rule>) -> Option<usize>) {
+    if settings::is_managed(rules::KEY) {
+        beep();
+        return;
+    }
+    let changed = RULES.with(|cell| {
+        let mut rules = cell.borrow_mut();
+        let select = change(&mut rules)?;
+        Some((rules.clone(), select))
+    });
+    match changed {
+        Some((rules, select)) => {
+            commit(&rules);
+            select_row(select);
+        }
+        None => beep(),
+    }
+}
+
+// A new rule is for the first condition no rule has taken yet
+extern "C" fn add_rule(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    change_rules(|rules| {
+        let condition = Condition::ALL
+            .iter()
+            .filter_map(|(key, argument)| Condition::parse(key, argument))
+            .find(|condition| rules.iter().all(|rule| rule.condition != *condition))?;
+        rules.push(Rule {
+            enabled: true,
+            condition,
+            mode: None,
+            minutes: None,
+        });
+        Some(rules.len() - 1)
+    });
+}
+
+extern "C" fn remove_rule(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    let row = selected_row();
+    change_rules(|rules| {
+        let row = row.filter(|&row| row < rules.len())?;
+        rules.remove(row);
+        Some(row.min(rules.len().saturating_sub(1)))
+    });
+}
+
+extern "C" fn move_rule_up(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    let row = selected_row();
+    change_rules(|rules| {
+        let row = row.filter(|&row| row > 0 && row < rules.len())?;
+        rules.swap(row, row - 1);
+        Some(row - 1)
+    });
+}
+
+extern "C" fn move_rule_down(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    let row = selected_row();
+    change_rules(|rules| {
+        let row = row.filter(|&row| row + 1 < rules.len())?;
+        rules.swap(row, row + 1);
+        Some(row + 1)
+    });
+}
+
+// The table's data source, and the buttons' target
+fn register_class() -> &'static AnyClass {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        let mut builder = ClassBuilder::new(c"AwakeTriggerEditor", objc2::class!(NSObject))
+            .expect("AwakeTriggerEditor class already registered");
+
+        type Fn3 = extern "C" fn(*mut AnyObject, Sel, *mut AnyObject);
+
+        unsafe {
+            builder.add_method(
+                sel!(numberOfRowsInTableView:),
+                number_of_rows as extern "C" fn(_, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(tableView:objectValueForTableColumn:row:),
+                object_value as extern "C" fn(_, _, _, _, _) -> _,
+            );
+            builder.add_method(
+                sel!(tableView:setObjectValue:forTableColumn:row:),
+                set_object_value as extern "C" fn(_, _, _, _, _, _),
+            );
+            builder.add_method(sel!(addRule:), add_rule as Fn3);
+            builder.add_method(sel!(removeRule:), remove_rule as Fn3);
+            builder.add_method(sel!(moveRuleUp:), move_rule_up as Fn3);
+            builder.add_method(sel!(moveRuleDown:), move_rule_down as Fn3);
+        }
+        builder.register();
+    });
+    AnyClass::get(c"AwakeTriggerEditor").unwrap()
+}
+
+unsafe fn pop_up_cell(titles: &[&str]) -> Retained<AnyObject> {
+    let cell: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSPopUpButtonCell").unwrap(), alloc];
+    let cell: Retained<AnyObject> =
+        msg_send![cell, initTextCell: &*NSString::from_str(""), pullsDown: false];
+    for title in titles {
+        let _: () = msg_send![&cell, addItemWithTitle: &*NSString::from_str(title)];
+    }
+    let _: () = msg_send![&cell, setBordered: false];
+    cell
+}
+
+unsafe fn switch_cell() -> Retained<AnyObject> {
+    let cell: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSButtonCell").unwrap(), alloc];
+    let cell: Retained<AnyObject> = msg_send![cell, initTextCell: &*NSString::from_str("")];
+    let _: () = msg_send![&cell, setButtonType: BUTTON_TYPE_SWITCH];
+    cell
+}
+
+fn create() -> Editor {
+    unsafe {
+        let width = COLUMNS.iter().map(|(_, _, width)| width + 8.0).sum::<f64>() + 24.0;
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(width, 320.0));
+
+        let window: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSWindow").unwrap(), alloc];
+        let window: Retained<AnyObject> = msg_send![
+            window,
+            initWithContentRect: frame,
+            styleMask: STYLE_MASK,
+            backing: BACKING_BUFFERED,
+            defer: false
+        ];
+        // Keep the window alive across close/reopen; we hold the only reference
+        let _: () = msg_send![&window, setReleasedWhenClosed: false];
+        let _: () = msg_send![&window, setTitle: &*NSString::from_str("Triggers")];
+
+        // Never released: the table and buttons only hold it weakly
+        let source: *mut AnyObject = msg_send![register_class(), new];
+
+        let content: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSView").unwrap(), alloc];
+        let content: Retained<AnyObject> = msg_send![content, initWithFrame: frame];
+
+        let table_frame = NSRect::new(
+            NSPoint::new(0.0, BUTTON_BAR_HEIGHT),
+            NSSize::new(frame.size.width, frame.size.height - BUTTON_BAR_HEIGHT),
+        );
+        let scroll: Allocated<AnyObject> =
+            msg_send![AnyClass::get(c"NSScrollView").unwrap(), alloc];
+        let scroll: Retained<AnyObject> = msg_send![scroll, initWithFrame: table_frame];
+        let _: () = msg_send![&scroll, setHasVerticalScroller: true];
+        let _: () = msg_send![&scroll, setAutoresizingMask: AUTORESIZE_FILL];
+
+        let table: Allocated<AnyObject> = msg_send![AnyClass::get(c"NSTableView").unwrap(), alloc];
+        let table: Retained<AnyObject> = msg_send![table, initWithFrame: table_frame];
+        let _: () = msg_send![&table, setUsesAlternatingRowBackgroundColors: true];
+        for (identifier, title, width) in COLUMNS {
+            let column: Allocated<AnyObject> =
+                msg_send![AnyClass::get(c"NSTableColumn").unwrap(), alloc];
+            let column: Retained<AnyObject> =
+                msg_send![column, initWithIdentifier: &*NSString::from_str(identifier)];
+            let _: () = msg_send![&column, setTitle: &*NSString::from_str(title)];
+            let _: () = msg_send![&column, setWidth: width];
+            let cell = match identifier {
+                "enabled" => Some(switch_cell()),
+                "condition" => Some(pop_up_cell(&CONDITION_TITLES)),
+                "mode" => Some(pop_up_cell(&MODE_TITLES)),
+                _ => None,
+            };
+            if let Some(cell) = cell {
+                let _: () = msg_send![&column, setDataCell: &*cell];
+            }
+            let _: () = msg_send![&table, addTableColumn: &*column];
+        }
+        let _: () = msg_send![&table, setDataSource: source];
+
+        let _: () = msg_send![&scroll, setDocumentView: &*table];
+        let _: () = msg_send![&content, addSubview: &*scroll];
+
+        let buttons = [
+            ("+", sel!(addRule:)),
+            ("\u{2212}", sel!(removeRule:)),
+            ("Move Up", sel!(moveRuleUp:)),
+            ("Move Down", sel!(moveRuleDown:)),
+        ];
+        let mut x = 12.0;
+        for (title, action) in buttons {
+            let button: *mut AnyObject = msg_send![
+                AnyClass::get(c"NSButton").unwrap(),
+                buttonWithTitle: &*NSString::from_str(title),
+                target: source,
+                action: action
+            ];
+            let _: () = msg_send![button, sizeToFit];
+            let fitted: NSRect = msg_send![button, frame];
+            let button_frame = NSRect::new(NSPoint::new(x, 8.0), fitted.size);
+            let _: () = msg_send![button, setFrame: button_frame];
+            let _: () = msg_send![button, setAutoresizingMask: AUTORESIZE_BOTTOM];
+            let _: () = msg_send![&content, addSubview: button];
+            x += fitted.size.width + 4.0;
+        }
+
+        let _: () = msg_send![&window, setContentView: &*content];
+        let _: () = msg_send![&window, center];
+
+        Editor { window, table }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cell_edits_apply_or_are_refused() {
+        let mut rule = Rule {
+            enabled: true,
+            condition: Condition::Ssh,
+            mode: None,
+            minutes: None,
+        };
+        assert_eq!(edit(&mut rule, "enabled", "0"), Some(()));
+        assert!(!rule.enabled);
+        // The port column's pop-up index, starting with its example port
+        assert_eq!(edit(&mut rule, "condition", "2"), Some(()));
+        assert_eq!(rule.condition, Condition::Port(vec![3000]));
+        assert_eq!(edit(&mut rule, "argument", "8080, 5900"), Some(()));
+        assert_eq!(rule.condition, Condition::Port(vec![8080, 5900]));
+        assert_eq!(edit(&mut rule, "argument", "http"), None);
+        assert_eq!(edit(&mut rule, "mode", "4"), Some(()));
+        assert_eq!(rule.mode, Some(Mode::Server));
+        assert_eq!(edit(&mut rule, "minutes", "90"), Some(()));
+        assert_eq!(rule.minutes, Some(90));
+        assert_eq!(edit(&mut rule, "minutes", "soon"), None);
+        assert_eq!(edit(&mut rule, "minutes", "10081"), None);
+        assert_eq!(edit(&mut rule, "minutes", ""), Some(()));
+        assert_eq!(rule.minutes, None);
+    }
+}
@@ -0,0 +1,80 @@
+//! Workspace notifications that may change what triggers find — the Mac
+//! waking, an app launching or quitting, the displays changing or waking —
+//! passed to the trigger scheduler so its checks look again at once rather
+//! than at their next turn.
+
+use std::ffi::c_void;
+
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+use objc2::{msg_send, sel};
+use objc2_foundation::NSString;
+
+use crate::triggers::poll::{self, Event};
+
+// `kCGDisplayBeginConfigurationFlag`: a change is about to happen; it's
+// reported again once done
+const BEGIN_CONFIGURATION: u32 = 1 << 0;
+
+type ReconfigurationCallback = extern "C" fn(u32, u32, *mut c_void);
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGDisplayRegisterReconfigurationCallback(
+        callback: ReconfigurationCallback,
+        context: *mut c_void,
+    ) -> i32;
+}
+
+extern "C" fn displays_changed(_display: u32, flags: u32, _context: *mut c_void) {
+    if flags & BEGIN_CONFIGURATION == 0 {
+        poll::notify(Event::DisplaysChanged);
+    }
+}
+
+pub unsafe fn observe(delegate: *mut AnyObject) {
+    let Some(workspace) = AnyClass::get(c"NSWorkspace") else {
+        return;
+    };
+    let workspace: *mut AnyObject = msg_send![workspace, sharedWorkspace];
+    let center: *mut AnyObject = msg_send![workspace, notificationCenter];
+    let observed = [
+        ("NSWorkspaceDidWakeNotification", sel!(triggersWoke:)),
+        (
+            "NSWorkspaceScreensDidWakeNotification",
+            sel!(triggersDisplaysWoke:),
+        ),
+        (
+            "NSWorkspaceDidLaunchApplicationNotification",
+            sel!(triggersAppsChanged:),
+        ),
+        (
+            "NSWorkspaceDidTerminateApplicationNotification",
+            sel!(triggersAppsChanged:),
+        ),
+    ];
+    for (name, selector) in observed {
+        let name = NSString::from_str(name);
+        let _: () = msg_send![
+            center,
+            addObserver: delegate,
+            selector: selector,
+            name: &*name,
+            object: std::ptr::null::<AnyObject>()
+        ];
+    }
+    CGDisplayRegisterReconfigurationCallback(displays_changed, std::ptr::null_mut());
+}
+
+pub extern "C" fn woke(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    poll::notify(Event::Woke);
+    super::kiosk::woke();
+}
+
+pub extern "C" fn displays_woke(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    crate::triggers::workday::displays_woke();
+    poll::notify(Event::DisplaysWoke);
+}
+
+pub extern "C" fn apps_changed(_this: *mut AnyObject, _cmd: Sel, _notification: *mut AnyObject) {
+    poll::notify(Event::AppsChanged);
+}
@@ -0,0 +1,94 @@
+//! **Uninstall Awake…**: after a confirmation, everything Awake has left on
+//! the Mac is removed — the LaunchAgent, the preferences, the Application
+//! Support folder (event log, status, resume and hand-off files, hooks, the
+//! remote's token, crash reports) and the copy of the status in the widget's
+//! app group — and then Awake quits, leaving only the app to be binned.
+//!
+//! Assertions are released first, and without the hand-off or resume a quit
+//! would arrange, so nothing is recreated on the way out. The iCloud Drive
+//! copy of the settings is left, as other Macs may still use it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use objc2::runtime::AnyObject;
+use objc2::{msg_send, MainThreadMarker};
+use objc2_app_kit::NSApplication;
+
+use super::alert::{self, Style};
+use super::{bundle, launch_agent};
+use crate::settings;
+
+// The widget's copy of `status.json`; only the file is ours, not the group
+fn group_status() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        Path::new(&home)
+            .join("Library/Group Containers")
+            .join(crate::status_file::app_group())
+            .join("status.json"),
+    )
+}
+
+// Gone already counts as removed
+fn removed(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Removes what it can; returns what couldn't be, as lines for the user
+fn remove_everything() -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Err(e) = launch_agent::set_launch_at_login(false) {
+        failures.push(format!("The login item: {}", e));
+    }
+    settings::remove_all(bundle::identifier());
+    let files = [
+        crate::app_support_dir().map(|dir| (dir, true)),
+        group_status().map(|path| (path, false)),
+    ];
+    for (path, is_dir) in files.into_iter().flatten() {
+        let result = if is_dir {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        if let Err(e) = removed(result) {
+            failures.push(format!("{}: {}", path.display(), e));
+        }
+    }
+    failures
+}
+
+pub fn uninstall(mtm: MainThreadMarker) {
+    let response = alert::ask_with(
+        "Uninstall Awake?",
+        "This removes Awake's login item, preferences, event log, hooks and every other \
+         file it keeps, then quits. Afterwards, move Awake to the Bin to finish. \
+         Settings synced to iCloud Drive are kept.",
+        &["Uninstall", "Cancel"],
+        Style::Critical,
+        None,
+        mtm,
+    );
+    if response != Some(0) {
+        return;
+    }
+    super::clean_up_for_good();
+    let failures = remove_everything();
+    if !failures.is_empty() {
+        alert::ask(
+            "Some of Awake's files couldn't be removed",
+            &failures.join("\n"),
+            &["Quit"],
+            mtm,
+        );
+    }
+    unsafe {
+        let app = NSApplication::sharedApplication(mtm);
+        let _: () = msg_send![&app, terminate: std::ptr::null::<AnyObject>()];
+    }
+}
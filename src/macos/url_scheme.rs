@@ -0,0 +1,96 @@
+//! `awake://` links, for a widget, a Shortcut or a web page to act on the
+//! running app: `awake://toggle`, `awake://off`, or `awake://for/60` for an
+//! hour's timer. What follows the scheme is an `action`, the same argument the
+//! launcher items in `awake status --alfred` carry, so `awake://for:60` works
+//! too.
+//!
+//! The scheme is declared in the bundle's `Info.plist`, and only the schemes
+//! declared there are answered (see `bundle`); Launch Services delivers each
+//! link as a `kAEGetURL` Apple event.
+
+use objc2::runtime::{AnyClass, AnyObject, Sel};
+use objc2::{msg_send, sel};
+use objc2_foundation::NSString;
+
+use crate::action;
+use crate::state::Command;
+
+// `kInternetEventClass` and `kAEGetURL`, both 'GURL'
+const GET_URL: u32 = u32::from_be_bytes(*b"GURL");
+// `keyDirectObject`
+const DIRECT_OBJECT: u32 = u32::from_be_bytes(*b"----");
+
+/// The command a link stands for, if it uses one of `schemes`
+fn parse(url: &str, schemes: &[String]) -> Option<Command> {
+    let (scheme, rest) = url.split_once(':')?;
+    schemes
+        .iter()
+        .any(|ours| ours.eq_ignore_ascii_case(scheme))
+        .then(|| action::parse(rest))?
+}
+
+/// Has `delegate`'s `handleURL:withReplyEvent:` called for each link opened
+pub unsafe fn observe(delegate: *mut AnyObject) {
+    let Some(manager) = AnyClass::get(c"NSAppleEventManager") else {
+        return;
+    };
+    let manager: *mut AnyObject = msg_send![manager, sharedAppleEventManager];
+    let _: () = msg_send![
+        manager,
+        setEventHandler: delegate,
+        andSelector: sel!(handleURL:withReplyEvent:),
+        forEventClass: GET_URL,
+        andEventID: GET_URL
+    ];
+}
+
+pub extern "C" fn handle_url(
+    _this: *mut AnyObject,
+    _cmd: Sel,
+    event: *mut AnyObject,
+    _reply: *mut AnyObject,
+) {
+    let url = unsafe {
+        let descriptor: *mut AnyObject = msg_send![event, paramDescriptorForKeyword: DIRECT_OBJECT];
+        if descriptor.is_null() {
+            return;
+        }
+        let url: *mut NSString = msg_send![descriptor, stringValue];
+        match url.as_ref() {
+            Some(url) => url.to_string(),
+            None => return,
+        }
+    };
+    match parse(&url, super::bundle::url_schemes()) {
+        // Nobody at a kiosk should be able to switch it off with a link
+        Some(_) if super::kiosk::is_enabled() => {}
+        Some(command) => super::long_session::handle(command),
+        None => eprintln!("Awake: unknown link {}", url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_our_links_are_followed() {
+        let ours = ["awake".to_string()];
+        assert!(matches!(
+            parse("awake://toggle", &ours),
+            Some(Command::Toggle)
+        ));
+        assert!(matches!(
+            parse("awake:for:15", &ours),
+            Some(Command::ActivateFor { minutes: 15 })
+        ));
+        assert!(parse("https://toggle", &ours).is_none());
+        // A fork declaring its own scheme doesn't answer the official one
+        let fork = ["awake-dev".to_string()];
+        assert!(parse("awake://toggle", &fork).is_none());
+        assert!(matches!(
+            parse("Awake-Dev://off", &fork),
+            Some(Command::Deactivate)
+        ));
+    }
+}
@@ -1,78 +1,73 @@
-//! Awake - Ultra-lightweight macOS menu bar app to prevent sleep
-//! Uses IOKit power assertions directly (no child processes)
+//! Awake - Ultra-lightweight menu bar / tray app to prevent sleep
+//! Uses the OS power APIs directly (IOKit assertions on macOS, power requests on
+//! Windows, D-Bus inhibitors on Linux); no child processes
+// No console window for the tray app; `selftest` re-attaches to the parent's
+#![cfg_attr(windows, windows_subsystem = "windows")]
+
+mod action;
+mod arch;
+mod battery;
+#[cfg(target_os = "macos")]
+mod bonjour;
+mod change;
+#[cfg(target_os = "macos")]
+mod clock;
+mod completions;
+mod crash;
+mod duration;
+mod error;
+mod event_log;
+mod hooks;
+mod keepawake;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+mod metrics;
+mod mode;
+#[cfg(target_os = "macos")]
+mod process;
+#[cfg(target_os = "macos")]
+mod quiet_hours;
+mod remote;
+mod selftest;
+#[cfg(target_os = "macos")]
+mod settings;
+#[cfg(target_os = "macos")]
+mod solar;
+mod state;
+mod stats;
+mod status_command;
+mod status_file;
+mod timer;
+mod triggers;
+mod watch;
+mod webhooks;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use linux as frontend;
+#[cfg(target_os = "macos")]
+use macos as frontend;
+#[cfg(windows)]
+use windows as frontend;
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+compile_error!("Awake supports macOS, Windows and Linux");
 
-use objc2::rc::Retained;
-use objc2::runtime::{AnyClass, AnyObject, ClassBuilder, Sel};
-use objc2::{msg_send, sel, ClassType, MainThreadMarker, MainThreadOnly};
-use objc2_app_kit::{
-    NSApplication, NSApplicationActivationPolicy, NSImage, NSMenu, NSMenuItem, NSStatusBar,
-};
-use objc2_foundation::NSString;
-
-use core_foundation::base::TCFType;
-use core_foundation::string::CFString;
 use std::env;
-use std::fs;
-use std::os::unix::fs::PermissionsExt;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
-use std::sync::{Arc, Condvar, Mutex};
-use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
-// Grand Central Dispatch — dispatch to main thread for AppKit safety
-// `_dispatch_main_q` is the actual symbol behind the dispatch_get_main_queue() macro.
-extern "C" {
-    static _dispatch_main_q: std::ffi::c_void;
-    fn dispatch_async_f(
-        queue: *const std::ffi::c_void,
-        context: *mut std::ffi::c_void,
-        work: extern "C" fn(*mut std::ffi::c_void),
-    );
-}
-
-// IOKit power management bindings
-#[link(name = "IOKit", kind = "framework")]
-extern "C" {
-    fn IOPMAssertionCreateWithName(
-        assertion_type: core_foundation::string::CFStringRef,
-        level: u32,
-        name: core_foundation::string::CFStringRef,
-        assertion_id: *mut u32,
-    ) -> i32;
-    fn IOPMAssertionRelease(assertion_id: u32) -> i32;
-}
-
-const IOPM_ASSERTION_LEVEL_ON: u32 = 255;
-const LAUNCH_AGENT_LABEL: &str = "io.tmss.awake";
-
-// Sleep prevention modes
-const MODE_DISPLAY: u8 = 0;
-const MODE_SYSTEM: u8 = 1;
-const MODE_BOTH: u8 = 2;
-
-// Global state
-static ASSERTION_ID: AtomicU32 = AtomicU32::new(0);
-static ASSERTION_ID_2: AtomicU32 = AtomicU32::new(0);
-static TIMER_EXPIRY: AtomicU64 = AtomicU64::new(0);
-type TimerCancel = Arc<(Mutex<bool>, Condvar)>;
-static TIMER_CANCEL: Mutex<Option<TimerCancel>> = Mutex::new(None);
-static TIMER_THREAD: Mutex<Option<thread::JoinHandle<()>>> = Mutex::new(None);
-static CURRENT_MODE: AtomicU8 = AtomicU8::new(MODE_BOTH);
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// Wrapper for raw pointers to ObjC objects so they can be in statics
-struct RawId(*mut AnyObject);
-unsafe impl Send for RawId {}
-unsafe impl Sync for RawId {}
+use keepawake::KindSet;
+use mode::Mode;
 
-static STATUS_ITEM: Mutex<RawId> = Mutex::new(RawId(std::ptr::null_mut()));
-static STATUS_MENU: Mutex<RawId> = Mutex::new(RawId(std::ptr::null_mut()));
-static LOGIN_ITEM: Mutex<RawId> = Mutex::new(RawId(std::ptr::null_mut()));
-static MODE_ITEMS: Mutex<[RawId; 3]> = Mutex::new([
-    RawId(std::ptr::null_mut()),
-    RawId(std::ptr::null_mut()),
-    RawId(std::ptr::null_mut()),
-]);
+const DEFAULT_REMINDER_HOURS: u64 = 6;
+// The longest maximum session length and reminder interval: a week
+const MAX_SESSION_MINUTES: u64 = 7 * 24 * 60;
+const MAX_REMINDER_HOURS: u64 = 7 * 24;
 
 fn now_secs() -> u64 {
     SystemTime::now()
@@ -81,583 +76,391 @@ fn now_secs() -> u64 {
         .unwrap_or(0)
 }
 
-fn is_awake() -> bool {
-    ASSERTION_ID.load(Ordering::Acquire) != 0
+#[cfg(all(target_os = "macos", not(test)))]
+fn app_support_dir() -> Option<PathBuf> {
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join("Library/Application Support/Awake"))
 }
 
-fn create_assertion(assertion_type: &str) -> u32 {
-    let atype = CFString::new(assertion_type);
-    let aname = CFString::new("Awake App");
-    let mut aid: u32 = 0;
-
-    let result = unsafe {
-        IOPMAssertionCreateWithName(
-            atype.as_concrete_TypeRef(),
-            IOPM_ASSERTION_LEVEL_ON,
-            aname.as_concrete_TypeRef(),
-            &mut aid,
-        )
-    };
-
-    if result == 0 {
-        aid
-    } else {
-        eprintln!(
-            "IOPMAssertionCreateWithName({}) failed: error {}",
-            assertion_type, result
-        );
-        0
+#[cfg(all(target_os = "linux", not(test)))]
+fn app_support_dir() -> Option<PathBuf> {
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(data_home).join("awake"));
     }
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".local/share/awake"))
 }
 
-fn release_assertion(id: &AtomicU32) {
-    let aid = id.swap(0, Ordering::AcqRel);
-    if aid != 0 {
-        let result = unsafe { IOPMAssertionRelease(aid) };
-        if result != 0 {
-            eprintln!("IOPMAssertionRelease failed: error {}", result);
-        }
-    }
+#[cfg(all(windows, not(test)))]
+fn app_support_dir() -> Option<PathBuf> {
+    let app_data = env::var("APPDATA").ok()?;
+    Some(PathBuf::from(app_data).join("Awake"))
 }
 
-fn activate() {
-    if is_awake() {
-        return;
-    }
-
-    let mode = CURRENT_MODE.load(Ordering::Relaxed);
-
-    match mode {
-        MODE_DISPLAY => {
-            let aid = create_assertion("PreventUserIdleDisplaySleep");
-            if aid != 0 {
-                ASSERTION_ID.store(aid, Ordering::Release);
-            }
-        }
-        MODE_SYSTEM => {
-            let aid = create_assertion("PreventUserIdleSystemSleep");
-            if aid != 0 {
-                ASSERTION_ID.store(aid, Ordering::Release);
-            }
-        }
-        _ => {
-            let aid1 = create_assertion("PreventUserIdleDisplaySleep");
-            let aid2 = create_assertion("PreventUserIdleSystemSleep");
-            if aid1 != 0 && aid2 != 0 {
-                ASSERTION_ID.store(aid1, Ordering::Release);
-                ASSERTION_ID_2.store(aid2, Ordering::Release);
-            } else {
-                // Roll back on partial failure
-                if aid1 != 0 {
-                    unsafe { IOPMAssertionRelease(aid1) };
-                }
-                if aid2 != 0 {
-                    unsafe { IOPMAssertionRelease(aid2) };
-                }
-                eprintln!(
-                    "Failed to create both IOKit assertions (display={}, system={})",
-                    aid1, aid2
-                );
-            }
-        }
-    }
-
-    if is_awake() {
-        update_icon("cup.and.saucer.fill");
-    }
+// Tests log and keep their files somewhere of their own
+#[cfg(test)]
+fn app_support_dir() -> Option<PathBuf> {
+    Some(env::temp_dir().join(format!("awake-tests-{}", std::process::id())))
 }
 
-fn deactivate() {
-    TIMER_EXPIRY.store(0, Ordering::Release);
-    cancel_timer();
-    release_assertion(&ASSERTION_ID);
-    release_assertion(&ASSERTION_ID_2);
-    update_icon("moon.zzz.fill");
+// Preferences that can also be given on the command line
+// (`defaults write io.tmss.awake LockFile /tmp/awake.lock`)
+#[cfg(target_os = "macos")]
+fn setting(key: &str) -> Option<String> {
+    settings::get_string(key).filter(|value| !value.is_empty())
 }
 
-fn toggle() {
-    if is_awake() {
-        deactivate();
-    } else {
-        activate();
-    }
+#[cfg(not(target_os = "macos"))]
+fn setting(_key: &str) -> Option<String> {
+    None
 }
 
-fn set_mode(mode: u8) {
-    let was_awake = is_awake();
-    if was_awake {
-        deactivate();
+// `flag`'s number, which must be in `range`, else the preference `key`'s.
+// A stored one can't be fixed from the menu bar, so rather than stopping the
+// app, one out of range is logged and brought into it, and one that isn't a
+// number is logged and ignored.
+fn number(flag: Option<&String>, key: &str, range: RangeInclusive<u64>) -> Option<u64> {
+    if let Some(flag) = flag {
+        let number = flag.trim().parse().ok().filter(|n| range.contains(n));
+        return Some(number.unwrap_or_else(|| usage()));
     }
-
-    CURRENT_MODE.store(mode, Ordering::Relaxed);
-    update_mode_menu_state();
-
-    if was_awake {
-        activate();
+    let text = setting(key)?;
+    let number = text.trim().parse::<u64>().ok();
+    let clamped = number.map(|n| n.clamp(*range.start(), *range.end()));
+    if number.is_none() || clamped != number {
+        event_log::log(event_log::Event::BadSetting { key, value: &text });
     }
+    clamped
 }
 
-fn update_mode_menu_state() {
-    let current = CURRENT_MODE.load(Ordering::Relaxed);
-    let items = MODE_ITEMS.lock().unwrap();
-    for (i, item) in items.iter().enumerate() {
-        if !item.0.is_null() {
-            let state: isize = if i as u8 == current { 1 } else { 0 };
-            unsafe {
-                let _: () = msg_send![item.0, setState: state];
-            }
-        }
-    }
+#[cfg(target_os = "macos")]
+fn setting_bool(key: &str) -> bool {
+    settings::get_bool(key)
 }
 
-fn cancel_timer() {
-    if let Some(cancel) = TIMER_CANCEL.lock().unwrap().take() {
-        let (lock, cvar) = &*cancel;
-        *lock.lock().unwrap() = true;
-        cvar.notify_one();
-    }
-    // Take and drop the old handle (don't join — thread will exit promptly via condvar)
-    TIMER_THREAD.lock().unwrap().take();
-}
-
-fn activate_for_duration(minutes: u64) {
-    deactivate();
-    activate();
-
-    if !is_awake() {
-        return;
-    }
-
-    cancel_timer();
-
-    let expiry = now_secs() + (minutes * 60);
-    TIMER_EXPIRY.store(expiry, Ordering::Release);
-
-    let cancel_pair = Arc::new((Mutex::new(false), Condvar::new()));
-    *TIMER_CANCEL.lock().unwrap() = Some(Arc::clone(&cancel_pair));
-
-    let handle = thread::spawn(move || {
-        let (lock, cvar) = &*cancel_pair;
-        let duration = Duration::from_secs(minutes * 60);
-        let guard = lock.lock().unwrap();
-        // Single wait for the full duration — wakes only on cancel or expiry
-        let (guard, _timeout) = cvar.wait_timeout(guard, duration).unwrap();
-        if *guard {
-            return; // Cancelled
-        }
-        drop(guard);
-        if TIMER_EXPIRY.load(Ordering::Acquire) == expiry {
-            // Must dispatch to main thread — deactivate() touches AppKit UI objects
-            extern "C" fn deactivate_on_main(_ctx: *mut std::ffi::c_void) {
-                deactivate();
-            }
-            unsafe {
-                dispatch_async_f(&_dispatch_main_q, std::ptr::null_mut(), deactivate_on_main);
-            }
-        }
-    });
-
-    *TIMER_THREAD.lock().unwrap() = Some(handle);
+#[cfg(not(target_os = "macos"))]
+fn setting_bool(_key: &str) -> bool {
+    false
 }
 
-fn update_icon(symbol_name: &str) {
-    let guard = STATUS_ITEM.lock().unwrap();
-    let si = guard.0;
-    if !si.is_null() {
-        unsafe {
-            let button: *mut AnyObject = msg_send![si, button];
-            if !button.is_null() {
-                let name = NSString::from_str(symbol_name);
-                let desc: Option<&NSString> = None;
-                let img: Option<Retained<NSImage>> = msg_send![NSImage::class(), imageWithSystemSymbolName: &*name, accessibilityDescription: desc];
-                if let Some(img) = img {
-                    let _: () = msg_send![&*img, setTemplate: true];
-                    let _: () = msg_send![button, setImage: &*img];
-                }
-            }
-        }
+// Kiosk mode, from `--kiosk` or `KioskMode`
+#[cfg(target_os = "macos")]
+fn kiosk_mode(flag: bool) -> bool {
+    if flag {
+        macos::enter_kiosk_mode();
     }
+    macos::is_kiosk()
 }
 
-// Launch at login
-fn xml_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&apos;")
-}
-
-fn launch_agent_path() -> Option<PathBuf> {
-    let home = env::var("HOME").ok()?;
-    Some(
-        PathBuf::from(home)
-            .join("Library/LaunchAgents")
-            .join(format!("{}.plist", LAUNCH_AGENT_LABEL)),
-    )
+#[cfg(not(target_os = "macos"))]
+fn kiosk_mode(flag: bool) -> bool {
+    flag
 }
 
-fn is_launch_at_login() -> bool {
-    launch_agent_path().is_some_and(|p| p.exists())
+// Holding ⌥ while Awake launches asks for safe mode
+#[cfg(target_os = "macos")]
+fn safe_mode_key_held() -> bool {
+    macos::option_key_held()
 }
 
-fn get_app_path() -> String {
-    env::current_exe()
-        .ok()
-        .and_then(|p| p.to_str().map(String::from))
-        .unwrap_or_default()
+#[cfg(not(target_os = "macos"))]
+fn safe_mode_key_held() -> bool {
+    false
 }
 
-fn set_launch_at_login(enable: bool) {
-    let Some(path) = launch_agent_path() else {
-        eprintln!("HOME not set; cannot manage launch agent");
-        return;
+fn usage() -> ! {
+    eprintln!(
+        "usage: awake [--dry-run] [--safe-mode] [--kiosk] [--mode display|system|both|server] [--watch <path>] \
+         [--while-exists <path>] [--trigger-policy edge|override[:<minutes>]] \
+         [--trigger-mode <trigger>=<mode>[,...]] \
+         [--max-session <minutes>] [--overnight <percent>] [--display-grace] \
+         [--battery-timer] [--timer-precision minutes|seconds|rounded] \
+         [--remind-after <hours>] [--while-ssh] \
+         [--while-port <port>[,<port>...]] [--while-containers] \
+         [--while-building] [--while-mirroring] \
+         [--while-wireless-audio] [--workday <hh:mm>-<hh:mm>] \
+         [--between <time>-<time>] [--location <lat>,<long>] [--follow <host>[:<port>]] [--remote <port>] \
+         [--webhook <url>] [--crash-reports]"
+    );
+    eprintln!("       awake selftest [--dry-run]");
+    eprintln!("       awake status [--quiet|--porcelain|--prompt|--arch|--alfred|--raycast]");
+    eprintln!(
+        "       awake --host <host>[:<port>] [--token <token>] on [--minutes <n>]|off|toggle|status"
+    );
+    eprintln!("       awake hosts");
+    eprintln!("       awake completions bash|zsh|fish");
+    std::process::exit(2);
+}
+
+// The value following `flag`, if the flag was given
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    Some(args.get(i + 1).unwrap_or_else(|| usage()))
+}
+
+/// Starts the triggers asked for on the command line, in settings and in rules
+fn start_triggers(args: &[String]) {
+    let value = |flag: &str| flag_value(args, flag);
+    // Each trigger's own mode, set before it first reports
+    let modes_list = value("--trigger-mode")
+        .cloned()
+        .or_else(|| setting("TriggerModes"));
+    let modes: Vec<(&str, KindSet)> = match &modes_list {
+        Some(list) => triggers::parse_modes(list)
+            .unwrap_or_else(|| usage())
+            .into_iter()
+            .map(|(key, mode)| {
+                let mode = Mode::parse(mode).unwrap_or_else(|| usage());
+                (key, mode.kinds())
+            })
+            .collect(),
+        None => Vec::new(),
     };
-
-    if enable {
-        let app_path = get_app_path();
-        if app_path.is_empty() {
-            return;
-        }
-
-        if let Some(parent) = path.parent() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                eprintln!("Failed to create LaunchAgents directory: {}", e);
-                return;
-            }
-        }
-
-        let plist = format!(
-            r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-<dict>
-    <key>Label</key>
-    <string>{}</string>
-    <key>ProgramArguments</key>
-    <array>
-        <string>{}</string>
-    </array>
-    <key>RunAtLoad</key>
-    <true/>
-</dict>
-</plist>
-"#,
-            LAUNCH_AGENT_LABEL,
-            xml_escape(&app_path)
-        );
-
-        if let Err(e) = fs::write(&path, plist) {
-            eprintln!("Failed to write LaunchAgent plist: {}", e);
-            return;
-        }
-        if let Err(e) = fs::set_permissions(&path, fs::Permissions::from_mode(0o644)) {
-            eprintln!("Failed to set plist permissions: {}", e);
-        }
-    } else if let Err(e) = fs::remove_file(&path) {
-        if e.kind() != std::io::ErrorKind::NotFound {
-            eprintln!("Failed to remove LaunchAgent plist: {}", e);
+    let trigger_mode = |key: &str, name: &str| {
+        if let Some(&(_, kinds)) = modes.iter().find(|(k, _)| *k == key) {
+            start(state::Command::SetTriggerMode {
+                name: name.into(),
+                kinds: Some(kinds),
+            });
         }
+    };
+    if let Some(path) = value("--while-exists")
+        .cloned()
+        .or_else(|| setting("LockFile"))
+    {
+        let name = format!("{} exists", path);
+        trigger_mode("lockfile", &name);
+        triggers::path_exists::spawn(name, path.into());
     }
-
-    update_login_item_state();
-}
-
-fn toggle_launch_at_login() {
-    set_launch_at_login(!is_launch_at_login());
-}
-
-fn update_login_item_state() {
-    let guard = LOGIN_ITEM.lock().unwrap();
-    let item = guard.0;
-    if !item.is_null() {
-        let state: isize = if is_launch_at_login() { 1 } else { 0 };
-        unsafe {
-            let _: () = msg_send![item, setState: state];
-        }
+    #[cfg(unix)]
+    if args.iter().any(|arg| arg == "--while-ssh") || setting_bool("WhileSSHConnected") {
+        trigger_mode("ssh", triggers::ssh::NAME);
+        triggers::ssh::spawn();
     }
-}
-
-// Action handlers
-extern "C" fn toggle_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    toggle();
-}
-
-extern "C" fn login_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    toggle_launch_at_login();
-}
-
-extern "C" fn timer_15_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    activate_for_duration(15);
-}
-
-extern "C" fn timer_30_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    activate_for_duration(30);
-}
-
-extern "C" fn timer_60_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    activate_for_duration(60);
-}
-
-extern "C" fn timer_120_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    activate_for_duration(120);
-}
-
-extern "C" fn mode_display_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    set_mode(MODE_DISPLAY);
-}
-
-extern "C" fn mode_system_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    set_mode(MODE_SYSTEM);
-}
-
-extern "C" fn mode_both_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    set_mode(MODE_BOTH);
-}
-
-extern "C" fn button_clicked(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    unsafe {
-        let mtm = MainThreadMarker::new_unchecked();
-        let app = NSApplication::sharedApplication(mtm);
-        let event: *mut AnyObject = msg_send![&app, currentEvent];
-        if !event.is_null() {
-            let event_type: u64 = msg_send![event, type];
-            let modifier_flags: u64 = msg_send![event, modifierFlags];
-            // Right mouse down (3) or right mouse up (4), or control+left click
-            let is_right_click =
-                event_type == 3 || event_type == 4 || (modifier_flags & 0x40000) != 0;
-            if is_right_click {
-                let status_item_ptr = STATUS_ITEM.lock().unwrap().0;
-                let menu_ptr = STATUS_MENU.lock().unwrap().0;
-                if !status_item_ptr.is_null() && !menu_ptr.is_null() {
-                    let _: () = msg_send![status_item_ptr, setMenu: menu_ptr];
-                    let button: *mut AnyObject = msg_send![status_item_ptr, button];
-                    let _: () = msg_send![button, performClick: std::ptr::null::<AnyObject>()];
-                    let _: () = msg_send![status_item_ptr, setMenu: std::ptr::null::<AnyObject>()];
-                }
-                return;
-            }
-        }
+    #[cfg(unix)]
+    if let Some(ports) = value("--while-port")
+        .cloned()
+        .or_else(|| setting("WhilePortConnected"))
+    {
+        let ports = triggers::port::parse_ports(&ports).unwrap_or_else(|| usage());
+        trigger_mode("port", &triggers::port::name(&ports));
+        triggers::port::spawn(ports);
     }
-    toggle();
-}
-
-extern "C" fn quit_action(_this: *mut AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
-    deactivate();
-    // deactivate() calls cancel_timer(), so the thread is already signalled.
-    // Join it to ensure clean shutdown before terminating the app.
-    if let Some(handle) = TIMER_THREAD.lock().unwrap().take() {
-        let _ = handle.join();
-    }
-    unsafe {
-        let mtm = MainThreadMarker::new_unchecked();
-        let app = NSApplication::sharedApplication(mtm);
-        let _: () = msg_send![&app, terminate: std::ptr::null::<AnyObject>()];
+    #[cfg(unix)]
+    if args.iter().any(|arg| arg == "--while-containers") || setting_bool("WhileContainersRunning")
+    {
+        trigger_mode("containers", triggers::containers::NAME);
+        triggers::containers::spawn();
     }
-}
-
-fn register_delegate_class() -> &'static AnyClass {
-    static REGISTER: std::sync::Once = std::sync::Once::new();
-    let mut cls_ptr: Option<&'static AnyClass> = None;
-
-    REGISTER.call_once(|| {
-        let superclass = objc2::class!(NSObject);
-        let mut builder = ClassBuilder::new(c"AwakeDelegate", superclass)
-            .expect("AwakeDelegate class already registered");
-
-        type Fn3 = extern "C" fn(*mut AnyObject, Sel, *mut AnyObject);
-
-        unsafe {
-            builder.add_method(sel!(toggle:), toggle_action as Fn3);
-            builder.add_method(sel!(toggleLogin:), login_action as Fn3);
-            builder.add_method(sel!(timer15:), timer_15_action as Fn3);
-            builder.add_method(sel!(timer30:), timer_30_action as Fn3);
-            builder.add_method(sel!(timer60:), timer_60_action as Fn3);
-            builder.add_method(sel!(timer120:), timer_120_action as Fn3);
-            builder.add_method(sel!(modeDisplay:), mode_display_action as Fn3);
-            builder.add_method(sel!(modeSystem:), mode_system_action as Fn3);
-            builder.add_method(sel!(modeBoth:), mode_both_action as Fn3);
-            builder.add_method(sel!(quit:), quit_action as Fn3);
-            builder.add_method(sel!(buttonClicked:), button_clicked as Fn3);
+    #[cfg(unix)]
+    if args.iter().any(|arg| arg == "--while-building") || setting_bool("WhileBuilding") {
+        trigger_mode("build", triggers::build::NAME);
+        triggers::build::spawn();
+    }
+    #[cfg(unix)]
+    if let Some(leader) = value("--follow")
+        .cloned()
+        .or_else(|| setting("FollowLeader"))
+    {
+        trigger_mode("leader", &triggers::leader::name(&leader));
+        if !triggers::leader::spawn(&leader) {
+            usage();
         }
-
-        cls_ptr = Some(builder.register());
-    });
-
-    if let Some(c) = cls_ptr {
-        c
-    } else {
-        AnyClass::get(c"AwakeDelegate").unwrap()
+    }
+    #[cfg(target_os = "macos")]
+    if args.iter().any(|arg| arg == "--while-mirroring") || setting_bool("WhileMirroring") {
+        // Presenting needs the display awake, whatever the menu's mode
+        start(state::Command::SetTriggerMode {
+            name: triggers::mirroring::NAME.into(),
+            kinds: Some(Mode::Display.kinds()),
+        });
+        trigger_mode("mirroring", triggers::mirroring::NAME);
+        triggers::mirroring::spawn();
+    }
+    #[cfg(target_os = "macos")]
+    if args.iter().any(|arg| arg == "--while-wireless-audio") || setting_bool("WhileWirelessAudio")
+    {
+        // The music needs the Mac, not the screen
+        start(state::Command::SetTriggerMode {
+            name: triggers::audio::NAME.into(),
+            kinds: Some(Mode::System.kinds()),
+        });
+        trigger_mode("audio", triggers::audio::NAME);
+        triggers::audio::spawn();
+    }
+    #[cfg(target_os = "macos")]
+    if let Some(hours) = value("--workday").cloned().or_else(|| setting("Workday")) {
+        let hours = triggers::workday::parse_hours(&hours).unwrap_or_else(|| usage());
+        trigger_mode("workday", triggers::workday::NAME);
+        triggers::workday::spawn(hours);
+    }
+    #[cfg(target_os = "macos")]
+    if let Some(window) = value("--between")
+        .cloned()
+        .or_else(|| setting("AwakeBetween"))
+    {
+        let window = solar::parse_window(&window).unwrap_or_else(|| usage());
+        trigger_mode("schedule", &triggers::schedule::name(window));
+        triggers::schedule::spawn(window);
+    }
+    // Those set up in the editor window, after any given on the command line
+    #[cfg(unix)]
+    if let Some(rules) = setting(triggers::rules::KEY) {
+        triggers::rules::apply(&triggers::rules::parse(&rules));
     }
 }
 
-fn create_menu_item(
-    title: &str,
-    action: Sel,
-    delegate: *mut AnyObject,
-    mtm: MainThreadMarker,
-) -> Retained<NSMenuItem> {
-    unsafe {
-        let title_str = NSString::from_str(title);
-        let empty = NSString::from_str("");
-        let item = NSMenuItem::initWithTitle_action_keyEquivalent(
-            NSMenuItem::alloc(mtm),
-            &title_str,
-            Some(action),
-            &empty,
-        );
-        let _: () = msg_send![&item, setTarget: delegate];
-        item
+// Applies a command-line request before the front-end starts
+fn start(cmd: state::Command) {
+    if let Err(e) = state::handle(cmd) {
+        eprintln!("Awake: {}", e);
     }
 }
 
 fn main() {
-    let mtm = MainThreadMarker::new().expect("must run on main thread");
-
-    unsafe {
-        let app = NSApplication::sharedApplication(mtm);
-        app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
-
-        let delegate_class = register_delegate_class();
-        let delegate: *mut AnyObject = msg_send![delegate_class, new];
-
-        let status_bar = NSStatusBar::systemStatusBar();
-        let status_item = status_bar.statusItemWithLength(-1.0); // NSVariableStatusItemLength
-
-        // Set initial icon
-        {
-            let button: *mut AnyObject = msg_send![&status_item, button];
-            if !button.is_null() {
-                let name = NSString::from_str("moon.zzz.fill");
-                let desc: Option<&NSString> = None;
-                let img: Option<Retained<NSImage>> = msg_send![NSImage::class(), imageWithSystemSymbolName: &*name, accessibilityDescription: desc];
-                if let Some(img) = img {
-                    let _: () = msg_send![&*img, setTemplate: true];
-                    let _: () = msg_send![button, setImage: &*img];
-                }
-            }
-        }
-
-        STATUS_ITEM.lock().unwrap().0 = Retained::as_ptr(&status_item) as *mut _;
-
-        let menu = NSMenu::new(mtm);
-
-        // Toggle
-        let toggle_item = create_menu_item("Toggle", sel!(toggle:), delegate, mtm);
-        menu.addItem(&toggle_item);
-
-        // Separator
-        let sep = NSMenuItem::separatorItem(mtm);
-        menu.addItem(&sep);
-
-        // Timer submenu
-        let timer_title = NSString::from_str("Awake For...");
-        let empty = NSString::from_str("");
-        let timer_menu_item = NSMenuItem::initWithTitle_action_keyEquivalent(
-            NSMenuItem::alloc(mtm),
-            &timer_title,
-            None,
-            &empty,
-        );
-        let timer_submenu = NSMenu::new(mtm);
-        timer_submenu.addItem(&create_menu_item(
-            "15 minutes",
-            sel!(timer15:),
-            delegate,
-            mtm,
-        ));
-        timer_submenu.addItem(&create_menu_item(
-            "30 minutes",
-            sel!(timer30:),
-            delegate,
-            mtm,
-        ));
-        timer_submenu.addItem(&create_menu_item("1 hour", sel!(timer60:), delegate, mtm));
-        timer_submenu.addItem(&create_menu_item("2 hours", sel!(timer120:), delegate, mtm));
-        timer_menu_item.setSubmenu(Some(&timer_submenu));
-        menu.addItem(&timer_menu_item);
+    // --dry-run stubs out the power backend so the UI and timers can be exercised safely
+    keepawake::init(env::args().any(|arg| arg == "--dry-run"));
+    // How precisely a time left is shown, by the app and `awake status` alike
+    if let Some(name) = flag_value(&env::args().collect::<Vec<_>>(), "--timer-precision")
+        .cloned()
+        .or_else(|| setting("TimerPrecision"))
+    {
+        duration::set_precision(duration::Precision::parse(&name).unwrap_or_else(|| usage()));
+    }
 
-        // Mode submenu
-        let mode_title = NSString::from_str("Mode");
-        let mode_menu_item = NSMenuItem::initWithTitle_action_keyEquivalent(
-            NSMenuItem::alloc(mtm),
-            &mode_title,
-            None,
-            &empty,
-        );
-        let mode_submenu = NSMenu::new(mtm);
+    if env::args().nth(1).as_deref() == Some("selftest") {
+        #[cfg(windows)]
+        windows::attach_console();
+        std::process::exit(selftest::run());
+    }
+    if env::args().nth(1).as_deref() == Some("status") {
+        #[cfg(windows)]
+        windows::attach_console();
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(status_command::run(&args));
+    }
+    if env::args().nth(1).as_deref() == Some("--host") {
+        #[cfg(windows)]
+        windows::attach_console();
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(remote::run(&args));
+    }
+    if env::args().nth(1).as_deref() == Some("hosts") {
+        #[cfg(windows)]
+        windows::attach_console();
+        std::process::exit(remote::hosts());
+    }
+    if env::args().nth(1).as_deref() == Some("completions") {
+        #[cfg(windows)]
+        windows::attach_console();
+        let args: Vec<String> = env::args().skip(2).collect();
+        std::process::exit(completions::run(&args));
+    }
 
-        let mode_display = create_menu_item("Display Only", sel!(modeDisplay:), delegate, mtm);
-        let mode_system = create_menu_item("System Only", sel!(modeSystem:), delegate, mtm);
-        let mode_both = create_menu_item("Display + System", sel!(modeBoth:), delegate, mtm);
+    // Subscribed before any command, so the file never misses a change
+    state::on_state_change(status_file::write);
+    metrics::install();
 
+    let args: Vec<String> = env::args().collect();
+    let value = |flag: &str| flag_value(&args, flag);
+    if args.iter().any(|arg| arg == "--crash-reports") || setting_bool("CrashReports") {
+        crash::install();
+    }
+    // Read before safe mode can hide the setting; a kiosk's ⌥ is ignored
+    let kiosk = kiosk_mode(args.iter().any(|arg| arg == "--kiosk"));
+    // For recovering from settings that misbehave: defaults, and no triggers
+    let safe_mode = args.iter().any(|arg| arg == "--safe-mode") || (safe_mode_key_held() && !kiosk);
+    #[cfg(target_os = "macos")]
+    if safe_mode {
+        settings::enter_safe_mode();
+    } else {
+        settings::migrate();
+    }
+    if !safe_mode {
+        hooks::install();
+        if let Some(urls) = value("--webhook")
+            .cloned()
+            .or_else(|| setting("WebhookURL"))
         {
-            let mut items = MODE_ITEMS.lock().unwrap();
-            items[MODE_DISPLAY as usize].0 = Retained::as_ptr(&mode_display) as *mut _;
-            items[MODE_SYSTEM as usize].0 = Retained::as_ptr(&mode_system) as *mut _;
-            items[MODE_BOTH as usize].0 = Retained::as_ptr(&mode_both) as *mut _;
+            webhooks::install(webhooks::parse_urls(&urls).unwrap_or_else(|| usage()));
         }
+    }
 
-        mode_submenu.addItem(&mode_display);
-        mode_submenu.addItem(&mode_system);
-        mode_submenu.addItem(&mode_both);
-
-        mode_menu_item.setSubmenu(Some(&mode_submenu));
-        menu.addItem(&mode_menu_item);
-        update_mode_menu_state();
-
-        // Separator
-        let sep2 = NSMenuItem::separatorItem(mtm);
-        menu.addItem(&sep2);
-
-        // Launch at Login
-        let login_item = create_menu_item("Launch at Login", sel!(toggleLogin:), delegate, mtm);
-        LOGIN_ITEM.lock().unwrap().0 = Retained::as_ptr(&login_item) as *mut _;
-        menu.addItem(&login_item);
-        update_login_item_state();
-
-        // Separator
-        let sep3 = NSMenuItem::separatorItem(mtm);
-        menu.addItem(&sep3);
-
-        // About
-        let version = env!("CARGO_PKG_VERSION");
-        let about_title = NSString::from_str(&format!("Awake v{}", version));
-        let about_item = NSMenuItem::initWithTitle_action_keyEquivalent(
-            NSMenuItem::alloc(mtm),
-            &about_title,
-            None,
-            &empty,
-        );
-        let _: () = msg_send![&about_item, setEnabled: false];
-        menu.addItem(&about_item);
-
-        // Separator
-        let sep4 = NSMenuItem::separatorItem(mtm);
-        menu.addItem(&sep4);
-
-        // Quit
-        let quit_item = create_menu_item("Quit", sel!(quit:), delegate, mtm);
-        menu.addItem(&quit_item);
-
-        // Store menu for right-click access (don't set it on status item —
-        // left click toggles, right click shows menu)
-        STATUS_MENU.lock().unwrap().0 = Retained::as_ptr(&menu) as *mut _;
-
-        // Set button action for left-click toggle
+    // Set before anything can start a session, so none escapes the cap
+    if let Some(minutes) = number(
+        value("--max-session"),
+        "MaxSessionMinutes",
+        1..=MAX_SESSION_MINUTES,
+    ) {
+        start(state::Command::SetMaxSession(Some(minutes)));
+    }
+    if let Some(name) = value("--mode") {
+        let mode = Mode::parse(name).unwrap_or_else(|| usage());
+        // Nothing is held yet, so this only picks what the first activation takes
+        start(state::Command::SetMode(mode.kinds()));
+    }
+    if let Some(path) = value("--watch") {
+        start(state::Command::WatchPath(path.into()));
+    }
+    if let Some(policy) = value("--trigger-policy")
+        .cloned()
+        .or_else(|| setting("TriggerPolicy"))
+    {
+        let policy = triggers::Policy::parse(&policy).unwrap_or_else(|| usage());
+        start(state::Command::SetTriggerPolicy(policy));
+    }
+    if args.iter().any(|arg| arg == "--display-grace") || setting_bool("DisplaySleepGrace") {
+        start(state::Command::SetDisplayGrace(true));
+    }
+    if args.iter().any(|arg| arg == "--battery-timer") || setting_bool("BatteryTimer") {
+        battery::watch_power_source();
+        start(state::Command::SetBatteryTimer(true));
+    }
+    // A session left on this long without a timer gets a "still needed?"; 0 never asks
+    let remind_after = number(
+        value("--remind-after"),
+        "ReminderHours",
+        0..=MAX_REMINDER_HOURS,
+    )
+    .unwrap_or(DEFAULT_REMINDER_HOURS);
+    start(state::Command::SetReminder(
+        (remind_after > 0).then_some(remind_after),
+    ));
+    if let Some(percent) = value("--overnight") {
+        let percent = percent
+            .parse::<u8>()
+            .ok()
+            .filter(|p| (1..=100).contains(p))
+            .unwrap_or_else(|| usage());
+        start(state::Command::SetOvernight(Some(percent)));
+    }
+    // Where the sun's times are worked out for, if not found with CoreLocation
+    #[cfg(target_os = "macos")]
+    if let Some(location) = value("--location").cloned().or_else(|| setting("Location")) {
+        let (latitude, longitude) = solar::parse_location(&location).unwrap_or_else(|| usage());
+        solar::set_location(latitude, longitude);
+    }
+    if !safe_mode {
+        start_triggers(&args);
+        if let Some(port) = value("--remote")
+            .cloned()
+            .or_else(|| setting("RemoteControlPort"))
         {
-            let button: *mut AnyObject = msg_send![&status_item, button];
-            if !button.is_null() {
-                let _: () = msg_send![button, setAction: sel!(buttonClicked:)];
-                let _: () = msg_send![button, setTarget: delegate];
-            }
+            let port = port
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|&port| port != 0)
+                .unwrap_or_else(|| usage());
+            remote::start(port);
         }
+    }
 
-        // Send right-click events to our button handler
-        // Fire action on left mouse up and right mouse down/up
-        let mask: i64 = (1 << 2) | (1 << 3) | (1 << 4);
-        let _: () = msg_send![&status_item, sendActionOn: mask];
-
-        app.run();
+    // On macOS the front-end switches a kiosk on, once it can hold it there
+    #[cfg(not(target_os = "macos"))]
+    if kiosk {
+        start(state::Command::Activate);
     }
+
+    frontend::run();
 }
@@ -0,0 +1,133 @@
+//! Prometheus metrics for the remote's `GET /metrics`, so a homelab can graph
+//! when its machines were kept awake alongside everything else it scrapes:
+//!
+//! - `awake_active`: 1 while a session is on
+//! - `awake_session_seconds_total`: time spent awake since launch
+//! - `awake_assertion_failures_total`: power assertions the OS refused, or
+//!   that failed to release
+//!
+//! Kept by a state observer, as the remote's status is, so a scrape never
+//! waits for the main thread.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::state::{self, Status};
+
+static ASSERTION_FAILURES: AtomicU64 = AtomicU64::new(0);
+static SESSIONS: Mutex<Sessions> = Mutex::new(Sessions {
+    since: None,
+    finished_secs: 0,
+});
+
+/// Time awake: sessions that have ended, and when the current one started
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Sessions {
+    since: Option<u64>,
+    finished_secs: u64,
+}
+
+impl Sessions {
+    fn update(&mut self, awake: bool, now: u64) {
+        match (self.since, awake) {
+            (None, true) => self.since = Some(now),
+            (Some(since), false) => {
+                self.finished_secs += now.saturating_sub(since);
+                self.since = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn total_secs(&self, now: u64) -> u64 {
+        self.finished_secs + self.since.map_or(0, |since| now.saturating_sub(since))
+    }
+}
+
+/// Counts a failed assertion, for `awake_assertion_failures_total`
+pub fn count_assertion_failure() {
+    ASSERTION_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+fn remember(status: &Status) {
+    if let Ok(mut sessions) = SESSIONS.lock() {
+        sessions.update(status.is_awake(), crate::now_secs());
+    }
+}
+
+/// Starts keeping the metrics
+pub fn install() {
+    state::on_state_change(remember);
+}
+
+// Prometheus's text format
+fn render(sessions: Sessions, failures: u64, now: u64) -> String {
+    let metric = |name: &str, kind: &str, help: &str, value: u64| {
+        format!(
+            "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+            name, help, name, kind, name, value
+        )
+    };
+    [
+        metric(
+            "awake_active",
+            "gauge",
+            "Whether Awake is keeping the machine awake.",
+            u64::from(sessions.since.is_some()),
+        ),
+        metric(
+            "awake_session_seconds_total",
+            "counter",
+            "Seconds spent awake since Awake started.",
+            sessions.total_secs(now),
+        ),
+        metric(
+            "awake_assertion_failures_total",
+            "counter",
+            "Power assertions that failed to be taken or released.",
+            failures,
+        ),
+    ]
+    .concat()
+}
+
+/// The metrics as of now
+pub fn text() -> String {
+    let sessions = SESSIONS.lock().map(|s| *s).unwrap_or_default();
+    render(
+        sessions,
+        ASSERTION_FAILURES.load(Ordering::Relaxed),
+        crate::now_secs(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_time_adds_up() {
+        let mut sessions = Sessions::default();
+        sessions.update(true, 100);
+        // Another change while on doesn't restart the count
+        sessions.update(true, 150);
+        assert_eq!(sessions.total_secs(160), 60);
+        sessions.update(false, 200);
+        sessions.update(false, 300);
+        assert_eq!(sessions.total_secs(1000), 100);
+        sessions.update(true, 1000);
+        assert_eq!(sessions.total_secs(1030), 130);
+    }
+
+    #[test]
+    fn metrics_are_in_prometheus_text_format() {
+        let sessions = Sessions {
+            since: Some(40),
+            finished_secs: 60,
+        };
+        let text = render(sessions, 2, 100);
+        assert!(text.contains("# TYPE awake_active gauge\nawake_active 1\n"));
+        assert!(text.contains("\nawake_session_seconds_total 120\n"));
+        assert!(text.ends_with("\nawake_assertion_failures_total 2\n"));
+    }
+}
@@ -0,0 +1,81 @@
+//! The preset modes offered in the menus, on the command line (`--mode
+//! server`) and in trigger rules. A session can hold any other combination of
+//! kinds too; these are the ones with a name.
+
+use std::fmt;
+
+use crate::keepawake::{Kind, KindSet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Display,
+    System,
+    Both,
+    /// For machines serving file shares, web servers and the like: network
+    /// connections stay up through idle and dark wake
+    Server,
+}
+
+impl Mode {
+    /// In the order the menus list them
+    pub const ALL: [Mode; 4] = [Mode::Display, Mode::System, Mode::Both, Mode::Server];
+
+    pub const fn kinds(self) -> KindSet {
+        match self {
+            Mode::Display => KindSet::of(&[Kind::Display]),
+            Mode::System => KindSet::of(&[Kind::System]),
+            Mode::Both => KindSet::of(&[Kind::Display, Kind::System]),
+            Mode::Server => KindSet::of(&[Kind::System, Kind::NetworkClient]),
+        }
+    }
+
+    /// As written on the command line and in settings
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Display => "display",
+            Mode::System => "system",
+            Mode::Both => "both",
+            Mode::Server => "server",
+        }
+    }
+
+    /// As shown in the Mode menu
+    pub const fn title(self) -> &'static str {
+        match self {
+            Mode::Display => "Display Only",
+            Mode::System => "System Only",
+            Mode::Both => "Display + System",
+            Mode::Server => "Serving Network Clients",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+impl From<Mode> for KindSet {
+    fn from(mode: Mode) -> KindSet {
+        mode.kinds()
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_read_back() {
+        for mode in Mode::ALL {
+            assert_eq!(Mode::parse(&mode.to_string()), Some(mode));
+        }
+        assert_eq!(Mode::parse("Server"), None);
+        assert_eq!(Mode::Server.kinds().to_string(), "system+network-client");
+    }
+}
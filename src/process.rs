@@ -0,0 +1,18 @@
+//! Runs external diagnostic commands on demand and captures their output
+
+use std::process::Command;
+
+/// Run `program` to completion, returning stdout (plus stderr on failure) as text
+pub fn run_capture(program: &str, args: &[&str]) -> String {
+    match Command::new(program).args(args).output() {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            if !output.status.success() {
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                text.push_str(&format!("\n{} exited with {}\n", program, output.status));
+            }
+            text
+        }
+        Err(e) => format!("Failed to run {}: {}\n", program, e),
+    }
+}
@@ -0,0 +1,99 @@
+//! Quiet hours — a daily window in which Awake never activates by itself and
+//! stays silent. Manual activation always works.
+//!
+//! The window is configured with `defaults write io.tmss.awake QuietHoursStart 22:00`
+//! (and `QuietHoursEnd`), or a solar time such as `sunset` (see `solar`); the
+//! menu item only toggles it on and off.
+
+use crate::settings;
+use crate::solar::Time;
+
+pub const KEY_ENABLED: &str = "QuietHoursEnabled";
+const KEY_START: &str = "QuietHoursStart";
+const KEY_END: &str = "QuietHoursEnd";
+
+// Minutes since local midnight
+const DEFAULT_START: u32 = 22 * 60;
+const DEFAULT_END: u32 = 7 * 60;
+
+pub fn is_enabled() -> bool {
+    settings::get_bool(KEY_ENABLED)
+}
+
+pub fn set_enabled(enable: bool) {
+    settings::set_bool(KEY_ENABLED, enable);
+}
+
+fn time(key: &str) -> Option<Time> {
+    settings::get_string(key).and_then(|s| Time::parse(&s))
+}
+
+/// Configured (start, end) in minutes since local midnight, today. A solar
+/// time that can't be worked out yet falls back to the default.
+pub fn window() -> (u32, u32) {
+    let start = time(KEY_START)
+        .and_then(|t| t.minutes_today())
+        .unwrap_or(DEFAULT_START);
+    let end = time(KEY_END)
+        .and_then(|t| t.minutes_today())
+        .unwrap_or(DEFAULT_END);
+    (start, end)
+}
+
+/// Whether either end of the window is a solar time
+pub fn follows_sun() -> bool {
+    [KEY_START, KEY_END]
+        .into_iter()
+        .any(|key| time(key).is_some_and(|t| t.follows_sun()))
+}
+
+/// True when quiet hours are enabled and the local time falls inside the window
+pub fn is_quiet_now() -> bool {
+    if !is_enabled() {
+        return false;
+    }
+    let (start, end) = window();
+    contains(start, end, local_minutes_now())
+}
+
+pub fn format_hhmm(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+pub fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let h: u32 = h.parse().ok()?;
+    let m: u32 = m.parse().ok()?;
+    if h < 24 && m < 60 {
+        Some(h * 60 + m)
+    } else {
+        None
+    }
+}
+
+// Windows may wrap past midnight (22:00–07:00); an empty window never matches
+pub fn contains(start: u32, end: u32, now: u32) -> bool {
+    if start <= end {
+        now >= start && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+pub fn local_time() -> Option<libc::tm> {
+    unsafe {
+        let t = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        (!libc::localtime_r(&t, &mut tm).is_null()).then_some(tm)
+    }
+}
+
+/// Minutes since local midnight
+pub fn local_minutes_now() -> u32 {
+    local_time().map_or(0, |tm| (tm.tm_hour * 60 + tm.tm_min) as u32)
+}
+
+/// A number for today's local date, different each day
+pub fn local_day_now() -> i32 {
+    local_time().map_or(0, |tm| tm.tm_year * 1000 + tm.tm_yday)
+}
@@ -0,0 +1,594 @@
+//! Remote control over the local network: a small HTTP service with a web
+//! page of buttons, for toggling Awake from a phone across the room. Opt-in,
+//! with `--remote <port>` or `RemoteControlPort`; on macOS it's advertised
+//! over Bonjour as `_awake._tcp`.
+//!
+//! Every request but the page itself needs the token kept in `remote-token`
+//! next to the event log, as `?token=` or an `Authorization: Bearer` header;
+//! the page passes on the one in its own address, so the link to bookmark is
+//! `http://<mac>.local:<port>/?token=<token>`.
+//!
+//! `GET /status` returns `status.json`'s contents and `GET /metrics` the
+//! `metrics` for Prometheus; `POST /on`, `/toggle`, `/off` and
+//! `/for/<minutes>` act as the `action`s of the same name.
+//!
+//! `awake --host` is the other end, for driving a fleet of build or render
+//! Macs from one: `awake --host mini.local on --minutes 120`.
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::state::{self, Command, Status};
+use crate::{action, metrics, status_command, status_file};
+
+const TOKEN_FILE: &str = "remote-token";
+// Requests are a line and a few headers; anything longer isn't ours
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REPLY_BYTES: u64 = 64 * 1024;
+// Each client gets its own thread, up to this many at once
+const MAX_CLIENTS: usize = 16;
+// Bytes of randomness in a token
+const TOKEN_BYTES: usize = 16;
+/// What `awake --host` connects to when the host names no port
+pub const DEFAULT_PORT: u16 = 8765;
+#[cfg(target_os = "macos")]
+const BROWSE_WAIT: Duration = Duration::from_secs(2);
+const EXIT_ERROR: i32 = 2;
+
+const PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width,initial-scale=1">
+<title>Awake</title>
+<style>body{font:18px -apple-system,sans-serif;text-align:center;margin:2em}button{display:block;width:100%;font-size:1.2em;margin:.5em 0;padding:.8em}</style>
+</head><body>
+<h1 id="state">Awake</h1>
+<button onclick="act('toggle')">Toggle</button>
+<button onclick="act('for/60')">Keep Awake for 1 Hour</button>
+<button onclick="act('for/120')">Keep Awake for 2 Hours</button>
+<button onclick="act('off')">Turn Off</button>
+<script>
+const token = new URLSearchParams(location.search).get("token") || "";
+const q = "?token=" + encodeURIComponent(token);
+async function show() {
+  const r = await fetch("/status" + q);
+  const s = r.ok ? await r.json() : null;
+  document.getElementById("state").textContent =
+    !s ? "Not allowed" : s.awake ? "On — " + s.reason : "Off";
+}
+async function act(a) { await fetch("/" + a + q, {method: "POST"}); setTimeout(show, 300); }
+show(); setInterval(show, 5000);
+</script>
+</body></html>
+"#;
+
+// The latest status, as `status.json` has it, for `GET /status`
+static LATEST: Mutex<String> = Mutex::new(String::new());
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+}
+
+/// The request line and headers, as far as this service cares
+fn parse_request(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut words = lines.next()?.split(' ');
+    let method = words.next()?.to_string();
+    let target = words.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(str::to_string);
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case("authorization") {
+            if let Some(bearer) = value.trim().strip_prefix("Bearer ") {
+                token = Some(bearer.trim().to_string());
+            }
+        }
+    }
+    Some(Request {
+        method,
+        path: path.to_string(),
+        token,
+    })
+}
+
+// Compares every byte, so the time taken doesn't tell how much was right
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// What to answer: the status code, the content type and the body, and the
+/// command the request asks for
+fn respond(
+    request: &Request,
+    token: &str,
+    status: &str,
+    metrics: &str,
+) -> (u16, &'static str, String, Option<Command>) {
+    let text = |code, body: &str| (code, "text/plain", body.to_string(), None);
+    if request.method == "GET" && request.path == "/" {
+        return (200, "text/html; charset=utf-8", PAGE.into(), None);
+    }
+    if !request
+        .token
+        .as_deref()
+        .is_some_and(|given| token_matches(given, token))
+    {
+        return text(401, "a token is needed");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => (200, "application/json", status.into(), None),
+        ("GET", "/metrics") => (200, "text/plain; version=0.0.4", metrics.into(), None),
+        ("POST", path) => match action::parse(path) {
+            Some(command) => (202, "text/plain", "accepted".into(), Some(command)),
+            None => text(404, "no such action"),
+        },
+        _ => text(404, "not found"),
+    }
+}
+
+fn reason(code: u16) -> &'static str {
+    match code {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        _ => "Not Found",
+    }
+}
+
+fn serve(stream: TcpStream, token: &str) {
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_BYTES));
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if line == "\r\n" || line == "\n" => break,
+            Ok(_) => head.push_str(&line),
+        }
+    }
+    let (code, content_type, body, command) = match parse_request(&head) {
+        Some(request) => {
+            let status = LATEST.lock().map(|s| s.clone()).unwrap_or_default();
+            respond(&request, token, &status, &metrics::text())
+        }
+        None => (400, "text/plain", "bad request".into(), None),
+    };
+    if let Some(command) = command {
+        state::post(command);
+    }
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason(code),
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = (&stream).write_all(response.as_bytes());
+}
+
+/// `TOKEN_BYTES` from the system's random number generator, in hex
+fn new_token() -> Option<String> {
+    let bytes = random_bytes()?;
+    Some(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(unix)]
+fn random_bytes() -> Option<[u8; TOKEN_BYTES]> {
+    let mut bytes = [0; TOKEN_BYTES];
+    fs::File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .ok()?;
+    Some(bytes)
+}
+
+#[cfg(windows)]
+fn random_bytes() -> Option<[u8; TOKEN_BYTES]> {
+    use windows_sys::Win32::Security::Cryptography::{
+        BCryptGenRandom, BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+    };
+
+    let mut bytes = [0; TOKEN_BYTES];
+    let status = unsafe {
+        BCryptGenRandom(
+            std::ptr::null_mut(),
+            bytes.as_mut_ptr(),
+            TOKEN_BYTES as u32,
+            BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+        )
+    };
+    (status == 0).then_some(bytes)
+}
+
+/// The token requests must carry, made on first use
+pub fn own_token() -> Option<String> {
+    let path = crate::app_support_dir()?.join(TOKEN_FILE);
+    if let Some(token) = fs::read_to_string(&path)
+        .ok()
+        .map(|text| text.trim().to_string())
+        .filter(|token| !token.is_empty())
+    {
+        return Some(token);
+    }
+    let token = new_token()?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    // Only this user's to read from the moment it exists; an empty one left
+    // behind is replaced
+    let _ = fs::remove_file(&path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).ok()?;
+    writeln!(file, "{}", token).ok()?;
+    Some(token)
+}
+
+fn remember(status: &Status) {
+    if let Ok(mut latest) = LATEST.lock() {
+        *latest = status_file::to_json(status, crate::now_secs());
+    }
+}
+
+/// Serves the remote on `port` for the rest of the run. Call from the main
+/// thread, before the front-end starts.
+pub fn start(port: u16) {
+    let Some(token) = own_token() else {
+        eprintln!("Awake: can't read or make the remote control's token");
+        return;
+    };
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "Awake: can't serve the remote control on port {}: {}",
+                port, e
+            );
+            return;
+        }
+    };
+    state::on_state_change(remember);
+    #[cfg(target_os = "macos")]
+    crate::bonjour::advertise(port);
+    thread::spawn(move || accept(listener, &token));
+}
+
+// Serves each connection on its own thread, so one that sends nothing holds
+// up no one else until `TIMEOUT`; past `MAX_CLIENTS`, new ones are dropped
+fn accept(listener: TcpListener, token: &str) {
+    let token: Arc<str> = token.into();
+    let clients = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        if clients.fetch_add(1, Ordering::AcqRel) >= MAX_CLIENTS {
+            clients.fetch_sub(1, Ordering::AcqRel);
+            continue;
+        }
+        let (token, clients) = (Arc::clone(&token), Arc::clone(&clients));
+        thread::spawn(move || {
+            serve(stream, &token);
+            clients.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+}
+
+/// For `awake --host`: `(host, port, token, method, path)` from `args`, which
+/// are the host (with or without a port), then `--token <token>` if given,
+/// then `on [--minutes <n>]`, `off`, `toggle` or `status`
+fn plan(args: &[String]) -> Option<(String, u16, Option<String>, &'static str, String)> {
+    let (host, rest) = args.split_first()?;
+    let (host, port) = parse_host(host)?;
+    let (token, rest) = match rest {
+        [flag, token, rest @ ..] if flag == "--token" => (Some(token.clone()), rest),
+        rest => (None, rest),
+    };
+    let (method, path) = match rest {
+        [verb] if verb == "on" => ("POST", "/on".to_string()),
+        [verb, flag, minutes] if verb == "on" && flag == "--minutes" => {
+            let minutes: u64 = minutes
+                .parse()
+                .ok()
+                .filter(|minutes| (1..=action::MAX_MINUTES).contains(minutes))?;
+            ("POST", format!("/for/{}", minutes))
+        }
+        [verb] if verb == "off" || verb == "toggle" => ("POST", format!("/{}", verb)),
+        [verb] if verb == "status" => ("GET", "/status".to_string()),
+        _ => return None,
+    };
+    Some((host, port, token, method, path))
+}
+
+/// `mini.local`, `mini.local:9000`, `fe80::1` or `[fe80::1]:9000`, with
+/// `DEFAULT_PORT` if none is given
+pub fn parse_host(text: &str) -> Option<(String, u16)> {
+    let port = |port: &str| port.parse().ok().filter(|&port| port != 0);
+    let (host, port) = match text.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']')? {
+            (host, "") => (host, DEFAULT_PORT),
+            (host, rest) => (host, port(rest.strip_prefix(':')?)?),
+        },
+        None => match text.split_once(':') {
+            // More than one colon is a bare IPv6 address, which has no port
+            Some((host, rest)) if !rest.contains(':') => (host, port(rest)?),
+            _ => (text, DEFAULT_PORT),
+        },
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+/// `given`, else `AWAKE_TOKEN`. Never this machine's own token, which would
+/// go to whatever host was named, mistyped or not.
+pub fn token_for(given: Option<String>) -> Option<String> {
+    given.or_else(|| std::env::var("AWAKE_TOKEN").ok())
+}
+
+/// The status code and body of the reply to one request
+pub fn send(
+    host: &str,
+    port: u16,
+    token: &str,
+    method: &str,
+    path: &str,
+) -> std::io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    // An IPv6 address is bracketed in the `Host` header
+    let authority = if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    };
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, authority, token
+    )?;
+    let mut reply = String::new();
+    stream.take(MAX_REPLY_BYTES).read_to_string(&mut reply)?;
+    let code = reply
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    let body = reply.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    Ok((code, body.to_string()))
+}
+
+/// `awake --host <host>[:<port>] [--token <token>] on|off|toggle|status`:
+/// asks another machine's remote control; returns the exit code. The token
+/// is `--token`'s, else `AWAKE_TOKEN`.
+pub fn run(args: &[String]) -> i32 {
+    let Some((host, port, token, method, path)) = plan(args) else {
+        crate::usage();
+    };
+    let Some(token) = token_for(token) else {
+        eprintln!(
+            "awake: no token for {}; pass --token or set AWAKE_TOKEN",
+            host
+        );
+        return EXIT_ERROR;
+    };
+    match send(&host, port, &token, method, &path) {
+        Ok((200, body)) => status_command::show(&body),
+        Ok((202, _)) => 0,
+        Ok((401, _)) => {
+            eprintln!("awake: {} refused the token", host);
+            EXIT_ERROR
+        }
+        Ok((code, body)) => {
+            eprintln!("awake: {} answered {}: {}", host, code, body.trim());
+            EXIT_ERROR
+        }
+        Err(e) => {
+            eprintln!("awake: can't reach {}:{}: {}", host, port, e);
+            EXIT_ERROR
+        }
+    }
+}
+
+/// `awake hosts`: lists the instances advertising a remote control nearby
+pub fn hosts() -> i32 {
+    #[cfg(target_os = "macos")]
+    {
+        for host in crate::bonjour::browse(BROWSE_WAIT) {
+            println!("{}\t{}:{}", host.name, host.host, host.port);
+        }
+        0
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        eprintln!("awake: finding other machines needs macOS's Bonjour");
+        EXIT_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn request(method: &str, path: &str, token: Option<&str>) -> Request {
+        Request {
+            method: method.into(),
+            path: path.into(),
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn requests_read_back() {
+        assert_eq!(
+            parse_request("POST /for/60?x=1&token=abc HTTP/1.1\r\nHost: mini.local\r\n"),
+            Some(request("POST", "/for/60", Some("abc")))
+        );
+        assert_eq!(
+            parse_request("GET /status HTTP/1.1\r\nauthorization: Bearer abc\r\n"),
+            Some(request("GET", "/status", Some("abc")))
+        );
+        assert_eq!(parse_request(""), None);
+    }
+
+    #[test]
+    fn only_the_page_is_open_without_the_token() {
+        let (code, _, body, _) = respond(&request("GET", "/", None), TOKEN, "{}", "");
+        assert_eq!(code, 200);
+        assert!(body.contains("<html>"));
+        let (code, _, _, command) =
+            respond(&request("POST", "/toggle", Some("nope")), TOKEN, "{}", "");
+        assert_eq!(code, 401);
+        assert!(command.is_none());
+        assert_eq!(
+            respond(&request("GET", "/status", None), TOKEN, "{}", "").0,
+            401
+        );
+    }
+
+    #[test]
+    fn a_token_unlocks_status_and_actions() {
+        let (code, _, body, _) = respond(
+            &request("GET", "/status", Some(TOKEN)),
+            TOKEN,
+            "{\"awake\":true}",
+            "",
+        );
+        assert_eq!((code, body.as_str()), (200, "{\"awake\":true}"));
+        let (code, _, _, command) =
+            respond(&request("POST", "/for/60", Some(TOKEN)), TOKEN, "{}", "");
+        assert_eq!(code, 202);
+        assert!(matches!(
+            command,
+            Some(Command::ActivateFor { minutes: 60 })
+        ));
+        assert_eq!(
+            respond(&request("POST", "/sleep", Some(TOKEN)), TOKEN, "{}", "").0,
+            404
+        );
+    }
+
+    #[test]
+    fn metrics_need_the_token() {
+        let metrics = "awake_active 1\n";
+        let (code, content_type, body, _) = respond(
+            &request("GET", "/metrics", Some(TOKEN)),
+            TOKEN,
+            "{}",
+            metrics,
+        );
+        assert_eq!((code, body.as_str()), (200, metrics));
+        assert!(content_type.starts_with("text/plain"));
+        assert_eq!(
+            respond(&request("GET", "/metrics", None), TOKEN, "{}", metrics).0,
+            401
+        );
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn host_commands_become_requests() {
+        assert_eq!(
+            plan(&words("mini.local on --minutes 120")),
+            Some((
+                "mini.local".into(),
+                DEFAULT_PORT,
+                None,
+                "POST",
+                "/for/120".into()
+            ))
+        );
+        assert_eq!(
+            plan(&words("10.0.0.5:9000 --token abc status")),
+            Some((
+                "10.0.0.5".into(),
+                9000,
+                Some("abc".into()),
+                "GET",
+                "/status".into()
+            ))
+        );
+        assert_eq!(plan(&words("mini.local on")).unwrap().4, "/on");
+        assert_eq!(plan(&words("mini.local on --minutes 0")), None);
+        assert_eq!(
+            plan(&words("mini.local on --minutes 10080")).unwrap().4,
+            "/for/10080"
+        );
+        assert_eq!(plan(&words("mini.local on --minutes 10081")), None);
+        assert_eq!(
+            plan(&words("mini.local on --minutes 18446744073709551615")),
+            None
+        );
+        assert_eq!(plan(&words("mini.local:0 off")), None);
+        assert_eq!(plan(&words("mini.local sleep")), None);
+    }
+
+    #[test]
+    fn hosts_parse_with_or_without_a_port() {
+        let host = |host: &str, port| Some((host.to_string(), port));
+        assert_eq!(parse_host("mini.local"), host("mini.local", DEFAULT_PORT));
+        assert_eq!(parse_host("mini.local:9000"), host("mini.local", 9000));
+        assert_eq!(parse_host("fe80::1"), host("fe80::1", DEFAULT_PORT));
+        assert_eq!(parse_host("::1"), host("::1", DEFAULT_PORT));
+        assert_eq!(parse_host("[::1]"), host("::1", DEFAULT_PORT));
+        assert_eq!(parse_host("[fe80::1]:9000"), host("fe80::1", 9000));
+        assert_eq!(parse_host("[fe80::1]9000"), None);
+        assert_eq!(parse_host("mini.local:http"), None);
+        assert_eq!(parse_host(":9000"), None);
+    }
+
+    #[test]
+    fn tokens_are_long_and_differ() {
+        let (a, b) = (new_token().unwrap(), new_token().unwrap());
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_token_file_is_only_its_owner_s() {
+        use std::os::unix::fs::PermissionsExt;
+        let token = own_token().unwrap();
+        assert_eq!(own_token(), Some(token));
+        let path = crate::app_support_dir().unwrap().join(TOKEN_FILE);
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn a_silent_client_holds_up_no_one() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || accept(listener, TOKEN));
+        let _silent = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let started = std::time::Instant::now();
+        let (code, _) = send("127.0.0.1", port, TOKEN, "GET", "/").unwrap();
+        assert_eq!(code, 200);
+        assert!(started.elapsed() < TIMEOUT);
+    }
+}
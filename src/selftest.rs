@@ -0,0 +1,158 @@
+//! `awake selftest` — exercises the moving parts outside the UI and prints a
+//! pass/fail report suitable for pasting into a bug report.
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::keepawake::{Kind, PowerAssertion};
+use crate::timer::{TimerState, Waiter};
+
+const TIMER_TEST_SECS: u64 = 2;
+
+struct Report {
+    passed: u32,
+    failed: u32,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.passed += 1;
+                println!("PASS  {}", name);
+            }
+            Err(reason) => {
+                self.failed += 1;
+                println!("FAIL  {} ({})", name, reason);
+            }
+        }
+    }
+}
+
+/// Runs every check and returns the process exit code (0 when all pass)
+pub fn run() -> i32 {
+    println!(
+        "Awake v{} selftest ({}, {})",
+        env!("CARGO_PKG_VERSION"),
+        os_description(),
+        std::env::consts::ARCH
+    );
+    println!();
+
+    let mut report = Report {
+        passed: 0,
+        failed: 0,
+    };
+
+    for kind in [Kind::Display, Kind::System] {
+        report.check(
+            &format!("create/release {} assertion", kind.name()),
+            check_assertion(kind),
+        );
+    }
+    report.check(
+        &format!("timer expires after {}s", TIMER_TEST_SECS),
+        check_timer(),
+    );
+    #[cfg(target_os = "macos")]
+    report.check("launch agent write/read/remove", check_launch_agent());
+
+    println!();
+    println!("{} passed, {} failed", report.passed, report.failed);
+    if report.failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn os_description() -> String {
+    let version = crate::process::run_capture("/usr/bin/sw_vers", &["-productVersion"]);
+    format!("macOS {}", version.trim())
+}
+
+#[cfg(windows)]
+fn os_description() -> String {
+    "Windows".into()
+}
+
+#[cfg(target_os = "linux")]
+fn os_description() -> String {
+    // PRETTY_NAME="Ubuntu 24.04 LTS"
+    std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "Linux".into())
+}
+
+fn check_assertion(kind: Kind) -> Result<(), String> {
+    PowerAssertion::acquire(kind, "Awake Selftest")
+        .and_then(PowerAssertion::release)
+        .map_err(|e| e.to_string())
+}
+
+fn check_timer() -> Result<(), String> {
+    let mut state = TimerState::new();
+    let token = state.start(crate::now_secs(), TIMER_TEST_SECS);
+    let (tx, rx) = mpsc::channel();
+    let started = Instant::now();
+    let waiter = Waiter::spawn(token, TIMER_TEST_SECS, move |token| {
+        let _ = tx.send(token);
+    });
+
+    let result = match rx.recv_timeout(Duration::from_secs(TIMER_TEST_SECS * 2)) {
+        Ok(_) if started.elapsed() < Duration::from_secs(TIMER_TEST_SECS) => {
+            Err(format!("fired early after {:?}", started.elapsed()))
+        }
+        Ok(elapsed) if !state.fire(elapsed) => Err("elapsed token was rejected".into()),
+        Ok(_) if state.expiry().is_some() => Err("still armed after firing".into()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("did not fire".into()),
+    };
+
+    waiter.cancel();
+    waiter.join();
+    result
+}
+
+#[cfg(target_os = "macos")]
+fn check_launch_agent() -> Result<(), String> {
+    use crate::macos::{launch_agent_label, write_launch_agent};
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = env::temp_dir().join(format!("awake-selftest-{}", std::process::id()));
+    let path = dir.join(format!("{}.plist", launch_agent_label()));
+    // Exercise escaping along with the round trip
+    let app_path = "/Applications/Awake & Co.app/Contents/MacOS/awake";
+
+    let result = (|| {
+        write_launch_agent(&path, app_path).map_err(|e| format!("write: {}", e))?;
+        let contents = fs::read_to_string(&path).map_err(|e| format!("read: {}", e))?;
+        if !contents.contains(launch_agent_label()) || !contents.contains("Awake &amp; Co.app") {
+            return Err("unexpected plist contents".into());
+        }
+        let mode = fs::metadata(&path)
+            .map_err(|e| format!("stat: {}", e))?
+            .permissions()
+            .mode();
+        if mode & 0o777 != 0o644 {
+            return Err(format!("permissions {:o}, expected 644", mode & 0o777));
+        }
+        fs::remove_file(&path).map_err(|e| format!("remove: {}", e))?;
+        if path.exists() {
+            return Err("file still exists after removal".into());
+        }
+        Ok(())
+    })();
+
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
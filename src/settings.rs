@@ -0,0 +1,604 @@
+//! User preferences, stored in NSUserDefaults (`defaults read io.tmss.awake`).
+//!
+//! A configuration profile for the `io.tmss.awake` domain overrides them: its
+//! values are what these functions read, and the user can't change them.
+//!
+//! In safe mode (`--safe-mode`, or ⌥ held at launch) the stored preferences,
+//! managed ones included, are set aside: reads see only what has been set
+//! since launch, and writes go no further than memory.
+//!
+//! The stored layout is versioned (`SettingsVersion`). A change to it adds a
+//! step to `MIGRATIONS`, which `migrate` runs over the stored preferences at
+//! launch, `from_json` over imported copies and `from_synced_json` over synced
+//! ones, so an upgrade carries old settings forward instead of losing them.
+
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::Mutex;
+
+use objc2_foundation::{NSString, NSUserDefaults};
+
+use crate::event_log::json_string;
+
+// The preferences set in safe mode, or `None` outside it
+static SAFE_MODE: Mutex<Option<Values>> = Mutex::new(None);
+
+pub fn enter_safe_mode() {
+    *SAFE_MODE.lock().unwrap() = Some(Vec::new());
+}
+
+pub fn is_safe_mode() -> bool {
+    SAFE_MODE.lock().unwrap().is_some()
+}
+
+// Runs `f` on safe mode's preferences, if in safe mode
+fn in_safe_mode<R>(f: impl FnOnce(&mut Values) -> R) -> Option<R> {
+    SAFE_MODE.lock().unwrap().as_mut().map(f)
+}
+
+fn store(values: &mut Values, key: &str, value: Value) {
+    values.retain(|(k, _)| k != key);
+    values.push((key.to_string(), value));
+}
+
+pub fn get_bool(key: &str) -> bool {
+    if let Some(value) = in_safe_mode(|values| lookup(values, key) == Some(&Value::Bool(true))) {
+        return value;
+    }
+    NSUserDefaults::standardUserDefaults().boolForKey(&NSString::from_str(key))
+}
+
+pub fn set_bool(key: &str, value: bool) {
+    if in_safe_mode(|values| store(values, key, Value::Bool(value))).is_some() {
+        return;
+    }
+    NSUserDefaults::standardUserDefaults().setBool_forKey(value, &NSString::from_str(key));
+}
+
+pub fn get_string(key: &str) -> Option<String> {
+    if let Some(value) = in_safe_mode(|values| match lookup(values, key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None,
+    }) {
+        return value;
+    }
+    NSUserDefaults::standardUserDefaults()
+        .stringForKey(&NSString::from_str(key))
+        .map(|s| s.to_string())
+}
+
+/// Set by a configuration profile, so writes to it have no effect
+pub fn is_managed(key: &str) -> bool {
+    !is_safe_mode()
+        && NSUserDefaults::standardUserDefaults().objectIsForcedForKey(&NSString::from_str(key))
+}
+
+/// Every preference Awake reads, for export and import
+const KEYS: &[(&str, Type)] = &[
+    ("QuietHoursEnabled", Type::Bool),
+    ("QuietHoursStart", Type::String),
+    ("QuietHoursEnd", Type::String),
+    ("LockFile", Type::String),
+    ("TriggerPolicy", Type::String),
+    ("TriggerModes", Type::String),
+    ("TriggerRules", Type::String),
+    ("HandOffToCaffeinate", Type::Bool),
+    ("MaxSessionMinutes", Type::String),
+    ("TimerPrecision", Type::String),
+    ("MenuLayout", Type::String),
+    ("ConfirmLongerThanHours", Type::String),
+    ("DisplaySleepGrace", Type::Bool),
+    ("PauseTimerWhileDisplaySleeps", Type::Bool),
+    ("BatteryTimer", Type::Bool),
+    ("ReminderHours", Type::String),
+    ("ResumeAfterRestart", Type::Bool),
+    ("ActivateOnLaunch", Type::Bool),
+    ("IconBadge", Type::Bool),
+    ("SeparateCountdown", Type::Bool),
+    ("TextOnlyIcon", Type::Bool),
+    ("ActiveTitle", Type::String),
+    ("IdleTitle", Type::String),
+    ("ActiveIconPath", Type::String),
+    ("IdleIconPath", Type::String),
+    ("ActiveIconPathDark", Type::String),
+    ("IdleIconPathDark", Type::String),
+    ("ColoredIcons", Type::Bool),
+    ("PlaySounds", Type::Bool),
+    ("ActivateSound", Type::String),
+    ("DeactivateSound", Type::String),
+    ("ExpirySound", Type::String),
+    ("SpeakChanges", Type::Bool),
+    ("HiddenMenuBarFeedback", Type::String),
+    ("FloatingIndicator", Type::Bool),
+    ("FloatingIndicatorPosition", Type::String),
+    ("FlashIconOnChange", Type::Bool),
+    ("StillWhileRecording", Type::Bool),
+    ("ScreenRecorders", Type::String),
+    ("HotCorner", Type::String),
+    ("HotCornerAction", Type::String),
+    ("BatteryHealthGuard", Type::String),
+    ("MaxTemperature", Type::String),
+    ("MaxTemperatureSensor", Type::String),
+    ("ShowThermalDiagnostics", Type::Bool),
+    ("WebhookURL", Type::String),
+    ("CrashReports", Type::Bool),
+    ("WhileSSHConnected", Type::Bool),
+    ("WhilePortConnected", Type::String),
+    ("WhileContainersRunning", Type::Bool),
+    ("WhileBuilding", Type::Bool),
+    ("WhileMirroring", Type::Bool),
+    ("WhileWirelessAudio", Type::Bool),
+    ("Workday", Type::String),
+    ("AwakeBetween", Type::String),
+    ("Location", Type::String),
+    ("RemoteControlPort", Type::String),
+    ("FollowLeader", Type::String),
+    ("KioskMode", Type::Bool),
+    ("RosettaHintShown", Type::Bool),
+    ("SettingsVersion", Type::String),
+];
+
+const KEY_VERSION: &str = "SettingsVersion";
+
+/// Takes the preferences from one layout to the next: `MIGRATIONS[0]` from
+/// version 1 to 2, and so on
+type Migration = fn(&mut Values);
+
+const MIGRATIONS: &[Migration] = &[];
+
+/// Keys a migration retired, read only so it can carry their values over;
+/// they're removed once it has
+const RETIRED: &[(&str, Type)] = &[];
+
+/// This build's layout. Version 1 is the first, which stored no version.
+pub const VERSION: u32 = 1 + MIGRATIONS.len() as u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Bool,
+    String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    String(String),
+}
+
+/// Some preferences and their values, in `KEYS` order
+pub type Values = Vec<(String, Value)>;
+
+fn lookup<'a>(values: &'a Values, key: &str) -> Option<&'a Value> {
+    values.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn is_set(key: &str) -> bool {
+    if let Some(set) = in_safe_mode(|values| lookup(values, key).is_some()) {
+        return set;
+    }
+    NSUserDefaults::standardUserDefaults()
+        .objectForKey(&NSString::from_str(key))
+        .is_some()
+}
+
+/// The preferences that have been set
+pub fn current() -> Values {
+    read(KEYS.iter())
+}
+
+fn read<'a>(keys: impl Iterator<Item = &'a (&'static str, Type)>) -> Values {
+    keys.filter(|(key, _)| is_set(key))
+        .map(|&(key, kind)| {
+            let value = match kind {
+                Type::Bool => Value::Bool(get_bool(key)),
+                Type::String => Value::String(get_string(key).unwrap_or_default()),
+            };
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+/// Makes `values` the preferences: keys it doesn't mention are cleared.
+/// Managed keys are left as the profile has them.
+pub fn replace(values: &Values) {
+    if in_safe_mode(|current| *current = values.clone()).is_some() {
+        return;
+    }
+    let defaults = NSUserDefaults::standardUserDefaults();
+    for (key, _) in KEYS.iter().filter(|(key, _)| !is_managed(key)) {
+        defaults.removeObjectForKey(&NSString::from_str(key));
+    }
+    for (key, value) in values.iter().filter(|(key, _)| !is_managed(key)) {
+        match value {
+            Value::Bool(b) => set_bool(key, *b),
+            Value::String(s) => set_string(key, s),
+        }
+    }
+}
+
+/// Clears every stored preference in `domain`, the bundle identifier, in
+/// safe mode too, for uninstalling: those in `KEYS` and any others, such as
+/// `SyncWithICloud`. Managed keys belong to the profile and stay.
+pub fn remove_all(domain: &str) {
+    NSUserDefaults::standardUserDefaults()
+        .removePersistentDomainForName(&NSString::from_str(domain));
+}
+
+fn version_of(values: &Values) -> Result<u32, String> {
+    match lookup(values, KEY_VERSION) {
+        None => Ok(1),
+        Some(Value::String(s)) => s
+            .parse()
+            .ok()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| format!("{} should be a number", KEY_VERSION)),
+        Some(Value::Bool(_)) => Err(format!("{} should be a string", KEY_VERSION)),
+    }
+}
+
+// `values` brought up to the layout after the last of `steps`. A layout
+// newer than that is refused rather than guessed at.
+fn upgrade(mut values: Values, steps: &[Migration]) -> Result<Values, String> {
+    let (from, to) = (version_of(&values)?, 1 + steps.len() as u32);
+    if from > to {
+        return Err(format!(
+            "these settings are from a newer Awake (layout {}, this one knows {})",
+            from, to
+        ));
+    }
+    for step in &steps[from as usize - 1..] {
+        step(&mut values);
+    }
+    store(&mut values, KEY_VERSION, Value::String(to.to_string()));
+    Ok(values)
+}
+
+/// Brings the stored preferences up to this build's layout. Ones written by
+/// a newer Awake are left as they are, for it to find again.
+pub fn migrate() {
+    if is_safe_mode() {
+        return;
+    }
+    let stored = read(KEYS.iter().chain(RETIRED));
+    if version_of(&stored) == Ok(VERSION) {
+        return;
+    }
+    match upgrade(stored, MIGRATIONS) {
+        Ok(mut values) => {
+            values.retain(|(key, _)| KEYS.iter().any(|(known, _)| known == key));
+            replace(&values);
+            let defaults = NSUserDefaults::standardUserDefaults();
+            for (key, _) in RETIRED {
+                defaults.removeObjectForKey(&NSString::from_str(key));
+            }
+        }
+        Err(e) => eprintln!("Awake: settings left as they are: {}", e),
+    }
+}
+
+/// One JSON object, as exported
+pub fn to_json(values: &Values) -> String {
+    let fields: Vec<String> = values
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Bool(b) => b.to_string(),
+                Value::String(s) => json_string(s),
+            };
+            format!("  {}: {}", json_string(key), value)
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", fields.join(",\n"))
+}
+
+/// Reads an exported object, refusing keys Awake doesn't know and values of
+/// the wrong type, and migrates it from the layout it was written in
+pub fn from_json(json: &str) -> Result<Values, String> {
+    known(parse_object(json)?)
+}
+
+/// Reads a synced copy like `from_json`, except that a key this version
+/// doesn't know, which a newer Mac may have added, is set aside rather than
+/// refusing the file. Those come back second, to be written back unchanged.
+pub fn from_synced_json(json: &str) -> Result<(Values, Values), String> {
+    let (values, unknown): (Values, Values) = parse_object(json)?
+        .into_iter()
+        .partition(|(key, _)| KEYS.iter().chain(RETIRED).any(|(known, _)| known == key));
+    Ok((known(values)?, unknown))
+}
+
+// `values` checked against the keys Awake knows and migrated from the layout
+// they were written in
+fn known(values: Values) -> Result<Values, String> {
+    for (key, value) in &values {
+        match KEYS.iter().chain(RETIRED).find(|(known, _)| known == key) {
+            None => return Err(format!("unknown setting {}", key)),
+            Some((_, Type::Bool)) if !matches!(value, Value::Bool(_)) => {
+                return Err(format!("{} should be true or false", key))
+            }
+            Some((_, Type::String)) if !matches!(value, Value::String(_)) => {
+                return Err(format!("{} should be a string", key))
+            }
+            Some(_) => {}
+        }
+    }
+    let values = upgrade(values, MIGRATIONS)?;
+    Ok(KEYS
+        .iter()
+        .filter_map(|(key, _)| Some((key.to_string(), lookup(&values, key)?.clone())))
+        .collect())
+}
+
+/// The preferences that have been set, as one JSON object
+pub fn export() -> String {
+    to_json(&current())
+}
+
+/// Replaces the preferences with those in an exported file. Nothing changes
+/// if the file is refused.
+pub fn import(json: &str) -> Result<(), String> {
+    replace(&from_json(json)?);
+    Ok(())
+}
+
+/// The outcome of a three-way merge
+#[derive(Debug, PartialEq, Eq)]
+pub struct Merged {
+    pub values: Values,
+    /// Keys changed differently on both sides; the local value was kept
+    pub conflicts: Vec<String>,
+}
+
+/// Combines two copies of the preferences that have both moved on from
+/// `base`, the last copy they agreed on. A key changed on one side takes that
+/// side's value; changed on both, the local one wins.
+pub fn merge(base: &Values, local: &Values, remote: &Values) -> Merged {
+    let mut merged = Merged {
+        values: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    for (key, _) in KEYS {
+        let (b, l, r) = (lookup(base, key), lookup(local, key), lookup(remote, key));
+        let value = if l == r || r == b {
+            l
+        } else if l == b {
+            r
+        } else {
+            merged.conflicts.push(key.to_string());
+            l
+        };
+        if let Some(value) = value {
+            merged.values.push((key.to_string(), value.clone()));
+        }
+    }
+    merged
+}
+
+pub fn set_string(key: &str, value: &str) {
+    if in_safe_mode(|values| store(values, key, Value::String(value.into()))).is_some() {
+        return;
+    }
+    let value = NSString::from_str(value);
+    // An NSString is a valid property-list value
+    unsafe {
+        NSUserDefaults::standardUserDefaults()
+            .setObject_forKey(Some(&value), &NSString::from_str(key));
+    }
+}
+
+// A flat object of string and boolean values: all an export ever contains
+fn parse_object(json: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut chars = json.chars().peekable();
+    let mut fields = Vec::new();
+    expect(&mut chars, '{')?;
+    skip_space(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_space(&mut chars);
+            let key = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            skip_space(&mut chars);
+            let value = match chars.peek() {
+                Some('"') => Value::String(parse_string(&mut chars)?),
+                _ => Value::Bool(parse_bool(&mut chars)?),
+            };
+            fields.push((key, value));
+            skip_space(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".into()),
+            }
+        }
+    }
+    skip_space(&mut chars);
+    match chars.next() {
+        None => Ok(fields),
+        Some(_) => Err("unexpected text after the settings".into()),
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, wanted: char) -> Result<(), String> {
+    skip_space(chars);
+    match chars.next() {
+        Some(c) if c == wanted => Ok(()),
+        Some(c) => Err(format!("expected '{}', found '{}'", wanted, c)),
+        None => Err(format!("expected '{}', found the end", wanted)),
+    }
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err("expected a string".into());
+    }
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or("bad \\u escape")?;
+                    out.push(c);
+                }
+                c @ ('"' | '\\' | '/') => out.push(c),
+                c => return Err(format!("bad escape '\\{}'", c)),
+            },
+            c => out.push(c),
+        }
+    }
+}
+
+fn parse_bool(chars: &mut Peekable<Chars>) -> Result<bool, String> {
+    let word: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+    match word.as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err("expected a string, true or false".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exported_object_parses_back() {
+        let json = format!(
+            "{{\n  \"QuietHoursEnabled\": true,\n  \"LockFile\": {}\n}}\n",
+            json_string("/tmp/a \"b\".lock")
+        );
+        assert_eq!(
+            parse_object(&json),
+            Ok(vec![
+                ("QuietHoursEnabled".into(), Value::Bool(true)),
+                ("LockFile".into(), Value::String("/tmp/a \"b\".lock".into())),
+            ])
+        );
+        assert_eq!(parse_object(" {} "), Ok(vec![]));
+    }
+
+    #[test]
+    fn settings_of_the_wrong_kind_are_refused() {
+        assert!(from_json("{\"Colour\": \"red\"}").is_err());
+        assert!(from_json("{\"LockFile\": true}").is_err());
+        assert!(from_json("{\"QuietHoursEnabled\": \"yes\"}").is_err());
+    }
+
+    #[test]
+    fn synced_copies_set_unknown_settings_aside() {
+        let json = "{\"LockFile\": \"/a\", \"Colour\": \"red\"}";
+        assert_eq!(
+            from_synced_json(json),
+            Ok((values(&[("LockFile", "/a")]), values(&[("Colour", "red")])))
+        );
+        assert!(from_synced_json("{\"LockFile\": true}").is_err());
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> Values {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn merge_takes_each_side_s_changes_and_keeps_local_on_conflict() {
+        let base = values(&[("QuietHoursStart", "22:00"), ("LockFile", "/a")]);
+        let local = values(&[("QuietHoursStart", "23:00"), ("LockFile", "/a")]);
+        let remote = values(&[
+            ("QuietHoursStart", "21:00"),
+            ("QuietHoursEnd", "06:00"),
+            ("LockFile", "/b"),
+        ]);
+        assert_eq!(
+            merge(&base, &local, &remote),
+            Merged {
+                values: values(&[
+                    ("QuietHoursStart", "23:00"),
+                    ("QuietHoursEnd", "06:00"),
+                    ("LockFile", "/b"),
+                ]),
+                conflicts: vec!["QuietHoursStart".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn merge_removes_a_key_removed_on_one_side() {
+        let base = values(&[("LockFile", "/a")]);
+        let merged = merge(&base, &base, &values(&[]));
+        assert_eq!(merged.values, values(&[]));
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn migrations_run_from_the_stored_layout() {
+        fn rename(values: &mut Values) {
+            if let Some(i) = values.iter().position(|(key, _)| key == "Lockfile") {
+                values[i].0 = "LockFile".into();
+            }
+        }
+        fn double_max(values: &mut Values) {
+            for (key, value) in values.iter_mut() {
+                if let (true, Value::String(s)) = (key == "MaxSessionMinutes", value) {
+                    *s = (s.parse::<u32>().unwrap() * 2).to_string();
+                }
+            }
+        }
+        let steps: &[Migration] = &[rename, double_max];
+
+        let unversioned = values(&[("Lockfile", "/a"), ("MaxSessionMinutes", "30")]);
+        assert_eq!(
+            upgrade(unversioned, steps),
+            Ok(values(&[
+                ("LockFile", "/a"),
+                ("MaxSessionMinutes", "60"),
+                ("SettingsVersion", "3"),
+            ]))
+        );
+        // Only the steps after the stored layout run
+        let two = values(&[("MaxSessionMinutes", "30"), ("SettingsVersion", "2")]);
+        assert_eq!(
+            upgrade(two, steps),
+            Ok(values(&[
+                ("MaxSessionMinutes", "60"),
+                ("SettingsVersion", "3")
+            ]))
+        );
+        let newer = values(&[("SettingsVersion", "4")]);
+        assert!(upgrade(newer, steps).is_err());
+        assert!(upgrade(values(&[("SettingsVersion", "x")]), steps).is_err());
+    }
+
+    #[test]
+    fn imports_are_stamped_with_this_layout() {
+        let imported = from_json("{\"LockFile\": \"/a\"}").unwrap();
+        assert_eq!(
+            lookup(&imported, KEY_VERSION),
+            Some(&Value::String(VERSION.to_string()))
+        );
+        let future = format!("{{\"SettingsVersion\": \"{}\"}}", VERSION + 1);
+        assert!(from_json(&future).is_err());
+    }
+
+    #[test]
+    fn malformed_objects_are_refused() {
+        assert!(parse_object("").is_err());
+        assert!(parse_object("{\"LockFile\": 3}").is_err());
+        assert!(parse_object("{\"LockFile\": \"x\"").is_err());
+        assert!(parse_object("{\"LockFile\": \"x\"} trailing").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Sunrise and sunset, so a schedule can follow the sun rather than the clock
+//! (`sunset`, `sunrise+30`, `sunset-15`) and needn't be edited as the seasons
+//! change. Quiet hours and `--between` take them wherever they take a time.
+//!
+//! The sun's times need to know where the Mac is: given with `--location` (or
+//! `Location`) as `<latitude>,<longitude>`, or else found once at launch with
+//! CoreLocation. Until then, and on days the sun doesn't rise or set, a solar
+//! time doesn't resolve.
+
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::quiet_hours::{format_hhmm, local_time, parse_hhmm};
+
+const DAY_MINUTES: i32 = 24 * 60;
+
+// Latitude and longitude in degrees, once known
+static LOCATION: Mutex<Option<(f64, f64)>> = Mutex::new(None);
+// Set when a schedule follows the sun, so the front-end looks for a location
+static WANTED: AtomicBool = AtomicBool::new(false);
+
+/// A time of day: on the clock, or the sun's, offset by some minutes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Time {
+    Clock(u32),
+    Sunrise(i32),
+    Sunset(i32),
+}
+
+impl Time {
+    /// `18:30`, `sunset`, `sunrise+30` or `sunset-15`
+    pub fn parse(text: &str) -> Option<Time> {
+        let text = text.trim();
+        let solar = |rest: &str| -> Option<i32> {
+            match rest.chars().next() {
+                None => Some(0),
+                Some('+') => rest[1..].parse().ok(),
+                Some('-') => rest[1..].parse::<i32>().ok().map(|m| -m),
+                _ => None,
+            }
+        };
+        if let Some(rest) = text.strip_prefix("sunrise") {
+            solar(rest).map(Time::Sunrise)
+        } else if let Some(rest) = text.strip_prefix("sunset") {
+            solar(rest).map(Time::Sunset)
+        } else {
+            parse_hhmm(text).map(Time::Clock)
+        }
+    }
+
+    pub fn follows_sun(&self) -> bool {
+        !matches!(self, Time::Clock(_))
+    }
+
+    /// Minutes since midnight, given the day's sunrise and sunset (if known)
+    fn resolve(&self, sun: Option<(u32, u32)>) -> Option<u32> {
+        let (base, offset) = match *self {
+            Time::Clock(minutes) => return Some(minutes),
+            Time::Sunrise(offset) => (sun?.0, offset),
+            Time::Sunset(offset) => (sun?.1, offset),
+        };
+        Some((base as i32 + offset).rem_euclid(DAY_MINUTES) as u32)
+    }
+
+    /// Minutes since local midnight today, if it can be worked out
+    pub fn minutes_today(&self) -> Option<u32> {
+        if !self.follows_sun() {
+            return self.resolve(None);
+        }
+        let tm = local_time()?;
+        let (latitude, longitude) = location()?;
+        let offset = (tm.tm_gmtoff / 60) as i32;
+        self.resolve(sun_times(
+            latitude,
+            longitude,
+            tm.tm_yday as u32 + 1,
+            offset,
+        ))
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, offset) = match *self {
+            Time::Clock(minutes) => return f.write_str(&format_hhmm(minutes)),
+            Time::Sunrise(offset) => ("sunrise", offset),
+            Time::Sunset(offset) => ("sunset", offset),
+        };
+        match offset {
+            0 => f.write_str(name),
+            offset => write!(f, "{}{:+}", name, offset),
+        }
+    }
+}
+
+/// `sunset-00:00` or `07:00-sunrise+30`: a window's start and end. A `-`
+/// may also begin an offset, so the first split where both halves read wins.
+pub fn parse_window(text: &str) -> Option<(Time, Time)> {
+    text.match_indices('-').find_map(|(i, _)| {
+        let (start, end) = (Time::parse(&text[..i])?, Time::parse(&text[i + 1..])?);
+        Some((start, end))
+    })
+}
+
+/// `51.5,-0.12`: a latitude and longitude in degrees
+pub fn parse_location(text: &str) -> Option<(f64, f64)> {
+    let (latitude, longitude) = text.split_once(',')?;
+    let latitude: f64 = latitude.trim().parse().ok()?;
+    let longitude: f64 = longitude.trim().parse().ok()?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+pub fn set_location(latitude: f64, longitude: f64) {
+    *LOCATION.lock().unwrap() = Some((latitude, longitude));
+}
+
+pub fn location() -> Option<(f64, f64)> {
+    *LOCATION.lock().unwrap()
+}
+
+/// Notes that a schedule follows the sun
+pub fn want_location() {
+    WANTED.store(true, Ordering::Relaxed);
+}
+
+/// Whether a schedule follows the sun and nothing has said where the Mac is
+pub fn needs_location() -> bool {
+    WANTED.load(Ordering::Relaxed) && location().is_none()
+}
+
+/// Sunrise and sunset on `day` (of the year, from 1) in minutes since local
+/// midnight, `utc_offset` minutes ahead of UTC; `None` in polar day or night.
+/// NOAA's approximation, good to a minute or two away from the poles.
+fn sun_times(latitude: f64, longitude: f64, day: u32, utc_offset: i32) -> Option<(u32, u32)> {
+    let year = 2.0 * PI / 365.0 * (day as f64 - 1.0);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * year.cos()
+            - 0.032077 * year.sin()
+            - 0.014615 * (2.0 * year).cos()
+            - 0.040849 * (2.0 * year).sin());
+    let declination = 0.006918 - 0.399912 * year.cos() + 0.070257 * year.sin()
+        - 0.006758 * (2.0 * year).cos()
+        + 0.000907 * (2.0 * year).sin()
+        - 0.002697 * (3.0 * year).cos()
+        + 0.00148 * (3.0 * year).sin();
+    // The sun's centre 50′ below the horizon, for refraction and its radius
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = 90.833f64.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let local = |angle: f64| {
+        let utc = 720.0 - 4.0 * (longitude + angle) - equation_of_time;
+        ((utc.round() as i32) + utc_offset).rem_euclid(DAY_MINUTES) as u32
+    };
+    Some((local(hour_angle), local(-hour_angle)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_parse_and_print() {
+        assert_eq!(Time::parse("18:30"), Some(Time::Clock(18 * 60 + 30)));
+        assert_eq!(Time::parse("sunset"), Some(Time::Sunset(0)));
+        assert_eq!(Time::parse("sunrise+30"), Some(Time::Sunrise(30)));
+        assert_eq!(Time::parse("sunset-15"), Some(Time::Sunset(-15)));
+        assert_eq!(Time::parse("sundown"), None);
+        assert_eq!(Time::Sunset(-15).to_string(), "sunset-15");
+        assert_eq!(Time::Sunrise(30).to_string(), "sunrise+30");
+        assert_eq!(
+            parse_window("sunset-15-00:00"),
+            Some((Time::Sunset(-15), Time::Clock(0)))
+        );
+        assert_eq!(
+            parse_window("07:00-sunrise"),
+            Some((Time::Clock(7 * 60), Time::Sunrise(0)))
+        );
+        assert_eq!(parse_location("51.5, -0.12"), Some((51.5, -0.12)));
+        assert_eq!(parse_location("91,0"), None);
+    }
+
+    #[test]
+    fn the_sun_rises_on_time() {
+        let hhmm = |h: u32, m: u32| h * 60 + m;
+        // London at midsummer (BST) and midwinter, and Sydney in June (AEST)
+        assert_eq!(
+            sun_times(51.5074, -0.1278, 172, 60),
+            Some((hhmm(4, 43), hhmm(21, 21)))
+        );
+        assert_eq!(
+            sun_times(51.5074, -0.1278, 355, 0),
+            Some((hhmm(8, 3), hhmm(15, 53)))
+        );
+        assert_eq!(
+            sun_times(-33.87, 151.21, 172, 600),
+            Some((hhmm(7, 0), hhmm(16, 53)))
+        );
+        // Svalbard's midnight sun
+        assert_eq!(sun_times(78.2, 15.6, 172, 120), None);
+        let sunset = Time::Sunset(-30).resolve(Some((hhmm(4, 43), hhmm(21, 21))));
+        assert_eq!(sunset, Some(hhmm(20, 51)));
+        assert_eq!(Time::Sunrise(0).resolve(None), None);
+    }
+}
@@ -0,0 +1,1277 @@
+//! Single owner of Awake's session state.
+//!
+//! `State` lives in a main-thread `thread_local!` and is only ever touched from
+//! there. Menu actions call `handle` directly; other threads (timer waiters, and
+//! anything else that runs in the background) `post` a `Command`, which the
+//! front-end hops onto its UI thread. Every change is therefore applied one at a time, in order — a
+//! mode switch can't interleave with a timer expiring halfway through.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use crate::error::AwakeError;
+use crate::keepawake::{self, KindSet, PowerSession};
+use crate::mode::Mode;
+use crate::timer::{TimerState, Token, Waiter};
+use crate::triggers::Policy;
+use crate::watch::{Target, Watch};
+use crate::{duration, event_log, frontend, metrics};
+
+// How long switching on lasts on battery power, with the battery timer on
+const BATTERY_TIMER_MINUTES: u64 = 60;
+
+pub enum Command {
+    Toggle,
+    /// Switch on until switched off; nothing if already on
+    Activate,
+    Deactivate,
+    ActivateFor {
+        minutes: u64,
+    },
+    Extend {
+        seconds: u64,
+    },
+    /// Hold exactly `kinds` from now on; an empty set is ignored
+    SetMode(KindSet),
+    /// Hold `kinds` for this many minutes, then go back to the mode chosen
+    /// before. Choosing a mode meanwhile keeps that one instead.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    SetModeFor {
+        kinds: KindSet,
+        minutes: u64,
+    },
+    /// A time-boxed mode has run out
+    ModeOverrideElapsed(Token),
+    TimerElapsed(Token),
+    /// Stay awake while `path` is busy: growing, or (for a script) running
+    WatchPath(PathBuf),
+    /// The watch numbered `generation` is no longer busy
+    WatchFinished {
+        generation: u64,
+    },
+    /// A trigger's condition started or stopped holding
+    TriggerChanged {
+        name: String,
+        satisfied: bool,
+    },
+    /// Ignore (or stop ignoring) the named trigger, without forgetting it
+    SetTriggerEnabled {
+        name: String,
+        enabled: bool,
+    },
+    SetTriggerPolicy(Policy),
+    /// Sessions the named trigger starts hold `kinds` rather than the mode
+    /// chosen from the menu; `None` goes back to the menu's
+    SetTriggerMode {
+        name: String,
+        kinds: Option<KindSet>,
+    },
+    /// Sessions the named trigger starts end after this many minutes, even if
+    /// it still holds; `None` lets them last as long as it does
+    // Only trigger rules send this and the next, and Windows has none
+    #[cfg_attr(windows, allow(dead_code))]
+    SetTriggerLimit {
+        name: String,
+        minutes: Option<u64>,
+    },
+    /// Which trigger is credited first when several hold; triggers not named
+    /// keep their order, after these
+    #[cfg_attr(windows, allow(dead_code))]
+    OrderTriggers(Vec<String>),
+    /// Cap every session at this many minutes, however it was started; `None`
+    /// lifts the cap
+    SetMaxSession(Option<u64>),
+    /// Overnight mode: once a timed or watched session's job is done, stay on
+    /// until the battery has charged to this percentage. `None` switches it off.
+    SetOvernight(Option<u8>),
+    /// Whether the end of a session restarts the display's idle countdown
+    SetDisplayGrace(bool),
+    /// Whether switching on while on battery power starts an hour's timer
+    /// rather than an open-ended session, so one forgotten in a bag runs out
+    SetBatteryTimer(bool),
+    /// Ask whether a session is still needed once it has run this many hours
+    /// without a timer, and every as many hours after; `None` never asks
+    SetReminder(Option<u64>),
+    ReminderDue(Token),
+    /// A manual override of the triggers has run out
+    OverrideElapsed(Token),
+    /// The displays went to sleep (`true`) or woke. A timed session holding
+    /// the display pauses its countdown while they're dark, as it isn't
+    /// keeping anything lit, and picks it up again when they wake. A maximum
+    /// session length keeps counting.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    DisplayAsleep(bool),
+}
+
+/// Why the current session is active
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// A click or menu item, timed or not
+    Manual,
+    /// A dropped or `--watch`ed path
+    Watch(PathBuf),
+    /// The named trigger (the first to become satisfied, while it lasts)
+    Trigger(String),
+    /// Overnight mode, after the job: until the battery reaches this percentage
+    Charging(u8),
+}
+
+impl Source {
+    /// Stable identifier for logs and the status file
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Source::Manual => "manual",
+            Source::Watch(_) => "watch",
+            Source::Trigger(_) => "trigger",
+            Source::Charging(_) => "charging",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Source::Manual => "turned on manually".into(),
+            Source::Watch(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                format!("while {} is busy", name.to_string_lossy())
+            }
+            Source::Trigger(name) => format!("while {}", name),
+            Source::Charging(percent) => format!("until the battery reaches {}%", percent),
+        }
+    }
+}
+
+/// A trigger that has reported at least once
+#[derive(Clone, Debug)]
+pub struct Trigger {
+    pub name: String,
+    pub satisfied: bool,
+    /// Disabled from the menu: still watched, but it switches nothing
+    pub enabled: bool,
+    /// What its sessions hold, if not the menu's mode
+    pub kinds: Option<KindSet>,
+    /// How long its sessions may last, in minutes
+    pub minutes: Option<u64>,
+}
+
+impl Trigger {
+    pub fn is_active(&self) -> bool {
+        self.satisfied && self.enabled
+    }
+}
+
+/// A snapshot of the session, for the front-ends and the status file
+pub struct Status<'a> {
+    /// What the session holds, or the next one will when asleep
+    pub kinds: KindSet,
+    /// `None` when asleep
+    pub source: Option<&'a Source>,
+    /// When a timed session ends (Unix seconds); while its countdown is
+    /// paused, when it would if it carried on now
+    pub expiry: Option<u64>,
+    /// Until when triggers are held off by a manual switch-off
+    pub override_until: Option<u64>,
+    pub triggers: &'a [Trigger],
+    /// The overnight mode's target charge, while it's on
+    pub overnight: Option<u8>,
+    /// While a time-boxed mode lasts: when it ends, and the mode it goes
+    /// back to
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub mode_return: Option<(u64, KindSet)>,
+}
+
+impl Status<'_> {
+    pub fn is_awake(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Seconds until a timed session ends; `None` when it's open-ended, or
+    /// nothing is running
+    pub fn remaining_seconds(&self, now: u64) -> Option<u64> {
+        self.expiry
+            .filter(|_| self.is_awake())
+            .map(|expiry| expiry.saturating_sub(now))
+    }
+
+    /// What is held right now: empty when asleep, where `kinds` still names
+    /// what a session would take
+    pub fn active_assertion_kinds(&self) -> KindSet {
+        if self.is_awake() {
+            self.kinds
+        } else {
+            KindSet::default()
+        }
+    }
+
+    /// What started the running session, or holds it now
+    pub fn activation_source(&self) -> Option<&Source> {
+        self.source
+    }
+
+    /// One line for a tooltip or menu: "On — turned on manually, 42 min left",
+    /// with the time left as precise as `duration` is set to show it
+    pub fn summary(&self) -> String {
+        let Some(source) = self.source else {
+            return match self.override_until {
+                Some(until) => format!("Off \u{2014} triggers paused, {} left", time_left(until)),
+                None => "Off".into(),
+            };
+        };
+        let mut line = format!("On \u{2014} {}", source.describe());
+        if let Some(left) = self.remaining_seconds(crate::now_secs()) {
+            line.push_str(&format!(", {} left", duration::left(left)));
+        }
+        line
+    }
+
+    /// "Back to Display + System in 42 min", while a time-boxed mode lasts
+    #[cfg(target_os = "macos")]
+    pub fn mode_return_text(&self) -> Option<String> {
+        let (until, kinds) = self.mode_return?;
+        let name = Mode::ALL
+            .into_iter()
+            .find(|mode| mode.kinds() == kinds)
+            .map_or_else(|| kinds.to_string(), |mode| mode.title().to_string());
+        Some(format!("Back to {} in {}", name, time_left(until)))
+    }
+
+    /// "⚡ Active: /tmp/awake.lock exists", or `None` when no trigger is
+    pub fn active_triggers(&self) -> Option<String> {
+        let active: Vec<&str> = self
+            .triggers
+            .iter()
+            .filter(|t| t.is_active())
+            .map(|t| t.name.as_str())
+            .collect();
+        if active.is_empty() {
+            None
+        } else {
+            Some(format!("\u{26a1} Active: {}", active.join(", ")))
+        }
+    }
+}
+
+/// "Awake has kept your Mac on for 6 hours — still needed?"
+pub fn reminder_text(hours: u64, machine: &str) -> String {
+    let plural = if hours == 1 { "" } else { "s" };
+    format!(
+        "Awake has kept your {} on for {} hour{} \u{2014} still needed?",
+        machine, hours, plural
+    )
+}
+
+fn time_left(until: u64) -> String {
+    duration::left(until.saturating_sub(crate::now_secs()))
+}
+
+pub struct State {
+    // The menu's mode
+    kinds: KindSet,
+    // `None` when asleep
+    session: Option<PowerSession>,
+    // What `session` holds: `kinds`, or its trigger's own mode
+    held: KindSet,
+    // Set exactly when `session` is
+    source: Option<Source>,
+    timer: TimerState,
+    waiter: Option<Waiter>,
+    // Bumped per watch, so a superseded watch's report is ignored
+    watch_generation: u64,
+    watch: Option<Watch>,
+    // In the order they first reported, unless ordered since
+    triggers: Vec<Trigger>,
+    policy: Policy,
+    // Armed while a manual switch-off holds triggers off (`Policy::Override`)
+    override_timer: TimerState,
+    override_waiter: Option<Waiter>,
+    // Maximum session length in seconds, and when the current session began
+    max_session: Option<u64>,
+    started_at: Option<u64>,
+    overnight: Option<u8>,
+    display_grace: bool,
+    battery_timer: bool,
+    // Seconds between "still needed?" reminders, and their countdown
+    remind_every: Option<u64>,
+    reminder_timer: TimerState,
+    reminder_waiter: Option<Waiter>,
+    // Armed while a time-boxed mode lasts, and the mode it goes back to
+    mode_timer: TimerState,
+    mode_waiter: Option<Waiter>,
+    mode_before: KindSet,
+    // Seconds left on a timer paused while the displays sleep
+    paused: Option<u64>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const { RefCell::new(State::new()) };
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            kinds: Mode::Both.kinds(),
+            session: None,
+            held: Mode::Both.kinds(),
+            source: None,
+            timer: TimerState::new(),
+            waiter: None,
+            watch_generation: 0,
+            watch: None,
+            triggers: Vec::new(),
+            policy: Policy::Edge,
+            override_timer: TimerState::new(),
+            override_waiter: None,
+            max_session: None,
+            started_at: None,
+            overnight: None,
+            display_grace: false,
+            battery_timer: false,
+            remind_every: None,
+            reminder_timer: TimerState::new(),
+            reminder_waiter: None,
+            mode_timer: TimerState::new(),
+            mode_waiter: None,
+            mode_before: Mode::Both.kinds(),
+            paused: None,
+        }
+    }
+}
+
+/// Called with the status after every command
+type Observer = Box<dyn Fn(&Status)>;
+
+thread_local! {
+    // In the order they subscribed; main thread only, like `STATE`
+    static OBSERVERS: RefCell<Vec<Observer>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Calls `observer` with the status after every command, and on `refresh`.
+/// Observers run while the state is borrowed, so they read the status they
+/// are given and `post`, rather than `handle`, any command of their own.
+pub fn on_state_change(observer: impl Fn(&Status) + 'static) {
+    OBSERVERS.with(|observers| observers.borrow_mut().push(Box::new(observer)));
+}
+
+/// Apply `cmd` now. Main thread only. Errors are for the caller to present;
+/// the state is consistent either way.
+pub fn handle(cmd: Command) -> Result<(), AwakeError> {
+    STATE.with(|state| state.borrow_mut().handle(cmd))
+}
+
+/// Reads the current status; not from inside an observer
+#[cfg(target_os = "macos")]
+pub fn with_status<R>(f: impl FnOnce(&Status) -> R) -> R {
+    STATE.with(|state| f(&state.borrow().status()))
+}
+
+/// Seconds left on the running session's timer; not from inside an observer
+#[cfg(target_os = "macos")]
+pub fn remaining_seconds() -> Option<u64> {
+    with_status(|status| status.remaining_seconds(crate::now_secs()))
+}
+
+/// The kinds held right now, empty when asleep; not from inside an observer
+#[cfg(target_os = "macos")]
+pub fn active_assertion_kinds() -> KindSet {
+    with_status(|status| status.active_assertion_kinds())
+}
+
+/// Shows the current status again, for a front-end that has just built its UI
+pub fn refresh() {
+    STATE.with(|state| state.borrow().publish());
+}
+
+/// The kinds the next activation takes (or the current session holds)
+pub fn mode() -> KindSet {
+    STATE.with(|state| state.borrow().kinds)
+}
+
+/// Whether switching on while on battery power starts an hour's timer
+#[cfg(target_os = "macos")]
+pub fn battery_timer() -> bool {
+    STATE.with(|state| state.borrow().battery_timer)
+}
+
+/// The cap on session length, in minutes, if there is one
+#[cfg(target_os = "macos")]
+pub fn max_session_minutes() -> Option<u64> {
+    STATE.with(|state| state.borrow().max_session.map(|secs| secs / 60))
+}
+
+/// Whether `Extend` would add anything: a timer is running or paused and
+/// hasn't reached the maximum session length
+#[cfg(target_os = "macos")]
+pub fn can_extend() -> bool {
+    STATE.with(|state| {
+        let state = state.borrow();
+        match (state.expiry(), state.cap()) {
+            (Some(expiry), Some(cap)) => expiry < cap,
+            (expiry, _) => expiry.is_some(),
+        }
+    })
+}
+
+/// Queue `cmd` for the main thread. Safe to call from any thread.
+pub fn post(cmd: Command) {
+    frontend::post(cmd);
+}
+
+/// Releases everything and waits for the timer thread, ready for the app to exit
+pub fn shutdown() -> Result<(), AwakeError> {
+    let waiters = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        [
+            state.waiter.take(),
+            state.override_waiter.take(),
+            state.reminder_waiter.take(),
+            state.mode_waiter.take(),
+        ]
+    });
+    for waiter in waiters.into_iter().flatten() {
+        waiter.cancel();
+        waiter.join();
+    }
+    handle(Command::Deactivate)
+}
+
+// Quiet hours keep triggers from switching Awake on
+#[cfg(target_os = "macos")]
+fn quiet_now() -> bool {
+    crate::quiet_hours::is_quiet_now()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn quiet_now() -> bool {
+    false
+}
+
+fn log_error(error: &AwakeError) {
+    let (assertion_type, code) = match error {
+        AwakeError::AssertionFailed { kind, code } => (kind.name(), *code),
+        AwakeError::ReleaseFailed { code } => ("release", *code),
+        #[allow(unreachable_patterns)]
+        _ => return,
+    };
+    event_log::log(event_log::Event::AssertionError {
+        assertion_type,
+        code,
+    });
+    metrics::count_assertion_failure();
+}
+
+impl State {
+    fn is_awake(&self) -> bool {
+        self.session.is_some()
+    }
+
+    fn handle(&mut self, cmd: Command) -> Result<(), AwakeError> {
+        let was_awake = self.is_awake();
+        let mut result = match cmd {
+            Command::Toggle => {
+                if self.is_awake() {
+                    self.switch_off()
+                } else {
+                    self.stop_override();
+                    self.switch_on(crate::battery::on_battery())
+                }
+            }
+            Command::Activate if self.is_awake() => Ok(()),
+            Command::Activate => {
+                self.stop_override();
+                self.switch_on(crate::battery::on_battery())
+            }
+            Command::Deactivate => self.deactivate(),
+            Command::ActivateFor { minutes } => {
+                self.stop_override();
+                self.activate_for(minutes.saturating_mul(60))
+            }
+            Command::Extend { seconds } => {
+                self.extend(seconds);
+                self.enforce_max_session();
+                Ok(())
+            }
+            Command::SetMode(mode) => {
+                if !mode.is_empty() {
+                    self.stop_mode_override();
+                }
+                self.set_mode(mode)
+            }
+            Command::SetModeFor { kinds, minutes } => {
+                self.set_mode_for(kinds, minutes.saturating_mul(60))
+            }
+            Command::ModeOverrideElapsed(token) => {
+                if self.mode_timer.fire(token) {
+                    self.mode_waiter = None;
+                    self.set_mode(self.mode_before)
+                } else {
+                    Ok(())
+                }
+            }
+            Command::DisplayAsleep(true) => {
+                self.pause_timer();
+                Ok(())
+            }
+            Command::DisplayAsleep(false) => {
+                self.resume_timer();
+                Ok(())
+            }
+            Command::TimerElapsed(token) => {
+                if self.timer.fire(token) {
+                    self.job_done()
+                } else {
+                    Ok(())
+                }
+            }
+            Command::WatchPath(path) => self.watch(path),
+            Command::TriggerChanged { name, satisfied } => self.trigger_changed(name, satisfied),
+            Command::SetTriggerEnabled { name, enabled } => {
+                self.update_trigger(name, |t| t.enabled = enabled)
+            }
+            Command::SetTriggerPolicy(policy) => {
+                self.policy = policy;
+                Ok(())
+            }
+            Command::SetTriggerMode { name, kinds } => self.set_trigger_mode(name, kinds),
+            Command::SetTriggerLimit { name, minutes } => {
+                self.update_trigger(name, |t| t.minutes = minutes)
+            }
+            Command::OrderTriggers(names) => {
+                // Stable, so the unnamed keep their order at the end
+                self.triggers.sort_by_key(|t| {
+                    names
+                        .iter()
+                        .position(|name| *name == t.name)
+                        .unwrap_or(names.len())
+                });
+                Ok(())
+            }
+            Command::SetMaxSession(minutes) => {
+                self.max_session = minutes.map(|m| m.saturating_mul(60));
+                self.enforce_max_session();
+                Ok(())
+            }
+            Command::SetOvernight(percent) => {
+                self.overnight = percent;
+                Ok(())
+            }
+            Command::SetDisplayGrace(enabled) => {
+                self.display_grace = enabled;
+                Ok(())
+            }
+            Command::SetBatteryTimer(enabled) => {
+                self.battery_timer = enabled;
+                Ok(())
+            }
+            Command::SetReminder(hours) => {
+                self.remind_every = hours.map(|h| h.saturating_mul(3600));
+                if self.is_awake() {
+                    self.arm_reminder();
+                }
+                Ok(())
+            }
+            Command::ReminderDue(token) => {
+                if self.reminder_timer.fire(token) {
+                    self.remind();
+                }
+                Ok(())
+            }
+            Command::OverrideElapsed(token) => {
+                if self.override_timer.fire(token) {
+                    self.override_waiter = None;
+                    self.resume_triggers()
+                } else {
+                    Ok(())
+                }
+            }
+            Command::WatchFinished { generation } => {
+                if self.watch.is_some() && generation == self.watch_generation {
+                    self.job_done()
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        // Ending a long session leaves the OS with hours of idle time banked,
+        // and the display would sleep seconds later; start the count afresh
+        if was_awake && !self.is_awake() && self.display_grace {
+            let declared = keepawake::declare_user_activity("Awake session ended");
+            result = result.and(declared);
+        }
+        if let Err(e) = &result {
+            log_error(e);
+        }
+        self.publish();
+        result
+    }
+
+    fn status(&self) -> Status<'_> {
+        Status {
+            kinds: if self.is_awake() {
+                self.held
+            } else {
+                self.kinds
+            },
+            source: self.source.as_ref(),
+            expiry: self.expiry(),
+            override_until: self.override_timer.expiry(),
+            triggers: &self.triggers,
+            overnight: self.overnight,
+            mode_return: self
+                .mode_timer
+                .expiry()
+                .map(|until| (until, self.mode_before)),
+        }
+    }
+
+    // After every command, so the icon, tooltip and status file never lag
+    fn publish(&self) {
+        let status = self.status();
+        OBSERVERS.with(|observers| {
+            for observer in observers.borrow().iter() {
+                observer(&status);
+            }
+        });
+    }
+
+    fn activate(&mut self, source: Source) -> Result<(), AwakeError> {
+        if self.is_awake() {
+            return Ok(());
+        }
+
+        let kinds = self.kinds_for(&source);
+        self.session = Some(PowerSession::begin(kinds, "Awake App")?);
+        self.held = kinds;
+        event_log::log(event_log::Event::Activated {
+            mode: &kinds.to_string(),
+            source: source.kind(),
+            reason: &source.describe(),
+        });
+        self.source = Some(source);
+        self.started_at = Some(crate::now_secs());
+        self.enforce_max_session();
+        self.arm_reminder();
+        Ok(())
+    }
+
+    // A trigger with a mode of its own holds that; anything else, the menu's
+    fn kinds_for(&self, source: &Source) -> KindSet {
+        let trigger_kinds = match source {
+            Source::Trigger(name) => self
+                .triggers
+                .iter()
+                .find(|t| &t.name == name)
+                .and_then(|t| t.kinds),
+            _ => None,
+        };
+        trigger_kinds.unwrap_or(self.kinds)
+    }
+
+    // The running session now belongs to `source`
+    fn hand_over(&mut self, source: Source) -> Result<(), AwakeError> {
+        let kinds = self.kinds_for(&source);
+        self.source = Some(source);
+        self.hold(kinds)
+    }
+
+    // The running session, timer and all, goes on holding `kinds`; any new
+    // assertions are taken before the old ones go, leaving no gap
+    fn hold(&mut self, kinds: KindSet) -> Result<(), AwakeError> {
+        let Some(session) = self.session.as_mut() else {
+            return Ok(());
+        };
+        if kinds == self.held {
+            return Ok(());
+        }
+        let changed = session.change_kinds(kinds, "Awake App");
+        // Only a failed release leaves the new kinds held
+        if !matches!(changed, Err(AwakeError::AssertionFailed { .. })) {
+            self.held = kinds;
+            event_log::log(event_log::Event::ModeChanged {
+                mode: &kinds.to_string(),
+            });
+        }
+        changed
+    }
+
+    fn arm_reminder(&mut self) {
+        self.stop_reminder();
+        let Some(seconds) = self.remind_every else {
+            return;
+        };
+        let token = self.reminder_timer.start(crate::now_secs(), seconds);
+        self.reminder_waiter = Some(Waiter::spawn(token, seconds, |token| {
+            post(Command::ReminderDue(token))
+        }));
+    }
+
+    fn stop_reminder(&mut self) {
+        self.reminder_timer.cancel();
+        if let Some(waiter) = self.reminder_waiter.take() {
+            waiter.cancel();
+        }
+    }
+
+    // Quiet hours keep it silent. Either way the next reminder is armed.
+    fn remind(&mut self) {
+        if let Some(hours) = self.open_ended_hours() {
+            if !quiet_now() {
+                frontend::remind(hours);
+            }
+        }
+        self.arm_reminder();
+    }
+
+    // Whole hours an open-ended session has run. Only one can be forgotten;
+    // a timed one ends by itself, paused or not.
+    fn open_ended_hours(&self) -> Option<u64> {
+        match (self.expiry(), self.started_at) {
+            (None, Some(started)) => Some(crate::now_secs().saturating_sub(started) / 3600),
+            _ => None,
+        }
+    }
+
+    // When the session's timer ends, counting a paused one as if it
+    // carried on now
+    fn expiry(&self) -> Option<u64> {
+        let paused = self
+            .paused
+            .map(|left| crate::now_secs().saturating_add(left));
+        match (self.timer.expiry(), paused) {
+            (Some(expiry), Some(paused)) => Some(expiry.min(paused)),
+            (expiry, paused) => expiry.or(paused),
+        }
+    }
+
+    // When the maximum session length ends the running session
+    fn cap(&self) -> Option<u64> {
+        self.max_session
+            .zip(self.started_at)
+            .map(|(max, started)| started.saturating_add(max))
+    }
+
+    // Under a maximum session length even an indefinite, watched or triggered
+    // session gets a countdown, and no timer or extension runs past the cap
+    fn enforce_max_session(&mut self) {
+        let Some(cap) = self.cap() else {
+            return;
+        };
+        if let Some(token) = self.timer.limit(cap) {
+            let wait = token.expiry.saturating_sub(crate::now_secs());
+            self.arm(token, wait);
+        }
+    }
+
+    // Always ends up asleep; a failed release is still reported
+    fn deactivate(&mut self) -> Result<(), AwakeError> {
+        self.stop_timer();
+        self.stop_watch();
+        self.source = None;
+        self.started_at = None;
+        self.stop_reminder();
+        match self.session.take() {
+            Some(session) => {
+                let result = session.end();
+                event_log::log(event_log::Event::Deactivated);
+                result
+            }
+            None => Ok(()),
+        }
+    }
+
+    // A timer has run out or a watch has finished. Overnight, the session
+    // carries on while the battery charges; that watch ending (or the maximum
+    // session length running out meanwhile) ends it.
+    fn job_done(&mut self) -> Result<(), AwakeError> {
+        match self.overnight {
+            Some(percent) if !matches!(self.source, Some(Source::Charging(_))) => {
+                self.stop_timer();
+                self.stop_watch();
+                let result = self.hand_over(Source::Charging(percent));
+                self.start_watch(Target::Charging(percent));
+                self.enforce_max_session();
+                result
+            }
+            _ => self.deactivate(),
+        }
+    }
+
+    // Switching on by hand is open-ended, except on battery power with the
+    // battery timer on
+    fn switch_on(&mut self, on_battery: bool) -> Result<(), AwakeError> {
+        if self.battery_timer && on_battery {
+            return self.activate_for(BATTERY_TIMER_MINUTES * 60);
+        }
+        self.activate(Source::Manual)
+    }
+
+    fn activate_for(&mut self, seconds: u64) -> Result<(), AwakeError> {
+        let released = self.deactivate();
+        if let Err(activated) = self.activate(Source::Manual) {
+            // The first failure is the one returned; the other is still logged
+            if released.is_err() {
+                log_error(&activated);
+            }
+            return released.and(Err(activated));
+        }
+
+        let token = self.timer.start(crate::now_secs(), seconds);
+        self.arm(token, seconds);
+        self.enforce_max_session();
+        released
+    }
+
+    // Only meaningful for a timed session; indefinite sessions are left alone.
+    // A paused timer gets the time when it resumes, up to the cap.
+    fn extend(&mut self, seconds: u64) {
+        if let Some(left) = self.paused {
+            let until_cap = self.cap().map(|cap| cap.saturating_sub(crate::now_secs()));
+            self.paused = Some(
+                left.saturating_add(seconds)
+                    .min(until_cap.unwrap_or(u64::MAX)),
+            );
+            return;
+        }
+        if let Some(token) = self.timer.extend(seconds) {
+            let wait = token.expiry.saturating_sub(crate::now_secs());
+            self.arm(token, wait);
+        }
+    }
+
+    // Changed in place: a running session keeps its timer, watch, reminder
+    // and start time, and only the assertions that differ are swapped
+    fn set_mode(&mut self, kinds: KindSet) -> Result<(), AwakeError> {
+        // A session with nothing in it would look awake and keep nothing awake
+        if kinds.is_empty() {
+            return Ok(());
+        }
+
+        self.kinds = kinds;
+        frontend::show_mode(kinds);
+        match &self.source {
+            Some(source) => self.hold(self.kinds_for(source)),
+            None => {
+                event_log::log(event_log::Event::ModeChanged {
+                    mode: &kinds.to_string(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    // Another time box replaces the first but still goes back to the mode
+    // from before either
+    fn set_mode_for(&mut self, kinds: KindSet, seconds: u64) -> Result<(), AwakeError> {
+        if kinds.is_empty() {
+            return Ok(());
+        }
+        let before = if self.mode_timer.expiry().is_some() {
+            self.mode_before
+        } else {
+            self.kinds
+        };
+        self.stop_mode_override();
+        let result = self.set_mode(kinds);
+        self.mode_before = before;
+        let token = self.mode_timer.start(crate::now_secs(), seconds);
+        self.mode_waiter = Some(Waiter::spawn(token, seconds, |token| {
+            post(Command::ModeOverrideElapsed(token))
+        }));
+        result
+    }
+
+    fn stop_mode_override(&mut self) {
+        self.mode_timer.cancel();
+        if let Some(waiter) = self.mode_waiter.take() {
+            waiter.cancel();
+        }
+    }
+
+    // Like a timed session, but it ends when the watch says so
+    fn watch(&mut self, path: PathBuf) -> Result<(), AwakeError> {
+        self.stop_timer();
+        self.stop_watch();
+        let result = if self.is_awake() {
+            // A session already running (manual or triggered) now belongs to the watch
+            let result = self.hand_over(Source::Watch(path.clone()));
+            self.enforce_max_session();
+            result
+        } else {
+            self.activate(Source::Watch(path.clone()))?;
+            Ok(())
+        };
+
+        self.start_watch(Target::for_path(path));
+        result
+    }
+
+    fn start_watch(&mut self, target: Target) {
+        self.watch_generation += 1;
+        let generation = self.watch_generation;
+        self.watch = Some(Watch::spawn(target, move || {
+            post(Command::WatchFinished { generation })
+        }));
+    }
+
+    fn trigger_changed(&mut self, name: String, satisfied: bool) -> Result<(), AwakeError> {
+        self.update_trigger(name, |t| t.satisfied = satisfied)
+    }
+
+    // Applies at once to a session the trigger is holding
+    fn set_trigger_mode(&mut self, name: String, kinds: Option<KindSet>) -> Result<(), AwakeError> {
+        if kinds.is_some_and(|kinds| kinds.is_empty()) {
+            return Ok(());
+        }
+        let holding = self.source == Some(Source::Trigger(name.clone()));
+        self.update_trigger(name.clone(), |t| t.kinds = kinds)?;
+        if holding {
+            self.hand_over(Source::Trigger(name))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Only edges matter: a trigger that was already active when the user
+    // switched Awake off doesn't switch it straight back on
+    fn update_trigger(
+        &mut self,
+        name: String,
+        update: impl FnOnce(&mut Trigger),
+    ) -> Result<(), AwakeError> {
+        let index = match self.triggers.iter().position(|t| t.name == name) {
+            Some(index) => index,
+            None => {
+                self.triggers.push(Trigger {
+                    name: name.clone(),
+                    satisfied: false,
+                    enabled: true,
+                    kinds: None,
+                    minutes: None,
+                });
+                self.triggers.len() - 1
+            }
+        };
+        let was_active = self.triggers[index].is_active();
+        update(&mut self.triggers[index]);
+
+        match (was_active, self.triggers[index].is_active()) {
+            (false, true) => {
+                if self.is_awake() || quiet_now() || self.override_timer.expiry().is_some() {
+                    return Ok(());
+                }
+                self.activate_trigger(name)
+            }
+            (true, false) if self.source == Some(Source::Trigger(name)) => {
+                match self.first_active_trigger() {
+                    // Still held by another trigger; credit that one now
+                    Some(next) => self.hand_over(Source::Trigger(next)),
+                    None => self.deactivate(),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // A trigger with a time limit starts a timed session, which it doesn't
+    // restart until it next becomes satisfied
+    fn activate_trigger(&mut self, name: String) -> Result<(), AwakeError> {
+        let minutes = self
+            .triggers
+            .iter()
+            .find(|t| t.name == name)
+            .and_then(|t| t.minutes);
+        self.activate(Source::Trigger(name))?;
+        if let Some(minutes) = minutes {
+            let seconds = minutes.saturating_mul(60);
+            let token = self.timer.start(crate::now_secs(), seconds);
+            self.arm(token, seconds);
+            self.enforce_max_session();
+        }
+        Ok(())
+    }
+
+    fn first_active_trigger(&self) -> Option<String> {
+        self.triggers
+            .iter()
+            .find(|t| t.is_active())
+            .map(|t| t.name.clone())
+    }
+
+    // Switching off by hand; under `Policy::Override` that also holds the
+    // triggers off for a while
+    fn switch_off(&mut self) -> Result<(), AwakeError> {
+        let result = self.deactivate();
+        if let Policy::Override { minutes } = self.policy {
+            let seconds = minutes.saturating_mul(60);
+            let token = self.override_timer.start(crate::now_secs(), seconds);
+            if let Some(waiter) = self.override_waiter.take() {
+                waiter.cancel();
+            }
+            self.override_waiter = Some(Waiter::spawn(token, seconds, |token| {
+                post(Command::OverrideElapsed(token))
+            }));
+        }
+        result
+    }
+
+    fn stop_override(&mut self) {
+        self.override_timer.cancel();
+        if let Some(waiter) = self.override_waiter.take() {
+            waiter.cancel();
+        }
+    }
+
+    // The override is over: a trigger that held throughout takes over again
+    fn resume_triggers(&mut self) -> Result<(), AwakeError> {
+        match self.first_active_trigger() {
+            Some(name) if !self.is_awake() && !quiet_now() => self.activate_trigger(name),
+            _ => Ok(()),
+        }
+    }
+
+    fn stop_watch(&mut self) {
+        if let Some(watch) = self.watch.take() {
+            watch.cancel();
+        }
+    }
+
+    // The waiter only sleeps; whether its token still counts is decided back here
+    fn arm(&mut self, token: Token, wait_secs: u64) {
+        self.stop_timer_thread();
+        self.waiter = Some(Waiter::spawn(token, wait_secs, |token| {
+            post(Command::TimerElapsed(token))
+        }));
+    }
+
+    fn stop_timer(&mut self) {
+        self.timer.cancel();
+        self.stop_timer_thread();
+        self.paused = None;
+    }
+
+    // Only a timer of a session holding the display pauses; the maximum
+    // session length, counted from the start, doesn't, and stays armed
+    fn pause_timer(&mut self) {
+        if self.paused.is_some() || !self.held.contains(keepawake::Kind::Display) {
+            return;
+        }
+        let Some(expiry) = self.timer.expiry() else {
+            return;
+        };
+        // The countdown is the cap's own
+        if self.cap().is_some_and(|cap| expiry >= cap) {
+            return;
+        }
+        self.stop_timer();
+        self.paused = Some(expiry.saturating_sub(crate::now_secs()));
+        self.enforce_max_session();
+    }
+
+    fn resume_timer(&mut self) {
+        let Some(seconds) = self.paused.take() else {
+            return;
+        };
+        if self.is_awake() {
+            let token = self.timer.start(crate::now_secs(), seconds);
+            self.arm(token, seconds);
+            self.enforce_max_session();
+        }
+    }
+
+    fn stop_timer_thread(&mut self) {
+        // Don't join — the thread exits promptly via its condvar
+        if let Some(waiter) = self.waiter.take() {
+            waiter.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+
+    // With the dry-run backend, so nothing is held for real
+    fn state() -> State {
+        static DRY_RUN: Once = Once::new();
+        DRY_RUN.call_once(|| keepawake::init(true));
+        State::new()
+    }
+
+    // Within a second or two of `secs` from now, as the clock may tick
+    fn in_about(expiry: Option<u64>, secs: u64) -> bool {
+        let at = crate::now_secs() + secs;
+        expiry.is_some_and(|expiry| expiry.abs_diff(at) <= 2)
+    }
+
+    #[test]
+    fn on_battery_power_the_battery_timer_switches_on_for_an_hour() {
+        let mut state = state();
+        state.handle(Command::SetBatteryTimer(true)).unwrap();
+        state.switch_on(true).unwrap();
+        assert!(in_about(state.timer.expiry(), BATTERY_TIMER_MINUTES * 60));
+        state.deactivate().unwrap();
+
+        state.switch_on(false).unwrap();
+        assert!(state.is_awake());
+        assert_eq!(state.timer.expiry(), None);
+        state.deactivate().unwrap();
+
+        state.handle(Command::SetBatteryTimer(false)).unwrap();
+        state.switch_on(true).unwrap();
+        assert_eq!(state.timer.expiry(), None);
+    }
+
+    // Lengths from links, settings and rules can be anything; each saturates
+    // at the end of time rather than overflowing
+    #[test]
+    fn huge_lengths_run_to_the_end_of_time() {
+        let huge = Some(u64::MAX);
+        let build = || "build".to_string();
+        type Expiry = fn(&State) -> Option<u64>;
+        let cases: Vec<(Vec<Command>, Expiry)> = vec![
+            (vec![Command::ActivateFor { minutes: u64::MAX }], |s| {
+                s.timer.expiry()
+            }),
+            (vec![Command::SetMaxSession(huge), Command::Activate], |s| {
+                s.timer.expiry()
+            }),
+            (vec![Command::SetReminder(huge), Command::Activate], |s| {
+                s.reminder_timer.expiry()
+            }),
+            (
+                vec![
+                    Command::SetTriggerPolicy(Policy::Override { minutes: u64::MAX }),
+                    Command::Activate,
+                    Command::Toggle,
+                ],
+                |s| s.override_timer.expiry(),
+            ),
+            (
+                vec![
+                    Command::SetTriggerLimit {
+                        name: build(),
+                        minutes: huge,
+                    },
+                    Command::TriggerChanged {
+                        name: build(),
+                        satisfied: true,
+                    },
+                ],
+                |s| s.timer.expiry(),
+            ),
+            (
+                vec![Command::SetModeFor {
+                    kinds: Mode::Server.kinds(),
+                    minutes: u64::MAX,
+                }],
+                |s| s.mode_timer.expiry(),
+            ),
+        ];
+        for (commands, expiry) in cases {
+            let mut state = state();
+            for command in commands {
+                state.handle(command).unwrap();
+            }
+            assert_eq!(expiry(&state), huge);
+        }
+    }
+
+    #[test]
+    fn changing_the_mode_keeps_the_timer() {
+        let mut state = state();
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        let expiry = state.timer.expiry();
+        state
+            .handle(Command::SetMode(Mode::Display.kinds()))
+            .unwrap();
+        assert_eq!(state.held, Mode::Display.kinds());
+        assert_eq!(state.source, Some(Source::Manual));
+        assert_eq!(state.timer.expiry(), expiry);
+        assert!(state.waiter.is_some());
+    }
+
+    #[test]
+    fn a_time_boxed_mode_goes_back_to_the_one_before() {
+        let mut state = state();
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        let expiry = state.timer.expiry();
+        let kinds = Mode::Server.kinds();
+        state
+            .handle(Command::SetModeFor { kinds, minutes: 60 })
+            .unwrap();
+        assert_eq!(state.held, kinds);
+        assert_eq!(
+            state.status().mode_return.map(|(_, kinds)| kinds),
+            Some(Mode::Both.kinds())
+        );
+
+        // Runs the time box out now
+        let token = state.mode_timer.limit(crate::now_secs()).unwrap();
+        state.handle(Command::ModeOverrideElapsed(token)).unwrap();
+        assert_eq!(state.held, Mode::Both.kinds());
+        assert_eq!(state.status().mode_return, None);
+        assert_eq!(state.timer.expiry(), expiry);
+    }
+
+    #[test]
+    fn a_paused_timer_survives_a_change_of_mode() {
+        let mut state = state();
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        state.handle(Command::DisplayAsleep(true)).unwrap();
+        state
+            .handle(Command::SetMode(Mode::Display.kinds()))
+            .unwrap();
+        state.handle(Command::DisplayAsleep(false)).unwrap();
+        assert!(in_about(state.timer.expiry(), 30 * 60));
+    }
+
+    #[test]
+    fn a_timer_pauses_while_the_displays_sleep() {
+        let mut state = state();
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        state.handle(Command::DisplayAsleep(true)).unwrap();
+        assert_eq!(state.timer.expiry(), None);
+        assert!(in_about(state.status().expiry, 30 * 60));
+        // Still a timed session, so not one to be reminded of
+        assert_eq!(state.open_ended_hours(), None);
+
+        state.handle(Command::DisplayAsleep(false)).unwrap();
+        assert_eq!(state.paused, None);
+        assert!(in_about(state.timer.expiry(), 30 * 60));
+    }
+
+    #[test]
+    fn the_maximum_session_length_never_pauses() {
+        let mut state = state();
+        state.handle(Command::SetMaxSession(Some(120))).unwrap();
+        state.handle(Command::Activate).unwrap();
+        state.handle(Command::DisplayAsleep(true)).unwrap();
+        assert_eq!(state.paused, None);
+        assert!(in_about(state.timer.expiry(), 120 * 60));
+        state.handle(Command::DisplayAsleep(false)).unwrap();
+
+        // A shorter timer pauses, with the cap still armed behind it
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        state.handle(Command::DisplayAsleep(true)).unwrap();
+        assert!(in_about(state.timer.expiry(), 120 * 60));
+        assert!(in_about(state.status().expiry, 30 * 60));
+    }
+
+    #[test]
+    fn extending_a_paused_timer_adds_to_what_is_left() {
+        let mut state = state();
+        state.handle(Command::SetMaxSession(Some(120))).unwrap();
+        state.handle(Command::ActivateFor { minutes: 30 }).unwrap();
+        state.handle(Command::DisplayAsleep(true)).unwrap();
+        state.handle(Command::Extend { seconds: 15 * 60 }).unwrap();
+        assert!(in_about(state.status().expiry, 45 * 60));
+        // The cap's countdown is left where it was
+        assert!(in_about(state.timer.expiry(), 120 * 60));
+
+        // But no further than the cap
+        state.handle(Command::Extend { seconds: 200 * 60 }).unwrap();
+        assert!(in_about(state.status().expiry, 120 * 60));
+        state.handle(Command::DisplayAsleep(false)).unwrap();
+        assert!(in_about(state.timer.expiry(), 120 * 60));
+    }
+}
@@ -0,0 +1,346 @@
+//! Awake time over the last day and week, read back from the event log, and a
+//! rough figure for the energy that cost — for the Statistics submenu.
+//!
+//! The log also suggests a timer: when sessions switched on by hand have
+//! started around this time of day on several days lately, the menu offers
+//! one of their usual length.
+
+use std::fs;
+
+use crate::event_log::{self, json_field};
+
+const DAY_SECS: u64 = 24 * 3600;
+const WEEK_SECS: u64 = 7 * DAY_SECS;
+
+// A suggestion looks back this far, at sessions that started within this
+// long of now's time of day, and needs them on this many days
+const HISTORY_SECS: u64 = 4 * WEEK_SECS;
+const AROUND_SECS: i64 = 30 * 60;
+const MIN_DAYS: usize = 3;
+// Suggested lengths are rounded to this
+const ROUND_MINUTES: u64 = 15;
+
+// Rough extra draw over sleeping, in watts: a laptop kept out of idle sleep,
+// and one with its display lit as well
+const SYSTEM_WATTS: f64 = 6.0;
+const DISPLAY_WATTS: f64 = 10.0;
+
+/// One stretch of being awake, from the log's `activated` to what came next
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span {
+    start: u64,
+    end: u64,
+    watts: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub day_secs: u64,
+    pub week_secs: u64,
+    pub week_wh: f64,
+}
+
+impl Stats {
+    /// The submenu's lines
+    pub fn lines(&self) -> [String; 3] {
+        [
+            format!("Last 24 hours: {}", duration(self.day_secs)),
+            format!("Last 7 days: {}", duration(self.week_secs)),
+            format!("Extra energy, 7 days: \u{2248} {}", energy(self.week_wh)),
+        ]
+    }
+}
+
+fn log() -> String {
+    event_log::files()
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .collect()
+}
+
+/// Reads the event log; `awake_now` says whether the last session is still going
+pub fn read(awake_now: bool) -> Stats {
+    let log = log();
+    summarize(
+        &spans(&log, awake_now, crate::now_secs()),
+        crate::now_secs(),
+    )
+}
+
+fn watts(mode: &str) -> f64 {
+    let lit = mode
+        .split('+')
+        .any(|kind| kind == "display" || kind == "user-activity");
+    if lit {
+        DISPLAY_WATTS
+    } else {
+        SYSTEM_WATTS
+    }
+}
+
+// A session runs until the next event that ends it, split where its mode
+// changed; one cut short by a crash ends at the next activation
+fn spans(log: &str, awake_now: bool, now: u64) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut open: Option<(u64, f64)> = None;
+    for line in log.lines() {
+        let (Some(ts), Some(event)) = (
+            json_field(line, "ts").and_then(|ts| ts.parse().ok()),
+            json_field(line, "event"),
+        ) else {
+            continue;
+        };
+        let reopen = match event.as_str() {
+            "activated" => true,
+            "mode_changed" => open.is_some(),
+            "deactivated" => false,
+            _ => continue,
+        };
+        if let Some((start, watts)) = open.take() {
+            spans.push(Span {
+                start,
+                end: ts,
+                watts,
+            });
+        }
+        if reopen {
+            open = Some((ts, watts(&json_field(line, "mode").unwrap_or_default())));
+        }
+    }
+    if let (Some((start, watts)), true) = (open, awake_now) {
+        spans.push(Span {
+            start,
+            end: now,
+            watts,
+        });
+    }
+    spans
+}
+
+// Sessions switched on by hand, from activation to whatever ended them; mode
+// changes don't
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn manual_sessions(log: &str) -> Vec<(u64, u64)> {
+    let mut sessions = Vec::new();
+    let mut open: Option<u64> = None;
+    for line in log.lines() {
+        let (Some(ts), Some(event)) = (
+            json_field(line, "ts").and_then(|ts| ts.parse().ok()),
+            json_field(line, "event"),
+        ) else {
+            continue;
+        };
+        if !matches!(event.as_str(), "activated" | "deactivated") {
+            continue;
+        }
+        if let Some(start) = open.take() {
+            sessions.push((start, ts));
+        }
+        if event == "activated" && json_field(line, "source").as_deref() == Some("manual") {
+            open = Some(ts);
+        }
+    }
+    sessions
+}
+
+/// The usual length, in minutes, of the manual sessions that started around
+/// now's time of day on at least `MIN_DAYS` days of the last few weeks. Local
+/// time is `utc_offset` seconds ahead of UTC.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn suggest(sessions: &[(u64, u64)], now: u64, utc_offset: i64) -> Option<u64> {
+    let local = |ts: u64| ts as i64 + utc_offset;
+    let time_of_day = |ts: u64| local(ts).rem_euclid(DAY_SECS as i64);
+    let mut lengths = Vec::new();
+    let mut days = Vec::new();
+    for &(start, end) in sessions {
+        let apart = (time_of_day(start) - time_of_day(now)).abs();
+        let apart = apart.min(DAY_SECS as i64 - apart);
+        if start + HISTORY_SECS < now || start > now || apart > AROUND_SECS {
+            continue;
+        }
+        lengths.push(end.saturating_sub(start));
+        let day = local(start).div_euclid(DAY_SECS as i64);
+        if !days.contains(&day) {
+            days.push(day);
+        }
+    }
+    if days.len() < MIN_DAYS {
+        return None;
+    }
+    lengths.sort_unstable();
+    let median = lengths[lengths.len() / 2] / 60;
+    let rounded = (median + ROUND_MINUTES / 2) / ROUND_MINUTES * ROUND_MINUTES;
+    Some(rounded.max(ROUND_MINUTES))
+}
+
+/// A timer to offer now, in minutes, from the event log
+#[cfg(target_os = "macos")]
+pub fn suggestion(utc_offset: i64) -> Option<u64> {
+    suggest(&manual_sessions(&log()), crate::now_secs(), utc_offset)
+}
+
+/// "You usually stay awake 2 h at this time — Start Now"
+#[cfg(target_os = "macos")]
+pub fn suggestion_title(minutes: u64) -> String {
+    let length = match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{} min", minutes),
+        (hours, 0) => format!("{} h", hours),
+        (hours, minutes) => format!("{} h {} min", hours, minutes),
+    };
+    format!(
+        "You usually stay awake {} at this time \u{2014} Start Now",
+        length
+    )
+}
+
+fn summarize(spans: &[Span], now: u64) -> Stats {
+    let within = |span: &Span, window: u64| {
+        let since = now.saturating_sub(window);
+        span.end.min(now).saturating_sub(span.start.max(since))
+    };
+    let mut stats = Stats::default();
+    for span in spans {
+        stats.day_secs += within(span, DAY_SECS);
+        let week = within(span, WEEK_SECS);
+        stats.week_secs += week;
+        stats.week_wh += span.watts * week as f64 / 3600.0;
+    }
+    stats
+}
+
+fn duration(secs: u64) -> String {
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    if hours == 0 {
+        format!("{} min", minutes)
+    } else {
+        format!("{} h {} min", hours, minutes)
+    }
+}
+
+fn energy(wh: f64) -> String {
+    if wh >= 1000.0 {
+        format!("{:.1} kWh", wh / 1000.0)
+    } else {
+        format!("{:.0} Wh", wh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"{"ts":1000,"event":"activated","mode":"system","source":"manual","reason":"turned on manually"}
+{"ts":4600,"event":"deactivated"}
+{"ts":5000,"event":"activated","mode":"display+system","source":"manual","reason":"turned on manually"}
+{"ts":6800,"event":"activated","mode":"system","source":"manual","reason":"turned on manually"}
+{"ts":7000,"event":"mode_changed","mode":"display"}"#;
+
+    #[test]
+    fn log_reads_back_as_spans() {
+        assert_eq!(
+            spans(LOG, false, 9000)[..2],
+            [
+                Span {
+                    start: 1000,
+                    end: 4600,
+                    watts: SYSTEM_WATTS
+                },
+                // No deactivation logged: cut short at the next activation
+                Span {
+                    start: 5000,
+                    end: 6800,
+                    watts: DISPLAY_WATTS
+                },
+            ]
+        );
+        // Still going, and split where its mode changed
+        assert_eq!(
+            spans(LOG, true, 9000)[2..],
+            [
+                Span {
+                    start: 6800,
+                    end: 7000,
+                    watts: SYSTEM_WATTS
+                },
+                Span {
+                    start: 7000,
+                    end: 9000,
+                    watts: DISPLAY_WATTS
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_window_counts() {
+        let now = WEEK_SECS + DAY_SECS;
+        let spans = [
+            // Half of it more than a week ago
+            Span {
+                start: DAY_SECS - 1800,
+                end: DAY_SECS + 1800,
+                watts: 10.0,
+            },
+            Span {
+                start: now - 3600,
+                end: now,
+                watts: 6.0,
+            },
+        ];
+        let stats = summarize(&spans, now);
+        assert_eq!(stats.day_secs, 3600);
+        assert_eq!(stats.week_secs, 1800 + 3600);
+        assert_eq!(stats.week_wh, 5.0 + 6.0);
+    }
+
+    #[test]
+    fn habits_suggest_a_timer() {
+        let day = DAY_SECS;
+        let nine = 9 * 3600;
+        let now = 30 * day + nine;
+        // Two hours from about nine on three days, once with a mode change
+        let log = format!(
+            r#"{{"ts":{},"event":"activated","mode":"system","source":"manual","reason":"turned on manually"}}
+{{"ts":{},"event":"mode_changed","mode":"display"}}
+{{"ts":{},"event":"deactivated"}}
+{{"ts":{},"event":"activated","mode":"system","source":"manual","reason":"turned on manually"}}
+{{"ts":{},"event":"deactivated"}}
+{{"ts":{},"event":"activated","mode":"system","source":"trigger","reason":"while ssh"}}
+{{"ts":{},"event":"deactivated"}}
+{{"ts":{},"event":"activated","mode":"system","source":"manual","reason":"turned on manually"}}
+{{"ts":{},"event":"deactivated"}}"#,
+            27 * day + nine,
+            27 * day + nine + 600,
+            27 * day + nine + 7200,
+            28 * day + nine - 900,
+            28 * day + nine + 6300,
+            28 * day + nine + 7000,
+            28 * day + nine + 9000,
+            29 * day + nine + 1200,
+            29 * day + nine + 1200 + 7500,
+        );
+        let sessions = manual_sessions(&log);
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(suggest(&sessions, now, 0), Some(120));
+        // Nothing at noon, and two days aren't a habit
+        assert_eq!(suggest(&sessions, now + 3 * 3600, 0), None);
+        assert_eq!(suggest(&sessions[1..], now, 0), None);
+    }
+
+    #[test]
+    fn lines_read_naturally() {
+        let stats = Stats {
+            day_secs: 45 * 60,
+            week_secs: 26 * 3600 + 5 * 60,
+            week_wh: 1400.0,
+        };
+        assert_eq!(
+            stats.lines(),
+            [
+                "Last 24 hours: 45 min".to_string(),
+                "Last 7 days: 26 h 5 min".into(),
+                "Extra energy, 7 days: \u{2248} 1.4 kWh".into(),
+            ]
+        );
+    }
+}
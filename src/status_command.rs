@@ -0,0 +1,401 @@
+//! `awake status` — prints the running app's state from `status.json`: a line
+//! for people, `--porcelain` fields for scripts, a glyph and the time left for
+//! a shell prompt or tmux status line with `--prompt`, the running build's
+//! architecture with `--arch`, nothing at all with `--quiet`, or, with
+//! `--alfred` or `--raycast`, the JSON a launcher extension lists directly.
+//! The line for people is followed by a hint when the app runs under Rosetta.
+//!
+//! The exit code is the state: `EXIT_ACTIVE`, `EXIT_INACTIVE`, or `EXIT_ERROR`
+//! when the status can't be read. Launcher output always exits 0 once
+//! printed, since launchers treat anything else as a failed script. Each
+//! launcher item carries an argument naming what it stands for (`toggle`,
+//! `for:60` for an hour's timer) for the extension to act on.
+
+use std::env;
+use std::fs;
+
+use crate::duration;
+use crate::event_log::{json_field, json_string};
+
+const EXIT_ACTIVE: i32 = 0;
+const EXIT_INACTIVE: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+// `--prompt`'s glyphs, each overridden by an environment variable; off shows
+// nothing unless asked to
+const PROMPT_ON: (&str, &str) = ("AWAKE_PROMPT_ON", "\u{2615}");
+const PROMPT_OFF: (&str, &str) = ("AWAKE_PROMPT_OFF", "");
+
+/// The launcher actions offered besides toggling: argument and title
+const TIMERS: [(&str, &str); 3] = [
+    ("for:15", "Keep Awake for 15 Minutes"),
+    ("for:60", "Keep Awake for 1 Hour"),
+    ("for:120", "Keep Awake for 2 Hours"),
+];
+
+/// What `status.json` says
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Snapshot {
+    awake: bool,
+    mode: String,
+    source: Option<String>,
+    reason: Option<String>,
+    expires: Option<u64>,
+    triggers_paused_until: Option<u64>,
+    /// Absent from files written before the app recorded it
+    arch: Option<String>,
+    translated: bool,
+}
+
+impl Snapshot {
+    fn parse(json: &str) -> Option<Snapshot> {
+        let secs = |key| json_field(json, key).and_then(|v| v.parse().ok());
+        Some(Snapshot {
+            awake: json_field(json, "awake")? == "true",
+            mode: json_field(json, "mode")?,
+            source: json_field(json, "source").filter(|s| s != "null"),
+            reason: json_field(json, "reason").filter(|r| r != "null"),
+            expires: secs("expires"),
+            triggers_paused_until: secs("triggers_paused_until"),
+            arch: json_field(json, "arch"),
+            translated: json_field(json, "translated").as_deref() == Some("true"),
+        })
+    }
+
+    // "42 min left", once the timer (or the trigger pause) has a deadline
+    fn time_left(until: Option<u64>, now: u64) -> Option<String> {
+        until.map(|until| format!("{} left", duration::left(until.saturating_sub(now))))
+    }
+
+    /// "On — turned on manually, 42 min left", like the menu's first line
+    fn summary(&self, now: u64) -> String {
+        let (mut line, left) = if self.awake {
+            let reason = self.reason.as_deref().unwrap_or("on");
+            (
+                format!("On \u{2014} {}", reason),
+                Self::time_left(self.expires, now),
+            )
+        } else if self.triggers_paused_until.is_some() {
+            let left = Self::time_left(self.triggers_paused_until, now);
+            ("Off \u{2014} triggers paused".to_string(), left)
+        } else {
+            ("Off".to_string(), None)
+        };
+        if let Some(left) = left {
+            line.push_str(", ");
+            line.push_str(&left);
+        }
+        line
+    }
+
+    /// One tab-separated line whose fields won't change: `on` or `off`, the
+    /// mode, the source, seconds left, and the reason, with `-` for what
+    /// doesn't apply. The reason comes last, as the only field with spaces.
+    fn porcelain(&self, now: u64) -> String {
+        let dash = |field: Option<String>| field.unwrap_or_else(|| "-".into());
+        let on = |field: &Option<String>| field.clone().filter(|_| self.awake);
+        [
+            if self.awake { "on" } else { "off" }.to_string(),
+            self.mode.clone(),
+            dash(on(&self.source)),
+            dash(
+                self.expires
+                    .filter(|_| self.awake)
+                    .map(|expires| expires.saturating_sub(now).to_string()),
+            ),
+            dash(on(&self.reason)),
+        ]
+        .join("\t")
+    }
+
+    /// "☕ 42m", "☕ 1h05m", or just the glyph for an open-ended session
+    fn prompt(&self, now: u64, on_glyph: &str, off_glyph: &str) -> String {
+        if !self.awake {
+            return off_glyph.into();
+        }
+        let Some(expires) = self.expires else {
+            return on_glyph.into();
+        };
+        let left = duration::compact(expires.saturating_sub(now));
+        format!("{} {}", on_glyph, left).trim_start().to_string()
+    }
+
+    /// "arm64", "x86_64 under Rosetta", or "-" if the app didn't say
+    fn arch(&self) -> String {
+        match &self.arch {
+            Some(arch) => crate::arch::describe(arch, self.translated),
+            None => "-".into(),
+        }
+    }
+
+    /// Why a translated build is worth replacing, for people
+    fn rosetta_hint(&self) -> Option<&'static str> {
+        self.translated.then_some(
+            "The Intel build is running under Rosetta, which costs CPU time; \
+             install the Apple silicon or universal build.",
+        )
+    }
+
+    fn toggle_title(&self) -> &'static str {
+        if self.awake {
+            "Turn Off"
+        } else {
+            "Turn On"
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        if self.awake {
+            "on"
+        } else {
+            "off"
+        }
+    }
+}
+
+/// Alfred's Script Filter JSON: the status first, actioned to toggle, then the
+/// timers. Icons are `on.png` and `off.png` in the workflow's folder.
+fn alfred(snapshot: &Snapshot, now: u64) -> String {
+    let item = |uid: &str, title: &str, subtitle: &str, arg: &str| {
+        format!(
+            r#"{{"uid":{},"title":{},"subtitle":{},"arg":{},"icon":{{"path":{}}}}}"#,
+            json_string(uid),
+            json_string(title),
+            json_string(subtitle),
+            json_string(arg),
+            json_string(&format!("{}.png", snapshot.icon()))
+        )
+    };
+    let mut items = vec![item(
+        "status",
+        &snapshot.summary(now),
+        &format!(
+            "{} \u{2014} mode: {}",
+            snapshot.toggle_title(),
+            snapshot.mode
+        ),
+        "toggle",
+    )];
+    for (arg, title) in TIMERS {
+        items.push(item(arg, title, &format!("Mode: {}", snapshot.mode), arg));
+    }
+    format!(r#"{{"items":[{}]}}"#, items.join(","))
+}
+
+/// A list for a Raycast extension, shaped like its `List.Item`s: the status,
+/// with the time left as an accessory and toggling and the timers as actions
+fn raycast(snapshot: &Snapshot, now: u64) -> String {
+    let action = |title: &str, arg: &str| {
+        format!(
+            r#"{{"title":{},"argument":{}}}"#,
+            json_string(title),
+            json_string(arg)
+        )
+    };
+    let mut actions = vec![action(snapshot.toggle_title(), "toggle")];
+    actions.extend(TIMERS.iter().map(|(arg, title)| action(title, arg)));
+    let expires = snapshot.expires.filter(|_| snapshot.awake);
+    let accessories = match Snapshot::time_left(expires, now) {
+        Some(left) => format!(r#"[{{"text":{}}}]"#, json_string(&left)),
+        None => "[]".into(),
+    };
+    format!(
+        r#"{{"items":[{{"id":"status","title":{},"subtitle":{},"icon":{},"accessories":{},"actions":[{}]}}]}}"#,
+        json_string(&snapshot.summary(now)),
+        json_string(&format!("Mode: {}", snapshot.mode)),
+        json_string(snapshot.icon()),
+        accessories,
+        actions.join(",")
+    )
+}
+
+// " (until 15:42)" after a timer's line, in the locale's clock
+#[cfg(target_os = "macos")]
+fn ends_at(snapshot: &Snapshot) -> String {
+    match snapshot.expires.filter(|_| snapshot.awake) {
+        Some(expires) => format!(" (until {})", crate::clock::at(expires)),
+        None => String::new(),
+    }
+}
+
+// Elsewhere the local time isn't read, so only the time left is given
+#[cfg(not(target_os = "macos"))]
+fn ends_at(_snapshot: &Snapshot) -> String {
+    String::new()
+}
+
+/// Prints `json`, another machine's `status.json`, as a line; returns the
+/// exit code
+pub fn show(json: &str) -> i32 {
+    let Some(snapshot) = Snapshot::parse(json) else {
+        eprintln!("awake: can't read the status");
+        return EXIT_ERROR;
+    };
+    println!("{}", snapshot.summary(crate::now_secs()));
+    if snapshot.awake {
+        EXIT_ACTIVE
+    } else {
+        EXIT_INACTIVE
+    }
+}
+
+/// Prints the status in the format `args` ask for; returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    if args.len() > 1 {
+        crate::usage();
+    }
+    let Some(path) = crate::app_support_dir().map(|dir| dir.join("status.json")) else {
+        eprintln!("awake: no home directory");
+        return EXIT_ERROR;
+    };
+    // Nothing written yet means Awake has never run, so nothing is held
+    let snapshot = match fs::read_to_string(&path) {
+        Ok(json) => match Snapshot::parse(&json) {
+            Some(snapshot) => snapshot,
+            None => {
+                eprintln!("awake: can't read {}", path.display());
+                return EXIT_ERROR;
+            }
+        },
+        Err(_) => Snapshot {
+            mode: "none".into(),
+            ..Snapshot::default()
+        },
+    };
+
+    let now = crate::now_secs();
+    match args.first().map(String::as_str) {
+        None => {
+            println!("{}{}", snapshot.summary(now), ends_at(&snapshot));
+            if let Some(hint) = snapshot.rosetta_hint() {
+                println!("{}", hint);
+            }
+        }
+        Some("--arch") => println!("{}", snapshot.arch()),
+        Some("--quiet") => {}
+        Some("--porcelain") => println!("{}", snapshot.porcelain(now)),
+        Some("--prompt") => {
+            let glyph = |(var, default): (&str, &str)| {
+                env::var(var).unwrap_or_else(|_| default.to_string())
+            };
+            println!(
+                "{}",
+                snapshot.prompt(now, &glyph(PROMPT_ON), &glyph(PROMPT_OFF))
+            );
+        }
+        Some("--alfred") => {
+            println!("{}", alfred(&snapshot, now));
+            return 0;
+        }
+        Some("--raycast") => {
+            println!("{}", raycast(&snapshot, now));
+            return 0;
+        }
+        Some(_) => crate::usage(),
+    }
+    if snapshot.awake {
+        EXIT_ACTIVE
+    } else {
+        EXIT_INACTIVE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMED: &str = r#"{"ts":7,"awake":true,"mode":"display+system","source":"manual","reason":"turned on \"manually\"","expires":3607,"triggers_paused_until":null}"#;
+
+    fn timed() -> Snapshot {
+        Snapshot::parse(TIMED).unwrap()
+    }
+
+    #[test]
+    fn status_file_reads_back() {
+        assert_eq!(
+            timed(),
+            Snapshot {
+                awake: true,
+                mode: "display+system".into(),
+                source: Some("manual".into()),
+                reason: Some("turned on \"manually\"".into()),
+                expires: Some(3607),
+                triggers_paused_until: None,
+                arch: None,
+                translated: false,
+            }
+        );
+        assert_eq!(Snapshot::parse("{}"), None);
+    }
+
+    #[test]
+    fn summary_matches_the_menu() {
+        assert_eq!(
+            timed().summary(7),
+            "On \u{2014} turned on \"manually\", 60 min left"
+        );
+        let paused = Snapshot {
+            mode: "system".into(),
+            triggers_paused_until: Some(600),
+            ..Snapshot::default()
+        };
+        assert_eq!(
+            paused.summary(0),
+            "Off \u{2014} triggers paused, 10 min left"
+        );
+    }
+
+    #[test]
+    fn porcelain_fields_stay_put() {
+        assert_eq!(
+            timed().porcelain(7),
+            "on\tdisplay+system\tmanual\t3600\tturned on \"manually\""
+        );
+        let off = Snapshot {
+            mode: "system".into(),
+            ..Snapshot::default()
+        };
+        assert_eq!(off.porcelain(7), "off\tsystem\t-\t-\t-");
+    }
+
+    #[test]
+    fn arch_names_a_translated_build() {
+        let translated =
+            Snapshot::parse(r#"{"awake":false,"mode":"system","arch":"x86_64","translated":true}"#)
+                .unwrap();
+        assert_eq!(translated.arch(), "x86_64 under Rosetta");
+        assert!(translated.rosetta_hint().is_some());
+        assert_eq!(timed().arch(), "-");
+        assert!(timed().rosetta_hint().is_none());
+    }
+
+    #[test]
+    fn prompt_is_a_glyph_and_the_time_left() {
+        assert_eq!(timed().prompt(7 + 1080, "\u{2615}", ""), "\u{2615} 42m");
+        let two_hours = Snapshot {
+            expires: Some(7 + 7200),
+            ..timed()
+        };
+        assert_eq!(two_hours.prompt(7, "\u{2615}", ""), "\u{2615} 2h00m");
+        // An empty glyph leaves just the time
+        assert_eq!(timed().prompt(7 + 600, "", ""), "50m");
+        let open_ended = Snapshot {
+            expires: None,
+            ..timed()
+        };
+        assert_eq!(open_ended.prompt(7, "ON", "off"), "ON");
+        assert_eq!(Snapshot::default().prompt(7, "ON", "off"), "off");
+    }
+
+    #[test]
+    fn launcher_json_carries_actions() {
+        let alfred = alfred(&timed(), 7);
+        assert!(alfred.starts_with(r#"{"items":[{"uid":"status","title":"On "#));
+        assert!(alfred.contains(r#""arg":"toggle","icon":{"path":"on.png"}"#));
+        assert!(alfred.contains(r#""arg":"for:60""#));
+
+        let raycast = raycast(&timed(), 7);
+        assert!(raycast.contains(r#""accessories":[{"text":"60 min left"}]"#));
+        assert!(raycast.contains(r#"{"title":"Turn Off","argument":"toggle"}"#));
+    }
+}
@@ -0,0 +1,123 @@
+//! `status.json` next to the event log: the current session, rewritten on
+//! every change, for scripts and status bars that want to read Awake's state
+//! without talking to it.
+//!
+//! On macOS a copy also goes to the app group's container (`group.` and the
+//! bundle identifier, so `group.io.tmss.awake` for official builds), once a
+//! widget extension has created it, since a sandboxed widget can read nothing
+//! else of ours. Its buttons open `awake://` links.
+//!
+//! `{"ts":…,"awake":true,"mode":"display+system","source":"trigger",
+//! "reason":"while /tmp/awake.lock exists","expires":null,
+//! "triggers_paused_until":null,"remaining_seconds":null,
+//! "assertions":"display+system","arch":"arm64","translated":false}`
+
+use std::fs;
+use std::path::Path;
+
+use crate::event_log::json_string;
+use crate::state::Status;
+
+const STATUS_FILE: &str = "status.json";
+/// The widget's app group: `group.` and the bundle identifier
+#[cfg(target_os = "macos")]
+pub fn app_group() -> String {
+    format!("group.{}", crate::macos::bundle_identifier())
+}
+
+pub fn to_json(status: &Status, ts: u64) -> String {
+    let (source, reason) = match status.activation_source() {
+        Some(source) => (json_string(source.kind()), json_string(&source.describe())),
+        None => ("null".into(), "null".into()),
+    };
+    let secs_or_null =
+        |secs: Option<u64>| secs.map_or_else(|| "null".to_string(), |s| s.to_string());
+    format!(
+        r#"{{"ts":{},"awake":{},"mode":{},"source":{},"reason":{},"expires":{},"triggers_paused_until":{},"remaining_seconds":{},"assertions":{},"arch":{},"translated":{}}}"#,
+        ts,
+        status.is_awake(),
+        json_string(&status.kinds.to_string()),
+        source,
+        reason,
+        secs_or_null(status.expiry),
+        secs_or_null(status.override_until),
+        secs_or_null(status.remaining_seconds(ts)),
+        json_string(&status.active_assertion_kinds().to_string()),
+        json_string(crate::arch::compiled_for()),
+        crate::arch::is_translated()
+    )
+}
+
+// Written aside and renamed over, so readers never see half a file
+fn write_to(dir: &Path, json: &str) {
+    let path = dir.join(STATUS_FILE);
+    let temp = dir.join(format!("{}.tmp", STATUS_FILE));
+    let result = fs::create_dir_all(dir)
+        .and_then(|()| fs::write(&temp, format!("{}\n", json)))
+        .and_then(|()| fs::rename(&temp, &path));
+    if let Err(e) = result {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+pub fn write(status: &Status) {
+    let Some(dir) = crate::app_support_dir() else {
+        return;
+    };
+    let json = to_json(status, crate::now_secs());
+    write_to(&dir, &json);
+    #[cfg(target_os = "macos")]
+    if let Some(group) = std::env::var_os("HOME")
+        .map(|home| {
+            Path::new(&home)
+                .join("Library/Group Containers")
+                .join(app_group())
+        })
+        .filter(|group| group.is_dir())
+    {
+        write_to(&group, &json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::state::Source;
+
+    #[test]
+    fn json_describes_the_session() {
+        let source = Source::Trigger("/tmp/awake.lock exists".into());
+        let awake = Status {
+            kinds: Mode::Both.kinds(),
+            source: Some(&source),
+            expiry: None,
+            override_until: None,
+            triggers: &[],
+            overnight: None,
+            mode_return: None,
+        };
+        assert_eq!(
+            to_json(&awake, 7),
+            format!(
+                r#"{{"ts":7,"awake":true,"mode":"display+system","source":"trigger","reason":"while /tmp/awake.lock exists","expires":null,"triggers_paused_until":null,"remaining_seconds":null,"assertions":"display+system","arch":"{}","translated":{}}}"#,
+                crate::arch::compiled_for(),
+                crate::arch::is_translated()
+            )
+        );
+
+        let asleep = Status {
+            kinds: Mode::Both.kinds(),
+            source: None,
+            expiry: None,
+            override_until: None,
+            triggers: &[],
+            overnight: None,
+            mode_return: None,
+        };
+        assert!(
+            to_json(&asleep, 7).contains(r#""awake":false,"mode":"display+system","source":null"#)
+                && to_json(&asleep, 7).contains(r#""assertions":"none","arch":"#)
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Countdown timer for timed sessions.
+//!
+//! `TimerState` is the pure state machine (no threads, no clock), so the ordering
+//! rules can be tested directly. A `Waiter` thread per armed countdown sleeps until
+//! it elapses (or is woken early via a condvar on cancel) and reports its token.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// One armed countdown. A fire is honoured only if its token is still the live one,
+/// so a waiter thread from a superseded timer can never end a newer session — even
+/// when both happen to share the same expiry second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Token {
+    generation: u64,
+    pub expiry: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct TimerState {
+    generation: u64,
+    live: Option<Token>,
+}
+
+impl TimerState {
+    pub const fn new() -> Self {
+        TimerState {
+            generation: 0,
+            live: None,
+        }
+    }
+
+    /// Arms a countdown of `seconds` from `now`, superseding any live one
+    pub fn start(&mut self, now: u64, seconds: u64) -> Token {
+        self.arm(now.saturating_add(seconds))
+    }
+
+    /// Pushes the live countdown back by `seconds`; `None` if nothing is running
+    pub fn extend(&mut self, seconds: u64) -> Option<Token> {
+        let live = self.live?;
+        Some(self.arm(live.expiry.saturating_add(seconds)))
+    }
+
+    /// Brings the countdown forward to `latest` if it would end later, or
+    /// arms one if none is running; `None` if it already ends in time
+    pub fn limit(&mut self, latest: u64) -> Option<Token> {
+        match self.live {
+            Some(live) if live.expiry <= latest => None,
+            _ => Some(self.arm(latest)),
+        }
+    }
+
+    pub fn cancel(&mut self) -> Option<Token> {
+        self.live.take()
+    }
+
+    /// Called when `token`'s wait elapses. True (and the timer disarms) only if
+    /// `token` hasn't been cancelled, superseded or extended in the meantime.
+    pub fn fire(&mut self, token: Token) -> bool {
+        if self.live == Some(token) {
+            self.live = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expiry(&self) -> Option<u64> {
+        self.live.map(|t| t.expiry)
+    }
+
+    fn arm(&mut self, expiry: u64) -> Token {
+        self.generation += 1;
+        let token = Token {
+            generation: self.generation,
+            expiry,
+        };
+        self.live = Some(token);
+        token
+    }
+}
+
+type CancelPair = Arc<(Mutex<bool>, Condvar)>;
+
+/// Thread that sleeps until one token's countdown elapses, then reports it.
+/// It doesn't decide whether the token still counts — the owner of the
+/// `TimerState` does, via `fire`.
+pub struct Waiter {
+    cancel: CancelPair,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Waiter {
+    pub fn spawn(
+        token: Token,
+        wait_secs: u64,
+        on_elapsed: impl FnOnce(Token) + Send + 'static,
+    ) -> Waiter {
+        let cancel: CancelPair = Arc::new((Mutex::new(false), Condvar::new()));
+        let cancel_pair = Arc::clone(&cancel);
+
+        let thread = thread::spawn(move || {
+            let (lock, cvar) = &*cancel_pair;
+            let guard = lock.lock().unwrap();
+            // Single wait for the full duration — wakes only on cancel or expiry
+            let (guard, _timeout) = cvar
+                .wait_timeout_while(guard, Duration::from_secs(wait_secs), |cancelled| {
+                    !*cancelled
+                })
+                .unwrap();
+            if *guard {
+                return; // Cancelled
+            }
+            drop(guard);
+            on_elapsed(token);
+        });
+
+        Waiter { cancel, thread }
+    }
+
+    /// Wakes the thread so it exits without reporting
+    pub fn cancel(&self) {
+        let (lock, cvar) = &*self.cancel;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    }
+
+    pub fn join(self) {
+        let _ = self.thread.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Start(u64),
+        Extend(u64),
+        Cancel,
+        // Deliver the elapsed wait of the nth token handed out so far (may be stale)
+        Fire(usize),
+        Advance(u64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (1u64..7200).prop_map(Op::Start),
+            (1u64..3600).prop_map(Op::Extend),
+            Just(Op::Cancel),
+            any::<usize>().prop_map(Op::Fire),
+            // Zero-length advances keep same-second restarts common
+            (0u64..3).prop_map(Op::Advance),
+        ]
+    }
+
+    proptest! {
+        // Against a trivial model: only the most recently armed, still-live token fires
+        #[test]
+        fn only_the_live_token_fires(ops in prop::collection::vec(op(), 1..64)) {
+            let mut state = TimerState::new();
+            let mut now = 1_000_000u64;
+            let mut issued: Vec<Token> = Vec::new();
+            let mut model_live: Option<usize> = None;
+
+            for op in ops {
+                match op {
+                    Op::Start(secs) => {
+                        let token = state.start(now, secs);
+                        prop_assert_eq!(token.expiry, now + secs);
+                        issued.push(token);
+                        model_live = Some(issued.len() - 1);
+                    }
+                    Op::Extend(secs) => {
+                        let before = state.expiry();
+                        match state.extend(secs) {
+                            Some(token) => {
+                                prop_assert_eq!(Some(token.expiry - secs), before);
+                                issued.push(token);
+                                model_live = Some(issued.len() - 1);
+                            }
+                            None => prop_assert!(model_live.is_none()),
+                        }
+                    }
+                    Op::Cancel => {
+                        state.cancel();
+                        model_live = None;
+                    }
+                    Op::Fire(n) if !issued.is_empty() => {
+                        let i = n % issued.len();
+                        let expected = model_live == Some(i);
+                        prop_assert_eq!(state.fire(issued[i]), expected);
+                        if expected {
+                            model_live = None;
+                        }
+                    }
+                    Op::Fire(_) => {}
+                    Op::Advance(secs) => now += secs,
+                }
+                prop_assert_eq!(state.expiry(), model_live.map(|i| issued[i].expiry));
+            }
+        }
+
+        // A cancel that lands before the waiter observes its timeout always wins
+        #[test]
+        fn cancel_beats_pending_fire(secs in 1u64..7200, now in 0u64..u32::MAX as u64) {
+            let mut state = TimerState::new();
+            let token = state.start(now, secs);
+            state.cancel();
+            prop_assert!(!state.fire(token));
+            prop_assert_eq!(state.expiry(), None);
+        }
+
+        // Restarting in the same second with the same duration yields an identical
+        // expiry; the superseded waiter must still not end the new session
+        #[test]
+        fn superseded_timer_with_same_expiry_never_fires(secs in 1u64..7200, now in 0u64..u32::MAX as u64) {
+            let mut state = TimerState::new();
+            let old = state.start(now, secs);
+            let new = state.start(now, secs);
+            prop_assert_eq!(old.expiry, new.expiry);
+            prop_assert!(!state.fire(old));
+            prop_assert!(state.fire(new));
+        }
+
+        // Extensions accumulate, never shorten, and invalidate the old waiter
+        #[test]
+        fn extensions_accumulate(start in 1u64..7200, extra in prop::collection::vec(1u64..3600, 1..8)) {
+            let mut state = TimerState::new();
+            let mut token = state.start(0, start);
+            for secs in &extra {
+                let previous = token;
+                token = state.extend(*secs).unwrap();
+                prop_assert!(token.expiry > previous.expiry);
+                prop_assert!(!state.fire(previous));
+            }
+            prop_assert_eq!(token.expiry, start + extra.iter().sum::<u64>());
+            prop_assert!(state.fire(token));
+            prop_assert_eq!(state.extend(60), None);
+        }
+
+        // A limit only ever shortens a countdown, and starts one if there was none
+        #[test]
+        fn limit_never_lengthens(secs in 1u64..7200, latest in 1u64..7200) {
+            let mut state = TimerState::new();
+            let token = state.start(0, secs);
+            let limited = state.limit(latest);
+            prop_assert_eq!(state.expiry(), Some(secs.min(latest)));
+            prop_assert_eq!(limited.is_some(), latest < secs);
+            prop_assert_eq!(state.fire(token), latest >= secs);
+
+            let mut idle = TimerState::new();
+            prop_assert_eq!(idle.limit(latest).map(|t| t.expiry), Some(latest));
+        }
+    }
+}
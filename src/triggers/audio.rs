@@ -0,0 +1,104 @@
+//! "Stay awake while audio plays on an AirPlay or Bluetooth speaker" — so
+//! whole-home audio streamed from the Mac doesn't stop when it idles. Holds
+//! the system awake by default, leaving the display free to sleep.
+//!
+//! The default output device's transport is read from CoreAudio. Only while
+//! that device is playing does the trigger hold: Bluetooth headphones left
+//! connected all day shouldn't keep the Mac from ever sleeping.
+
+use std::ffi::c_void;
+use std::time::Duration;
+
+use super::poll::Event;
+
+const POLL_SECS: u64 = 10;
+pub const NAME: &str = "audio is playing on an AirPlay or Bluetooth speaker";
+
+const fn code(name: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*name)
+}
+
+// `kAudioObjectSystemObject`
+const SYSTEM_OBJECT: u32 = 1;
+const DEFAULT_OUTPUT_DEVICE: u32 = code(b"dOut");
+const TRANSPORT_TYPE: u32 = code(b"tran");
+const IS_RUNNING_SOMEWHERE: u32 = code(b"gone");
+const SCOPE_GLOBAL: u32 = code(b"glob");
+const ELEMENT_MAIN: u32 = 0;
+// `kAudioDeviceTransportType…`
+const WIRELESS: [u32; 3] = [code(b"airp"), code(b"blue"), code(b"blea")];
+
+#[repr(C)]
+struct PropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyData(
+        object: u32,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> i32;
+}
+
+fn property(object: u32, selector: u32) -> Option<u32> {
+    let address = PropertyAddress {
+        selector,
+        scope: SCOPE_GLOBAL,
+        element: ELEMENT_MAIN,
+    };
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address,
+            0,
+            std::ptr::null(),
+            &mut size,
+            (&mut value as *mut u32).cast(),
+        )
+    };
+    (status == 0).then_some(value)
+}
+
+fn is_wireless(transport: u32) -> bool {
+    WIRELESS.contains(&transport)
+}
+
+fn playing_wirelessly() -> bool {
+    let Some(device) = property(SYSTEM_OBJECT, DEFAULT_OUTPUT_DEVICE) else {
+        return false;
+    };
+    property(device, TRANSPORT_TYPE).is_some_and(is_wireless)
+        && property(device, IS_RUNNING_SOMEWHERE).is_some_and(|running| running != 0)
+}
+
+pub fn spawn() {
+    super::poll::spawn(
+        NAME.into(),
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke],
+        playing_wirelessly,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn airplay_and_bluetooth_are_wireless() {
+        assert!(is_wireless(code(b"airp")));
+        assert!(is_wireless(code(b"blea")));
+        // Built-in speakers and USB interfaces
+        assert!(!is_wireless(code(b"bltn")));
+        assert!(!is_wireless(code(b"usb ")));
+    }
+}
@@ -0,0 +1,130 @@
+//! "Stay awake while a build is running" — Xcode, Cargo, compilers — and for
+//! a grace period after it, so a build left running isn't stopped by sleep
+//! halfway through.
+//!
+//! A build is counted from the processes build tools run, but only while they
+//! use the CPU: `cargo run` serving an app, or an `xcodebuild` waiting on a
+//! simulator, isn't building. Each check adds up the CPU time build tools used
+//! since the last one; the grace period bridges the quiet moments between
+//! steps, such as fetching or linking.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::processes::Process;
+
+const POLL_SECS: u64 = 10;
+/// Share of one core build tools must use between checks to count as building
+const CPU_THRESHOLD_PERCENT: u64 = 20;
+/// How long after the last busy check the build is still counted
+const GRACE_SECS: u64 = 120;
+pub const NAME: &str = "a build is running";
+
+const BUILD_TOOLS: [&str; 11] = [
+    "xcodebuild",
+    "swift-frontend",
+    "swiftc",
+    "cargo",
+    "rustc",
+    "clang",
+    "clang++",
+    "cc1",
+    "cc1plus",
+    "ld",
+    "make",
+];
+
+/// What the last check saw
+#[derive(Default)]
+struct Builds {
+    /// CPU time so far of each build tool, by pid; none before the first check
+    cpu: Option<HashMap<u32, Duration>>,
+    busy_until: Option<Instant>,
+}
+
+impl Builds {
+    /// Takes a check's processes, `elapsed` after the last one: whether a build
+    /// is running, or was within the grace period. The first check only takes
+    /// note, since what tools used before it could have been any time.
+    fn observe(&mut self, processes: &[Process], elapsed: Duration, now: Instant) -> bool {
+        let tools: HashMap<u32, Duration> = processes
+            .iter()
+            .filter(|process| BUILD_TOOLS.contains(&process.name.as_str()))
+            .map(|process| (process.pid, process.cpu))
+            .collect();
+        if let Some(before) = &self.cpu {
+            // A tool started since counts all it has used
+            let used: Duration = tools
+                .iter()
+                .map(|(pid, cpu)| cpu.saturating_sub(before.get(pid).copied().unwrap_or_default()))
+                .sum();
+            let busy =
+                used.as_millis() * 100 >= elapsed.as_millis() * CPU_THRESHOLD_PERCENT as u128;
+            if busy && !used.is_zero() {
+                self.busy_until = Some(now + Duration::from_secs(GRACE_SECS));
+            }
+        }
+        self.cpu = Some(tools);
+        self.busy_until.is_some_and(|until| now < until)
+    }
+}
+
+pub fn spawn() {
+    let mut builds = Builds::default();
+    let mut last = Instant::now();
+    let running = move || {
+        let now = Instant::now();
+        let running = builds.observe(&super::processes::all(), now - last, now);
+        last = now;
+        running
+    };
+    // No events: CPU time is only measured between evenly spaced checks
+    super::poll::spawn(NAME.into(), Duration::from_secs(POLL_SECS), &[], running);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, cpu_secs: u64) -> Process {
+        Process {
+            pid,
+            name: name.into(),
+            ppid: 1,
+            cpu: Duration::from_secs(cpu_secs),
+        }
+    }
+
+    #[test]
+    fn busy_build_tools_count_until_the_grace_period_ends() {
+        let mut builds = Builds::default();
+        let start = Instant::now();
+        let check = Duration::from_secs(POLL_SECS);
+        let at = |checks: u32| start + check * checks;
+
+        // Whatever cargo used before the first check doesn't count
+        assert!(!builds.observe(&[process(10, "cargo", 500)], check, at(0)));
+        // rustc started since, using a core for most of the interval
+        let building = [process(10, "cargo", 500), process(11, "rustc", 8)];
+        assert!(builds.observe(&building, check, at(1)));
+        // Linking: rustc is gone, nothing else is busy, the grace period holds
+        assert!(builds.observe(&[process(10, "cargo", 500)], check, at(2)));
+        let after_grace = start + check + Duration::from_secs(GRACE_SECS);
+        assert!(!builds.observe(&[process(10, "cargo", 500)], check, after_grace));
+    }
+
+    #[test]
+    fn idle_or_unrelated_processes_dont_count() {
+        let mut builds = Builds::default();
+        let start = Instant::now();
+        let check = Duration::from_secs(POLL_SECS);
+        builds.observe(
+            &[process(10, "cargo", 1), process(20, "node", 1)],
+            check,
+            start,
+        );
+        // `cargo run` serving an app: a second of CPU in ten is under 20%
+        let quiet = [process(10, "cargo", 2), process(20, "node", 9)];
+        assert!(!builds.observe(&quiet, check, start + check));
+    }
+}
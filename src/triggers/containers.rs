@@ -0,0 +1,140 @@
+//! "Stay awake while containers or virtual machines are running" — sleep
+//! pauses them mid-job, and a long build, test run or database in one seldom
+//! survives it cleanly.
+//!
+//! Containers are counted by asking the Docker engine over its socket which are
+//! running (Docker Desktop, Colima or a plain `dockerd`; `DOCKER_HOST` is
+//! honoured when it names a socket). No socket, or no answer, means no
+//! containers. Virtual machines are counted from the processes their
+//! hypervisors run per machine: UTM's and other front-ends' QEMU, VMware's
+//! `vmware-vmx` and Parallels' `prl_vm_app`.
+
+use std::env;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::poll::Event;
+
+const POLL_SECS: u64 = 30;
+// Long enough for a busy engine, short enough not to hold up the next check
+const SOCKET_TIMEOUT_SECS: u64 = 2;
+pub const NAME: &str = "containers or virtual machines are running";
+
+/// Processes that each stand for a running virtual machine; a trailing `-`
+/// matches as a prefix (`qemu-system-aarch64`)
+const VM_PROCESSES: [&str; 4] = ["QEMULauncher", "qemu-system-", "vmware-vmx", "prl_vm_app"];
+
+fn is_vm(name: &str) -> bool {
+    VM_PROCESSES.iter().any(|vm| match vm.strip_suffix('-') {
+        Some(_) => name.starts_with(vm),
+        None => name == *vm,
+    })
+}
+
+// Where the engine may be listening, most specific first
+fn sockets() -> Vec<PathBuf> {
+    let mut sockets = Vec::new();
+    if let Some(path) = env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+    {
+        sockets.push(path);
+    }
+    if let Ok(home) = env::var("HOME") {
+        let home = PathBuf::from(home);
+        sockets.push(home.join(".docker/run/docker.sock"));
+        sockets.push(home.join(".colima/default/docker.sock"));
+    }
+    if let Ok(runtime) = env::var("XDG_RUNTIME_DIR") {
+        sockets.push(PathBuf::from(runtime).join("docker.sock"));
+    }
+    sockets.push("/var/run/docker.sock".into());
+    sockets
+}
+
+/// Whether the engine's answer to `GET /containers/json` lists any: by
+/// default only running containers are listed, as a JSON array
+fn lists_containers(response: &str) -> bool {
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        return false;
+    };
+    let ok = head
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|status| status == "200");
+    let body = body.trim();
+    ok && body.starts_with('[') && body != "[]"
+}
+
+fn ask_engine(socket: &PathBuf) -> Option<String> {
+    let mut stream = UnixStream::connect(socket).ok()?;
+    let timeout = Some(Duration::from_secs(SOCKET_TIMEOUT_SECS));
+    stream.set_read_timeout(timeout).ok()?;
+    stream.set_write_timeout(timeout).ok()?;
+    // HTTP/1.0 so the engine closes the connection once it has answered
+    stream
+        .write_all(b"GET /containers/json HTTP/1.0\r\nHost: docker\r\n\r\n")
+        .ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    Some(response)
+}
+
+fn containers_running() -> bool {
+    // The first engine that answers speaks for them all: Docker Desktop's
+    // own socket and `/var/run/docker.sock` are usually the same engine
+    sockets()
+        .iter()
+        .find_map(ask_engine)
+        .is_some_and(|response| lists_containers(&response))
+}
+
+fn running() -> bool {
+    super::processes::all()
+        .iter()
+        .any(|process| is_vm(&process.name))
+        || containers_running()
+}
+
+pub fn spawn() {
+    // Docker Desktop, UTM, VMware Fusion and Parallels are apps, so starting
+    // or quitting one is seen at once
+    super::poll::spawn(
+        NAME.into(),
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke, Event::AppsChanged],
+        running,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_answers_read() {
+        let running = "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n\
+            [{\"Id\":\"8dfafdbc3a40\",\"State\":\"running\"}]\n";
+        assert!(lists_containers(running));
+        assert!(!lists_containers(
+            "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n[]\n"
+        ));
+        assert!(!lists_containers(
+            "HTTP/1.0 500 Internal Server Error\r\n\r\n{\"message\":\"oops\"}"
+        ));
+        assert!(!lists_containers(""));
+    }
+
+    #[test]
+    fn hypervisor_processes_are_vms() {
+        assert!(is_vm("QEMULauncher"));
+        // Linux cuts names to 15 bytes
+        assert!(is_vm("qemu-system-x86"));
+        assert!(is_vm("vmware-vmx"));
+        assert!(is_vm("prl_vm_app"));
+        assert!(!is_vm("qemu-img"));
+        assert!(!is_vm("vmware-vmx-helper"));
+    }
+}
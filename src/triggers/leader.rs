@@ -0,0 +1,91 @@
+//! "Stay awake while the leader is awake" — fleet mode, for kiosk and display
+//! installations where every machine must stay awake together. Each follower
+//! polls the leader's remote control (`remote.rs`) for its status and mirrors
+//! it: on while the leader is on, off once it's off.
+//!
+//! A follower that can't reach the leader keeps what it last saw for a few
+//! tries, so a dropped Wi-Fi packet doesn't blank every screen at once. The
+//! token is found as for `awake --host`, else it's this machine's own, since a
+//! fleet shares one token and a follower started from its settings has no
+//! `AWAKE_TOKEN` to read.
+
+use std::time::Duration;
+
+use super::poll::Event;
+use crate::event_log::json_field;
+use crate::remote;
+
+const POLL_SECS: u64 = 5;
+// Failed polls in a row before an unreachable leader counts as asleep
+const MAX_MISSES: u32 = 3;
+
+/// "the leader mini.local is awake"
+pub fn name(leader: &str) -> String {
+    format!("the leader {} is awake", leader)
+}
+
+/// What the follower believes, from one poll's answer after another
+#[derive(Default)]
+struct Follower {
+    awake: bool,
+    misses: u32,
+}
+
+impl Follower {
+    /// `answer` is the leader's state, or `None` if it didn't give one
+    fn observe(&mut self, answer: Option<bool>) -> bool {
+        match answer {
+            Some(awake) => {
+                self.awake = awake;
+                self.misses = 0;
+            }
+            None => {
+                self.misses += 1;
+                if self.misses >= MAX_MISSES {
+                    self.awake = false;
+                }
+            }
+        }
+        self.awake
+    }
+}
+
+fn leader_awake(host: &str, port: u16, token: &str) -> Option<bool> {
+    match remote::send(host, port, token, "GET", "/status") {
+        Ok((200, body)) => Some(json_field(&body, "awake")? == "true"),
+        _ => None,
+    }
+}
+
+/// Follows `leader` (`<host>[:<port>]`) for the rest of the run; `false` if
+/// it doesn't name a host or there's no token to ask with
+pub fn spawn(leader: &str) -> bool {
+    let token = remote::token_for(None).or_else(remote::own_token);
+    let (Some((host, port)), Some(token)) = (remote::parse_host(leader), token) else {
+        return false;
+    };
+    let mut follower = Follower::default();
+    super::poll::spawn(
+        name(leader),
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke],
+        move || follower.observe(leader_awake(&host, port, &token)),
+    );
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_silent_leader_is_trusted_for_a_while() {
+        let mut follower = Follower::default();
+        assert!(follower.observe(Some(true)));
+        assert!(follower.observe(None));
+        assert!(follower.observe(None));
+        assert!(!follower.observe(None));
+        assert!(follower.observe(Some(true)));
+        assert!(!follower.observe(Some(false)));
+    }
+}
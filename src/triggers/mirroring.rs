@@ -0,0 +1,43 @@
+//! "Stay awake while the screen is mirrored" — presenting to a projector, or
+//! AirPlay mirroring to an Apple TV, which would otherwise go dark mid-talk at
+//! the display's idle timeout. Holds the display awake by default; another
+//! mode can be given with `--trigger-mode mirroring=…`.
+//!
+//! Mirroring is read from CoreGraphics' displays; the front-end reports each
+//! display reconfiguration, so the check looks again as soon as one starts or
+//! stops instead of at its next turn.
+
+use std::time::Duration;
+
+use super::poll::Event;
+
+// Only a fallback, as reconfigurations are reported as they happen
+const POLL_SECS: u64 = 30;
+pub const NAME: &str = "the screen is mirrored";
+const MAX_DISPLAYS: usize = 16;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+    fn CGDisplayIsInMirrorSet(display: u32) -> i32;
+}
+
+fn mirrored() -> bool {
+    let mut displays = [0u32; MAX_DISPLAYS];
+    let mut count = 0;
+    let error =
+        unsafe { CGGetActiveDisplayList(MAX_DISPLAYS as u32, displays.as_mut_ptr(), &mut count) };
+    error == 0
+        && displays[..count as usize]
+            .iter()
+            .any(|&display| unsafe { CGDisplayIsInMirrorSet(display) } != 0)
+}
+
+pub fn spawn() {
+    super::poll::spawn(
+        NAME.into(),
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke, Event::DisplaysChanged],
+        mirrored,
+    );
+}
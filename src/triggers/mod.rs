@@ -0,0 +1,140 @@
+//! Triggers: conditions outside Awake that switch it on while they hold.
+//!
+//! Each trigger runs on its own thread and only reports edges, by posting
+//! `TriggerChanged` with its name; `state` decides what an edge does. A session
+//! a trigger started ends when the last satisfied trigger clears; a session the
+//! user started is left alone.
+
+#[cfg(target_os = "macos")]
+pub mod audio;
+#[cfg(unix)]
+pub mod build;
+#[cfg(unix)]
+pub mod containers;
+#[cfg(unix)]
+pub mod leader;
+#[cfg(target_os = "macos")]
+pub mod mirroring;
+pub mod path_exists;
+#[cfg(unix)]
+pub mod poll;
+#[cfg(unix)]
+pub mod port;
+#[cfg(unix)]
+pub mod processes;
+#[cfg(unix)]
+pub mod rules;
+#[cfg(target_os = "macos")]
+pub mod schedule;
+#[cfg(unix)]
+pub mod ssh;
+#[cfg(target_os = "macos")]
+pub mod workday;
+
+use crate::state;
+
+/// Who wins when the user and a trigger disagree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// The last thing to happen wins: switching off by hand holds until a
+    /// trigger next becomes satisfied
+    Edge,
+    /// Switching off by hand keeps triggers from switching Awake on for
+    /// `minutes`; once that passes, a trigger still satisfied switches it back on
+    Override { minutes: u64 },
+}
+
+impl Policy {
+    const DEFAULT_OVERRIDE_MINUTES: u64 = 60;
+    // A week
+    const MAX_OVERRIDE_MINUTES: u64 = 7 * 24 * 60;
+
+    /// `edge`, `override` or `override:<minutes>`, up to a week
+    pub fn parse(s: &str) -> Option<Policy> {
+        match s.split_once(':') {
+            None if s == "edge" => Some(Policy::Edge),
+            None if s == "override" => Some(Policy::Override {
+                minutes: Policy::DEFAULT_OVERRIDE_MINUTES,
+            }),
+            Some(("override", minutes)) => match minutes.parse() {
+                Ok(minutes) if (1..=Policy::MAX_OVERRIDE_MINUTES).contains(&minutes) => {
+                    Some(Policy::Override { minutes })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// What `--trigger-mode` calls each trigger
+pub const KEYS: [&str; 10] = [
+    "lockfile",
+    "ssh",
+    "port",
+    "containers",
+    "build",
+    "leader",
+    "mirroring",
+    "audio",
+    "workday",
+    "schedule",
+];
+
+/// `ssh=server,build=system`: pairs of a trigger's key and a mode's name,
+/// the mode not yet checked
+pub fn parse_modes(list: &str) -> Option<Vec<(&str, &str)>> {
+    list.split(',')
+        .map(|pair| {
+            let (key, mode) = pair.trim().split_once('=')?;
+            let (key, mode) = (key.trim(), mode.trim());
+            (KEYS.contains(&key) && !mode.is_empty()).then_some((key, mode))
+        })
+        .collect()
+}
+
+/// Called from a trigger's thread whenever its condition flips
+fn report(name: &str, satisfied: bool) {
+    state::post(state::Command::TriggerChanged {
+        name: name.to_string(),
+        satisfied,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_parse() {
+        assert_eq!(Policy::parse("edge"), Some(Policy::Edge));
+        assert_eq!(
+            Policy::parse("override"),
+            Some(Policy::Override { minutes: 60 })
+        );
+        assert_eq!(
+            Policy::parse("override:15"),
+            Some(Policy::Override { minutes: 15 })
+        );
+        assert_eq!(Policy::parse("override:0"), None);
+        assert_eq!(Policy::parse("override:10081"), None);
+        assert_eq!(Policy::parse("override:18446744073709551615"), None);
+        assert_eq!(Policy::parse("edge:5"), None);
+        assert_eq!(Policy::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn trigger_modes_parse() {
+        assert_eq!(
+            parse_modes("ssh=server, build = system"),
+            Some(vec![("ssh", "server"), ("build", "system")])
+        );
+        assert_eq!(
+            parse_modes("lockfile=display"),
+            Some(vec![("lockfile", "display")])
+        );
+        assert_eq!(parse_modes("ssh"), None);
+        assert_eq!(parse_modes("ssh="), None);
+        assert_eq!(parse_modes("printer=system"), None);
+    }
+}
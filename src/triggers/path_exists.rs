@@ -0,0 +1,89 @@
+//! "Stay awake while this path exists" — a lockfile that scripts can touch and
+//! remove (`touch /tmp/awake.lock`, `rm /tmp/awake.lock`) to drive Awake.
+//!
+//! On macOS the parent directory is watched with kqueue, so a change is seen
+//! as it happens; elsewhere the path is polled.
+
+use std::path::{Path, PathBuf};
+use std::thread;
+#[cfg(not(target_os = "macos"))]
+use std::time::Duration;
+
+#[cfg(not(target_os = "macos"))]
+const POLL_SECS: u64 = 2;
+
+/// Watches `path` for the rest of the run, reporting as `name`
+pub fn spawn(name: String, path: PathBuf) {
+    thread::spawn(move || {
+        let mut exists = path.exists();
+        super::report(&name, exists);
+        loop {
+            wait_for_change(&path, exists);
+            let now = path.exists();
+            if now != exists {
+                exists = now;
+                super::report(&name, exists);
+            }
+        }
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+fn wait_for_change(_path: &Path, _exists: bool) {
+    thread::sleep(Duration::from_secs(POLL_SECS));
+}
+
+/// Returns once the directory holding `path` changes (or a fallback timeout
+/// passes), or at once if `path` no longer `exists` as last seen
+#[cfg(target_os = "macos")]
+fn wait_for_change(path: &Path, exists: bool) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // Also covers changes kqueue can't see, like the parent being replaced
+    const FALLBACK_SECS: libc::time_t = 30;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(dir) = CString::new(dir.as_os_str().as_bytes()) else {
+        return;
+    };
+
+    unsafe {
+        let fd = libc::open(dir.as_ptr(), libc::O_EVTONLY);
+        if fd < 0 {
+            // The directory may not exist yet; check back later
+            thread::sleep(std::time::Duration::from_secs(FALLBACK_SECS as u64));
+            return;
+        }
+        let kq = libc::kqueue();
+        if kq < 0 {
+            libc::close(fd);
+            thread::sleep(std::time::Duration::from_secs(FALLBACK_SECS as u64));
+            return;
+        }
+        {
+            let mut change: libc::kevent = std::mem::zeroed();
+            change.ident = fd as usize;
+            change.filter = libc::EVFILT_VNODE;
+            change.flags = libc::EV_ADD | libc::EV_CLEAR;
+            change.fflags = libc::NOTE_WRITE | libc::NOTE_DELETE | libc::NOTE_RENAME;
+            libc::kevent(kq, &change, 1, std::ptr::null_mut(), 0, std::ptr::null());
+
+            // A change between the caller's check and registering would
+            // otherwise go unseen until the fallback
+            if path.exists() == exists {
+                let timeout = libc::timespec {
+                    tv_sec: FALLBACK_SECS,
+                    tv_nsec: 0,
+                };
+                let mut event: libc::kevent = std::mem::zeroed();
+                libc::kevent(kq, std::ptr::null(), 0, &mut event, 1, &timeout);
+            }
+            libc::close(kq);
+        }
+        libc::close(fd);
+    }
+}
@@ -0,0 +1,255 @@
+//! Triggers whose condition can only be looked at, not waited on. Their checks
+//! share one scheduler thread instead of a thread and a timer each, and run on
+//! a coarse grid of `SLOT_SECS`, so checks due at about the same time run
+//! together in one wake-up and the CPU stays idle in between — which is also
+//! what App Nap's timer coalescing would do to them anyway.
+//!
+//! Where the system can say that something may have changed (the Mac woke, an
+//! app launched or quit), the front-end calls `notify`, and the checks that
+//! subscribed to that `Event` run at once instead of at their next turn.
+
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Due times are rounded up to a multiple of this, counted from the start
+const SLOT_SECS: u64 = 5;
+
+/// Something the system reports that may change what checks find
+// Only the macOS front-end sends events so far
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Back from sleep: anything may have changed meanwhile
+    Woke,
+    /// An app launched or quit
+    AppsChanged,
+    /// A display was connected, removed, or started or stopped mirroring
+    DisplaysChanged,
+    /// The displays woke, as when the lid opens
+    DisplaysWoke,
+}
+
+type CheckFn = Box<dyn FnMut() -> bool + Send>;
+
+struct Check {
+    name: String,
+    every: Duration,
+    events: &'static [Event],
+    check: CheckFn,
+    // `None` until first run
+    satisfied: Option<bool>,
+    due: Instant,
+}
+
+/// What the front-end and triggers hand the scheduler thread
+#[derive(Default)]
+struct Inbox {
+    added: Vec<Check>,
+    events: Vec<Event>,
+}
+
+struct Scheduler {
+    inbox: Mutex<Inbox>,
+    wake: Condvar,
+}
+
+fn scheduler() -> &'static Scheduler {
+    static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+    SCHEDULER.get_or_init(|| {
+        thread::spawn(run);
+        Scheduler {
+            inbox: Mutex::new(Inbox::default()),
+            wake: Condvar::new(),
+        }
+    })
+}
+
+/// The first slot boundary at or after `at`
+fn slot_after(start: Instant, at: Instant) -> Instant {
+    let slot = Duration::from_secs(SLOT_SECS);
+    let slots = at
+        .saturating_duration_since(start)
+        .as_nanos()
+        .div_ceil(slot.as_nanos());
+    start + slot * slots as u32
+}
+
+/// Runs the checks that are due at `now`, or that subscribed to one of
+/// `events`, reporting each flip to `report`. Returns when the next is due.
+fn run_due(
+    checks: &mut [Check],
+    start: Instant,
+    now: Instant,
+    events: &[Event],
+    report: &mut dyn FnMut(&str, bool),
+) -> Option<Instant> {
+    for check in checks.iter_mut() {
+        let woken = check.events.iter().any(|event| events.contains(event));
+        if check.due > now && !woken {
+            continue;
+        }
+        let satisfied = (check.check)();
+        if check.satisfied != Some(satisfied) {
+            check.satisfied = Some(satisfied);
+            report(&check.name, satisfied);
+        }
+        check.due = slot_after(start, now + check.every);
+    }
+    checks.iter().map(|check| check.due).min()
+}
+
+fn run() {
+    let scheduler = scheduler();
+    let start = Instant::now();
+    let mut checks: Vec<Check> = Vec::new();
+    let mut next: Option<Instant> = None;
+    loop {
+        let (added, events) = {
+            let mut inbox = scheduler.inbox.lock().unwrap();
+            while inbox.added.is_empty() && inbox.events.is_empty() {
+                let now = Instant::now();
+                match next {
+                    Some(next) if next <= now => break,
+                    Some(next) => {
+                        inbox = scheduler.wake.wait_timeout(inbox, next - now).unwrap().0;
+                    }
+                    None => inbox = scheduler.wake.wait(inbox).unwrap(),
+                }
+            }
+            (
+                std::mem::take(&mut inbox.added),
+                std::mem::take(&mut inbox.events),
+            )
+        };
+        // Run outside the lock: a check may take a while, and `notify` is
+        // called from the main thread
+        checks.extend(added);
+        next = run_due(
+            &mut checks,
+            start,
+            Instant::now(),
+            &events,
+            &mut super::report,
+        );
+    }
+}
+
+/// Runs `check` every `every` (and on each of `events`) for the rest of the
+/// run, reporting as `name`. The first check runs straight away.
+pub fn spawn(
+    name: String,
+    every: Duration,
+    events: &'static [Event],
+    check: impl FnMut() -> bool + Send + 'static,
+) {
+    let scheduler = scheduler();
+    scheduler.inbox.lock().unwrap().added.push(Check {
+        name,
+        every,
+        events,
+        check: Box::new(check),
+        satisfied: None,
+        due: Instant::now(),
+    });
+    scheduler.wake.notify_one();
+}
+
+/// Tells the checks subscribed to `event` to look again now. Doesn't block.
+#[cfg(target_os = "macos")]
+pub fn notify(event: Event) {
+    let scheduler = scheduler();
+    scheduler.inbox.lock().unwrap().events.push(event);
+    scheduler.wake.notify_one();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn counting(
+        name: &str,
+        every: u64,
+        events: &'static [Event],
+        start: Instant,
+    ) -> (Check, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let check = Check {
+            name: name.into(),
+            every: Duration::from_secs(every),
+            events,
+            check: Box::new(move || counter.fetch_add(1, Ordering::SeqCst).is_multiple_of(2)),
+            satisfied: None,
+            due: start,
+        };
+        (check, runs)
+    }
+
+    #[test]
+    fn due_times_fall_on_slots() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        assert_eq!(slot_after(start, start), start);
+        assert_eq!(slot_after(start, secs(1)), secs(5));
+        assert_eq!(slot_after(start, secs(10)), secs(10));
+        assert_eq!(slot_after(start, secs(12)), secs(15));
+    }
+
+    #[test]
+    fn checks_run_when_due_or_woken() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let (ssh, ssh_runs) = counting("ssh", 10, &[Event::Woke], start);
+        let (vms, vm_runs) = counting("vms", 30, &[Event::Woke, Event::AppsChanged], start);
+        let mut checks = vec![ssh, vms];
+        let mut reports = Vec::new();
+        let mut report = |name: &str, satisfied: bool| reports.push((name.to_string(), satisfied));
+
+        // Both run at once, and report their first answer
+        assert_eq!(
+            run_due(&mut checks, start, start, &[], &mut report),
+            Some(secs(10))
+        );
+        // At 10 s only the SSH check is due
+        assert_eq!(
+            run_due(&mut checks, start, secs(10), &[], &mut report),
+            Some(secs(20))
+        );
+        assert_eq!(
+            (
+                ssh_runs.load(Ordering::SeqCst),
+                vm_runs.load(Ordering::SeqCst)
+            ),
+            (2, 1)
+        );
+        // An app quitting wakes only the checks that asked for it, moving
+        // their next turn onto the slot after
+        run_due(
+            &mut checks,
+            start,
+            secs(12),
+            &[Event::AppsChanged],
+            &mut report,
+        );
+        assert_eq!(
+            (
+                ssh_runs.load(Ordering::SeqCst),
+                vm_runs.load(Ordering::SeqCst)
+            ),
+            (2, 2)
+        );
+        assert_eq!(checks[1].due, secs(45));
+        assert_eq!(
+            reports,
+            [
+                ("ssh".to_string(), true),
+                ("vms".to_string(), true),
+                ("ssh".to_string(), false),
+                ("vms".to_string(), false),
+            ]
+        );
+    }
+}
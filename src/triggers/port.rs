@@ -0,0 +1,132 @@
+//! "Stay awake while a client is connected to one of these ports" — a dev
+//! server on 3000 or 8080, VNC on 5900 — so a session over the network isn't
+//! cut off by idle sleep.
+//!
+//! Only established inbound connections count: a port that's merely listening
+//! doesn't, nor do this machine's own connections out from it. Linux's TCP
+//! tables are read from `/proc/net`; on macOS `netstat` reads them from the
+//! kernel's `pcblist` sysctls.
+
+use std::time::Duration;
+
+use super::poll::Event;
+
+const POLL_SECS: u64 = 10;
+
+/// `3000,8080` — nonzero ports, comma-separated
+pub fn parse_ports(list: &str) -> Option<Vec<u16>> {
+    list.split(',')
+        .map(|port| port.trim().parse().ok().filter(|&port| port != 0))
+        .collect::<Option<Vec<u16>>>()
+        .filter(|ports| !ports.is_empty())
+}
+
+/// "a client is connected to port 3000 or 8080"
+pub fn name(ports: &[u16]) -> String {
+    let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+    format!("a client is connected to port {}", ports.join(" or "))
+}
+
+/// Watches `ports` for the rest of the run, as one trigger
+pub fn spawn(ports: Vec<u16>) {
+    let name = name(&ports);
+    let connected = move || established_ports().iter().any(|port| ports.contains(port));
+    super::poll::spawn(
+        name,
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke],
+        connected,
+    );
+}
+
+#[cfg(target_os = "linux")]
+fn established_ports() -> Vec<u16> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| proc_net_established(&table))
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn established_ports() -> Vec<u16> {
+    netstat_established(&crate::process::run_capture(
+        "/usr/sbin/netstat",
+        &["-an", "-p", "tcp"],
+    ))
+}
+
+// `/proc/net/tcp`: `0: 0100007F:0BB8 0100007F:D2F0 01 …`, the local address
+// and port in hex, then the remote one, then the state (`01` is established)
+#[cfg(any(target_os = "linux", test))]
+fn proc_net_established(table: &str) -> Vec<u16> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let (local, _remote, state) = (fields.next()?, fields.next()?, fields.next()?);
+            let port = local.rsplit(':').next()?;
+            (state == "01")
+                .then(|| u16::from_str_radix(port, 16).ok())
+                .flatten()
+        })
+        .collect()
+}
+
+// `netstat -an -p tcp`: `tcp4  0  0  127.0.0.1.3000  127.0.0.1.52345  ESTABLISHED`,
+// each address ending in `.port`
+#[cfg(any(target_os = "macos", test))]
+fn netstat_established(output: &str) -> Vec<u16> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [proto, _, _, local, _, "ESTABLISHED", ..] if proto.starts_with("tcp") => {
+                    local.rsplit('.').next()?.parse().ok()
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_lists_parse() {
+        assert_eq!(parse_ports("3000, 8080"), Some(vec![3000, 8080]));
+        assert_eq!(parse_ports("5900"), Some(vec![5900]));
+        assert_eq!(parse_ports("0"), None);
+        assert_eq!(parse_ports("3000,http"), None);
+        assert_eq!(parse_ports(""), None);
+        assert_eq!(
+            name(&[3000, 8080]),
+            "a client is connected to port 3000 or 8080"
+        );
+    }
+
+    #[test]
+    fn proc_net_tcp_reads_established_local_ports() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue\n   \
+            0: 00000000:0BB8 00000000:0000 0A 00000000:00000000\n   \
+            1: 0100007F:0BB8 0100007F:D2F0 01 00000000:00000000\n   \
+            2: 0100007F:D2F0 0100007F:0BB8 01 00000000:00000000\n";
+        // The listener (`0A`) doesn't count; the client's own end does, but on
+        // an ephemeral port no one would watch
+        assert_eq!(proc_net_established(table), vec![3000, 54000]);
+    }
+
+    #[test]
+    fn netstat_reads_established_local_ports() {
+        let output = "Active Internet connections (including servers)\n\
+            Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)\n\
+            tcp4       0      0  127.0.0.1.3000         127.0.0.1.52345        ESTABLISHED\n\
+            tcp46      0      0  *.5900                 *.*                    LISTEN\n\
+            tcp6       0      0  fe80::1%lo0.8080       fe80::1%lo0.61000      ESTABLISHED\n";
+        assert_eq!(netstat_established(output), vec![3000, 8080]);
+    }
+}
@@ -0,0 +1,151 @@
+//! Every running process, for triggers that look for one: `/proc/<pid>/stat`
+//! on Linux, libproc on macOS.
+
+use std::time::Duration;
+
+// `/proc` counts CPU time in `USER_HZ` ticks, 100 a second on every
+// architecture Linux runs on
+#[cfg(any(target_os = "linux", test))]
+const TICKS_PER_SEC: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    pub name: String,
+    /// Unknown (0) on macOS
+    pub ppid: u32,
+    /// User and system time so far. On Linux this includes children it has
+    /// waited for; on macOS it's unknown (zero) for other users' processes.
+    pub cpu: Duration,
+}
+
+// Every process, from `/proc/<pid>/stat`. Names are the kernel's `comm`, cut
+// to 15 bytes.
+#[cfg(target_os = "linux")]
+pub fn all() -> Vec<Process> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            parse_stat(
+                pid,
+                &std::fs::read_to_string(entry.path().join("stat")).ok()?,
+            )
+        })
+        .collect()
+}
+
+// `1234 (sshd-session) S 987 …`: the name is parenthesised and may itself hold
+// spaces or parentheses, so it runs to the last `)`. After it come the state,
+// the parent and, tenth to thirteenth, user and system ticks, then the same
+// for waited-for children.
+#[cfg(any(target_os = "linux", test))]
+fn parse_stat(pid: u32, stat: &str) -> Option<Process> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let ticks = fields
+        .get(11..15)
+        .map(|ticks| ticks.iter().filter_map(|t| t.parse::<u64>().ok()).sum())
+        .unwrap_or(0);
+    Some(Process {
+        pid,
+        name,
+        ppid,
+        cpu: Duration::from_millis(ticks * 1000 / TICKS_PER_SEC),
+    })
+}
+
+// libc's are deprecated in favour of the `mach2` crate; this is all that's needed
+#[cfg(target_os = "macos")]
+#[repr(C)]
+struct MachTimebase {
+    numer: u32,
+    denom: u32,
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn mach_timebase_info(info: *mut MachTimebase) -> libc::c_int;
+}
+
+#[cfg(target_os = "macos")]
+pub fn all() -> Vec<Process> {
+    unsafe {
+        let count = libc::proc_listallpids(std::ptr::null_mut(), 0);
+        if count <= 0 {
+            return Vec::new();
+        }
+        // Room for a few started since
+        let mut pids = vec![0 as libc::pid_t; count as usize + 16];
+        let bytes = (pids.len() * std::mem::size_of::<libc::pid_t>()) as libc::c_int;
+        let count = libc::proc_listallpids(pids.as_mut_ptr().cast(), bytes);
+        pids.truncate(count.max(0) as usize);
+
+        // Task times are in Mach ticks, nanoseconds only on Intel
+        let mut timebase = MachTimebase { numer: 1, denom: 1 };
+        mach_timebase_info(&mut timebase);
+
+        let mut name = [0u8; 64];
+        pids.into_iter()
+            .filter_map(|pid| {
+                let len = libc::proc_name(pid, name.as_mut_ptr().cast(), name.len() as u32);
+                if len <= 0 {
+                    return None;
+                }
+                let mut info: libc::proc_taskinfo = std::mem::zeroed();
+                let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+                let got = libc::proc_pidinfo(
+                    pid,
+                    libc::PROC_PIDTASKINFO,
+                    0,
+                    (&mut info as *mut libc::proc_taskinfo).cast(),
+                    size,
+                );
+                let ticks = if got == size {
+                    info.pti_total_user + info.pti_total_system
+                } else {
+                    0
+                };
+                Some(Process {
+                    pid: pid as u32,
+                    name: String::from_utf8_lossy(&name[..len as usize]).into_owned(),
+                    ppid: 0,
+                    cpu: Duration::from_nanos(
+                        ticks * timebase.numer as u64 / timebase.denom.max(1) as u64,
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_lines_parse() {
+        assert_eq!(
+            parse_stat(
+                1234,
+                "1234 (sshd-session) S 987 1234 1234 0 -1 4194560 1021 0 0 0 250 50 100 0 20"
+            ),
+            Some(Process {
+                pid: 1234,
+                name: "sshd-session".into(),
+                ppid: 987,
+                cpu: Duration::from_secs(4),
+            })
+        );
+        let odd = parse_stat(42, "42 (odd (name)) R 7 42").unwrap();
+        assert_eq!((odd.name.as_str(), odd.ppid), ("odd (name)", 7));
+        assert_eq!(odd.cpu, Duration::ZERO);
+        assert_eq!(parse_stat(1, "garbage"), None);
+    }
+}
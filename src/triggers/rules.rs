@@ -0,0 +1,327 @@
+//! Trigger rules: the triggers set up in the editor window rather than on the
+//! command line, each with whether it's on, its own mode and a time limit.
+//!
+//! They're kept as one string (`TriggerRules`), a rule per line of
+//! tab-separated fields, `-` where one is empty: `on` or `off`, the trigger's
+//! key, its argument, a mode and a limit in minutes, as in
+//! `off⇥port⇥3000,8080⇥server⇥60`.
+//!
+//! Order matters: when several hold at once, the first is credited with the
+//! session. Applying rules again (after an edit) starts the new ones and
+//! updates the rest; a trigger can't be stopped once started, so a rule removed
+//! or switched off is disabled instead, until Awake next starts.
+
+use std::cell::RefCell;
+
+use crate::mode::Mode;
+#[cfg(target_os = "macos")]
+use crate::solar::Time;
+use crate::state::{self, Command};
+
+pub const KEY: &str = "TriggerRules";
+/// The longest time limit a rule can set: a week
+pub const MAX_MINUTES: u64 = 7 * 24 * 60;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    LockFile(String),
+    Ssh,
+    Port(Vec<u16>),
+    Containers,
+    Build,
+    /// `<host>[:<port>]`
+    Leader(String),
+    #[cfg(target_os = "macos")]
+    Mirroring,
+    #[cfg(target_os = "macos")]
+    Audio,
+    /// From a morning wake to the end of these hours
+    #[cfg(target_os = "macos")]
+    Workday((u32, u32)),
+    /// Between two times of day, either of which may follow the sun
+    #[cfg(target_os = "macos")]
+    Schedule((Time, Time)),
+}
+
+impl Condition {
+    /// With the argument it takes, if any, as the editor starts it
+    #[cfg(target_os = "macos")]
+    pub const ALL: [(&'static str, &'static str); 10] = [
+        ("lockfile", "/tmp/awake.lock"),
+        ("ssh", ""),
+        ("port", "3000"),
+        ("containers", ""),
+        ("build", ""),
+        ("leader", "mini.local"),
+        ("mirroring", ""),
+        ("audio", ""),
+        ("workday", "07:00-18:00"),
+        ("schedule", "sunset-00:00"),
+    ];
+
+    /// `key` is one of `super::KEYS`
+    pub fn parse(key: &str, argument: &str) -> Option<Condition> {
+        let argument = argument.trim();
+        match key {
+            "lockfile" if !argument.is_empty() => Some(Condition::LockFile(argument.into())),
+            "ssh" => Some(Condition::Ssh),
+            "port" => super::port::parse_ports(argument).map(Condition::Port),
+            "containers" => Some(Condition::Containers),
+            "build" => Some(Condition::Build),
+            "leader" if crate::remote::parse_host(argument).is_some() => {
+                Some(Condition::Leader(argument.into()))
+            }
+            #[cfg(target_os = "macos")]
+            "mirroring" => Some(Condition::Mirroring),
+            #[cfg(target_os = "macos")]
+            "audio" => Some(Condition::Audio),
+            #[cfg(target_os = "macos")]
+            "workday" => super::workday::parse_hours(argument).map(Condition::Workday),
+            #[cfg(target_os = "macos")]
+            "schedule" => crate::solar::parse_window(argument).map(Condition::Schedule),
+            _ => None,
+        }
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    pub fn key(&self) -> &'static str {
+        match self {
+            Condition::LockFile(_) => "lockfile",
+            Condition::Ssh => "ssh",
+            Condition::Port(_) => "port",
+            Condition::Containers => "containers",
+            Condition::Build => "build",
+            Condition::Leader(_) => "leader",
+            #[cfg(target_os = "macos")]
+            Condition::Mirroring => "mirroring",
+            #[cfg(target_os = "macos")]
+            Condition::Audio => "audio",
+            #[cfg(target_os = "macos")]
+            Condition::Workday(_) => "workday",
+            #[cfg(target_os = "macos")]
+            Condition::Schedule(_) => "schedule",
+        }
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    pub fn argument(&self) -> String {
+        match self {
+            Condition::LockFile(path) | Condition::Leader(path) => path.clone(),
+            Condition::Port(ports) => {
+                let ports: Vec<String> = ports.iter().map(u16::to_string).collect();
+                ports.join(",")
+            }
+            #[cfg(target_os = "macos")]
+            Condition::Workday((start, end)) => {
+                use crate::quiet_hours::format_hhmm;
+                format!("{}-{}", format_hhmm(*start), format_hhmm(*end))
+            }
+            #[cfg(target_os = "macos")]
+            Condition::Schedule((start, end)) => format!("{}-{}", start, end),
+            _ => String::new(),
+        }
+    }
+
+    /// The trigger's name, as the menus show it and flags would start it
+    pub fn name(&self) -> String {
+        match self {
+            Condition::LockFile(path) => format!("{} exists", path),
+            Condition::Ssh => super::ssh::NAME.into(),
+            Condition::Port(ports) => super::port::name(ports),
+            Condition::Containers => super::containers::NAME.into(),
+            Condition::Build => super::build::NAME.into(),
+            Condition::Leader(leader) => super::leader::name(leader),
+            #[cfg(target_os = "macos")]
+            Condition::Mirroring => super::mirroring::NAME.into(),
+            #[cfg(target_os = "macos")]
+            Condition::Audio => super::audio::NAME.into(),
+            #[cfg(target_os = "macos")]
+            Condition::Workday(_) => super::workday::NAME.into(),
+            #[cfg(target_os = "macos")]
+            Condition::Schedule(window) => super::schedule::name(*window),
+        }
+    }
+
+    fn spawn(&self) {
+        match self {
+            Condition::LockFile(path) => super::path_exists::spawn(self.name(), path.into()),
+            Condition::Ssh => super::ssh::spawn(),
+            Condition::Port(ports) => super::port::spawn(ports.clone()),
+            Condition::Containers => super::containers::spawn(),
+            Condition::Build => super::build::spawn(),
+            Condition::Leader(leader) => {
+                if !super::leader::spawn(leader) {
+                    eprintln!("Awake: can't follow {}", leader);
+                }
+            }
+            #[cfg(target_os = "macos")]
+            Condition::Mirroring => super::mirroring::spawn(),
+            #[cfg(target_os = "macos")]
+            Condition::Audio => super::audio::spawn(),
+            #[cfg(target_os = "macos")]
+            Condition::Workday(hours) => super::workday::spawn(*hours),
+            #[cfg(target_os = "macos")]
+            Condition::Schedule(window) => super::schedule::spawn(*window),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub enabled: bool,
+    pub condition: Condition,
+    /// Its own mode, or `None` for the menu's
+    pub mode: Option<Mode>,
+    /// How long a session it starts may last
+    pub minutes: Option<u64>,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [enabled, key, argument, mode, minutes] = fields.as_slice() else {
+            return None;
+        };
+        fn dash(field: &str) -> Option<&str> {
+            Some(field).filter(|f| *f != "-" && !f.is_empty())
+        }
+        let mode = match dash(mode) {
+            Some(mode) => Some(Mode::parse(mode)?),
+            None => None,
+        };
+        let minutes = match dash(minutes) {
+            Some(minutes) => Some(
+                minutes
+                    .parse()
+                    .ok()
+                    .filter(|m| (1..=MAX_MINUTES).contains(m))?,
+            ),
+            None => None,
+        };
+        Some(Rule {
+            enabled: match *enabled {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            },
+            condition: Condition::parse(key, dash(argument).unwrap_or(""))?,
+            mode,
+            minutes,
+        })
+    }
+
+    #[cfg(any(target_os = "macos", test))]
+    fn line(&self) -> String {
+        let dash = |field: String| if field.is_empty() { "-".into() } else { field };
+        [
+            if self.enabled { "on" } else { "off" }.to_string(),
+            self.condition.key().to_string(),
+            dash(self.condition.argument()),
+            dash(self.mode.map(|mode| mode.to_string()).unwrap_or_default()),
+            dash(self.minutes.map(|m| m.to_string()).unwrap_or_default()),
+        ]
+        .join("\t")
+    }
+}
+
+/// Reads the rules back; a line that doesn't read is dropped
+pub fn parse(text: &str) -> Vec<Rule> {
+    text.lines().filter_map(Rule::parse).collect()
+}
+
+/// The rules as kept, for the editor to save
+#[cfg(any(target_os = "macos", test))]
+pub fn format(rules: &[Rule]) -> String {
+    let lines: Vec<String> = rules.iter().map(Rule::line).collect();
+    lines.join("\n")
+}
+
+thread_local! {
+    // The triggers rules have started, by name; main thread only
+    static STARTED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Makes `rules` the triggers in force, as described above
+pub fn apply(rules: &[Rule]) {
+    let names: Vec<String> = rules.iter().map(|rule| rule.condition.name()).collect();
+    STARTED.with(|started| {
+        let mut started = started.borrow_mut();
+        for name in started.iter().filter(|name| !names.contains(name)) {
+            state::post(Command::SetTriggerEnabled {
+                name: name.clone(),
+                enabled: false,
+            });
+        }
+        for (rule, name) in rules.iter().zip(&names) {
+            // Set up before the trigger first reports
+            state::post(Command::SetTriggerMode {
+                name: name.clone(),
+                kinds: rule.mode.map(Mode::kinds),
+            });
+            state::post(Command::SetTriggerLimit {
+                name: name.clone(),
+                minutes: rule.minutes,
+            });
+            state::post(Command::SetTriggerEnabled {
+                name: name.clone(),
+                enabled: rule.enabled,
+            });
+            if rule.enabled && !started.contains(name) {
+                rule.condition.spawn();
+                started.push(name.clone());
+            }
+        }
+    });
+    state::post(Command::OrderTriggers(names));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_read_back() {
+        let rules = vec![
+            Rule {
+                enabled: true,
+                condition: Condition::LockFile("/tmp/my job.lock".into()),
+                mode: Some(Mode::Display),
+                minutes: None,
+            },
+            Rule {
+                enabled: false,
+                condition: Condition::Port(vec![3000, 8080]),
+                mode: None,
+                minutes: Some(60),
+            },
+            Rule {
+                enabled: true,
+                condition: Condition::Ssh,
+                mode: Some(Mode::Server),
+                minutes: None,
+            },
+            Rule {
+                enabled: true,
+                condition: Condition::Leader("mini.local:9000".into()),
+                mode: None,
+                minutes: None,
+            },
+        ];
+        let text = format(&rules);
+        assert_eq!(text.lines().nth(1), Some("off\tport\t3000,8080\t-\t60"));
+        assert_eq!(parse(&text), rules);
+    }
+
+    #[test]
+    fn bad_lines_are_dropped() {
+        assert_eq!(parse("on\tlockfile\t-\t-\t-"), vec![]);
+        assert_eq!(parse("on\tport\thttp\t-\t-"), vec![]);
+        assert_eq!(parse("on\tssh\t-\tsometimes\t-"), vec![]);
+        assert_eq!(parse("on\tssh\t-\t-\t0"), vec![]);
+        assert_eq!(parse("on\tssh\t-\t-\t10081"), vec![]);
+        assert_eq!(parse("maybe\tssh\t-\t-\t-"), vec![]);
+        assert_eq!(parse("on\tssh").len(), 0);
+        assert_eq!(parse("on\tleader\tmini.local:0\t-\t-"), vec![]);
+        assert_eq!(parse("on\tbuild\t-\t-\t-\n\n").len(), 1);
+    }
+}
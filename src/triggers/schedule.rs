@@ -0,0 +1,38 @@
+//! "Stay awake between two times of day" — for signage and ambient displays,
+//! `--between sunset-00:00` keeps the display awake from dusk to midnight
+//! without the times needing edits as the seasons change. Either end may be a
+//! clock time or a solar one (see `solar`), and the window may run past
+//! midnight.
+
+use std::time::Duration;
+
+use crate::quiet_hours::{contains, local_minutes_now};
+use crate::solar::{self, Time};
+
+// Often enough for either end to be on time
+const POLL_SECS: u64 = 60;
+
+/// "it's between sunset and 00:00", as the menu shows it
+pub fn name(window: (Time, Time)) -> String {
+    format!("it's between {} and {}", window.0, window.1)
+}
+
+// Nothing holds until both ends can be worked out for today
+fn holds(window: (Time, Time)) -> bool {
+    match (window.0.minutes_today(), window.1.minutes_today()) {
+        (Some(start), Some(end)) => contains(start, end, local_minutes_now()),
+        _ => false,
+    }
+}
+
+pub fn spawn(window: (Time, Time)) {
+    if window.0.follows_sun() || window.1.follows_sun() {
+        solar::want_location();
+    }
+    super::poll::spawn(
+        name(window),
+        Duration::from_secs(POLL_SECS),
+        &[super::poll::Event::Woke],
+        move || holds(window),
+    );
+}
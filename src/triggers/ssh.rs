@@ -0,0 +1,50 @@
+//! "Stay awake while an SSH client is connected" — so remote work isn't cut off
+//! by idle sleep just because nobody is at the keyboard.
+//!
+//! Connections are counted from the processes `sshd` runs for them, which
+//! covers sessions without a terminal (`scp`, `rsync`, editors' remote modes)
+//! as well as logins; utmpx would only see the logins. On macOS launchd starts
+//! an `sshd` for each connection and nothing else runs by that name; on Linux a
+//! listener started by init forks one per connection, so it isn't counted.
+
+use std::time::Duration;
+
+use super::poll::Event;
+
+const POLL_SECS: u64 = 10;
+pub const NAME: &str = "an SSH client is connected";
+// `sshd-session` from OpenSSH 9.8 on
+const SSHD_NAMES: [&str; 2] = ["sshd", "sshd-session"];
+
+/// Whether a process named `name` under parent `ppid` serves a connection
+fn is_connection(name: &str, ppid: u32) -> bool {
+    SSHD_NAMES.contains(&name) && (cfg!(target_os = "macos") || ppid != 1)
+}
+
+fn connected() -> bool {
+    super::processes::all()
+        .iter()
+        .any(|process| is_connection(&process.name, process.ppid))
+}
+
+pub fn spawn() {
+    super::poll::spawn(
+        NAME.into(),
+        Duration::from_secs(POLL_SECS),
+        &[Event::Woke],
+        connected,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sshd_processes_are_connections() {
+        assert!(is_connection("sshd-session", 987));
+        assert!(!is_connection("bash", 987));
+        // The listener, started by init, only counts where there's none
+        assert_eq!(is_connection("sshd", 1), cfg!(target_os = "macos"));
+    }
+}
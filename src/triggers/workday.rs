@@ -0,0 +1,90 @@
+//! "Stay awake through the workday" — a lightweight work-hours automation.
+//! The first time the displays wake within the configured hours (the lid
+//! opening in the morning, or the screen waking from sleep), Awake switches
+//! on, and it switches off again at the end of those hours.
+//!
+//! Configured with `--workday 07:00-18:00` (or `Workday`); the hours can't wrap
+//! past midnight. Launching Awake within them counts as a wake, since the
+//! displays are on. The front-end reports each display wake, so the check
+//! looks at once rather than at its next turn.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::poll::Event;
+use crate::quiet_hours::{contains, local_day_now, local_minutes_now, parse_hhmm};
+
+// Often enough for the end of the day to be on time
+const POLL_SECS: u64 = 60;
+pub const NAME: &str = "the workday lasts";
+
+// A display wake not yet seen by the check; launching counts as one
+static WOKEN: AtomicBool = AtomicBool::new(true);
+
+/// `07:00-18:00`: the workday's start and end in minutes since local midnight
+pub fn parse_hours(text: &str) -> Option<(u32, u32)> {
+    let (start, end) = text.split_once('-')?;
+    let (start, end) = (parse_hhmm(start)?, parse_hhmm(end)?);
+    (start < end).then_some((start, end))
+}
+
+/// Whether the workday holds at `now` on `day`. A wake within `hours` opens
+/// it (`opened` remembers which day); it lasts until the hours end.
+fn holds(opened: &mut Option<i32>, woke: bool, day: i32, now: u32, hours: (u32, u32)) -> bool {
+    let within = contains(hours.0, hours.1, now);
+    if woke && within {
+        *opened = Some(day);
+    }
+    within && *opened == Some(day)
+}
+
+/// Called by the front-end when the displays wake
+pub fn displays_woke() {
+    WOKEN.store(true, Ordering::Relaxed);
+}
+
+pub fn spawn(hours: (u32, u32)) {
+    let mut opened = None;
+    super::poll::spawn(
+        NAME.into(),
+        Duration::from_secs(POLL_SECS),
+        &[Event::DisplaysWoke],
+        move || {
+            let woke = WOKEN.swap(false, Ordering::Relaxed);
+            holds(
+                &mut opened,
+                woke,
+                local_day_now(),
+                local_minutes_now(),
+                hours,
+            )
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hours_parse_within_a_day() {
+        assert_eq!(parse_hours("07:00-18:30"), Some((7 * 60, 18 * 60 + 30)));
+        assert_eq!(parse_hours("22:00-06:00"), None);
+        assert_eq!(parse_hours("07:00"), None);
+    }
+
+    #[test]
+    fn a_wake_opens_the_day_until_evening() {
+        let hours = (7 * 60, 18 * 60);
+        let mut opened = None;
+        // Woken at night: nothing, and the wake is spent
+        assert!(!holds(&mut opened, true, 1, 3 * 60, hours));
+        assert!(!holds(&mut opened, false, 1, 8 * 60, hours));
+        // The lid opens in the morning and the day holds until 18:00
+        assert!(holds(&mut opened, true, 1, 8 * 60, hours));
+        assert!(holds(&mut opened, false, 1, 17 * 60 + 59, hours));
+        assert!(!holds(&mut opened, false, 1, 18 * 60, hours));
+        // The next morning needs a wake of its own
+        assert!(!holds(&mut opened, false, 2, 8 * 60, hours));
+    }
+}
@@ -0,0 +1,260 @@
+//! Keeping awake for as long as a dropped path is busy.
+//!
+//! A plain file or folder counts as busy while it keeps growing (an export or
+//! download in progress): `Growth` is the pure rule, fed `Snapshot`s by a
+//! polling `Watch` thread. A dropped script is run instead, and counts as busy
+//! until it exits. The overnight mode's last stretch is a watch too: busy while
+//! the battery charges. Either way the thread reports back once, like a timer
+//! `Waiter`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::battery;
+
+/// How often a growing path is looked at
+const POLL_SECS: u64 = 5;
+/// How long a path may sit unchanged before it counts as finished
+const SETTLE_SECS: u64 = 60;
+/// How often the charge is looked at
+const CHARGE_POLL_SECS: u64 = 60;
+// Walking a huge folder every few seconds would cost more than it saves
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// Busy while its size or modification time keeps changing
+    Growing(PathBuf),
+    /// Run, and busy until it exits
+    Script(PathBuf),
+    /// Busy while the battery is plugged in and below this percentage
+    Charging(u8),
+}
+
+impl Target {
+    pub fn for_path(path: PathBuf) -> Target {
+        if is_script(&path) {
+            Target::Script(path)
+        } else {
+            Target::Growing(path)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_script(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn is_script(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().is_some_and(|ext| {
+            ["exe", "bat", "cmd"]
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Total size and newest modification time under a path; `None` once it's gone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub bytes: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    pub fn take(path: &Path) -> Option<Snapshot> {
+        let meta = fs::metadata(path).ok()?;
+        let mut snapshot = Snapshot {
+            bytes: meta.len(),
+            modified: meta.modified().ok(),
+        };
+        if meta.is_dir() {
+            snapshot.bytes = 0;
+            let mut pending = vec![path.to_path_buf()];
+            let mut seen = 0;
+            while let Some(dir) = pending.pop() {
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for entry in entries.flatten().take(MAX_ENTRIES.saturating_sub(seen)) {
+                    seen += 1;
+                    let Ok(meta) = entry.metadata() else {
+                        continue;
+                    };
+                    if meta.is_dir() {
+                        pending.push(entry.path());
+                    } else {
+                        snapshot.bytes += meta.len();
+                    }
+                    snapshot.modified = snapshot.modified.max(meta.modified().ok());
+                }
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+/// When a growing path has finished: it has gone, or sat unchanged for
+/// `settle_secs`
+#[derive(Debug)]
+pub struct Growth {
+    settle_secs: u64,
+    last: Option<Snapshot>,
+    changed_at: u64,
+}
+
+impl Growth {
+    pub fn new(now: u64, settle_secs: u64) -> Growth {
+        Growth {
+            settle_secs,
+            last: None,
+            changed_at: now,
+        }
+    }
+
+    /// Feeds the latest look at the path; false once it has finished
+    pub fn observe(&mut self, now: u64, snapshot: Option<Snapshot>) -> bool {
+        let Some(snapshot) = snapshot else {
+            return false;
+        };
+        if self.last != Some(snapshot) {
+            self.last = Some(snapshot);
+            self.changed_at = now;
+        }
+        now.saturating_sub(self.changed_at) < self.settle_secs
+    }
+}
+
+type CancelPair = Arc<(Mutex<bool>, Condvar)>;
+
+/// Thread that watches one target and reports when it's no longer busy. A
+/// cancelled watch exits quietly; a script it started keeps running.
+pub struct Watch {
+    cancel: CancelPair,
+}
+
+impl Watch {
+    pub fn spawn(target: Target, on_done: impl FnOnce() + Send + 'static) -> Watch {
+        let cancel: CancelPair = Arc::new((Mutex::new(false), Condvar::new()));
+        let cancel_pair = Arc::clone(&cancel);
+
+        thread::spawn(move || {
+            let finished = match target {
+                Target::Growing(path) => watch_growth(&path, &cancel_pair),
+                Target::Script(path) => watch_script(&path, &cancel_pair),
+                Target::Charging(percent) => watch_charge(percent, &cancel_pair),
+            };
+            if finished {
+                on_done();
+            }
+        });
+
+        Watch { cancel }
+    }
+
+    /// Wakes the thread so it exits without reporting
+    pub fn cancel(&self) {
+        let (lock, cvar) = &*self.cancel;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    }
+}
+
+/// Sleeps for `secs` unless cancelled first; true if cancelled
+fn wait(cancel: &CancelPair, secs: u64) -> bool {
+    let (lock, cvar) = &**cancel;
+    let guard = lock.lock().unwrap();
+    let (guard, _timeout) = cvar
+        .wait_timeout_while(guard, Duration::from_secs(secs), |cancelled| !*cancelled)
+        .unwrap();
+    *guard
+}
+
+fn watch_growth(path: &Path, cancel: &CancelPair) -> bool {
+    let mut growth = Growth::new(crate::now_secs(), SETTLE_SECS);
+    while growth.observe(crate::now_secs(), Snapshot::take(path)) {
+        if wait(cancel, POLL_SECS) {
+            return false;
+        }
+    }
+    true
+}
+
+fn watch_charge(percent: u8, cancel: &CancelPair) -> bool {
+    while battery::read().is_some_and(|b| b.is_charging_below(percent)) {
+        if wait(cancel, CHARGE_POLL_SECS) {
+            return false;
+        }
+    }
+    true
+}
+
+fn watch_script(path: &Path, cancel: &CancelPair) -> bool {
+    let mut command = process::Command::new(path);
+    if let Some(dir) = path.parent() {
+        command.current_dir(dir);
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Couldn't run {}: {}", path.display(), e);
+            return true;
+        }
+    };
+    loop {
+        match child.try_wait() {
+            Ok(None) => {}
+            Ok(Some(_)) | Err(_) => return true,
+        }
+        if wait(cancel, 1) {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(bytes: u64) -> Option<Snapshot> {
+        Some(Snapshot {
+            bytes,
+            modified: None,
+        })
+    }
+
+    #[test]
+    fn growing_path_stays_busy_until_it_settles() {
+        let mut growth = Growth::new(0, 60);
+        assert!(growth.observe(0, snapshot(10)));
+        assert!(growth.observe(50, snapshot(20)));
+        // Unchanged, but not yet for a full settle period
+        assert!(growth.observe(100, snapshot(20)));
+        assert!(!growth.observe(110, snapshot(20)));
+    }
+
+    #[test]
+    fn vanished_path_is_finished() {
+        let mut growth = Growth::new(0, 60);
+        assert!(growth.observe(0, snapshot(10)));
+        assert!(!growth.observe(5, None));
+    }
+
+    #[test]
+    fn folder_snapshot_sums_its_files() {
+        let dir = std::env::temp_dir().join(format!("awake-watch-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), [0u8; 3]).unwrap();
+        fs::write(dir.join("sub/b"), [0u8; 4]).unwrap();
+        let bytes = Snapshot::take(&dir).map(|s| s.bytes);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(bytes, Some(7));
+    }
+}
@@ -0,0 +1,125 @@
+//! Webhooks: an HTTP POST to each of the user's URLs as Awake switches on,
+//! switches off or a timer runs out, for dashboards and services like ntfy or
+//! a Slack incoming webhook.
+//!
+//! Set with `--webhook <url>` or `WebhookURL`, several separated by spaces.
+//! The body is `status.json`'s object with the event, and a `text` line that
+//! chat services show as the message:
+//!
+//! `{"text":"Awake: On — turned on manually","event":"activate","ts":…,
+//! "awake":true,…}`
+//!
+//! Sent with `curl`, which every supported OS ships and which speaks HTTPS,
+//! on a thread of its own so a slow service doesn't hold anything up. None
+//! are sent in safe mode.
+
+use std::cell::Cell;
+use std::process::Command;
+use std::thread;
+
+use crate::change::{self, Cue, Seen};
+use crate::event_log::json_string;
+use crate::state::{self, Status};
+use crate::status_file;
+
+// Seconds before giving up on a service
+const TIMEOUT_SECS: &str = "10";
+
+thread_local! {
+    static LAST: Cell<Seen> = const { Cell::new(Seen { awake: false, expiry: None }) };
+}
+
+/// `WebhookURL`'s (or `--webhook`'s) URLs; `None` if there are none, or one
+/// isn't HTTP
+pub fn parse_urls(text: &str) -> Option<Vec<String>> {
+    let urls: Vec<String> = text.split_whitespace().map(String::from).collect();
+    let valid = urls
+        .iter()
+        .all(|url| url.starts_with("https://") || url.starts_with("http://"));
+    (!urls.is_empty() && valid).then_some(urls)
+}
+
+fn payload(cue: Cue, status: &Status, ts: u64) -> String {
+    let json = status_file::to_json(status, ts);
+    format!(
+        r#"{{"text":{},"event":{},{}"#,
+        json_string(&format!("Awake: {}", status.summary())),
+        json_string(cue.name()),
+        &json[1..]
+    )
+}
+
+fn post(url: String, body: String) {
+    thread::spawn(move || {
+        let mut command = Command::new("curl");
+        command
+            .args(["-fsS", "-m", TIMEOUT_SECS, "-X", "POST"])
+            .args(["-H", "Content-Type: application/json", "--data-binary"])
+            .arg(&body)
+            .arg(&url);
+        // Without a console of its own, which would flash up from a tray app
+        #[cfg(windows)]
+        std::os::windows::process::CommandExt::creation_flags(
+            &mut command,
+            windows_sys::Win32::System::Threading::CREATE_NO_WINDOW,
+        );
+        let result = command.status();
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Webhook {} failed: curl exited with {}", url, status),
+            Err(e) => eprintln!("Webhook {} failed: {}", url, e),
+        }
+    });
+}
+
+/// Posts to `urls` on every change from now on
+pub fn install(urls: Vec<String>) {
+    state::on_state_change(move |status| {
+        let seen = Seen::of(status);
+        let before = LAST.with(|last| last.replace(seen));
+        let now = crate::now_secs();
+        if let Some(cue) = change::cue(before, seen, now) {
+            let body = payload(cue, status, now);
+            for url in &urls {
+                post(url.clone(), body.clone());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use crate::state::Source;
+
+    #[test]
+    fn urls_must_be_http() {
+        assert_eq!(
+            parse_urls(" https://ntfy.sh/awake  http://nas.local/hook\n"),
+            Some(vec![
+                "https://ntfy.sh/awake".into(),
+                "http://nas.local/hook".into()
+            ])
+        );
+        assert_eq!(parse_urls("ntfy.sh/awake"), None);
+        assert_eq!(parse_urls("  "), None);
+    }
+
+    #[test]
+    fn the_payload_is_the_status_with_the_event() {
+        let source = Source::Manual;
+        let status = Status {
+            kinds: Mode::System.kinds(),
+            source: Some(&source),
+            expiry: None,
+            override_until: None,
+            triggers: &[],
+            overnight: None,
+            mode_return: None,
+        };
+        assert!(payload(Cue::Activated, &status, 7).starts_with(
+            r#"{"text":"Awake: On — turned on manually","event":"activate","ts":7,"awake":true,"mode":"system","source":"manual","reason":"turned on manually","expires":null,"triggers_paused_until":null,"remaining_seconds":null,"assertions":"system","arch":"#
+        ));
+    }
+}
@@ -0,0 +1,436 @@
+//! Windows front-end: a notification-area (tray) icon. Left click toggles, right
+//! click opens the menu — the same layout as the macOS status item.
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::Shell::{
+    Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY,
+    NOTIFYICONDATAW,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DispatchMessageW,
+    GetCursorPos, GetMessageW, LoadIconW, MessageBoxW, PostMessageW, PostQuitMessage,
+    RegisterClassW, SetForegroundWindow, TrackPopupMenu, TranslateMessage, HMENU, HWND_MESSAGE,
+    IDI_APPLICATION, IDI_INFORMATION, IDNO, MB_ICONQUESTION, MB_SETFOREGROUND, MB_YESNO,
+    MENU_ITEM_FLAGS, MF_CHECKED, MF_GRAYED, MF_POPUP, MF_SEPARATOR, MF_STRING, MSG, TPM_NONOTIFY,
+    TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_APP, WM_CONTEXTMENU, WM_LBUTTONUP, WM_RBUTTONUP, WNDCLASSW,
+};
+
+use crate::error::AwakeError;
+use crate::keepawake::{self, KindSet};
+use crate::mode::Mode;
+use crate::{battery, state, stats};
+
+// Shell_NotifyIcon callbacks for our icon
+const WM_TRAY: u32 = WM_APP + 1;
+// A boxed `state::Command` from `post`, carried in the lParam
+const WM_COMMAND_POSTED: u32 = WM_APP + 2;
+// `remind`, with the hours in the wParam
+const WM_REMIND: u32 = WM_APP + 3;
+
+const TRAY_ICON_ID: u32 = 1;
+
+// Menu item IDs; `TrackPopupMenu` hands the chosen one back
+const ID_TOGGLE: usize = 1;
+const ID_TIMER_15: usize = 2;
+const ID_TIMER_30: usize = 3;
+const ID_TIMER_60: usize = 4;
+const ID_TIMER_120: usize = 5;
+const ID_EXTEND_15: usize = 6;
+const ID_MODE_DISPLAY: usize = 7;
+const ID_MODE_SYSTEM: usize = 8;
+const ID_MODE_BOTH: usize = 9;
+const ID_QUIT: usize = 10;
+const ID_MODE_SERVER: usize = 11;
+const ID_OVERNIGHT: usize = 12;
+// One per trigger, in `TRIGGERS` order
+const ID_TRIGGER_FIRST: usize = 100;
+
+// The hidden window that owns the tray icon; read by `post` from any thread
+static WINDOW: AtomicUsize = AtomicUsize::new(0);
+// Commands posted before the window existed; `run` applies them once it does
+static PENDING: Mutex<Vec<state::Command>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // UI thread only; the menu is rebuilt from these each time it opens
+    static MODE: Cell<KindSet> = const { Cell::new(Mode::Both.kinds()) };
+    static AWAKE: Cell<bool> = const { Cell::new(false) };
+    static SUMMARY: RefCell<String> = const { RefCell::new(String::new()) };
+    static ACTIVE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static TRIGGERS: RefCell<Vec<state::Trigger>> = const { RefCell::new(Vec::new()) };
+    static OVERNIGHT: Cell<bool> = const { Cell::new(false) };
+    static REMINDING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+fn window() -> HWND {
+    WINDOW.load(Ordering::Acquire) as HWND
+}
+
+// Errors surface here, at the UI boundary; for now they go to stderr
+fn present_error(error: &AwakeError) {
+    eprintln!("Awake: {}", error);
+}
+
+fn handle(cmd: state::Command) {
+    if let Err(e) = state::handle(cmd) {
+        present_error(&e);
+    }
+}
+
+/// Gives `selftest` a console to print to; the tray build has none of its own
+pub fn attach_console() {
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Queue `cmd` for the UI thread. Safe to call from any thread.
+pub fn post(cmd: state::Command) {
+    let mut pending = PENDING.lock().unwrap();
+    let hwnd = window();
+    if hwnd.is_null() {
+        pending.push(cmd);
+        return;
+    }
+    drop(pending);
+    let ctx = Box::into_raw(Box::new(cmd));
+    if unsafe { PostMessageW(hwnd, WM_COMMAND_POSTED, 0, ctx as LPARAM) } == 0 {
+        // Not queued, so reclaim it here
+        drop(unsafe { Box::from_raw(ctx) });
+    }
+}
+
+/// Asks whether a session that has run `hours` is still needed, once the
+/// current command is done: the message box runs its own message loop
+pub fn remind(hours: u64) {
+    unsafe {
+        PostMessageW(window(), WM_REMIND, hours as WPARAM, 0);
+    }
+}
+
+fn show_reminder(hours: u64) {
+    if REMINDING.with(|reminding| reminding.replace(true)) {
+        return;
+    }
+    let text = wide(&format!(
+        "{}\n\nYes keeps it on; No turns it off.",
+        state::reminder_text(hours, "PC")
+    ));
+    let title = wide("Awake");
+    // The message-only window can't own it, so it has no owner
+    let answer = unsafe {
+        MessageBoxW(
+            std::ptr::null_mut(),
+            text.as_ptr(),
+            title.as_ptr(),
+            MB_YESNO | MB_ICONQUESTION | MB_SETFOREGROUND,
+        )
+    };
+    REMINDING.with(|reminding| reminding.set(false));
+    if answer == IDNO {
+        handle(state::Command::Deactivate);
+    }
+}
+
+pub fn show_mode(current: KindSet) {
+    MODE.with(|mode| mode.set(current));
+}
+
+fn show_status(status: &state::Status) {
+    AWAKE.with(|cell| cell.set(status.is_awake()));
+    SUMMARY.with(|cell| *cell.borrow_mut() = status.summary());
+    ACTIVE.with(|cell| *cell.borrow_mut() = status.active_triggers());
+    TRIGGERS.with(|cell| *cell.borrow_mut() = status.triggers.to_vec());
+    OVERNIGHT.with(|cell| cell.set(status.overnight.is_some()));
+    update_icon(NIM_MODIFY);
+}
+
+// No bundled artwork yet, so stock icons stand in for the cup and the moon
+fn update_icon(message: u32) {
+    let awake = AWAKE.with(Cell::get);
+    let mut tip = SUMMARY.with(|summary| format!("Awake: {}", summary.borrow()));
+    if keepawake::is_dry_run() {
+        tip.push_str(" (dry run)");
+    }
+
+    let mut data: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+    data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+    data.hWnd = window();
+    data.uID = TRAY_ICON_ID;
+    data.uFlags = NIF_ICON | NIF_TIP | NIF_MESSAGE;
+    data.uCallbackMessage = WM_TRAY;
+    data.hIcon = unsafe {
+        LoadIconW(
+            std::ptr::null_mut(),
+            if awake {
+                IDI_INFORMATION
+            } else {
+                IDI_APPLICATION
+            },
+        )
+    };
+    for (dst, src) in data.szTip.iter_mut().zip(tip.encode_utf16().take(127)) {
+        *dst = src;
+    }
+
+    unsafe {
+        Shell_NotifyIconW(message, &data);
+    }
+}
+
+fn remove_icon() {
+    let mut data: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+    data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+    data.hWnd = window();
+    data.uID = TRAY_ICON_ID;
+    unsafe {
+        Shell_NotifyIconW(NIM_DELETE, &data);
+    }
+}
+
+fn append(menu: HMENU, flags: MENU_ITEM_FLAGS, id: usize, title: &str) {
+    let title = wide(title);
+    unsafe {
+        AppendMenuW(menu, flags, id, title.as_ptr());
+    }
+}
+
+fn separator(menu: HMENU) {
+    unsafe {
+        AppendMenuW(menu, MF_SEPARATOR, 0, std::ptr::null());
+    }
+}
+
+fn show_menu(hwnd: HWND) {
+    let mode = MODE.with(Cell::get);
+    let checked = |m: Mode| {
+        if m.kinds() == mode {
+            MF_CHECKED
+        } else {
+            MF_STRING
+        }
+    };
+
+    unsafe {
+        let menu = CreatePopupMenu();
+        let summary = SUMMARY.with(|summary| summary.borrow().clone());
+        append(menu, MF_GRAYED, 0, &summary);
+        if let Some(active) = ACTIVE.with(|active| active.borrow().clone()) {
+            append(menu, MF_GRAYED, 0, &active);
+        }
+        let triggers = TRIGGERS.with(|triggers| triggers.borrow().clone());
+        if !triggers.is_empty() {
+            let submenu = CreatePopupMenu();
+            for (i, trigger) in triggers.iter().enumerate() {
+                let flags = if trigger.enabled {
+                    MF_CHECKED
+                } else {
+                    MF_STRING
+                };
+                append(submenu, flags, ID_TRIGGER_FIRST + i, &trigger.name);
+            }
+            append(menu, MF_POPUP, submenu as usize, "Triggers");
+        }
+        separator(menu);
+        append(menu, MF_STRING, ID_TOGGLE, "Toggle");
+        separator(menu);
+
+        let timers = CreatePopupMenu();
+        append(timers, MF_STRING, ID_TIMER_15, "15 minutes");
+        append(timers, MF_STRING, ID_TIMER_30, "30 minutes");
+        append(timers, MF_STRING, ID_TIMER_60, "1 hour");
+        append(timers, MF_STRING, ID_TIMER_120, "2 hours");
+        separator(timers);
+        append(timers, MF_STRING, ID_EXTEND_15, "Extend by 15 minutes");
+        separator(timers);
+        let overnight = OVERNIGHT.with(Cell::get);
+        append(
+            timers,
+            if overnight { MF_CHECKED } else { MF_STRING },
+            ID_OVERNIGHT,
+            "Overnight: Then Until Charged",
+        );
+        append(menu, MF_POPUP, timers as usize, "Awake For...");
+
+        let modes = CreatePopupMenu();
+        let mode_item = |mode: Mode, id| append(modes, checked(mode), id, mode.title());
+        mode_item(Mode::Display, ID_MODE_DISPLAY);
+        mode_item(Mode::System, ID_MODE_SYSTEM);
+        mode_item(Mode::Both, ID_MODE_BOTH);
+        separator(modes);
+        mode_item(Mode::Server, ID_MODE_SERVER);
+        append(menu, MF_POPUP, modes as usize, "Mode");
+        separator(menu);
+
+        let stats = CreatePopupMenu();
+        for line in stats::read(AWAKE.with(Cell::get)).lines() {
+            append(stats, MF_GRAYED, 0, &line);
+        }
+        append(menu, MF_POPUP, stats as usize, "Statistics");
+
+        let dry_run_suffix = if keepawake::is_dry_run() {
+            " (dry run)"
+        } else {
+            ""
+        };
+        let about = format!("Awake v{}{}", env!("CARGO_PKG_VERSION"), dry_run_suffix);
+        append(menu, MF_GRAYED, 0, &about);
+        separator(menu);
+        append(menu, MF_STRING, ID_QUIT, "Quit");
+
+        // Without this the menu doesn't close when clicking elsewhere
+        SetForegroundWindow(hwnd);
+        let mut pt = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut pt);
+        let chosen = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_NONOTIFY | TPM_RIGHTBUTTON,
+            pt.x,
+            pt.y,
+            0,
+            hwnd,
+            std::ptr::null(),
+        );
+        // Also destroys the submenus
+        DestroyMenu(menu);
+
+        menu_action(chosen as usize);
+    }
+}
+
+fn menu_action(id: usize) {
+    let cmd = match id {
+        ID_TOGGLE => state::Command::Toggle,
+        ID_TIMER_15 => state::Command::ActivateFor { minutes: 15 },
+        ID_TIMER_30 => state::Command::ActivateFor { minutes: 30 },
+        ID_TIMER_60 => state::Command::ActivateFor { minutes: 60 },
+        ID_TIMER_120 => state::Command::ActivateFor { minutes: 120 },
+        ID_EXTEND_15 => state::Command::Extend { seconds: 15 * 60 },
+        ID_MODE_DISPLAY => state::Command::SetMode(Mode::Display.kinds()),
+        ID_MODE_SYSTEM => state::Command::SetMode(Mode::System.kinds()),
+        ID_MODE_BOTH => state::Command::SetMode(Mode::Both.kinds()),
+        ID_MODE_SERVER => state::Command::SetMode(Mode::Server.kinds()),
+        ID_OVERNIGHT => state::Command::SetOvernight(
+            (!OVERNIGHT.with(Cell::get)).then_some(battery::DEFAULT_CHARGE_PERCENT),
+        ),
+        ID_QUIT => return quit(),
+        id if id >= ID_TRIGGER_FIRST => {
+            let trigger = TRIGGERS.with(|t| t.borrow().get(id - ID_TRIGGER_FIRST).cloned());
+            match trigger {
+                Some(trigger) => state::Command::SetTriggerEnabled {
+                    name: trigger.name,
+                    enabled: !trigger.enabled,
+                },
+                None => return,
+            }
+        }
+        // Dismissed
+        _ => return,
+    };
+    handle(cmd);
+}
+
+fn quit() {
+    // Release power requests and join the timer thread before leaving the loop
+    if let Err(e) = state::shutdown() {
+        present_error(&e);
+    }
+    remove_icon();
+    unsafe {
+        PostQuitMessage(0);
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_TRAY => {
+            match (lparam & 0xffff) as u32 {
+                WM_LBUTTONUP => handle(state::Command::Toggle),
+                WM_RBUTTONUP | WM_CONTEXTMENU => show_menu(hwnd),
+                _ => {}
+            }
+            0
+        }
+        WM_COMMAND_POSTED => {
+            let cmd = Box::from_raw(lparam as *mut state::Command);
+            handle(*cmd);
+            0
+        }
+        WM_REMIND => {
+            show_reminder(wparam as u64);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+/// Creates the hidden window and tray icon, then runs the message loop
+pub fn run() {
+    let class_name = wide("AwakeTray");
+
+    unsafe {
+        let instance = GetModuleHandleW(std::ptr::null());
+        let mut class: WNDCLASSW = std::mem::zeroed();
+        class.lpfnWndProc = Some(window_proc);
+        class.hInstance = instance;
+        class.lpszClassName = class_name.as_ptr();
+        if RegisterClassW(&class) == 0 {
+            eprintln!("RegisterClassW failed");
+            return;
+        }
+
+        // Message-only: never shown, just receives tray callbacks and posts
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            std::ptr::null_mut(),
+            instance,
+            std::ptr::null(),
+        );
+        if hwnd.is_null() {
+            eprintln!("CreateWindowExW failed");
+            return;
+        }
+        let pending = {
+            let mut pending = PENDING.lock().unwrap();
+            WINDOW.store(hwnd as usize, Ordering::Release);
+            std::mem::take(&mut *pending)
+        };
+
+        show_mode(state::mode());
+        update_icon(NIM_ADD);
+        state::on_state_change(show_status);
+        // `--watch` may already have started a session
+        state::refresh();
+        for cmd in pending {
+            handle(cmd);
+        }
+
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}